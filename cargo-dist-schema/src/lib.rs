@@ -105,6 +105,16 @@ pub struct DistManifest {
     #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub upload_files: Vec<String>,
+    /// How builds were performed, for supply-chain attestation
+    ///
+    /// Entries are kept sorted so the output is deterministic.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<BuildProvenance>,
+    /// How each generic build went, in the order they ran
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub build_summary: Vec<BuildSummary>,
 }
 
 /// Info about an Asset (binary)
@@ -126,6 +136,54 @@ pub struct AssetInfo {
     pub linkage: Option<Linkage>,
 }
 
+/// How some binaries were built
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+pub struct BuildProvenance {
+    /// rust-style target triple the build was for
+    pub target_triple: String,
+    /// The command that was run
+    pub command: Vec<String>,
+    /// The C compiler the build was given (CC)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<String>,
+    /// The C++ compiler the build was given (CXX)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cxx: Option<String>,
+    /// sha256 digests of the binaries the build produced, keyed by file name
+    pub sha256: BTreeMap<String, String>,
+    /// Build IDs of the binaries the build produced, keyed by file name
+    ///
+    /// These match the binaries with their symbols (GNU build IDs for ELF, UUIDs for Mach-O).
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_ids: BTreeMap<String, String>,
+    /// A short hash of the commands run and the environment variables set for them
+    ///
+    /// Builds with the same signature were given the same inputs.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Custom key/values the binaries were tagged with (like `channel = "nightly"`)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// The outcome of a generic build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildSummary {
+    /// rust-style target triple the build was for
+    pub target_triple: String,
+    /// Whether the build (and collecting its outputs) succeeded
+    pub succeeded: bool,
+    /// How long it took, in milliseconds
+    pub duration_ms: u64,
+    /// How many files it contributed to the dist dir
+    pub artifacts: usize,
+}
+
 /// CI backend info
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CiInfo {
@@ -285,6 +343,10 @@ pub struct Artifact {
     #[serde(default)]
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub checksums: BTreeMap<String, String>,
+    /// The digest of the image's manifest, if this is a container image (e.g. `sha256:...`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub image_digest: Option<String>,
 }
 
 /// An asset contained in an artifact (executable, license, etc.)
@@ -438,6 +500,8 @@ impl DistManifest {
             ci: None,
             linkage: vec![],
             upload_files: vec![],
+            provenance: vec![],
+            build_summary: vec![],
         }
     }
 
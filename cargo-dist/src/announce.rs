@@ -35,6 +35,15 @@ impl<'a> DistGraphBuilder<'a> {
         self.manifest.announcement_title = Some(announcing.tag.clone());
         self.manifest.announcement_tag = Some(announcing.tag.clone());
         self.manifest.announcement_is_prerelease = announcing.prerelease;
+        self.inner.announcement_tag = Some(announcing.tag.clone());
+        self.inner.announcement_version = announcing
+            .version
+            .clone()
+            .or_else(|| {
+                let package = self.workspace.package(announcing.package?);
+                package.version.as_ref().map(|v| v.semver().clone())
+            })
+            .map(|v| v.to_string());
 
         // Refine the answers
         self.compute_announcement_changelog(announcing);
@@ -1,14 +1,12 @@
 //! Functionality required to invoke `cargo build` properly
 
-use std::env;
-
 use axoprocess::Cmd;
 use cargo_dist_schema::DistManifest;
 use miette::{Context, IntoDiagnostic};
 use tracing::warn;
 
 use crate::build::BuildExpectations;
-use crate::env::{calculate_ldflags, fetch_brew_env, parse_env, select_brew_env};
+use crate::env::{brew_env, calculate_ldflags, parse_env};
 use crate::{errors::*, BinaryIdx, BuildStep, DistGraphBuilder, TargetTriple, PROFILE_DIST};
 use crate::{
     CargoBuildStep, CargoTargetFeatureList, CargoTargetPackages, DistGraph, RustupStep, SortedMap,
@@ -130,13 +128,9 @@ pub fn build_cargo_target(
 
     let mut rustflags = target.rustflags.clone();
     let mut desired_extra_env = vec![];
-    let skip_brewfile = env::var("DO_NOT_USE_BREWFILE").is_ok();
-    if !skip_brewfile {
-        if let Some(env_output) = fetch_brew_env(dist_graph)? {
-            let brew_env = parse_env(&env_output)?;
-            desired_extra_env = select_brew_env(&brew_env);
-            rustflags = determine_brew_rustflags(&rustflags, &brew_env);
-        }
+    if let Some(brew_env) = brew_env(dist_graph)? {
+        desired_extra_env.clone_from(&brew_env.extra_env);
+        rustflags = determine_brew_rustflags(&rustflags, &parse_env(&brew_env.output)?);
     }
 
     let mut command = Cmd::new(&dist_graph.tools.cargo.cmd, "build your app with Cargo");
@@ -0,0 +1,176 @@
+//! Showing compiler diagnostics as GitHub Actions annotations
+//!
+//! With `github-annotations`, a generic build's output is scanned for
+//! GCC/Clang-style diagnostics once it finishes:
+//!
+//! ```text
+//! src/main.c:12:5: error: use of undeclared identifier 'x'
+//! ```
+//!
+//! and each one is printed to stdout as a workflow command, which GitHub shows
+//! inline on the line it's about:
+//!
+//! ```text
+//! ::error file=src/main.c,line=12,col=5::use of undeclared identifier 'x'
+//! ```
+//!
+//! This only happens when `GITHUB_ACTIONS` is set, so local builds are
+//! unaffected. Paths are made relative to the workspace (or the isolated copy
+//! of it), since that's what GitHub matches them against.
+
+use std::sync::OnceLock;
+
+use camino::Utf8Path;
+use regex::Regex;
+
+use super::relay::BuildLog;
+use crate::{DistGraph, DistResult};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// An error
+    Error,
+    /// A warning
+    Warning,
+}
+
+/// A diagnostic a compiler printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious it is
+    pub severity: Severity,
+    /// The file it's about
+    pub file: String,
+    /// The line it's about
+    pub line: u32,
+    /// The column it's about, if given
+    pub col: Option<u32>,
+    /// What the compiler said
+    pub message: String,
+}
+
+/// Matches `file:line[:col]: [fatal ]error|warning: message`
+fn diagnostic_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r"^(?P<file>(?:[A-Za-z]:)?[^:\s][^:]*):(?P<line>\d+):(?:(?P<col>\d+):)?\s*(?:fatal )?(?P<severity>error|warning):\s*(?P<message>.*)$",
+        )
+        .expect("diagnostic regex is invalid")
+    })
+}
+
+/// The diagnostics in a build's output, in order, without repeats
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for line in output.lines() {
+        let Some(captures) = diagnostic_regex().captures(line.trim_end()) else {
+            continue;
+        };
+        let Ok(line) = captures["line"].parse() else {
+            continue;
+        };
+        let diagnostic = Diagnostic {
+            severity: match &captures["severity"] {
+                "error" => Severity::Error,
+                _ => Severity::Warning,
+            },
+            file: captures["file"].to_owned(),
+            line,
+            col: captures
+                .name("col")
+                .and_then(|col| col.as_str().parse().ok()),
+            message: captures["message"].to_owned(),
+        };
+        // Headers included from several files get the same warning several times
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+/// Escape the message of a workflow command
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property of a workflow command
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// The workflow command that annotates a diagnostic
+pub fn workflow_command(diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let mut properties = format!(
+        "file={},line={}",
+        escape_property(&diagnostic.file),
+        diagnostic.line
+    );
+    if let Some(col) = diagnostic.col {
+        properties.push_str(&format!(",col={col}"));
+    }
+    format!(
+        "::{command} {properties}::{}",
+        escape_data(&diagnostic.message)
+    )
+}
+
+/// Make a diagnostic's path relative to one of `roots`, if it's in one
+fn relativize(diagnostic: &mut Diagnostic, roots: &[&Utf8Path]) {
+    let file = Utf8Path::new(&diagnostic.file);
+    let relative = roots
+        .iter()
+        .find_map(|root| file.strip_prefix(root).ok())
+        .or_else(|| file.strip_prefix("./").ok());
+    if let Some(relative) = relative {
+        diagnostic.file = relative.as_str().replace('\\', "/");
+    }
+}
+
+/// Whether to annotate diagnostics (only on GitHub Actions, when asked to)
+fn annotations_enabled(dist_graph: &DistGraph) -> bool {
+    dist_graph.generic_build.github_annotations.unwrap_or(false)
+        && std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+/// The workflow commands for the diagnostics in a build's output
+///
+/// `root` is where the build ran, if not the workspace.
+pub fn annotations(dist_graph: &DistGraph, output: &str, root: Option<&Utf8Path>) -> Vec<String> {
+    let mut roots = root.into_iter().collect::<Vec<_>>();
+    roots.push(&dist_graph.workspace_dir);
+    parse_diagnostics(output)
+        .into_iter()
+        .map(|mut diagnostic| {
+            relativize(&mut diagnostic, &roots);
+            workflow_command(&diagnostic)
+        })
+        .collect()
+}
+
+/// Print the annotations for what a build logged since `offset`, if we're on GitHub Actions and asked to
+pub(crate) fn emit_annotations(
+    dist_graph: &DistGraph,
+    log: &BuildLog,
+    offset: u64,
+    root: Option<&Utf8Path>,
+) -> DistResult<()> {
+    if !annotations_enabled(dist_graph) {
+        return Ok(());
+    }
+    let output = log.read_since(offset)?;
+    for command in annotations(dist_graph, &output, root) {
+        println!("{command}");
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+//! Making archives that are the same every time
+//!
+//! The archives axoasset makes list files in whatever order the filesystem
+//! returns them, with the build machine's owners, permissions, and mtimes, so
+//! two runs over identical files still produce different bytes. With
+//! `reproducible` we write archives ourselves instead:
+//!
+//! * entries are added in sorted order (directories before what's in them)
+//! * owners are always 0/0, with no user or group names
+//! * permissions are 0o755 for directories and executables, and 0o644 otherwise
+//! * every mtime is [`SOURCE_DATE_EPOCH`][sde] (or the unix epoch if it isn't set)
+//! * compressors don't record times or file names of their own
+//!
+//! [sde]: https://reproducible-builds.org/specs/source-date-epoch/
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    time::SystemTime,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::reproducible::{parse_source_date_epoch, SOURCE_DATE_EPOCH};
+use crate::{
+    config::{CompressionImpl, ZipStyle},
+    DistResult,
+};
+
+/// Permissions for directories and executables
+const EXECUTABLE_MODE: u32 = 0o755;
+/// Permissions for everything else
+const FILE_MODE: u32 = 0o644;
+/// Zip timestamps can't be earlier than this (1980-01-01)
+const ZIP_EPOCH: u64 = 315_532_800;
+
+/// A file or directory to put in an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// Where it is on disk
+    pub path: Utf8PathBuf,
+    /// Where it goes in the archive, always with `/` separators
+    pub name: String,
+    /// What it is
+    pub kind: EntryKind,
+}
+
+/// The kinds of [`ArchiveEntry`][]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A directory
+    Dir,
+    /// A file, and whether it's executable
+    File {
+        /// Whether it's executable
+        executable: bool,
+    },
+    /// A symlink, and what it points to
+    Symlink {
+        /// What it points to
+        target: Utf8PathBuf,
+    },
+}
+
+impl EntryKind {
+    /// The normalized permissions of an entry of this kind
+    pub fn mode(&self) -> u32 {
+        match self {
+            EntryKind::Dir | EntryKind::File { executable: true } => EXECUTABLE_MODE,
+            EntryKind::File { executable: false } => FILE_MODE,
+            EntryKind::Symlink { .. } => 0o777,
+        }
+    }
+}
+
+/// The time every entry gets stamped with, in seconds since the unix epoch
+pub(crate) fn archive_mtime() -> DistResult<u64> {
+    let Ok(value) = std::env::var(SOURCE_DATE_EPOCH) else {
+        return Ok(0);
+    };
+    let time = parse_source_date_epoch(&value)?;
+    Ok(time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs())
+}
+
+/// Everything under `src_path`, in the order it goes in the archive
+///
+/// Names are relative to `src_path`, under `with_root` if it's given.
+pub fn archive_entries(
+    src_path: &Utf8Path,
+    with_root: Option<&Utf8Path>,
+) -> DistResult<Vec<ArchiveEntry>> {
+    let mut entries = vec![];
+    let root = with_root.map(|root| root.as_str().replace('\\', "/"));
+    if let Some(root) = &root {
+        entries.push(ArchiveEntry {
+            path: src_path.to_owned(),
+            name: format!("{root}/"),
+            kind: EntryKind::Dir,
+        });
+    }
+    add_entries(src_path, root.as_deref(), &mut entries)?;
+    Ok(entries)
+}
+
+fn add_entries(
+    dir: &Utf8Path,
+    prefix: Option<&str>,
+    entries: &mut Vec<ArchiveEntry>,
+) -> DistResult<()> {
+    let mut children = dir
+        .read_dir_utf8()?
+        .map(|entry| entry.map(|entry| entry.path().to_owned()))
+        .collect::<Result<Vec<_>, _>>()?;
+    children.sort();
+    for path in children {
+        let file_name = path.file_name().unwrap_or_default();
+        let name = match prefix {
+            Some(prefix) => format!("{prefix}/{file_name}"),
+            None => file_name.to_owned(),
+        };
+        let metadata = path.symlink_metadata()?;
+        if metadata.is_symlink() {
+            let target = path.read_link_utf8()?;
+            entries.push(ArchiveEntry {
+                path,
+                name,
+                kind: EntryKind::Symlink { target },
+            });
+        } else if metadata.is_dir() {
+            entries.push(ArchiveEntry {
+                path: path.clone(),
+                name: format!("{name}/"),
+                kind: EntryKind::Dir,
+            });
+            add_entries(&path, Some(&name), entries)?;
+        } else {
+            entries.push(ArchiveEntry {
+                path,
+                name,
+                kind: EntryKind::File {
+                    executable: is_executable(&metadata),
+                },
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Archive everything under `src_path` at `dest_path`, the same way every time
+pub fn write_reproducible_archive(
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    zip_style: &ZipStyle,
+    with_root: Option<&Utf8Path>,
+    mtime: u64,
+) -> DistResult<()> {
+    if *zip_style == ZipStyle::TempDir {
+        return Ok(());
+    }
+    let entries = archive_entries(src_path, with_root)?;
+    let file = BufWriter::new(File::create(dest_path)?);
+    match zip_style {
+        ZipStyle::Zip => write_zip(file, &entries, mtime)?,
+        ZipStyle::Tar(CompressionImpl::Gzip) => {
+            let encoder = flate2::GzBuilder::new()
+                .mtime(0)
+                .write(file, flate2::Compression::default());
+            write_tar(encoder, &entries, mtime)?.finish()?.flush()?;
+        }
+        ZipStyle::Tar(CompressionImpl::Xzip) => {
+            let encoder = xz2::write::XzEncoder::new(file, 9);
+            write_tar(encoder, &entries, mtime)?.finish()?.flush()?;
+        }
+        ZipStyle::Tar(CompressionImpl::Zstd) => {
+            let encoder = zstd::Encoder::new(file, 0)?;
+            write_tar(encoder, &entries, mtime)?.finish()?.flush()?;
+        }
+        ZipStyle::TempDir => unreachable!(),
+    }
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: W, entries: &[ArchiveEntry], mtime: u64) -> DistResult<W> {
+    let mut builder = tar::Builder::new(writer);
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("")?;
+        header.set_groupname("")?;
+        header.set_mtime(mtime);
+        header.set_mode(entry.kind.mode());
+        match &entry.kind {
+            EntryKind::Dir => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, &entry.name, std::io::empty())?;
+            }
+            EntryKind::File { .. } => {
+                let mut file = File::open(&entry.path)?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(file.metadata()?.len());
+                builder.append_data(&mut header, &entry.name, &mut file)?;
+            }
+            EntryKind::Symlink { target } => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.name, target)?;
+            }
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn write_zip<W: Write + std::io::Seek>(
+    writer: W,
+    entries: &[ArchiveEntry],
+    mtime: u64,
+) -> DistResult<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+    let time = zip_datetime(mtime);
+    for entry in entries {
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(time)
+            .unix_permissions(entry.kind.mode());
+        match &entry.kind {
+            EntryKind::Dir => zip.add_directory(entry.name.trim_end_matches('/'), options)?,
+            // Zips can't be relied on to keep symlinks, so they get what they point to
+            EntryKind::File { .. } | EntryKind::Symlink { .. } => {
+                zip.start_file(entry.name.as_str(), options)?;
+                let mut contents = vec![];
+                File::open(&entry.path)?.read_to_end(&mut contents)?;
+                zip.write_all(&contents)?;
+            }
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// A unix time as a zip timestamp (which has no timezone, and starts in 1980)
+fn zip_datetime(mtime: u64) -> zip::DateTime {
+    let secs = mtime.max(ZIP_EPOCH);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+    zip::DateTime::from_date_and_time(
+        year,
+        month,
+        day,
+        (rem / 3_600) as u8,
+        (rem % 3_600 / 60) as u8,
+        (rem % 60) as u8,
+    )
+    .unwrap_or_default()
+}
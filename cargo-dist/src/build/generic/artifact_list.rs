@@ -0,0 +1,107 @@
+//! Per-target lists of what generic builds produced
+//!
+//! In fan-out CI each target's job only uploads its own outputs, and
+//! shouldn't need the whole dist-manifest to find them. With `artifact-lists`
+//! every target gets an `artifacts-<target>.json` in the dist dir, listing
+//! the binaries and symbols built for it:
+//!
+//! ```json
+//! {
+//!   "target": "x86_64-unknown-linux-gnu",
+//!   "artifacts": [
+//!     { "path": "app-x86_64-unknown-linux-gnu/app", "kind": "executable", "sha256": "...", "size": 1234 }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are relative to the dist dir, and sorted.
+
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ChecksumStyle, generate_checksum, BuildStep, DistGraph, DistResult, SortedMap,
+};
+
+/// The list of artifacts built for a target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactList {
+    /// The target triple
+    pub target: String,
+    /// Everything built for it
+    pub artifacts: Vec<ListedArtifact>,
+}
+
+/// An artifact in an [`ArtifactList`][]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListedArtifact {
+    /// Where it is, relative to the dist dir
+    pub path: Utf8PathBuf,
+    /// Whether it's an "executable" (or library) or "symbols"
+    pub kind: String,
+    /// Its sha256 digest
+    pub sha256: String,
+    /// Its size in bytes
+    pub size: u64,
+}
+
+/// Where the artifact list for a target goes
+pub fn artifact_list_path(dist_graph: &DistGraph, target: &str) -> Utf8PathBuf {
+    dist_graph.dist_dir.join(format!("artifacts-{target}.json"))
+}
+
+/// List what's been built for a target so far
+///
+/// This covers every generic build for the target, so targets with several
+/// builds end up with everything once the last of them is done.
+pub fn list_artifacts(dist_graph: &DistGraph, target: &str) -> DistResult<ArtifactList> {
+    let mut artifacts = SortedMap::new();
+    for step in &dist_graph.local_build_steps {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        if step.target_triple != target {
+            continue;
+        }
+        for binary_idx in &step.expected_binaries {
+            let binary = dist_graph.binary(*binary_idx);
+            let exes = binary.copy_exe_to.iter().map(|path| (path, "executable"));
+            let symbols = binary.copy_symbols_to.iter().map(|path| (path, "symbols"));
+            for (path, kind) in exes.chain(symbols) {
+                // Builds for this target that haven't run yet
+                let Ok(metadata) = path.metadata() else {
+                    continue;
+                };
+                let relative = path.strip_prefix(&dist_graph.dist_dir).unwrap_or(path);
+                artifacts.insert(
+                    relative.to_owned(),
+                    ListedArtifact {
+                        path: relative.to_owned(),
+                        kind: kind.to_owned(),
+                        sha256: generate_checksum(&ChecksumStyle::Sha256, path)?,
+                        size: metadata.len(),
+                    },
+                );
+            }
+        }
+    }
+    Ok(ArtifactList {
+        target: target.to_owned(),
+        artifacts: artifacts.into_values().collect(),
+    })
+}
+
+/// Write the artifact list for a target, if configured to
+pub(crate) fn write_artifact_list(
+    dist_graph: &DistGraph,
+    target: &str,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if !dist_graph.generic_build.artifact_lists.unwrap_or(false) {
+        return Ok(None);
+    }
+    let list = list_artifacts(dist_graph, target)?;
+    let path = artifact_list_path(dist_graph, target);
+    let contents = serde_json::to_string_pretty(&list).expect("serde_json failed");
+    axoasset::LocalAsset::write_new_all(&contents, &path)?;
+    Ok(Some(path))
+}
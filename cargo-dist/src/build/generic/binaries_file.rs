@@ -0,0 +1,118 @@
+//! Reading which binaries to build, and where to copy them, from a file
+//!
+//! A big target matrix is often generated by another tool, and doesn't belong
+//! in dist.toml. `binaries-file` names a JSON or TOML file (by its extension,
+//! relative to the workspace) listing binaries, the target to build each for,
+//! and where to copy it and its symbols (relative to the dist dir):
+//!
+//! ```toml
+//! [[binaries]]
+//! name = "app"
+//! target = "x86_64-unknown-linux-gnu"
+//! copy-exe-to = ["app-linux/app"]
+//! ```
+//!
+//! These are added to the binaries cargo-dist already knows it needs to build
+//! before builds are planned: a binary that's already being built for that
+//! target is also copied to the listed places, and otherwise it's built for the
+//! target too. Every binary listed must be one the workspace defines.
+
+use axoasset::SourceFile;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+
+use crate::{Binary, DistError, DistGraphBuilder, DistResult};
+
+/// The contents of a binaries file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BinariesFile {
+    /// The binaries to build
+    #[serde(default)]
+    pub binaries: Vec<ListedBinary>,
+}
+
+/// A binary to build, from a binaries file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ListedBinary {
+    /// The name of the binary
+    pub name: String,
+    /// The target to build it for
+    pub target: String,
+    /// Where to copy it, relative to the dist dir
+    #[serde(default)]
+    pub copy_exe_to: Vec<Utf8PathBuf>,
+    /// Where to copy its symbols, relative to the dist dir
+    #[serde(default)]
+    pub copy_symbols_to: Vec<Utf8PathBuf>,
+}
+
+/// Read a binaries file, as TOML if it's named `*.toml` and JSON otherwise
+pub fn load_binaries_file(path: &Utf8Path) -> DistResult<BinariesFile> {
+    let src = SourceFile::load_local(path)?;
+    let file = if path.extension() == Some("toml") {
+        src.deserialize_toml()?
+    } else {
+        src.deserialize_json()?
+    };
+    Ok(file)
+}
+
+impl<'a> DistGraphBuilder<'a> {
+    /// Add the binaries in `binaries-file`, if there is one
+    pub(crate) fn add_listed_binaries(&mut self) -> DistResult<()> {
+        let Some(path) = &self.inner.generic_build.binaries_file else {
+            return Ok(());
+        };
+        let path = self.inner.workspace_dir.join(path);
+        let file = load_binaries_file(&path)?;
+        for listed in file.binaries {
+            let copy_exe_to = listed
+                .copy_exe_to
+                .iter()
+                .map(|dest| self.inner.dist_dir.join(dest));
+            let copy_symbols_to = listed
+                .copy_symbols_to
+                .iter()
+                .map(|dest| self.inner.dist_dir.join(dest));
+            let binaries = &mut self.inner.binaries;
+            if let Some(binary) = binaries
+                .iter_mut()
+                .find(|binary| binary.name == listed.name && binary.target == listed.target)
+            {
+                binary.copy_exe_to.extend(copy_exe_to);
+                binary.copy_symbols_to.extend(copy_symbols_to);
+                continue;
+            }
+            let Some(template) = binaries.iter().find(|binary| binary.name == listed.name) else {
+                return Err(DistError::UnknownListedBinary {
+                    path: path.clone(),
+                    binary: listed.name,
+                });
+            };
+            let exe_ext = if listed.target.contains("windows") {
+                ".exe"
+            } else if listed.target.starts_with("wasm") {
+                ".wasm"
+            } else {
+                ""
+            };
+            let binary = Binary {
+                id: format!("{}-{}", listed.name, listed.target),
+                pkg_id: template.pkg_id.clone(),
+                pkg_spec: template.pkg_spec.clone(),
+                pkg_idx: template.pkg_idx,
+                file_name: format!("{}{exe_ext}", listed.name),
+                name: listed.name,
+                target: listed.target,
+                symbols_artifact: None,
+                copy_exe_to: copy_exe_to.collect(),
+                copy_symbols_to: copy_symbols_to.collect(),
+                features: template.features.clone(),
+            };
+            binaries.push(binary);
+        }
+        Ok(())
+    }
+}
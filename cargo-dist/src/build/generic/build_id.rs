@@ -0,0 +1,104 @@
+//! Recording the build ID linking each binary to its symbols
+//!
+//! Symbol servers find the debug info for a crash by the ID the binary was
+//! linked with. With `build-id`, Linux builds get `-Wl,--build-id` in LDFLAGS
+//! so the linker always writes a GNU build ID note, and once they're built we
+//! read that note (or the `LC_UUID` of a Mach-O binary, which is what its dSYM
+//! is matched by) and record it in the build's provenance, next to the
+//! binary's checksum.
+//!
+//! A Linux or macOS binary without one fails the build, since it can't be
+//! symbolicated. Other formats have no such ID, and are left alone.
+
+use std::fmt::Write;
+
+use camino::Utf8Path;
+use goblin::{
+    elf::note::NT_GNU_BUILD_ID,
+    mach::{load_command::CommandVariant, Mach, MachO, SingleArch},
+    Object,
+};
+
+use super::magic::expected_format;
+use crate::{config::BinaryFormat, DistGraph, DistResult};
+
+/// The linker flag that makes sure ELF binaries get a build ID
+pub const BUILD_ID_LDFLAG: &str = "-Wl,--build-id";
+
+/// Whether build IDs are wanted
+pub(crate) fn wants_build_id(dist_graph: &DistGraph) -> bool {
+    dist_graph.generic_build.build_id.unwrap_or(false)
+}
+
+/// Whether binaries for `target` should have a build ID
+pub fn has_build_id(target: &str) -> bool {
+    matches!(
+        expected_format(target),
+        BinaryFormat::Elf | BinaryFormat::MachO
+    )
+}
+
+/// The LDFLAGS a build for `target` needs to get a build ID, if any
+///
+/// Apple's linker always records a UUID, so only ELF targets need asking.
+pub(crate) fn build_id_ldflags(dist_graph: &DistGraph, target: &str) -> Option<&'static str> {
+    let elf = expected_format(target) == BinaryFormat::Elf;
+    (wants_build_id(dist_graph) && elf).then_some(BUILD_ID_LDFLAG)
+}
+
+/// Read the build ID of the binary at `path`, if it has one
+///
+/// ELF build IDs are given in hex, and Mach-O UUIDs in their usual uppercase
+/// hyphenated form, which is how each is looked up by symbol servers.
+pub fn read_build_id(path: &Utf8Path) -> DistResult<Option<String>> {
+    let bytes = std::fs::read(path)?;
+    let Ok(object) = Object::parse(&bytes) else {
+        return Ok(None);
+    };
+    let id = match object {
+        Object::Elf(elf) => elf
+            .iter_note_sections(&bytes, Some(".note.gnu.build-id"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .find(|note| note.n_type == NT_GNU_BUILD_ID)
+            .map(|note| hex(note.desc)),
+        Object::Mach(Mach::Binary(macho)) => macho_uuid(&macho),
+        Object::Mach(Mach::Fat(multi)) => match multi.get(0)? {
+            SingleArch::MachO(macho) => macho_uuid(&macho),
+            SingleArch::Archive(_) => None,
+        },
+        _ => None,
+    };
+    Ok(id)
+}
+
+/// The UUID of a Mach-O binary, if it has one
+fn macho_uuid(macho: &MachO) -> Option<String> {
+    macho
+        .load_commands
+        .iter()
+        .find_map(|command| match &command.command {
+            CommandVariant::Uuid(uuid) => Some(uuid.uuid),
+            _ => None,
+        })
+        .map(|uuid| {
+            let hex = hex(&uuid).to_uppercase();
+            format!(
+                "{}-{}-{}-{}-{}",
+                &hex[..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..]
+            )
+        })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for byte in bytes {
+        write!(&mut output, "{byte:02x}").unwrap();
+    }
+    output
+}
@@ -0,0 +1,77 @@
+//! Running generic builds as a different user
+//!
+//! Builds that run as root (typical in CI, and in containers) leave root-owned
+//! files behind, which later steps then can't delete. With `build-uid` and
+//! `build-gid` the build runs as that user and group instead: containers get
+//! `--user`, and builds on the host switch ids in the child just before exec.
+
+use std::process::Command;
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// The `--user` to run containerized builds as, if configured
+pub(crate) fn container_user(dist_graph: &DistGraph) -> Option<String> {
+    let config = &dist_graph.generic_build;
+    if config.build_uid.is_none() && config.build_gid.is_none() {
+        return None;
+    }
+    // The container engine needs a uid to go with a gid, so default to our own
+    let (our_uid, our_gid) = current_ids();
+    let uid = config.build_uid.unwrap_or(our_uid);
+    let gid = config.build_gid.unwrap_or(our_gid);
+    Some(format!("{uid}:{gid}"))
+}
+
+/// Make the (non-containerized) build command run as the configured user and group
+#[cfg(unix)]
+pub(crate) fn run_as_build_user(dist_graph: &DistGraph, command: &mut Command) -> DistResult<()> {
+    use std::os::unix::process::CommandExt;
+
+    let config = &dist_graph.generic_build;
+    let (uid, gid) = (config.build_uid, config.build_gid);
+    if uid.is_none() && gid.is_none() {
+        return Ok(());
+    }
+    // Only root can become someone else, and the child would just fail with EPERM
+    // SAFETY: these calls can't fail and have no preconditions
+    let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    let other_uid = uid.is_some_and(|uid| uid != euid);
+    let other_gid = gid.is_some_and(|gid| gid != egid);
+    if euid != 0 && (other_uid || other_gid) {
+        return Err(DistError::BuildUserNotPermitted {
+            user: format!(
+                "{}:{}",
+                uid.map(|uid| uid.to_string()).unwrap_or_default(),
+                gid.map(|gid| gid.to_string()).unwrap_or_default()
+            ),
+        });
+    }
+    // This also drops root's supplementary groups
+    if let Some(gid) = gid {
+        command.gid(gid);
+    }
+    if let Some(uid) = uid {
+        command.uid(uid);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn run_as_build_user(dist_graph: &DistGraph, _command: &mut Command) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    if config.build_uid.is_some() || config.build_gid.is_some() {
+        tracing::warn!("build-uid and build-gid only apply to container builds on this platform");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn current_ids() -> (u32, u32) {
+    // SAFETY: these calls can't fail and have no preconditions
+    unsafe { (libc::getuid(), libc::getgid()) }
+}
+
+#[cfg(not(unix))]
+fn current_ids() -> (u32, u32) {
+    (0, 0)
+}
@@ -0,0 +1,57 @@
+//! Compiler caching (sccache/ccache) for generic builds
+
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+use tracing::{info, warn};
+
+use crate::{config::CompilerCache, DistGraph};
+
+impl CompilerCache {
+    /// The executable for this cache
+    pub fn command(self) -> &'static str {
+        match self {
+            CompilerCache::Sccache => "sccache",
+            CompilerCache::Ccache => "ccache",
+        }
+    }
+
+    /// The env var that tells this cache where to keep its data
+    pub fn dir_env_var(self) -> &'static str {
+        match self {
+            CompilerCache::Sccache => "SCCACHE_DIR",
+            CompilerCache::Ccache => "CCACHE_DIR",
+        }
+    }
+}
+
+/// The directory cargo-dist keeps a compiler cache's data in
+pub(crate) fn cache_dir(dist_graph: &DistGraph, cache: CompilerCache) -> Utf8PathBuf {
+    dist_graph
+        .target_dir
+        .join("cargo-dist-cache")
+        .join(cache.command())
+}
+
+/// Wrap a (target-appropriate) compiler so it goes through the cache
+///
+/// Both caches are happy to be used via `CC="sccache gcc"`,
+/// which most buildsystems pass along verbatim.
+pub(crate) fn wrap_compiler(cache: CompilerCache, compiler: &str) -> String {
+    format!("{} {compiler}", cache.command())
+}
+
+/// Log the cache's hit/miss stats
+pub(crate) fn report_cache_stats(dist_graph: &DistGraph, cache: CompilerCache) {
+    let output = Cmd::new(cache.command(), "get compiler cache stats")
+        .arg("--show-stats")
+        .env(cache.dir_env_var(), cache_dir(dist_graph, cache))
+        .output();
+    match output {
+        Ok(output) => info!(
+            "{} stats:\n{}",
+            cache.command(),
+            String::from_utf8_lossy(&output.stdout)
+        ),
+        Err(e) => warn!("couldn't get {} stats: {e}", cache.command()),
+    }
+}
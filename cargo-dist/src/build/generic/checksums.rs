@@ -0,0 +1,80 @@
+//! A single checksum file covering everything the generic and extra builds produced
+
+use camino::Utf8PathBuf;
+
+use crate::{
+    config::ChecksumStyle, generate_checksum, BuildStep, DistGraph, DistResult, SortedSet,
+};
+
+/// The default name of the combined checksum file (e.g. `SHA256SUMS`)
+fn default_file_name(checksum: ChecksumStyle) -> String {
+    format!("{}SUMS", checksum.ext().to_uppercase())
+}
+
+/// Every output of a generic or extra build that lands in the dist dir, relative to it
+pub(crate) fn build_outputs(dist_graph: &DistGraph) -> SortedSet<Utf8PathBuf> {
+    let mut outputs = SortedSet::new();
+    for step in dist_graph
+        .local_build_steps
+        .iter()
+        .chain(&dist_graph.global_build_steps)
+    {
+        match step {
+            BuildStep::Generic(step) => {
+                for &binary_idx in &step.expected_binaries {
+                    let binary = dist_graph.binary(binary_idx);
+                    for dest in &binary.copy_exe_to {
+                        if let Ok(rel) = dest.strip_prefix(&dist_graph.dist_dir) {
+                            outputs.insert(rel.to_owned());
+                        }
+                    }
+                }
+            }
+            BuildStep::Extra(step) => {
+                outputs.extend(
+                    step.artifact_names
+                        .iter()
+                        .map(|(_, name)| Utf8PathBuf::from(name)),
+                );
+            }
+            _ => {}
+        }
+    }
+    let extra_outputs = dist_graph
+        .extra_outputs_copied
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    for path in extra_outputs.iter() {
+        if let Ok(rel) = path.strip_prefix(&dist_graph.dist_dir) {
+            outputs.insert(rel.to_owned());
+        }
+    }
+    outputs
+}
+
+/// Write the combined checksum file to the dist dir, if one is configured
+///
+/// The file uses the same format as `sha256sum`, so e.g. `sha256sum -c SHA256SUMS`
+/// can be run from the dist dir to verify everything.
+pub fn write_combined_checksums(dist_graph: &DistGraph) -> DistResult<Option<Utf8PathBuf>> {
+    let checksum = match dist_graph.generic_build.combined_checksum {
+        None | Some(ChecksumStyle::False) => return Ok(None),
+        Some(checksum) => checksum,
+    };
+    let file_name = dist_graph
+        .generic_build
+        .combined_checksum_file
+        .clone()
+        .unwrap_or_else(|| default_file_name(checksum));
+
+    let mut contents = String::new();
+    for output in build_outputs(dist_graph) {
+        let hash = generate_checksum(&checksum, &dist_graph.dist_dir.join(&output))?;
+        // See write_checksum for the details of this format
+        contents.push_str(&format!("{hash} *{output}\n"));
+    }
+
+    let dest_path = dist_graph.dist_dir.join(file_name);
+    axoasset::LocalAsset::write_new(&contents, &dest_path)?;
+    Ok(Some(dest_path))
+}
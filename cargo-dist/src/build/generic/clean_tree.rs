@@ -0,0 +1,139 @@
+//! Checking that generic builds don't modify tracked source files
+//!
+//! Some build systems regenerate files that are checked in, which makes the
+//! release differ from the commit it claims to be built from. With
+//! `forbid-source-changes` we compare `git status` before and after the build,
+//! and fail if the build changed anything git tracks.
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+
+use crate::{
+    config::ChecksumStyle, generate_checksum, DistError, DistGraph, DistResult, SortedMap,
+};
+
+/// The state of the tracked files that differ from HEAD, at some point in time
+pub(crate) struct TreeSnapshot {
+    git: String,
+    dir: Utf8PathBuf,
+    /// The root of the repo `dir` is in, which git reports paths relative to
+    repo_root: Utf8PathBuf,
+    /// Each modified file, and a hash of its contents (None if it's deleted)
+    modified: SortedMap<Utf8PathBuf, Option<String>>,
+}
+
+impl TreeSnapshot {
+    /// Snapshot the git repo at `dir`, if the check is enabled and `dir` is in one
+    pub(crate) fn take(dist_graph: &DistGraph, dir: &Utf8Path) -> DistResult<Option<Self>> {
+        if !dist_graph
+            .generic_build
+            .forbid_source_changes
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        let Some(git) = &dist_graph.tools.git else {
+            warn!("not checking whether the build modifies sources, as git isn't installed");
+            return Ok(None);
+        };
+        let toplevel = Cmd::new(&git.cmd, "detect a git repo")
+            .arg("-C")
+            .arg(dir)
+            .arg("rev-parse")
+            .arg("--show-toplevel")
+            .stderr(std::process::Stdio::piped())
+            .check(false)
+            .output()
+            .ok()
+            .filter(|output| output.status.success());
+        let Some(toplevel) = toplevel else {
+            return Ok(None);
+        };
+        let repo_root = Utf8PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim_end());
+        let modified = modified_files(&git.cmd, dir, &repo_root)?;
+        Ok(Some(Self {
+            git: git.cmd.clone(),
+            dir: dir.to_owned(),
+            repo_root,
+            modified,
+        }))
+    }
+
+    /// Fail if any tracked files changed since the snapshot was taken
+    pub(crate) fn check_unchanged(&self, target: &str) -> DistResult<()> {
+        let now = modified_files(&self.git, &self.dir, &self.repo_root)?;
+        let changed = changed_files(&self.modified, &now);
+        if changed.is_empty() {
+            return Ok(());
+        }
+        Err(DistError::BuildModifiedSources {
+            target: target.to_owned(),
+            files: changed
+                .iter()
+                .map(|path| path.as_str())
+                .collect::<Vec<_>>()
+                .join("\n  "),
+        })
+    }
+}
+
+/// The files that differ between two snapshots
+fn changed_files(
+    before: &SortedMap<Utf8PathBuf, Option<String>>,
+    after: &SortedMap<Utf8PathBuf, Option<String>>,
+) -> Vec<Utf8PathBuf> {
+    let mut changed = before
+        .keys()
+        .chain(after.keys())
+        .filter(|path| before.get(*path) != after.get(*path))
+        .cloned()
+        .collect::<Vec<_>>();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+/// The tracked files under `dir` that differ from HEAD, with their hashes
+///
+/// Hashing lets us notice the build changing a file that was already modified.
+/// Paths are relative to `repo_root`, as that's how git reports them even when
+/// `dir` is a subdirectory of the repo.
+fn modified_files(
+    git: &str,
+    dir: &Utf8Path,
+    repo_root: &Utf8Path,
+) -> DistResult<SortedMap<Utf8PathBuf, Option<String>>> {
+    let output = Cmd::new(git, "check for modified files")
+        .arg("-C")
+        .arg(dir)
+        .arg("status")
+        .arg("--porcelain")
+        .arg("--untracked-files=no")
+        .arg("-z")
+        // Changes elsewhere in the repo aren't the build's doing
+        .arg("--")
+        .arg(".")
+        .output()?;
+    let mut modified = SortedMap::new();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut entries = stdout.split('\0');
+    while let Some(entry) = entries.next() {
+        // Entries look like "XY path", and renames are followed by their old path
+        let Some(path) = entry.get(3..) else {
+            continue;
+        };
+        if entry.starts_with('R') || entry.starts_with('C') {
+            entries.next();
+        }
+        let path = Utf8PathBuf::from(path);
+        let full_path = repo_root.join(&path);
+        let hash = if full_path.is_file() {
+            Some(generate_checksum(&ChecksumStyle::Sha256, &full_path)?)
+        } else {
+            None
+        };
+        modified.insert(path, hash);
+    }
+    Ok(modified)
+}
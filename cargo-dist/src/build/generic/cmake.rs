@@ -0,0 +1,132 @@
+//! Support for generic builds that are CMake projects
+//!
+//! Rather than running the build-command verbatim, we follow the usual
+//! out-of-source convention of `cmake -S . -B build && cmake --build build`.
+
+use std::process::ExitStatus;
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::{
+    build_command, relay::BuildLog, resolved_cc, resolved_cxx, run_builds, toolchain_file,
+};
+use crate::{DistGraph, DistResult, GenericBuildStep};
+
+/// The build type we ask CMake for
+const CMAKE_BUILD_TYPE: &str = "Release";
+
+/// The directory a target gets configured and built in
+pub(crate) fn cmake_build_dir(dist_graph: &DistGraph, target: &GenericBuildStep) -> Utf8PathBuf {
+    let base = dist_graph
+        .generic_build
+        .cmake_build_dir
+        .clone()
+        .unwrap_or_else(|| Utf8PathBuf::from("build"));
+    // Give every target its own build dir, as CMake caches the compilers
+    // it configured with and refuses to change them afterwards
+    base.join(&target.target_triple)
+}
+
+/// The arguments for the configure step (`cmake -S . -B <build-dir> ...`)
+pub(crate) fn cmake_configure_args(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    build_dir: &Utf8Path,
+) -> Vec<String> {
+    let triple = target.target_triple.as_str();
+    let mut args = vec![
+        "-S".to_owned(),
+        ".".to_owned(),
+        "-B".to_owned(),
+        build_dir.to_string(),
+        format!("-DCMAKE_BUILD_TYPE={CMAKE_BUILD_TYPE}"),
+        format!("-DCMAKE_C_COMPILER={}", resolved_cc(dist_graph, triple)),
+        format!("-DCMAKE_CXX_COMPILER={}", resolved_cxx(dist_graph, triple)),
+    ];
+    // A CMake-specific toolchain file wins over the one for every builder
+    let toolchain_file = dist_graph
+        .generic_build
+        .cmake_toolchain_files
+        .as_ref()
+        .and_then(|files| files.get(triple))
+        .cloned()
+        .or_else(|| toolchain_file(dist_graph, target));
+    if let Some(toolchain_file) = toolchain_file {
+        args.push(format!("-DCMAKE_TOOLCHAIN_FILE={toolchain_file}"));
+    }
+    // Anything after the cmake executable in the build-command is user-provided
+    // configure arguments (e.g. `-DFOO=ON`)
+    args.extend(target.build_command.iter().skip(1).cloned());
+    args
+}
+
+/// The arguments for the build step (`cmake --build <build-dir> ...`)
+pub(crate) fn cmake_build_args(build_dir: &Utf8Path) -> Vec<String> {
+    vec![
+        "--build".to_owned(),
+        build_dir.to_string(),
+        // Multi-config generators (Visual Studio, Xcode) ignore CMAKE_BUILD_TYPE
+        "--config".to_owned(),
+        CMAKE_BUILD_TYPE.to_owned(),
+    ]
+}
+
+/// The command lines that configure and then build a CMake project for the given target
+pub(crate) fn cmake_command_lines(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> [Vec<String>; 2] {
+    let cmake = target
+        .build_command
+        .first()
+        .expect("The build command must contain at least one entry");
+    let build_dir = cmake_build_dir(dist_graph, target);
+
+    let mut configure = vec![cmake.clone()];
+    configure.extend(cmake_configure_args(dist_graph, target, &build_dir));
+    let mut build = vec![cmake.clone()];
+    build.extend(cmake_build_args(&build_dir));
+    [configure, build]
+}
+
+/// The commands that configure and then build a CMake project for the given target
+pub(crate) fn cmake_commands(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> DistResult<[Cmd; 2]> {
+    let [configure, build] = cmake_command_lines(dist_graph, target);
+    Ok([
+        build_command(dist_graph, &configure, Some(target))?,
+        build_command(dist_graph, &build, Some(target))?,
+    ])
+}
+
+/// Configure and build a CMake project for the given target
+///
+/// Both commands go through the same checks as any other build's commands.
+/// Returns the signature of both commands along with the build's status.
+pub(crate) fn run_cmake_build(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    log: &BuildLog,
+) -> DistResult<(ExitStatus, String)> {
+    let [configure, build] = cmake_command_lines(dist_graph, target);
+    run_builds(dist_graph, &[&configure, &build], Some(target), log)
+}
+
+/// Where CMake might have put an expected output under the build dir
+///
+/// Single-config generators put outputs directly in the build dir, while
+/// multi-config generators put them in a per-config subdirectory.
+pub(crate) fn output_candidates(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    file_name: &str,
+) -> Vec<Utf8PathBuf> {
+    let build_dir = cmake_build_dir(dist_graph, target);
+    vec![
+        build_dir.join(file_name),
+        build_dir.join(CMAKE_BUILD_TYPE).join(file_name),
+    ]
+}
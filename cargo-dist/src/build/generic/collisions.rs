@@ -0,0 +1,120 @@
+//! Renaming binaries that different targets would copy to the same place
+//!
+//! When builds for several targets copy a binary with the same name into the
+//! same directory, each copy would overwrite the last. With a `collision-rename`
+//! template, those copies are given names that tell them apart instead, like
+//! `app-x86_64` and `app-aarch64` for `collision-rename = "{name}-{arch}"`:
+//!
+//! * `{name}` is the file's name, minus its extension
+//! * `{target}` is the binary's target triple
+//! * `{arch}` is the target's architecture (the triple's first part)
+//!
+//! The extension (`.exe`, ...) is kept, and the archives the binaries go into
+//! get them under their new names. Only destinations claimed by binaries for
+//! different targets are renamed: anything else that collides (or still
+//! collides once renamed) is an error, as it is without a template.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{BinaryIdx, BuildStep, DistError, DistGraph, DistResult, SortedMap};
+
+/// Rename where generic builds' binaries are copied to, where that only collides because they're for different targets
+///
+/// Symbols files are named after the binary's id (which includes the target), so never need renaming.
+pub(crate) fn rename_colliding_destinations(dist_graph: &mut DistGraph) -> DistResult<()> {
+    let Some(template) = dist_graph.generic_build.collision_rename.clone() else {
+        return Ok(());
+    };
+    // Catch a bad template even if nothing collides
+    render_collision_name(&template, "name", &dist_graph.tools.cargo.host_target)?;
+
+    // destination => every binary copied there
+    let mut claimed = SortedMap::<Utf8PathBuf, Vec<BinaryIdx>>::new();
+    for step in &dist_graph.local_build_steps {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        for &binary_idx in &step.expected_binaries {
+            let binary = dist_graph.binary(binary_idx);
+            for dest in &binary.copy_exe_to {
+                let claimants = claimed.entry(dest.clone()).or_default();
+                if !claimants.contains(&binary_idx) {
+                    claimants.push(binary_idx);
+                }
+            }
+        }
+    }
+
+    for (dest, claimants) in claimed {
+        let first_target = &dist_graph.binary(claimants[0]).target;
+        if claimants
+            .iter()
+            .all(|idx| dist_graph.binary(*idx).target == *first_target)
+        {
+            continue;
+        }
+        for binary_idx in claimants {
+            let target = dist_graph.binary(binary_idx).target.clone();
+            let renamed = renamed_destination(&template, &dest, &target)?;
+            let binary = &mut dist_graph.binaries[binary_idx.0];
+            for path in &mut binary.copy_exe_to {
+                if *path == dest {
+                    path.clone_from(&renamed);
+                }
+            }
+            for artifact in &mut dist_graph.artifacts {
+                if let Some(path) = artifact.required_binaries.get_mut(&binary_idx) {
+                    if *path == dest {
+                        path.clone_from(&renamed);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Where a copy to `dest` of a binary for `target` goes once renamed
+fn renamed_destination(template: &str, dest: &Utf8Path, target: &str) -> DistResult<Utf8PathBuf> {
+    let file_name = dest.file_name().unwrap_or(dest.as_str());
+    let (name, ext) = match file_name.split_once('.') {
+        Some((name, ext)) => (name, Some(ext)),
+        None => (file_name, None),
+    };
+    let mut renamed = render_collision_name(template, name, target)?;
+    if let Some(ext) = ext {
+        renamed.push('.');
+        renamed.push_str(ext);
+    }
+    Ok(dest.with_file_name(renamed))
+}
+
+/// The name a file called `name` (minus its extension) is renamed to for `target`
+pub fn render_collision_name(template: &str, name: &str, target: &str) -> DistResult<String> {
+    let arch = target.split('-').next().unwrap_or(target);
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(DistError::UnknownCollisionPlaceholder {
+                template: template.to_owned(),
+                placeholder: rest[start..].to_owned(),
+            });
+        };
+        match &rest[start + 1..start + len] {
+            "name" => rendered.push_str(name),
+            "target" => rendered.push_str(target),
+            "arch" => rendered.push_str(arch),
+            placeholder => {
+                return Err(DistError::UnknownCollisionPlaceholder {
+                    template: template.to_owned(),
+                    placeholder: format!("{{{placeholder}}}"),
+                })
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
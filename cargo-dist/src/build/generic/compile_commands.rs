@@ -0,0 +1,127 @@
+//! Gathering the compilation databases builds produce
+//!
+//! Clang-based tooling (clangd, clang-tidy, most static analyzers) wants a
+//! `compile_commands.json` saying how each file was compiled, which build
+//! systems like CMake and Bear write next to the build. With `compile-commands`
+//! set, after each build we look for one in the directory the build ran in, its
+//! output dir, and (for CMake) its build dir, and copy the first we find to
+//! `compile-commands/<target>.json` in the dist dir. With `merged`, once every
+//! build is done, the databases of all the targets built are also concatenated
+//! into one `compile_commands.json` in the dist dir.
+//!
+//! A build that doesn't produce a database only gets a warning, since plenty of
+//! build systems need asking for one.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+
+use super::cmake;
+use crate::{
+    config::{CompileCommands, GenericBuilder},
+    BuildStep, DistError, DistGraph, DistResult, GenericBuildStep,
+};
+
+/// What build systems call the database
+pub const COMPILE_COMMANDS: &str = "compile_commands.json";
+
+/// The directory (under dist_dir) each target's database is copied to
+pub const COMPILE_COMMANDS_DIR: &str = "compile-commands";
+
+/// Where a target's database is copied to
+pub fn target_compile_commands(dist_dir: &Utf8Path, target: &str) -> Utf8PathBuf {
+    dist_dir
+        .join(COMPILE_COMMANDS_DIR)
+        .join(format!("{target}.json"))
+}
+
+/// Where a build might have left its database, relative to where it ran
+fn candidates(dist_graph: &DistGraph, step: &GenericBuildStep) -> Vec<Utf8PathBuf> {
+    let mut dirs = vec![Utf8PathBuf::new()];
+    dirs.extend(step.output_base.clone());
+    if step.builder == GenericBuilder::Cmake {
+        dirs.push(cmake::cmake_build_dir(dist_graph, step));
+    }
+    dirs.into_iter()
+        .map(|dir| dir.join(COMPILE_COMMANDS))
+        .collect()
+}
+
+/// Copy the database the build left in `root` into the dist dir, if we're gathering them
+///
+/// Returns where it was copied to.
+pub(crate) fn collect_compile_commands(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    root: &Utf8Path,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if dist_graph
+        .generic_build
+        .compile_commands
+        .unwrap_or_default()
+        == CompileCommands::Off
+    {
+        return Ok(None);
+    }
+    let dest = target_compile_commands(&dist_graph.dist_dir, &step.target_triple);
+    // Don't let a database from an earlier run pass for this one's
+    if dest.exists() {
+        std::fs::remove_file(&dest)?;
+    }
+    let Some(found) = candidates(dist_graph, step)
+        .into_iter()
+        .map(|path| root.join(path))
+        .find(|path| path.is_file())
+    else {
+        warn!(
+            "the build for {} didn't produce a {COMPILE_COMMANDS}",
+            step.target_triple
+        );
+        return Ok(None);
+    };
+    std::fs::create_dir_all(dest.parent().expect("database has a parent"))?;
+    std::fs::copy(found, &dest)?;
+    Ok(Some(dest))
+}
+
+/// Concatenate the databases of every target built into one, if they're being merged
+///
+/// Returns where the merged database was written.
+pub fn merge_compile_commands(dist_graph: &DistGraph) -> DistResult<Option<Utf8PathBuf>> {
+    if dist_graph
+        .generic_build
+        .compile_commands
+        .unwrap_or_default()
+        != CompileCommands::Merged
+    {
+        return Ok(None);
+    }
+    let mut targets = dist_graph
+        .local_build_steps
+        .iter()
+        .filter_map(|step| match step {
+            BuildStep::Generic(step) => Some(step.target_triple.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    targets.sort();
+    targets.dedup();
+    let mut merged = vec![];
+    for target in targets {
+        let path = target_compile_commands(&dist_graph.dist_dir, target);
+        if !path.exists() {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let entries = serde_json::from_str::<Vec<serde_json::Value>>(&contents).map_err(|e| {
+            DistError::InvalidCompileCommands {
+                path: path.clone(),
+                details: e.to_string(),
+            }
+        })?;
+        merged.extend(entries);
+    }
+    let dest = dist_graph.dist_dir.join(COMPILE_COMMANDS);
+    let contents = serde_json::to_string_pretty(&merged).expect("json values always serialize");
+    std::fs::write(&dest, contents)?;
+    Ok(Some(dest))
+}
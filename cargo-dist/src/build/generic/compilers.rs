@@ -0,0 +1,58 @@
+//! Picking a C/C++ compiler from the ones that are installed
+//!
+//! Without CC/CXX, generic builds get the platform's usual compiler (clang on
+//! macOS, gcc on Linux). `compiler-preference` lists compiler families to look
+//! for instead, most preferred first, like `["clang", "gcc"]`: the first one
+//! whose C compiler answers `--version` is used for both CC and CXX. It's only
+//! probed once per run, and if none of them work we fall back to the platform's
+//! compiler as usual.
+//!
+//! CC/CXX in the environment and the wasm toolchains still win over this.
+
+use crate::{config::CompilerFamily, DistGraph};
+
+impl CompilerFamily {
+    /// The C compiler of this family
+    pub fn cc(&self) -> &'static str {
+        match self {
+            CompilerFamily::Clang => "clang",
+            CompilerFamily::Gcc => "gcc",
+        }
+    }
+
+    /// The C++ compiler of this family
+    pub fn cxx(&self) -> &'static str {
+        match self {
+            CompilerFamily::Clang => "clang++",
+            CompilerFamily::Gcc => "g++",
+        }
+    }
+}
+
+/// The first family in `preference` whose C compiler `works`
+pub fn select_family(
+    preference: &[CompilerFamily],
+    works: impl Fn(&str) -> bool,
+) -> Option<CompilerFamily> {
+    preference.iter().copied().find(|family| works(family.cc()))
+}
+
+/// Whether `program --version` runs and succeeds
+pub fn compiler_works(program: &str) -> bool {
+    std::process::Command::new(program)
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The family picked from `compiler-preference`, if there is one and any work
+///
+/// Like [`crate::env::git_commit`][], this only probes the first time.
+pub fn detected_family(dist_graph: &DistGraph) -> Option<CompilerFamily> {
+    *dist_graph.compiler_family.get_or_init(|| {
+        let preference = dist_graph.generic_build.compiler_preference.as_ref()?;
+        select_family(preference, compiler_works)
+    })
+}
@@ -0,0 +1,73 @@
+//! Running generic builds inside a container, for hermetic cross-compilation
+//!
+//! The workspace is mounted into the container at the same path it has on the
+//! host, so any path the build (or we) compute means the same thing on both
+//! sides, and outputs show up exactly where we'd look for them anyway.
+
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+
+use super::{build_user, limits};
+use crate::{DistGraph, DistResult};
+
+/// The container engine we use if none is configured
+const DEFAULT_CONTAINER_ENGINE: &str = "docker";
+
+/// The container engine builds are run with
+pub(crate) fn engine(dist_graph: &DistGraph) -> &str {
+    dist_graph
+        .generic_build
+        .container_engine
+        .as_deref()
+        .unwrap_or(DEFAULT_CONTAINER_ENGINE)
+}
+
+/// Wrap a fully-assembled build command so that it runs in `image`
+///
+/// The environment we computed for the build is forwarded with `-e`,
+/// rather than being set on the container engine itself.
+pub(crate) fn containerize(dist_graph: &DistGraph, command: &Cmd, image: &str) -> DistResult<Cmd> {
+    let engine = engine(dist_graph);
+    let workspace = &dist_graph.workspace_dir;
+    let workdir = match command.get_current_dir() {
+        Some(dir) => dir.to_owned(),
+        None => std::env::current_dir()?,
+    };
+    let workdir = Utf8PathBuf::from_path_buf(workdir).expect("current dir isn't utf8!?");
+
+    let mut wrapped = Cmd::new(engine, format!("exec containerized build: {image}"));
+    wrapped
+        .arg("run")
+        .arg("--rm")
+        .arg("--volume")
+        .arg(format!("{workspace}:{workspace}"))
+        .arg("--workdir")
+        .arg(workdir.as_str());
+    if let Some(user) = build_user::container_user(dist_graph) {
+        wrapped.arg("--user").arg(user);
+    }
+    // The engine only passes stdin along if asked to
+    if dist_graph.generic_build.stdin_file.is_some() {
+        wrapped.arg("--interactive");
+    }
+    for arg in limits::container_args(dist_graph) {
+        wrapped.arg(arg);
+    }
+    for (key, val) in command.get_envs() {
+        // Removals only make sense for the host environment, which
+        // the container doesn't inherit anyway
+        let Some(val) = val else {
+            continue;
+        };
+        wrapped.arg("--env").arg(format!(
+            "{}={}",
+            key.to_string_lossy(),
+            val.to_string_lossy()
+        ));
+    }
+    wrapped.arg(image).arg(command.get_program());
+    for arg in command.get_args() {
+        wrapped.arg(arg);
+    }
+    Ok(wrapped)
+}
@@ -0,0 +1,115 @@
+//! Builds that make data for other builds, rather than anything to ship
+//!
+//! Some builds need files generated by another command first, like a parser
+//! generated from a grammar, or resources to embed. `data-builds` declares
+//! those commands, and the files they make:
+//!
+//! ```toml
+//! data-builds = [{ name = "grammar", build-command = ["make", "grammar"], outputs = ["gen/*.tables"] }]
+//! depends-on = { app = ["grammar"] }
+//! ```
+//!
+//! A data build runs (for each target, in the workspace) before any build that
+//! lists it in `depends-on`. Its outputs, which are found relative to the
+//! workspace, are then copied to `cargo-dist-data/<target>/<name>` in the
+//! target dir rather than the dist dir, and never become artifacts or show up
+//! in the manifest. Builds that depend on it are told where they are in
+//! `CARGO_DIST_DATA_<NAME>` (the name upper-cased, with anything that isn't a
+//! letter or digit made an underscore), so `CARGO_DIST_DATA_GRAMMAR` here.
+
+use camino::Utf8PathBuf;
+use tracing::info;
+
+use super::{
+    outputs::collect_and_copy_outputs, outputs::glob_outputs, relay::BuildLog, run_builds,
+};
+use crate::{config::DataBuild, DistError, DistGraph, DistResult, GenericBuildStep};
+
+/// The data build called `name`
+pub fn data_build<'a>(dist_graph: &'a DistGraph, name: &str) -> Option<&'a DataBuild> {
+    dist_graph
+        .generic_build
+        .data_builds
+        .iter()
+        .flatten()
+        .find(|data| data.name == name)
+}
+
+/// Where the outputs of a data build for `target` are kept
+pub fn data_dir(dist_graph: &DistGraph, target: &str, name: &str) -> Utf8PathBuf {
+    dist_graph
+        .target_dir
+        .join("cargo-dist-data")
+        .join(target)
+        .join(name)
+}
+
+/// The variable that tells dependent builds where a data build's outputs are
+pub fn data_env_var(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("CARGO_DIST_DATA_{name}")
+}
+
+/// The variables that tell a build where the data builds it depends on put their outputs
+pub(crate) fn data_env(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+) -> Vec<(String, Utf8PathBuf)> {
+    step.depends_on
+        .iter()
+        .filter(|dependency| data_build(dist_graph, dependency).is_some())
+        .map(|name| {
+            let dir = data_dir(dist_graph, &step.target_triple, name);
+            (data_env_var(name), dir)
+        })
+        .collect()
+}
+
+/// Run a data build, and keep its outputs for the builds that depend on it
+pub(crate) fn build_data(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    name: &str,
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let data = data_build(dist_graph, name).expect("data builds are planned from data-builds");
+    eprintln!(
+        "building data {name} for {} (via {})",
+        target.target_triple,
+        target.build_command.join(" ")
+    );
+    let log = BuildLog::create(dist_graph, &format!("data-{name}-{}", target.target_triple))?
+        .with_verbosity(target.verbosity);
+    let (status, _signature) = run_builds(dist_graph, &target.commands(), Some(target), &log)?;
+    info!("build log written to {}", log.path);
+    if !status.success() {
+        eprintln!("Build exited non-zero: {}", status);
+    }
+
+    // Nothing from an earlier run should look like it came from this one
+    let dir = data_dir(dist_graph, &target.target_triple, name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::create_dir_all(&dir)?;
+    let mut copied = vec![];
+    for pattern in &data.outputs {
+        let expected = glob_outputs(&dist_graph.workspace_dir, pattern)?;
+        if expected.is_empty() {
+            return Err(DistError::MissingExtraOutputs {
+                target: target.target_triple.clone(),
+                pattern: pattern.clone(),
+            });
+        }
+        copied.extend(collect_and_copy_outputs(&expected, &dir, dist_graph)?);
+    }
+    Ok(copied)
+}
@@ -0,0 +1,115 @@
+//! Splitting debug info out of ELF binaries
+//!
+//! With `split-debuginfo` we ship stripped binaries, and put their debug info
+//! in a separate `.debug` file (for symbol servers and the like). This is the
+//! classic objcopy dance:
+//!
+//! 1. `objcopy --only-keep-debug bin bin.debug` to extract the debug info
+//! 2. `strip --strip-debug --strip-unneeded bin` to remove it from the binary
+//!    (along with any existing debuglink)
+//! 3. `objcopy --add-gnu-debuglink=bin.debug bin` so debuggers can find it again
+//!
+//! We work on a copy of each output, since stripping the build's own outputs
+//! would leave build systems thinking a stripped binary is up to date.
+
+use std::env;
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::{staging, symbols};
+use crate::{build::output_kind::OutputKind, Binary, DistGraph, DistResult, GenericBuildStep};
+
+/// Whether a target's binaries are ELF, and so can have their debug info split this way
+pub fn is_elf_target(target: &str) -> bool {
+    !target.contains("windows") && !target.contains("apple") && !target.contains("wasm")
+}
+
+/// Whether split-debuginfo applies to the given binary
+pub(crate) fn wants_split_debuginfo(dist_graph: &DistGraph, binary: &Binary) -> bool {
+    dist_graph.generic_build.split_debuginfo.unwrap_or(false)
+        && is_elf_target(&binary.target)
+        // Static libraries aren't linked yet, so stripping them would break them
+        && OutputKind::detect(&binary.file_name, &binary.target) != OutputKind::StaticLibrary
+}
+
+/// Split the debug info out of a build output, if configured to
+///
+/// Returns the stripped copy of the output and its debug info, in that order.
+pub(crate) fn split_debuginfo(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    binary: &Binary,
+    output: &Utf8Path,
+) -> DistResult<Option<(Utf8PathBuf, Utf8PathBuf)>> {
+    if !wants_split_debuginfo(dist_graph, binary) {
+        return Ok(None);
+    }
+
+    let staging = staging::work_dir(dist_graph, &target.target_triple)
+        .join("split-debuginfo")
+        .join(&target.target_triple);
+    std::fs::create_dir_all(&staging)?;
+    let stripped = staging.join(&binary.file_name);
+    let debug_name = debug_file_name(binary);
+    let debug = staging.join(&debug_name);
+    std::fs::copy(output, &stripped)?;
+
+    let host = &dist_graph.tools.cargo.host_target;
+    let objcopy = resolved_binutil("OBJCOPY", "objcopy", &target.target_triple, host);
+    let strip = resolved_binutil("STRIP", "strip", &target.target_triple, host);
+    let name = &binary.file_name;
+    Cmd::new(&objcopy, format!("extract debug info from {name}"))
+        .arg("--only-keep-debug")
+        .arg(&stripped)
+        .arg(&debug)
+        .run()?;
+    Cmd::new(strip, format!("strip debug info from {name}"))
+        .arg("--strip-debug")
+        .arg("--strip-unneeded")
+        // Any existing link would point at the wrong debug file
+        .arg("--remove-section=.gnu_debuglink")
+        .arg(&stripped)
+        .run()?;
+    // The debuglink records the debug file's name, so run this next to it
+    Cmd::new(&objcopy, format!("link {name} to its debug info"))
+        .arg(format!("--add-gnu-debuglink={debug_name}"))
+        .arg(&binary.file_name)
+        .current_dir(&staging)
+        .run()?;
+
+    Ok(Some((stripped, debug)))
+}
+
+/// What to call a binary's debug file
+///
+/// The debuglink records this name, and debuggers look for exactly that file, so
+/// it has to be the name the symbols artifact ships under (once it's
+/// decompressed, if `compress-symbols` is on), not the binary's.
+pub(crate) fn debug_file_name(binary: &Binary) -> String {
+    binary
+        .copy_symbols_to
+        .first()
+        .and_then(|path| path.file_name())
+        .map(|name| symbols::uncompressed_name(name).to_owned())
+        .unwrap_or_else(|| format!("{}.debug", binary.file_name))
+}
+
+/// The binutils tool to use for a target, like `aarch64-linux-gnu-objcopy`
+///
+/// `env_var` (like OBJCOPY) overrides it, the same way CC does for compilers.
+pub fn resolved_binutil(env_var: &str, tool: &str, target: &str, host: &str) -> String {
+    env::var(env_var).unwrap_or_else(|_| format!("{}{tool}", binutils_prefix(target, host)))
+}
+
+/// The prefix cross binutils for a target usually have, like `aarch64-linux-gnu-`
+fn binutils_prefix(target: &str, host: &str) -> String {
+    if target == host {
+        return String::new();
+    }
+    // GNU triples don't have rust's "unknown" vendor
+    match target.split('-').collect::<Vec<_>>().as_slice() {
+        [arch, "unknown", rest @ ..] => format!("{arch}-{}-", rest.join("-")),
+        _ => format!("{target}-"),
+    }
+}
@@ -0,0 +1,40 @@
+//! Which targets to build for when none are given
+//!
+//! Without `--target`, a local build is for every target the packages
+//! support, which is rarely what's wanted for a quick build on a laptop.
+//! `default-targets` lists what to build instead:
+//!
+//! ```toml
+//! [dist.generic-build]
+//! default-targets = ["host", "wasm32-wasi"]
+//! ```
+//!
+//! `"host"` stands for the machine cargo-dist is running on. It only applies
+//! to building artifacts (`--artifacts=local` or `host`): planning a release
+//! (`--artifacts=global` or `all`) still covers every target, so CI, which
+//! plans with everything and then passes each build its `--target`, is
+//! unaffected.
+
+use crate::config::{ArtifactMode, GenericBuildConfig};
+
+/// What `"host"` in `default-targets` stands for
+pub const HOST: &str = "host";
+
+/// The targets to build for if none were given, if they're configured for this kind of run
+pub fn default_targets(
+    config: &GenericBuildConfig,
+    artifact_mode: ArtifactMode,
+    host_target: &str,
+) -> Option<Vec<String>> {
+    if !matches!(artifact_mode, ArtifactMode::Local | ArtifactMode::Host) {
+        return None;
+    }
+    let mut targets = Vec::<String>::new();
+    for target in config.default_targets.as_ref()? {
+        let target = if target == HOST { host_target } else { target };
+        if !targets.iter().any(|existing| existing == target) {
+            targets.push(target.to_owned());
+        }
+    }
+    (!targets.is_empty()).then_some(targets)
+}
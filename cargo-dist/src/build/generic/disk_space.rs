@@ -0,0 +1,152 @@
+//! Stopping before the disk fills up
+//!
+//! A build that runs out of space partway through fails in confusing ways, and
+//! can leave half-written binaries behind. So before each build command runs,
+//! we check how much space is free on the dist dir's filesystem, and stop with
+//! an error if that's less than `min-free-space` (1GiB unless configured, like
+//! `min-free-space = "5GiB"`; `"0"` turns the check off) plus what the build is
+//! likely to write.
+//!
+//! What a build will write is a guess: each of its binaries is taken to be as
+//! big as the copy an earlier run left in the dist dir, or 64MiB if there's
+//! none, once for every place it's copied to. Checking before every command
+//! (rather than once at the start) catches space running out as builds pile
+//! up their outputs.
+//!
+//! Free space is only checked on unix and Windows, and builds in containers or
+//! on remote hosts are checked against the space here, where their outputs end
+//! up.
+
+use camino::Utf8Path;
+
+use super::sizes::parse_size;
+use crate::{DistError, DistGraph, DistResult, GenericBuildStep};
+
+/// How much space must be left free, unless configured otherwise
+const DEFAULT_MIN_FREE_SPACE: u64 = 1 << 30;
+/// How big we guess a binary we've never seen will be
+const DEFAULT_BINARY_ESTIMATE: u64 = 64 << 20;
+
+/// How much space must be left free on the dist dir's filesystem
+pub fn min_free_space(dist_graph: &DistGraph) -> DistResult<u64> {
+    match &dist_graph.generic_build.min_free_space {
+        Some(size) => {
+            parse_size(size).ok_or_else(|| DistError::InvalidMinFreeSpace { size: size.clone() })
+        }
+        None => Ok(DEFAULT_MIN_FREE_SPACE),
+    }
+}
+
+/// Roughly how much a build will write to the dist dir
+pub fn estimated_size(dist_graph: &DistGraph, target: Option<&GenericBuildStep>) -> u64 {
+    let Some(target) = target.filter(|step| !step.extra) else {
+        // Extra artifacts' builds say nothing about what they'll make
+        return DEFAULT_BINARY_ESTIMATE;
+    };
+    target
+        .expected_binaries
+        .iter()
+        .map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            let copies = binary.copy_exe_to.iter().chain(&binary.copy_symbols_to);
+            let size = binary
+                .copy_exe_to
+                .iter()
+                .find_map(|path| path.metadata().ok())
+                .map(|metadata| metadata.len())
+                .unwrap_or(DEFAULT_BINARY_ESTIMATE);
+            size.saturating_mul(copies.count() as u64)
+        })
+        .sum()
+}
+
+/// Check there's room for a build, finding out how much space is free with `free_space`
+pub fn check_free_space(
+    dist_graph: &DistGraph,
+    target: Option<&GenericBuildStep>,
+    free_space: impl Fn(&Utf8Path) -> Option<u64>,
+) -> DistResult<()> {
+    let min_free = min_free_space(dist_graph)?;
+    if min_free == 0 {
+        return Ok(());
+    }
+    // The dist dir might not have been made yet
+    let Some(dir) = dist_graph.dist_dir.ancestors().find(|dir| dir.exists()) else {
+        return Ok(());
+    };
+    let Some(free) = free_space(dir) else {
+        return Ok(());
+    };
+    let needed = min_free.saturating_add(estimated_size(dist_graph, target));
+    if free < needed {
+        return Err(DistError::LowDiskSpace {
+            build: target
+                .filter(|step| !step.extra)
+                .map(|step| step.target_triple.clone())
+                .unwrap_or_else(|| "extra artifacts".to_owned()),
+            dir: dist_graph.dist_dir.clone(),
+            free,
+            needed,
+        });
+    }
+    Ok(())
+}
+
+/// Check there's room for a build on the dist dir's filesystem
+pub(crate) fn ensure_free_space(
+    dist_graph: &DistGraph,
+    target: Option<&GenericBuildStep>,
+) -> DistResult<()> {
+    check_free_space(dist_graph, target, free_space)
+}
+
+/// How many bytes unprivileged users can still write to the filesystem `dir` is on
+#[cfg(unix)]
+pub fn free_space(dir: &Utf8Path) -> Option<u64> {
+    let path = std::ffi::CString::new(dir.as_str()).ok()?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is a valid C string and stats is big enough for what's written to it
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs succeeded, so it filled stats in
+    let stats = unsafe { stats.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Some((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+}
+
+/// How many bytes the current user can still write to the volume `dir` is on
+#[cfg(windows)]
+pub fn free_space(dir: &Utf8Path) -> Option<u64> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+    }
+
+    let dir = OsStr::new(dir.as_str())
+        .encode_wide()
+        .chain([0])
+        .collect::<Vec<_>>();
+    let mut available = 0;
+    // SAFETY: dir is nul-terminated, and the sizes we don't want may be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            dir.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn free_space(_dir: &Utf8Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,49 @@
+//! Showing what builds did to the dist dir
+//!
+//! When an artifact doesn't end up where you expected, the manifest only
+//! tells you where it should have gone. With `cargo dist build --diff-dist-dir`
+//! we snapshot the dist dir before the local builds and print what changed
+//! afterwards, including stray files nothing expected:
+//!
+//! ```text
+//! dist dir changes:
+//!   + app-x86_64-unknown-linux-gnu/app (1234 bytes)
+//!   ~ SHA256SUMS (120 -> 240 bytes)
+//!   - stale.txt (10 bytes)
+//! ```
+//!
+//! Files count as changed when their size or modification time did.
+
+use camino::Utf8Path;
+
+use super::watch::SourceSnapshot;
+use crate::DistResult;
+
+/// Snapshot the dist dir, to diff against later
+pub fn snapshot_dist_dir(dist_dir: &Utf8Path) -> DistResult<SourceSnapshot> {
+    SourceSnapshot::take(&[dist_dir.to_owned()], &[])
+}
+
+/// Describe what changed in the dist dir between two snapshots of it
+pub fn render_dist_diff(
+    dist_dir: &Utf8Path,
+    before: &SourceSnapshot,
+    after: &SourceSnapshot,
+) -> String {
+    let changed = after.changed_since(before);
+    if changed.is_empty() {
+        return "dist dir changes: none\n".to_owned();
+    }
+    let mut output = "dist dir changes:\n".to_owned();
+    for path in changed {
+        let relative = path.strip_prefix(dist_dir).unwrap_or(&path);
+        let line = match (before.size(&path), after.size(&path)) {
+            (None, Some(size)) => format!("  + {relative} ({size} bytes)\n"),
+            (Some(size), None) => format!("  - {relative} ({size} bytes)\n"),
+            (Some(old), Some(new)) => format!("  ~ {relative} ({old} -> {new} bytes)\n"),
+            (None, None) => continue,
+        };
+        output.push_str(&line);
+    }
+    output
+}
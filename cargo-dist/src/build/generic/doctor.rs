@@ -0,0 +1,103 @@
+//! Checking generic builds have what they need, before running any of them
+//!
+//! `cargo dist doctor` looks at the builds we'd run and checks that the
+//! programs each would need are installed: the build command itself, the C
+//! and C++ compilers we'd hand it (unless it's a Go build without cgo), and
+//! the configured linker. Builds in containers only need the container engine,
+//! since the image brings everything else. Programs are looked up on the PATH
+//! builds would get, including anything `setup-scripts` add to it.
+
+use std::ffi::OsString;
+
+use camino::Utf8PathBuf;
+use serde::Serialize;
+
+use super::{
+    container, linker, resolved_cc, resolved_cxx, shell::find_program_in, uses_c_compilers,
+};
+use crate::{env::setup_env, BuildStep, DistGraph, DistResult, TargetTriple};
+
+/// Whether one program a build needs is installed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DoctorCheck {
+    /// The target whose build needs it
+    pub target: TargetTriple,
+    /// What it's for, like "build command" or "CC"
+    pub role: String,
+    /// The program
+    pub program: String,
+    /// Where it was found, if it was
+    pub found: Option<Utf8PathBuf>,
+}
+
+/// Check every program the given builds would need
+pub fn doctor(dist_graph: &DistGraph, steps: &[BuildStep]) -> DistResult<Vec<DoctorCheck>> {
+    // setup-scripts can put things on the PATH, so look where builds would
+    let mut search_path = std::env::var_os("PATH").unwrap_or_default();
+    for (key, value) in setup_env(dist_graph)? {
+        if key == "PATH" {
+            search_path = value.clone().map(OsString::from).unwrap_or_default();
+        }
+    }
+
+    let mut checks = vec![];
+    for step in steps {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        let target = &step.target_triple;
+        let mut needed = vec![];
+        if step.container_image.is_some() {
+            needed.push(("container engine", container::engine(dist_graph).to_owned()));
+        } else {
+            for command in step.commands() {
+                if let Some(program) = command.first() {
+                    needed.push(("build command", program.clone()));
+                }
+            }
+            if uses_c_compilers(dist_graph, step) {
+                let cc = resolved_cc(dist_graph, target);
+                if let Some(linker) = &dist_graph.generic_build.linker {
+                    needed.push(("linker", linker::select_linker(linker, &cc).program));
+                }
+                needed.push(("CXX", resolved_cxx(dist_graph, target)));
+                needed.push(("CC", cc));
+            }
+        }
+        for (role, program) in needed {
+            // Compilers can come with arguments, like "zig cc"
+            let program = program
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_owned();
+            let check = DoctorCheck {
+                target: target.clone(),
+                role: role.to_owned(),
+                found: find_program_in(&program, &search_path),
+                program,
+            };
+            if !checks.contains(&check) {
+                checks.push(check);
+            }
+        }
+    }
+    checks.sort_by(|a, b| (&a.target, &a.role).cmp(&(&b.target, &b.role)));
+    Ok(checks)
+}
+
+/// Render the checks as a pass/fail list
+pub fn render_checks(checks: &[DoctorCheck]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        let (status, location) = match &check.found {
+            Some(path) => ("ok", format!(" ({path})")),
+            None => ("MISSING", String::new()),
+        };
+        out.push_str(&format!(
+            "{status:<8}{}: {} {}{location}\n",
+            check.target, check.role, check.program
+        ));
+    }
+    out
+}
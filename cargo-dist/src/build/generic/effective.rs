@@ -0,0 +1,203 @@
+//! Showing the config generic builds really end up with
+//!
+//! Between defaults, per-target settings, profiles, flags profiles and
+//! variants, it can be hard to tell what a build will actually be given.
+//! `cargo dist config --effective` resolves all of it the way a build would,
+//! and prints what each planned build gets:
+//!
+//! ```toml
+//! [[builds]]
+//! target = "x86_64-unknown-linux-gnu"
+//! builder = "command"
+//! commands = [["make", "release"]]
+//! cc = "gcc"
+//! cflags = "-O2 -march=x86-64-v3"
+//!
+//! [builds.env]
+//! CARGO_DIST_TARGET = "x86_64-unknown-linux-gnu"
+//!
+//! [builds.binaries.app]
+//! copy-exe-to = ["target/distrib/app-x86_64-unknown-linux-gnu/app"]
+//! ```
+//!
+//! Like repro manifests, the commands and environment are as they'd run on
+//! the host, before being wrapped to run in a container or on a remote host,
+//! and the values of variables that look like secrets are redacted.
+
+use std::collections::BTreeMap;
+
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+use serde::Serialize;
+
+use super::{
+    cmake::cmake_command_lines,
+    prepare_command,
+    repro::{is_secret, REDACTED},
+};
+use crate::{config::GenericBuilder, BuildStep, DistGraph, DistResult, GenericBuildStep};
+
+/// What every planned generic build is given, after everything's resolved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EffectiveConfig {
+    /// The builds, in the order they'd run
+    pub builds: Vec<EffectiveBuild>,
+}
+
+/// What one generic build is given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EffectiveBuild {
+    /// The target it builds for
+    pub target: String,
+    /// The kind of build system it drives
+    pub builder: GenericBuilder,
+    /// The variant it builds, if there are any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Whether it's only built for the host
+    pub host_only: bool,
+    /// The commands it runs, in order
+    pub commands: Vec<Vec<String>>,
+    /// Where they run, if not where cargo-dist is run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<Utf8PathBuf>,
+    /// The C compiler (CC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<String>,
+    /// The C++ compiler (CXX)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cxx: Option<String>,
+    /// CFLAGS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cflags: Option<String>,
+    /// CXXFLAGS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cxxflags: Option<String>,
+    /// LDFLAGS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ldflags: Option<String>,
+    /// The container it runs in, if it does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_image: Option<String>,
+    /// The machine it runs on over SSH, if it does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_host: Option<String>,
+    /// The variables we hide from it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_env: Vec<String>,
+    /// Every variable we set for it
+    pub env: BTreeMap<String, String>,
+    /// Where each of its binaries is copied to, by name
+    pub binaries: BTreeMap<String, EffectiveDestinations>,
+}
+
+/// Where a binary is copied to once it's built
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EffectiveDestinations {
+    /// Where the binary goes
+    pub copy_exe_to: Vec<Utf8PathBuf>,
+    /// Where its symbols go
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub copy_symbols_to: Vec<Utf8PathBuf>,
+}
+
+/// Resolve what each of the given steps' builds would be given
+pub fn effective_config(
+    dist_graph: &DistGraph,
+    steps: &[BuildStep],
+) -> DistResult<EffectiveConfig> {
+    let mut builds = vec![];
+    for step in steps {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        builds.push(effective_build(dist_graph, step)?);
+    }
+    Ok(EffectiveConfig { builds })
+}
+
+/// Resolve what a step's build would be given
+pub(crate) fn effective_build(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+) -> DistResult<EffectiveBuild> {
+    let command_lines = match step.builder {
+        GenericBuilder::Cmake => Vec::from(cmake_command_lines(dist_graph, step)),
+        GenericBuilder::Command | GenericBuilder::Go => step
+            .commands()
+            .into_iter()
+            .map(<[String]>::to_vec)
+            .collect(),
+    };
+    let commands = command_lines
+        .iter()
+        .map(|line| prepare_command(dist_graph, line, Some(step)))
+        .collect::<DistResult<Vec<Cmd>>>()?;
+    // They're all prepared for the same step, so they have the same environment
+    let command = commands
+        .first()
+        .expect("a build must have at least one command");
+
+    let mut env = BTreeMap::new();
+    let mut removed_env = vec![];
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(_) if is_secret(&key) => {
+                env.insert(key, REDACTED.to_owned());
+            }
+            Some(value) => {
+                env.insert(key, value.to_string_lossy().into_owned());
+            }
+            None => removed_env.push(key),
+        }
+    }
+    let var = |key: &str| env.get(key).cloned();
+
+    let mut binaries = BTreeMap::<String, EffectiveDestinations>::new();
+    for binary_idx in &step.expected_binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        let dests = binaries.entry(binary.name.clone()).or_default();
+        dests.copy_exe_to.extend(binary.copy_exe_to.iter().cloned());
+        dests
+            .copy_symbols_to
+            .extend(binary.copy_symbols_to.iter().cloned());
+    }
+
+    Ok(EffectiveBuild {
+        target: step.target_triple.clone(),
+        builder: step.builder,
+        variant: step.variant.clone(),
+        host_only: step.host_only,
+        commands: commands
+            .iter()
+            .map(|command| {
+                std::iter::once(command.get_program())
+                    .chain(command.get_args())
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect()
+            })
+            .collect(),
+        cwd: command
+            .get_current_dir()
+            .and_then(|dir| Utf8PathBuf::from_path_buf(dir.to_owned()).ok()),
+        cc: var("CC"),
+        cxx: var("CXX"),
+        cflags: var("CFLAGS"),
+        cxxflags: var("CXXFLAGS"),
+        ldflags: var("LDFLAGS"),
+        container_image: step.container_image.clone(),
+        remote_host: step.remote_host.as_ref().map(|remote| remote.host.clone()),
+        removed_env,
+        env,
+        binaries,
+    })
+}
+
+/// Render the effective config as TOML
+pub fn render_effective_config(config: &EffectiveConfig) -> String {
+    axoasset::toml::to_string_pretty(config).expect("toml serialization failed")
+}
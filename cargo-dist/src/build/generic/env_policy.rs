@@ -0,0 +1,103 @@
+//! Choosing which of our environment builds inherit
+//!
+//! Builds normally inherit all of cargo-dist's environment, so anything set on
+//! the machine (or by CI) can change what they make. `env-policy` (or, for
+//! particular targets, `target-env-policy`) narrows that down:
+//!
+//! * `inherit` (the default) passes everything on.
+//! * `filtered` only passes on the variables `env-allowlist` names (which can
+//!   end in `*` to match a prefix, like `CARGO_*`).
+//! * `clean` passes on nothing.
+//!
+//! Either way, builds still get the few variables they need to run programs at
+//! all (like `PATH` and `HOME`, and `SYSTEMROOT` on Windows), and everything we
+//! set for them ourselves: Homebrew's environment, `extra-env`, `env-file`,
+//! compilers and flags, and so on.
+
+use std::ffi::OsString;
+
+use axoprocess::Cmd;
+
+use crate::{config::EnvPolicy, DistGraph, GenericBuildStep};
+
+/// The variables builds always inherit, since programs can't be found or run without them
+pub const ESSENTIAL_VARS: &[&str] = &[
+    "PATH",
+    "HOME",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "USERPROFILE",
+];
+
+/// The policy for a build, from `target-env-policy` or else `env-policy`
+pub fn env_policy(dist_graph: &DistGraph, target: Option<&GenericBuildStep>) -> EnvPolicy {
+    let config = &dist_graph.generic_build;
+    target
+        .and_then(|step| config.target_env_policy.as_ref()?.get(&step.target_triple))
+        .copied()
+        .or(config.env_policy)
+        .unwrap_or_default()
+}
+
+/// Whether `name` is `pattern`, or starts with it if it ends in `*`
+fn matches(name: &str, pattern: &str) -> bool {
+    // Windows doesn't care about the case of variable names
+    let (name, pattern) = if cfg!(windows) {
+        (name.to_ascii_uppercase(), pattern.to_ascii_uppercase())
+    } else {
+        (name.to_owned(), pattern.to_owned())
+    };
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Which of the given variables a build keeps under `policy`
+///
+/// None means it inherits all of them.
+pub fn inherited_env(
+    policy: EnvPolicy,
+    allowlist: &[String],
+    vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Option<Vec<(OsString, OsString)>> {
+    if policy == EnvPolicy::Inherit {
+        return None;
+    }
+    let allowed = |name: &str| {
+        ESSENTIAL_VARS
+            .iter()
+            .any(|essential| matches(name, essential))
+            || (policy == EnvPolicy::Filtered
+                && allowlist.iter().any(|pattern| matches(name, pattern)))
+    };
+    let kept = vars
+        .into_iter()
+        .filter(|(name, _)| name.to_str().is_some_and(allowed))
+        .collect();
+    Some(kept)
+}
+
+/// Make a build only inherit what its policy allows
+pub(crate) fn apply_env_policy(
+    dist_graph: &DistGraph,
+    command: &mut Cmd,
+    target: Option<&GenericBuildStep>,
+) {
+    let allowlist = dist_graph
+        .generic_build
+        .env_allowlist
+        .as_deref()
+        .unwrap_or_default();
+    let policy = env_policy(dist_graph, target);
+    if let Some(kept) = inherited_env(policy, allowlist, std::env::vars_os()) {
+        command.env_clear();
+        command.envs(kept);
+    }
+}
@@ -0,0 +1,193 @@
+//! A stream of build events, for progress UIs
+//!
+//! The build summary and dist-manifest only exist once everything's done,
+//! which is too late for a dashboard showing builds as they happen. With
+//! `event-stream`, we write a JSON object per line (JSON Lines) as things
+//! happen instead:
+//!
+//! ```json
+//! {"event":"build_started","target":"x86_64-unknown-linux-gnu"}
+//! {"event":"build_output","line":"cc -o app main.c"}
+//! {"event":"artifact_copied","path":"target/distrib/app-x86_64-unknown-linux-gnu/app","sha256":"..."}
+//! {"event":"build_finished","target":"x86_64-unknown-linux-gnu","status":"succeeded","duration_ms":1234}
+//! ```
+//!
+//! Extra-artifact builds have a `null` target. The stream goes to a file
+//! (relative to the workspace), or on unix to an already-open file descriptor
+//! given as `fd:<number>`, so a wrapper can read it through a pipe.
+//!
+//! Failing to write an event only warns: a dashboard going away shouldn't
+//! fail the release it was watching.
+
+use std::{
+    fs::File,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use camino::Utf8Path;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{config::ChecksumStyle, generate_checksum, DistGraph, DistResult};
+
+/// Something that happened during a build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BuildEvent {
+    /// A build is starting
+    BuildStarted {
+        /// The target being built (None for extra-artifact builds)
+        target: Option<String>,
+    },
+    /// A build printed a line (without its line ending or terminal escapes)
+    BuildOutput {
+        /// The line
+        line: String,
+    },
+    /// A build copied a file into the dist dir
+    ArtifactCopied {
+        /// Where it was copied to
+        path: String,
+        /// Its sha256 digest
+        sha256: String,
+    },
+    /// A build is done
+    BuildFinished {
+        /// The target that was built (None for extra-artifact builds)
+        target: Option<String>,
+        /// "succeeded" or "failed"
+        status: String,
+        /// How long it took, in milliseconds
+        duration_ms: u64,
+    },
+}
+
+/// Where events get written
+pub struct EventSink {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSink").finish_non_exhaustive()
+    }
+}
+
+impl EventSink {
+    /// Write events to the given writer
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+
+    /// Write an event, warning if that fails
+    pub fn emit(&self, event: &BuildEvent) {
+        let line = serde_json::to_string(event).expect("serde_json failed");
+        let mut out = self
+            .out
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = writeln!(out, "{line}").and_then(|()| out.flush()) {
+            warn!("couldn't write to the event stream: {e}");
+        }
+    }
+}
+
+/// The configured event sink, opened the first time it's needed
+pub(crate) fn sink(dist_graph: &DistGraph) -> DistResult<Option<Arc<EventSink>>> {
+    if let Some(sink) = dist_graph.event_sink.get() {
+        return Ok(sink.clone());
+    }
+    let opened = match &dist_graph.generic_build.event_stream {
+        Some(destination) => Some(Arc::new(EventSink::new(open(
+            &dist_graph.workspace_dir,
+            destination,
+        )?))),
+        None => None,
+    };
+    Ok(dist_graph.event_sink.get_or_init(|| opened).clone())
+}
+
+/// Write an event to the configured sink, if any
+pub(crate) fn emit(dist_graph: &DistGraph, event: BuildEvent) -> DistResult<()> {
+    if let Some(sink) = sink(dist_graph)? {
+        sink.emit(&event);
+    }
+    Ok(())
+}
+
+/// Run a build, with events for when it starts and finishes
+pub(crate) fn with_events<T>(
+    dist_graph: &DistGraph,
+    target: Option<&str>,
+    build: impl FnOnce() -> DistResult<T>,
+) -> DistResult<T> {
+    let started = emit_started(dist_graph, target)?;
+    let result = build();
+    emit_finished(dist_graph, target, started, result.is_ok())?;
+    result
+}
+
+/// Report that a build started, returning when it did
+pub(crate) fn emit_started(dist_graph: &DistGraph, target: Option<&str>) -> DistResult<Instant> {
+    emit(
+        dist_graph,
+        BuildEvent::BuildStarted {
+            target: target.map(|target| target.to_owned()),
+        },
+    )?;
+    Ok(Instant::now())
+}
+
+/// Report that a build which started at `started` finished
+pub(crate) fn emit_finished(
+    dist_graph: &DistGraph,
+    target: Option<&str>,
+    started: Instant,
+    succeeded: bool,
+) -> DistResult<()> {
+    let status = if succeeded { "succeeded" } else { "failed" };
+    emit(
+        dist_graph,
+        BuildEvent::BuildFinished {
+            target: target.map(|target| target.to_owned()),
+            status: status.to_owned(),
+            duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        },
+    )
+}
+
+/// Report that files were copied into the dist dir, if anyone's listening
+pub(crate) fn emit_copied<'a>(
+    dist_graph: &DistGraph,
+    paths: impl IntoIterator<Item = &'a Utf8Path>,
+) -> DistResult<()> {
+    let Some(sink) = sink(dist_graph)? else {
+        return Ok(());
+    };
+    for path in paths {
+        sink.emit(&BuildEvent::ArtifactCopied {
+            path: path.to_string(),
+            sha256: generate_checksum(&ChecksumStyle::Sha256, path)?,
+        });
+    }
+    Ok(())
+}
+
+/// Open an `event-stream` destination: `fd:<number>`, or a path
+fn open(workspace_dir: &Utf8Path, destination: &str) -> DistResult<File> {
+    #[cfg(unix)]
+    if let Some(fd) = destination
+        .strip_prefix("fd:")
+        .and_then(|fd| fd.parse::<i32>().ok())
+    {
+        use std::os::fd::FromRawFd;
+        // Whoever ran us opened this for us to write to, and nothing else in
+        // this process uses it
+        return Ok(unsafe { File::from_raw_fd(fd) });
+    }
+    Ok(File::create(workspace_dir.join(destination))?)
+}
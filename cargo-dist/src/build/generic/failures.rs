@@ -0,0 +1,103 @@
+//! Capturing the files that explain why a generic build failed
+//!
+//! Build systems tend to put their most useful diagnostics in files rather
+//! than on stdout (autotools' `config.log`, CMake's `CMakeError.log`...),
+//! which are lost with the CI runner. With `failure-artifacts` we copy them
+//! to somewhere in the dist dir that CI can upload.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+
+use crate::{DistGraph, DistResult, GenericBuildStep};
+
+/// The directory (under dist_dir) failure artifacts are written to
+pub(crate) const BUILD_FAILURES_DIR: &str = "build-failures";
+
+/// Where failure artifacts for the given step go
+pub(crate) fn failure_artifacts_dir(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> Utf8PathBuf {
+    dist_graph
+        .dist_dir
+        .join(BUILD_FAILURES_DIR)
+        .join(&target.target_triple)
+}
+
+/// Copy every file under `root` matching one of the `failure-artifacts` patterns
+///
+/// Files keep their path relative to `root`. Returns the paths written to.
+pub(crate) fn capture_failure_artifacts(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    root: &Utf8Path,
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let Some(patterns) = &dist_graph.generic_build.failure_artifacts else {
+        return Ok(vec![]);
+    };
+    let dest_dir = failure_artifacts_dir(dist_graph, target);
+    let patterns = patterns
+        .iter()
+        .filter_map(|pattern| match glob::Pattern::new(pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("ignoring invalid failure-artifacts pattern {pattern}: {e}");
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if patterns.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // In-tree builds run in ".", which we need to compare against the target dir
+    let root = if root.is_absolute() {
+        root.to_owned()
+    } else {
+        Utf8PathBuf::from_path_buf(std::env::current_dir()?)
+            .unwrap_or_default()
+            .join(root)
+    };
+    let mut files = vec![];
+    // The target dir holds our own outputs (including previously captured
+    // failure artifacts), and can be huge, so don't go looking in it
+    find_files(&root, &dist_graph.target_dir, &mut files)?;
+
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let mut captured = vec![];
+    for path in files {
+        let relative = path.strip_prefix(&root).unwrap_or(&path);
+        if !patterns
+            .iter()
+            .any(|pattern| pattern.matches_path_with(relative.as_std_path(), options))
+        {
+            continue;
+        }
+        let dest = dest_dir.join(relative);
+        std::fs::create_dir_all(dest.parent().expect("failure artifacts have parents"))?;
+        std::fs::copy(&path, &dest)?;
+        captured.push(dest);
+    }
+    Ok(captured)
+}
+
+/// Every file under `dir`, except anything in `skip` or VCS metadata
+fn find_files(dir: &Utf8Path, skip: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> DistResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path == skip || entry.file_name() == ".git" {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_files(path, skip, files)?;
+        } else if path.is_file() {
+            files.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
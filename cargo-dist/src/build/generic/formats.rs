@@ -0,0 +1,95 @@
+//! Checking that extra builds produced the right kind of files
+//!
+//! An extra build that fails halfway can still leave a file at the expected
+//! path, like an empty installer script or a truncated schema, which would
+//! otherwise be uploaded as-is. An extra artifact's `formats` says what kind of
+//! file each of its artifacts should be, and once the build's done we take a
+//! quick look at each one:
+//!
+//! * `script`: not empty, and starting with a shebang (unless it's a
+//!   `.ps1`, `.bat` or `.cmd`, which Windows runs by extension)
+//! * `json`: parses as JSON
+//! * `archive`: starts with the magic bytes of a zip, tarball, or a gzip, xz,
+//!   zstd or bzip2 file
+//! * `oci-image`: an OCI image layout, or a tarball of one (see [`super::oci`][])
+//!
+//! Anything that doesn't look right fails the build. Artifacts without a
+//! format aren't checked.
+
+use std::io::Read;
+
+use camino::Utf8Path;
+
+use super::oci;
+use crate::{config::ArtifactFormat, DistError, DistResult};
+
+/// Extensions of scripts that don't need a shebang
+const WINDOWS_SCRIPT_EXTS: &[&str] = &["ps1", "bat", "cmd"];
+
+/// How compressed files and zips start
+const ARCHIVE_MAGIC: &[&[u8]] = &[
+    // zip
+    b"PK\x03\x04",
+    // gzip
+    &[0x1f, 0x8b],
+    // xz
+    &[0xfd, b'7', b'z', b'X', b'Z', 0x00],
+    // zstd
+    &[0x28, 0xb5, 0x2f, 0xfd],
+    // bzip2
+    b"BZh",
+];
+
+/// Where a tarball's magic is, and what it is
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// Check that the artifact at `path` is the kind of file it should be
+pub(crate) fn check_artifact_format(path: &Utf8Path, format: ArtifactFormat) -> DistResult<()> {
+    let invalid = |reason: &str| DistError::InvalidExtraArtifact {
+        path: path.to_owned(),
+        format,
+        reason: reason.to_owned(),
+    };
+    match format {
+        ArtifactFormat::Script => {
+            let header = read_header(path, 2)?;
+            if header.is_empty() {
+                return Err(invalid("it's empty"));
+            }
+            let needs_shebang = !path.extension().is_some_and(|ext| {
+                WINDOWS_SCRIPT_EXTS.contains(&ext.to_ascii_lowercase().as_str())
+            });
+            if needs_shebang && !header.starts_with(b"#!") {
+                return Err(invalid("it doesn't start with a shebang"));
+            }
+        }
+        ArtifactFormat::Json => {
+            let contents = std::fs::read_to_string(path)?;
+            if let Err(e) = serde_json::from_str::<serde::de::IgnoredAny>(&contents) {
+                return Err(invalid(&e.to_string()));
+            }
+        }
+        ArtifactFormat::Archive => {
+            let header = read_header(path, TAR_MAGIC_OFFSET + TAR_MAGIC.len())?;
+            let is_archive = ARCHIVE_MAGIC.iter().any(|magic| header.starts_with(magic))
+                || header.get(TAR_MAGIC_OFFSET..) == Some(TAR_MAGIC);
+            if !is_archive {
+                return Err(invalid("it doesn't start like any archive we know"));
+            }
+        }
+        ArtifactFormat::OciImage => {
+            oci::oci_image_digest(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Up to the first `len` bytes of a file
+fn read_header(path: &Utf8Path, len: usize) -> DistResult<Vec<u8>> {
+    let mut header = vec![];
+    std::fs::File::open(path)?
+        .take(len as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
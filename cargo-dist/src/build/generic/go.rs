@@ -0,0 +1,42 @@
+//! Support for generic builds that are Go projects
+
+/// Rust target triple architectures, and the `GOARCH` Go uses for them
+const GOARCH_MAPPING: &[(&str, &str)] = &[
+    ("x86_64", "amd64"),
+    ("aarch64", "arm64"),
+    ("i686", "386"),
+    ("i586", "386"),
+    ("armv7", "arm"),
+    ("arm", "arm"),
+    ("powerpc64le", "ppc64le"),
+    ("riscv64gc", "riscv64"),
+    ("s390x", "s390x"),
+    ("wasm32", "wasm"),
+];
+
+/// Rust target triple fragments, and the `GOOS` Go uses for them
+///
+/// These are checked in order, so more specific fragments must come first
+/// (android triples also contain "linux").
+const GOOS_MAPPING: &[(&str, &str)] = &[
+    ("apple-darwin", "darwin"),
+    ("apple-ios", "ios"),
+    ("linux-android", "android"),
+    ("linux", "linux"),
+    ("windows", "windows"),
+    ("freebsd", "freebsd"),
+    ("netbsd", "netbsd"),
+    ("openbsd", "openbsd"),
+    ("illumos", "illumos"),
+    ("wasi", "wasip1"),
+];
+
+/// Translate a Rust target triple into Go's `(GOOS, GOARCH)`
+pub(crate) fn go_os_arch(target: &str) -> Option<(&'static str, &'static str)> {
+    let arch = target.split('-').next()?;
+    let (_, goarch) = GOARCH_MAPPING.iter().find(|(rust, _)| *rust == arch)?;
+    let (_, goos) = GOOS_MAPPING
+        .iter()
+        .find(|(fragment, _)| target.contains(fragment))?;
+    Some((goos, goarch))
+}
@@ -0,0 +1,99 @@
+//! Commands run once around all the builds
+//!
+//! Some setup is for the whole build rather than any one target, like starting
+//! a local package registry every build fetches from. `before-all` runs once
+//! before any generic or extra build, and if it fails nothing is built.
+//! `after-all` runs once they're all done, even if one failed, so it can tear
+//! that setup down or gather up results. It's told how the builds went with
+//! `CARGO_DIST_BUILD_RESULT` (`success` or `failure`) and
+//! `CARGO_DIST_FAILED_TARGETS`, the targets whose builds failed, one per line.
+//!
+//! Both run on the host, like extra builds, with their output in the build
+//! logs as `before-all.log` and `after-all.log`.
+
+use cargo_dist_schema::BuildSummary;
+
+use super::{
+    prepare_command,
+    relay::{run_relayed, BuildLog},
+    wrap_command,
+};
+use crate::{DistError, DistGraph, DistResult};
+
+/// The variable after-all is told whether the builds succeeded in
+pub const BUILD_RESULT: &str = "CARGO_DIST_BUILD_RESULT";
+
+/// The variable after-all gets the targets that failed in
+pub const FAILED_TARGETS: &str = "CARGO_DIST_FAILED_TARGETS";
+
+/// Run `build` between before-all and after-all
+///
+/// `build` records how each generic build went in the summary it's given,
+/// which is returned if everything succeeds. If the builds fail that's the
+/// error returned, even if after-all fails too.
+pub fn run_with_hooks(
+    dist_graph: &DistGraph,
+    build: impl FnOnce(&mut Vec<BuildSummary>) -> DistResult<()>,
+) -> DistResult<Vec<BuildSummary>> {
+    run_before_all(dist_graph)?;
+    let mut build_summary = vec![];
+    let built = build(&mut build_summary);
+    let failed = build_summary
+        .iter()
+        .filter(|build| !build.succeeded)
+        .map(|build| build.target_triple.as_str())
+        .collect::<Vec<_>>();
+    let after_all = run_after_all(dist_graph, built.is_ok(), &failed);
+    built?;
+    after_all?;
+    Ok(build_summary)
+}
+
+/// Run before-all, if it's configured
+pub fn run_before_all(dist_graph: &DistGraph) -> DistResult<()> {
+    let Some(command_string) = &dist_graph.generic_build.before_all else {
+        return Ok(());
+    };
+    run_hook(dist_graph, "before-all", command_string, &[])
+}
+
+/// Run after-all, if it's configured, telling it whether the builds succeeded
+pub fn run_after_all(
+    dist_graph: &DistGraph,
+    succeeded: bool,
+    failed_targets: &[&str],
+) -> DistResult<()> {
+    let Some(command_string) = &dist_graph.generic_build.after_all else {
+        return Ok(());
+    };
+    let result = if succeeded { "success" } else { "failure" };
+    let env = [
+        (BUILD_RESULT, result.to_owned()),
+        (FAILED_TARGETS, failed_targets.join("\n")),
+    ];
+    run_hook(dist_graph, "after-all", command_string, &env)
+}
+
+fn run_hook(
+    dist_graph: &DistGraph,
+    hook: &str,
+    command_string: &[String],
+    env: &[(&str, String)],
+) -> DistResult<()> {
+    eprintln!("running {hook} ({})", command_string.join(" "));
+    let log = BuildLog::create(dist_graph, hook)?;
+    let mut command = prepare_command(dist_graph, command_string, None)?;
+    for (key, value) in env {
+        command.env(key, value);
+    }
+    let mut command = wrap_command(dist_graph, command, None)?;
+    command.check(false);
+    let status = run_relayed(dist_graph, &mut command, &log)?;
+    if !status.success() {
+        return Err(DistError::BuildHookFailed {
+            hook: hook.to_owned(),
+            status: status.to_string(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Running all the builds again when the machine, not a build, let them down
+//!
+//! `retries` runs a failed build again straight away, which doesn't help when
+//! the runner is what's broken: the disk filled up, or the kernel killed the
+//! build to free memory. With `infra-retries`, a failure like that starts all
+//! the builds over (hooks included), a bounded number of times. Everything
+//! else, like a compile error, fails straight away as usual.
+//!
+//! A failure is put down to the machine when:
+//!
+//! * a file couldn't be written because the disk (or a quota) was full
+//! * a build couldn't be started for lack of memory or processes
+//! * a build was killed by `SIGKILL` (what the OOM killer sends) or `SIGBUS`,
+//!   or exited with one of the `infra-exit-codes`
+//!
+//! Builds stopped for exceeding `max-memory-mb` or `max-cpu-seconds`, and
+//! interrupted builds, are never retried.
+
+use std::process::ExitStatus;
+
+use cargo_dist_schema::DistManifest;
+use tracing::warn;
+
+use super::staging_manifest::reset_staging_manifest;
+use crate::{DistError, DistGraph, DistResult};
+
+/// Run `attempt` until it succeeds, fails for a reason that isn't the machine's, or we run out of retries
+///
+/// Each retry starts from the manifest as it was before the first attempt.
+pub(crate) fn with_infra_retries<T>(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    mut attempt: impl FnMut(&mut DistManifest) -> DistResult<T>,
+) -> DistResult<T> {
+    let retries = dist_graph.generic_build.infra_retries.unwrap_or(0);
+    let pristine = (retries > 0).then(|| manifest.clone());
+    let mut retried = 0;
+    loop {
+        let result = attempt(manifest);
+        let Err(e) = &result else {
+            return result;
+        };
+        let Some(pristine) = pristine.as_ref().filter(|_| retried < retries) else {
+            return result;
+        };
+        let Some(reason) = infra_failure(dist_graph, e) else {
+            return result;
+        };
+        retried += 1;
+        warn!("the builds failed because {reason}, running them all again ({retried}/{retries})");
+        manifest.clone_from(pristine);
+        reset_staging_manifest(dist_graph)?;
+    }
+}
+
+/// Why an error is the machine's fault rather than the build's, if it is
+pub fn infra_failure(dist_graph: &DistGraph, error: &DistError) -> Option<String> {
+    match error {
+        DistError::Io(e) | DistError::CopyFailed { details: e, .. } => io_failure(e),
+        DistError::Cmd(axoprocess::AxoprocessError::Exec { cause, .. }) => io_failure(cause),
+        DistError::Cmd(axoprocess::AxoprocessError::Status { status, .. }) => {
+            status_failure(dist_graph, *status)
+        }
+        _ => None,
+    }
+}
+
+/// Why an exit status is the machine's fault, if it is
+fn status_failure(dist_graph: &DistGraph, status: ExitStatus) -> Option<String> {
+    let code = status.code();
+    let extra_codes = dist_graph.generic_build.infra_exit_codes.as_deref();
+    if code.is_some_and(|code| extra_codes.unwrap_or_default().contains(&code)) {
+        return Some(format!(
+            "a build exited with {status}, which is in infra-exit-codes"
+        ));
+    }
+    killed_by(status).map(|signal| format!("a build was killed by {signal}"))
+}
+
+/// The signal that killed a process, if it's one only the machine sends
+#[cfg(unix)]
+fn killed_by(status: ExitStatus) -> Option<&'static str> {
+    use std::os::unix::process::ExitStatusExt;
+
+    // Shells and container engines report a child killed by a signal as 128 + the signal
+    let signal = status.signal().or_else(|| {
+        status
+            .code()
+            .filter(|code| *code > 128)
+            .map(|code| code - 128)
+    })?;
+    match signal {
+        libc::SIGKILL => Some("SIGKILL (was it out of memory?)"),
+        libc::SIGBUS => Some("SIGBUS (was a mapped file's disk full?)"),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn killed_by(_status: ExitStatus) -> Option<&'static str> {
+    None
+}
+
+/// Why an i/o error is the machine's fault, if it is
+fn io_failure(error: &std::io::Error) -> Option<String> {
+    let code = error.raw_os_error()?;
+    if is_disk_full(code) {
+        Some(format!("the disk was full ({error})"))
+    } else if is_out_of_resources(code) {
+        Some(format!("the machine ran out of resources ({error})"))
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn is_disk_full(code: i32) -> bool {
+    code == libc::ENOSPC || code == libc::EDQUOT
+}
+
+#[cfg(unix)]
+fn is_out_of_resources(code: i32) -> bool {
+    code == libc::ENOMEM || code == libc::EAGAIN
+}
+
+#[cfg(windows)]
+fn is_disk_full(code: i32) -> bool {
+    /// There is not enough space on the disk
+    const ERROR_DISK_FULL: i32 = 112;
+    /// The disk is full
+    const ERROR_HANDLE_DISK_FULL: i32 = 39;
+    code == ERROR_DISK_FULL || code == ERROR_HANDLE_DISK_FULL
+}
+
+#[cfg(windows)]
+fn is_out_of_resources(code: i32) -> bool {
+    /// Not enough memory resources are available to process this command
+    const ERROR_NOT_ENOUGH_MEMORY: i32 = 8;
+    /// Not enough memory resources are available to complete this operation
+    const ERROR_OUTOFMEMORY: i32 = 14;
+    code == ERROR_NOT_ENOUGH_MEMORY || code == ERROR_OUTOFMEMORY
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_disk_full(_code: i32) -> bool {
+    false
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_out_of_resources(_code: i32) -> bool {
+    false
+}
@@ -0,0 +1,64 @@
+//! Checking the files a build uses haven't been tampered with
+//!
+//! Builds that use vendored tarballs (or download them) can list them in
+//! `verify-inputs` along with their sha256. Inputs that are already there are
+//! checked before the build runs, so a tampered file never gets built. Ones
+//! that aren't there yet are expected to be downloaded by the build, and are
+//! checked as soon as it finishes, before anything it built is used.
+
+use camino::Utf8Path;
+
+use crate::{
+    config::{ChecksumStyle, InputChecksum},
+    generate_checksum, DistError, DistGraph, DistResult,
+};
+
+/// Check the inputs that already exist, returning the ones the build still has to fetch
+pub(crate) fn verify_inputs_before_build(
+    dist_graph: &DistGraph,
+    root: &Utf8Path,
+) -> DistResult<Vec<InputChecksum>> {
+    let mut pending = vec![];
+    for input in dist_graph.generic_build.verify_inputs.iter().flatten() {
+        if root.join(&input.path).exists() {
+            verify_input(input, root)?;
+        } else {
+            pending.push(input.clone());
+        }
+    }
+    Ok(pending)
+}
+
+/// Check the inputs the build was supposed to fetch
+pub(crate) fn verify_inputs_after_build(
+    pending: &[InputChecksum],
+    root: &Utf8Path,
+) -> DistResult<()> {
+    for input in pending {
+        if !root.join(&input.path).exists() {
+            return Err(DistError::BuildInputMissing {
+                path: input.path.clone(),
+            });
+        }
+        verify_input(input, root)?;
+    }
+    Ok(())
+}
+
+/// Check that an input has the hash it's supposed to
+pub fn verify_input(input: &InputChecksum, root: &Utf8Path) -> DistResult<()> {
+    let actual = generate_checksum(&ChecksumStyle::Sha256, &root.join(&input.path))?;
+    if actual.eq_ignore_ascii_case(input.sha256.trim()) {
+        return Ok(());
+    }
+    Err(DistError::BuildInputChecksumMismatch {
+        path: input.path.clone(),
+        origin: input
+            .url
+            .as_ref()
+            .map(|url| format!(" (from {url})"))
+            .unwrap_or_default(),
+        expected: input.sha256.clone(),
+        actual,
+    })
+}
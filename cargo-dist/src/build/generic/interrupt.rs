@@ -0,0 +1,127 @@
+//! Handling Ctrl-C during a generic build
+//!
+//! If we just died on Ctrl-C, the build we spawned could outlive us and keep
+//! writing to the dist dir. Instead, builds run in their own process group,
+//! and while one is running we catch Ctrl-C ourselves, forward it to the
+//! build, wait for the build to exit, and then clean up after it.
+//!
+//! (On Windows the build shares our console, so it gets the Ctrl-C anyway.)
+
+use std::{
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+};
+
+use tracing::warn;
+
+use crate::{DistGraph, GenericBuildStep};
+
+/// The exit code a shell reports for a process killed by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Whether we've been interrupted during a build
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The process id of the build that's currently running (if any)
+static RUNNING_BUILD: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Start catching Ctrl-C (idempotent)
+pub(crate) fn install_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        if let Err(e) = ctrlc::set_handler(on_interrupt) {
+            warn!("couldn't install a Ctrl-C handler, interrupted builds won't be cleaned up: {e}");
+        }
+    });
+}
+
+fn on_interrupt() {
+    let running = *RUNNING_BUILD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match running {
+        Some(pid) => {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            forward_interrupt(pid);
+        }
+        // Not in a build, so behave like we never caught it
+        None => std::process::exit(INTERRUPTED_EXIT_CODE),
+    }
+}
+
+#[cfg(unix)]
+fn forward_interrupt(pid: u32) {
+    // The build is the leader of its own process group, so this reaches
+    // anything it spawned too
+    // SAFETY: kill has no memory-safety preconditions
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn forward_interrupt(_pid: u32) {}
+
+/// Set up a build command so that Ctrl-C reaches it through us
+pub(crate) fn isolate(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Marks a build as running until dropped, so Ctrl-C gets forwarded to it
+pub(crate) struct RunningBuild(());
+
+impl RunningBuild {
+    /// Register the build with the given process id
+    pub(crate) fn new(pid: u32) -> Self {
+        *RUNNING_BUILD
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(pid);
+        Self(())
+    }
+}
+
+impl Drop for RunningBuild {
+    fn drop(&mut self) {
+        *RUNNING_BUILD
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+}
+
+/// Whether a build was interrupted with Ctrl-C
+pub(crate) fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Remove anything an interrupted build step left in the dist dir
+///
+/// Returns the paths that were removed.
+pub(crate) fn remove_partial_outputs(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> Vec<camino::Utf8PathBuf> {
+    let mut removed = vec![];
+    for binary_idx in &target.expected_binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        for path in binary.copy_exe_to.iter().chain(&binary.copy_symbols_to) {
+            // symlink_metadata so we also catch dangling symlinks
+            if path.symlink_metadata().is_err() {
+                continue;
+            }
+            match std::fs::remove_file(path) {
+                Ok(()) => removed.push(path.clone()),
+                Err(e) => warn!("couldn't clean up {path}: {e}"),
+            }
+        }
+    }
+    removed
+}
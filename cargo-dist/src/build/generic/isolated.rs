@@ -0,0 +1,114 @@
+//! Support for running generic builds outside the source tree
+//!
+//! Build systems that build in-tree leave their outputs lying around the
+//! source tree, where they dirty `git status` and can end up in the next
+//! release. With `isolated-build` we instead copy the sources to a scratch
+//! directory, build there, and delete it afterwards.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{DistGraph, DistResult, GenericBuildStep};
+
+/// Entries of the source tree we never copy
+const IGNORED_ENTRIES: &[&str] = &[".git"];
+
+/// Where the given step should be built, if it's an isolated build
+pub(crate) fn isolated_build_dir(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> Option<Utf8PathBuf> {
+    // Extra builds (and data builds) always run in the workspace
+    if !dist_graph.generic_build.isolated_build.unwrap_or(false)
+        || target.extra
+        || target.data.is_some()
+    {
+        return None;
+    }
+    // This lives in the target dir (rather than the system temp dir) so that
+    // containerized builds, which only see the workspace, can see it too.
+    Some(
+        dist_graph
+            .target_dir
+            .join("cargo-dist-build")
+            .join(&target.target_triple),
+    )
+}
+
+/// A scratch copy of the source tree, which is deleted when dropped
+pub(crate) struct IsolatedBuildDir {
+    /// The copy of the source tree
+    pub path: Utf8PathBuf,
+}
+
+impl IsolatedBuildDir {
+    /// Copy the workspace into the step's isolated build dir, if it has one
+    pub(crate) fn create(
+        dist_graph: &DistGraph,
+        target: &GenericBuildStep,
+    ) -> DistResult<Option<Self>> {
+        let Some(path) = isolated_build_dir(dist_graph, target) else {
+            return Ok(None);
+        };
+        // Clear out anything a previous (interrupted) build left behind
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+        std::fs::create_dir_all(&path)?;
+        copy_tree(&dist_graph.workspace_dir, &path, &dist_graph.target_dir)?;
+        Ok(Some(Self { path }))
+    }
+}
+
+impl Drop for IsolatedBuildDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            tracing::warn!("couldn't clean up build dir {}: {e}", self.path);
+        }
+    }
+}
+
+/// Recursively copy `src` to `dest`, skipping `skip` (the target dir) and VCS metadata
+///
+/// We deliberately copy rather than hardlink, as a build that edits a file in
+/// place would otherwise be editing the real sources. `std::fs::copy` already
+/// clones files instead of duplicating their contents on filesystems that
+/// support it (APFS, btrfs, XFS), which keeps this cheap for large trees.
+pub(crate) fn copy_tree(src: &Utf8Path, dest: &Utf8Path, skip: &Utf8Path) -> DistResult<()> {
+    for entry in src.read_dir_utf8()? {
+        let entry = entry?;
+        let src_path = entry.path();
+        if src_path == skip || IGNORED_ENTRIES.contains(&entry.file_name()) {
+            continue;
+        }
+        let dest_path = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_link(src_path, &dest_path)?;
+        } else if file_type.is_dir() {
+            std::fs::create_dir_all(&dest_path)?;
+            copy_tree(src_path, &dest_path, skip)?;
+        } else {
+            std::fs::copy(src_path, &dest_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reproduce a symlink as-is, so that relative links still work in the copy
+#[cfg(unix)]
+fn copy_link(src_path: &Utf8Path, dest_path: &Utf8Path) -> DistResult<()> {
+    std::os::unix::fs::symlink(src_path.read_link_utf8()?, dest_path)?;
+    Ok(())
+}
+
+/// Copy whatever the symlink points to, as creating symlinks needs special privileges here
+#[cfg(not(unix))]
+fn copy_link(src_path: &Utf8Path, dest_path: &Utf8Path) -> DistResult<()> {
+    if src_path.is_dir() {
+        std::fs::create_dir_all(dest_path)?;
+        copy_tree(src_path, dest_path, Utf8Path::new(""))
+    } else {
+        std::fs::copy(src_path, dest_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! Arranging generic build outputs into an install layout
+//!
+//! Installers (and anyone packaging our outputs by hand) want files in a
+//! predictable place, not wherever the build happened to leave them. With
+//! `[layout]` we stage each target's outputs into an FHS-like tree under the
+//! dist dir, sorted by what they are: executables in `bin`, libraries in
+//! `lib`, and everything from `extra-outputs` in `share`.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::staging::{dist_relative, staged_path};
+use crate::{
+    build::{
+        copy_into,
+        output_kind::{apply_output_mode, OutputKind},
+    },
+    config::InstallLayout,
+    DistGraph, DistResult, GenericBuildStep,
+};
+
+/// Where layouts go under the dist dir unless configured otherwise
+const DEFAULT_PREFIX: &str = "layout";
+
+impl InstallLayout {
+    /// The root of the layout for a target
+    pub fn root(&self, dist_graph: &DistGraph, target: &str) -> Utf8PathBuf {
+        let prefix = self
+            .prefix
+            .as_deref()
+            .unwrap_or(Utf8Path::new(DEFAULT_PREFIX));
+        dist_graph.dist_dir.join(prefix).join(target)
+    }
+
+    /// The directory (relative to the root) outputs of the given kind go in
+    pub fn dir_for(&self, kind: OutputKind, target: &str) -> &Utf8Path {
+        let bin_dir = self.bin_dir.as_deref().unwrap_or(Utf8Path::new("bin"));
+        let lib_dir = self.lib_dir.as_deref().unwrap_or(Utf8Path::new("lib"));
+        match kind {
+            OutputKind::Executable => bin_dir,
+            // Windows looks for DLLs next to the executables that load them
+            OutputKind::DynamicLibrary if target.contains("windows") => bin_dir,
+            OutputKind::DynamicLibrary | OutputKind::StaticLibrary => lib_dir,
+        }
+    }
+
+    /// The directory (relative to the root) extra outputs go in
+    pub fn share_dir(&self) -> &Utf8Path {
+        self.share_dir.as_deref().unwrap_or(Utf8Path::new("share"))
+    }
+}
+
+/// Stage a build's outputs into its target's layout, if one is configured
+///
+/// `binaries` are the (file name, path) of each binary to ship, and `extras`
+/// the copies of extra-outputs that were made in the dist dir, which keep
+/// their path relative to it. Returns the paths that were written to, which
+/// are in the staging dir if there is one.
+pub(crate) fn assemble_layout(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    binaries: &[(String, Utf8PathBuf)],
+    extras: &[Utf8PathBuf],
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let Some(layout) = &dist_graph.generic_build.layout else {
+        return Ok(vec![]);
+    };
+    let triple = &target.target_triple;
+    let root = staged_path(dist_graph, triple, &layout.root(dist_graph, triple));
+    let symlinks = dist_graph.generic_build.symlinks.unwrap_or_default();
+    let copy_mode = dist_graph.generic_build.copy_mode.unwrap_or_default();
+    let mut staged = vec![];
+
+    for (file_name, path) in binaries {
+        let kind = OutputKind::detect(file_name, triple);
+        let dest = root.join(layout.dir_for(kind, triple)).join(file_name);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let written = copy_into(path, &dest, symlinks, copy_mode)?;
+        apply_output_mode(&written, kind)?;
+        staged.push(written);
+    }
+    for path in extras {
+        let relative = dist_relative(dist_graph, triple, path);
+        let dest = root.join(layout.share_dir()).join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        staged.push(copy_into(path, &dest, symlinks, copy_mode)?);
+    }
+    Ok(staged)
+}
@@ -0,0 +1,291 @@
+//! Capping the memory and CPU time a generic build may use
+//!
+//! A runaway build (a compiler stuck in a loop, a link that eats all the
+//! memory) can take a shared CI runner down with it. With `max-memory-mb` and
+//! `max-cpu-seconds` we cap what builds may use:
+//!
+//! * on unix, with rlimits (`RLIMIT_AS` and `RLIMIT_CPU`) set just before the
+//!   build execs, which everything it spawns inherits. These apply to each
+//!   process separately.
+//! * on Windows, with a Job Object the build is put in right after it starts,
+//!   which limits the build as a whole.
+//! * in containers, with the container engine's own `--memory` and `--ulimit`.
+//!
+//! A build that hits its CPU limit gets SIGXCPU, so we can say for sure what
+//! happened. Running out of memory just makes allocations fail, which builds
+//! react to in all sorts of ways, so we can only recognize the usual ones
+//! (aborting, crashing, or being killed).
+
+use std::process::{Child, Command, ExitStatus};
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// How long after the CPU limit (and SIGXCPU) a build gets before it's killed
+const CPU_GRACE_SECONDS: u64 = 5;
+
+/// The configured memory limit, in bytes
+fn memory_limit(dist_graph: &DistGraph) -> Option<u64> {
+    dist_graph
+        .generic_build
+        .max_memory_mb
+        .map(|mb| mb.saturating_mul(1024 * 1024))
+}
+
+/// The arguments that apply the limits to a container
+pub(crate) fn container_args(dist_graph: &DistGraph) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(mb) = dist_graph.generic_build.max_memory_mb {
+        args.push("--memory".to_owned());
+        args.push(format!("{mb}m"));
+    }
+    if let Some(seconds) = dist_graph.generic_build.max_cpu_seconds {
+        args.push("--ulimit".to_owned());
+        args.push(format!(
+            "cpu={seconds}:{}",
+            seconds.saturating_add(CPU_GRACE_SECONDS)
+        ));
+    }
+    args
+}
+
+/// Arrange for the command to run with the configured rlimits
+#[cfg(unix)]
+pub(crate) fn apply_limits(dist_graph: &DistGraph, command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    let memory = memory_limit(dist_graph);
+    let cpu = dist_graph.generic_build.max_cpu_seconds;
+    if memory.is_none() && cpu.is_none() {
+        return;
+    }
+    // SAFETY: getrlimit and setrlimit are async-signal-safe. Unlike lowering
+    // our priority, failing to set a limit someone asked for is an error.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = memory {
+                let mut limit = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(libc::RLIMIT_AS, &mut limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // We can lower the hard limit, but never raise it
+                let bytes = (bytes as libc::rlim_t).min(limit.rlim_max);
+                limit.rlim_cur = bytes;
+                limit.rlim_max = bytes;
+                if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(seconds) = cpu {
+                let mut limit = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(libc::RLIMIT_CPU, &mut limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let hard =
+                    (seconds.saturating_add(CPU_GRACE_SECONDS) as libc::rlim_t).min(limit.rlim_max);
+                limit.rlim_cur = (seconds as libc::rlim_t).min(hard);
+                limit.rlim_max = hard;
+                if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Limits are applied to the running build on this platform (see [`confine`][])
+#[cfg(not(unix))]
+pub(crate) fn apply_limits(_dist_graph: &DistGraph, _command: &mut Command) {}
+
+/// Something keeping a running build confined, which must outlive it
+pub(crate) struct Confinement {
+    #[cfg(windows)]
+    _job: Option<job::Job>,
+}
+
+/// Apply the configured limits to a build that just started
+///
+/// rlimits were already set before it started, so this only matters on Windows.
+pub(crate) fn confine(dist_graph: &DistGraph, child: &Child) -> DistResult<Confinement> {
+    #[cfg(windows)]
+    {
+        let memory = memory_limit(dist_graph);
+        let cpu = dist_graph.generic_build.max_cpu_seconds;
+        let job = if memory.is_some() || cpu.is_some() {
+            Some(job::confine(child, memory, cpu)?)
+        } else {
+            None
+        };
+        Ok(Confinement { _job: job })
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = (dist_graph, child);
+        Ok(Confinement {})
+    }
+}
+
+/// Turn a build that was stopped for exceeding a limit into an error saying so
+pub(crate) fn check_limits(dist_graph: &DistGraph, status: ExitStatus) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    let (limit, setting, value) = match stopped_by(status) {
+        Some(Stopped::Cpu) => match config.max_cpu_seconds {
+            Some(seconds) => ("CPU time", "max-cpu-seconds", seconds),
+            None => return Ok(()),
+        },
+        Some(Stopped::Memory) => match config.max_memory_mb {
+            Some(mb) => ("memory", "max-memory-mb", mb),
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+    Err(DistError::BuildResourceLimit {
+        limit: limit.to_owned(),
+        setting: format!("{setting} = {value}"),
+        status: status.to_string(),
+    })
+}
+
+/// Which limit an exit status suggests a build ran into
+enum Stopped {
+    Cpu,
+    Memory,
+}
+
+#[cfg(unix)]
+fn stopped_by(status: ExitStatus) -> Option<Stopped> {
+    use std::os::unix::process::ExitStatusExt;
+
+    // Shells and container engines report a child killed by a signal as 128 + the signal
+    let signal = status.signal().or_else(|| {
+        status
+            .code()
+            .filter(|code| *code > 128)
+            .map(|code| code - 128)
+    })?;
+    match signal {
+        libc::SIGXCPU => Some(Stopped::Cpu),
+        libc::SIGKILL | libc::SIGABRT | libc::SIGSEGV | libc::SIGBUS => Some(Stopped::Memory),
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn stopped_by(status: ExitStatus) -> Option<Stopped> {
+    /// What processes get when their job runs out of time
+    const ERROR_NOT_ENOUGH_QUOTA: u32 = 1816;
+    /// An allocation failed
+    const STATUS_NO_MEMORY: u32 = 0xC000_0017;
+    match status.code()? as u32 {
+        ERROR_NOT_ENOUGH_QUOTA => Some(Stopped::Cpu),
+        STATUS_NO_MEMORY => Some(Stopped::Memory),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn stopped_by(_status: ExitStatus) -> Option<Stopped> {
+    None
+}
+
+/// The bits of the Win32 Job Object API we need
+#[cfg(windows)]
+mod job {
+    use std::{ffi::c_void, os::windows::io::AsRawHandle, process::Child};
+
+    type Handle = *mut c_void;
+
+    const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: i32 = 9;
+    const JOB_OBJECT_LIMIT_JOB_TIME: u32 = 0x0004;
+    const JOB_OBJECT_LIMIT_JOB_MEMORY: u32 = 0x0200;
+    /// Job times are in 100ns ticks
+    const TICKS_PER_SECOND: i64 = 10_000_000;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct BasicLimitInformation {
+        per_process_user_time_limit: i64,
+        per_job_user_time_limit: i64,
+        limit_flags: u32,
+        minimum_working_set_size: usize,
+        maximum_working_set_size: usize,
+        active_process_limit: u32,
+        affinity: usize,
+        priority_class: u32,
+        scheduling_class: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ExtendedLimitInformation {
+        basic: BasicLimitInformation,
+        io_info: [u64; 6],
+        process_memory_limit: usize,
+        job_memory_limit: usize,
+        peak_process_memory_used: usize,
+        peak_job_memory_used: usize,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateJobObjectW(attributes: *mut c_void, name: *const u16) -> Handle;
+        fn SetInformationJobObject(job: Handle, class: i32, info: *const c_void, len: u32) -> i32;
+        fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    /// A Job Object, closed when dropped (which leaves the limits in place)
+    pub(super) struct Job(Handle);
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: we own the handle, and nothing uses it after this
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+
+    /// Put a process in a new job with the given limits
+    pub(super) fn confine(
+        child: &Child,
+        memory: Option<u64>,
+        cpu_seconds: Option<u64>,
+    ) -> std::io::Result<Job> {
+        let mut info = ExtendedLimitInformation::default();
+        if let Some(bytes) = memory {
+            info.basic.limit_flags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+            info.job_memory_limit = usize::try_from(bytes).unwrap_or(usize::MAX);
+        }
+        if let Some(seconds) = cpu_seconds {
+            info.basic.limit_flags |= JOB_OBJECT_LIMIT_JOB_TIME;
+            info.basic.per_job_user_time_limit =
+                i64::try_from(seconds).unwrap_or(i64::MAX / TICKS_PER_SECOND) * TICKS_PER_SECOND;
+        }
+        // SAFETY: the info struct matches JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        // and every handle is checked before use
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null_mut(), std::ptr::null());
+            if job.is_null() {
+                return Err(std::io::Error::last_os_error());
+            }
+            let job = Job(job);
+            let set = SetInformationJobObject(
+                job.0,
+                JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                &info as *const ExtendedLimitInformation as *const c_void,
+                std::mem::size_of::<ExtendedLimitInformation>() as u32,
+            );
+            if set == 0 || AssignProcessToJobObject(job.0, child.as_raw_handle()) == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(job)
+        }
+    }
+}
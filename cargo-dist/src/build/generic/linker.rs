@@ -0,0 +1,76 @@
+//! Selecting a linker for generic builds, independently of the compiler
+//!
+//! There are two ways to pick a linker, and which build systems use varies:
+//! some run `$LD` themselves, while most link through the compiler, which
+//! gcc and clang let us steer with `-fuse-ld=<name>` in LDFLAGS. We do both
+//! where the compiler understands it. MSVC's `cl.exe` has no such flag, so it
+//! only gets `LD`.
+
+use axoprocess::Cmd;
+use camino::Utf8Path;
+
+use crate::{DistError, DistResult};
+
+/// How a configured linker gets passed to a build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkerSelection {
+    /// The linker executable, exported as LD
+    pub program: String,
+    /// The flag to add to LDFLAGS, if the compiler understands one
+    pub ldflag: Option<String>,
+}
+
+/// Work out how to use `linker` (like "mold", "lld", or a path) with the compiler `cc`
+pub fn select_linker(linker: &str, cc: &str) -> LinkerSelection {
+    let is_path = linker.contains('/') || linker.contains('\\');
+    if is_msvc(cc) {
+        let program = match linker {
+            "lld" => "lld-link".to_owned(),
+            _ => linker.to_owned(),
+        };
+        return LinkerSelection {
+            program,
+            ldflag: None,
+        };
+    }
+    if is_path {
+        // gcc only takes names in -fuse-ld, so paths can only go in LD
+        return LinkerSelection {
+            program: linker.to_owned(),
+            ldflag: None,
+        };
+    }
+    // -fuse-ld=mold makes the compiler run ld.mold
+    let name = linker.strip_prefix("ld.").unwrap_or(linker);
+    LinkerSelection {
+        program: format!("ld.{name}"),
+        ldflag: Some(format!("-fuse-ld={name}")),
+    }
+}
+
+/// Error out if the selected linker can't be run
+pub(crate) fn ensure_linker_installed(linker: &str, selection: &LinkerSelection) -> DistResult<()> {
+    let installed = Cmd::new(&selection.program, "check for the linker")
+        .arg("--version")
+        .check(false)
+        .log(None)
+        .output()
+        .is_ok();
+    if installed {
+        Ok(())
+    } else {
+        Err(DistError::LinkerMissing {
+            linker: linker.to_owned(),
+            program: selection.program.clone(),
+        })
+    }
+}
+
+/// Whether a C compiler is MSVC's (rather than something gcc-compatible)
+fn is_msvc(cc: &str) -> bool {
+    // The compiler may come with arguments, like a compiler-cache wrapper
+    let program = cc.split_whitespace().last().unwrap_or(cc);
+    Utf8Path::new(program)
+        .file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("cl"))
+}
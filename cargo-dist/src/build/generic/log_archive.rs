@@ -0,0 +1,64 @@
+//! Bundling build logs into the release
+//!
+//! With `archive-build-logs = true`, once every build has finished, the logs
+//! in `build-logs/` are packed into `build-logs.tar.gz` in the dist dir and
+//! listed in the manifest as an artifact of each release, so they get
+//! published with everything else (for audits and the like).
+//!
+//! This is off by default because logs can include details of the build
+//! machine's environment that not everyone wants to publish.
+
+use axoasset::LocalAsset;
+use camino::Utf8PathBuf;
+use cargo_dist_schema::DistManifest;
+
+use super::relay::BUILD_LOG_DIR;
+use crate::{DistGraph, DistResult};
+
+/// The name of the archive of build logs
+pub const BUILD_LOG_ARCHIVE: &str = "build-logs.tar.gz";
+
+/// Archive the build logs and add the archive to the manifest, if that's enabled
+///
+/// Returns where the archive was written, if it was.
+pub(crate) fn archive_build_logs(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    no_local_paths: bool,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if !dist_graph.generic_build.archive_build_logs.unwrap_or(false) {
+        return Ok(None);
+    }
+    let log_dir = dist_graph.dist_dir.join(BUILD_LOG_DIR);
+    if !log_dir.is_dir() {
+        return Ok(None);
+    }
+    let dest_path = dist_graph.dist_dir.join(BUILD_LOG_ARCHIVE);
+    LocalAsset::tar_gz_dir(&log_dir, &dest_path, Some(BUILD_LOG_DIR))?;
+
+    let id = BUILD_LOG_ARCHIVE.to_owned();
+    manifest.artifacts.insert(
+        id.clone(),
+        cargo_dist_schema::Artifact {
+            name: Some(id.clone()),
+            kind: cargo_dist_schema::ArtifactKind::ExtraArtifact,
+            target_triples: vec![],
+            path: (!no_local_paths).then(|| dest_path.to_string()),
+            assets: vec![],
+            install_hint: None,
+            description: Some("logs of the builds that produced this release".to_owned()),
+            checksum: None,
+            checksums: Default::default(),
+            image_digest: None,
+        },
+    );
+    for release in &mut manifest.releases {
+        if !release.artifacts.contains(&id) {
+            release.artifacts.push(id.clone());
+        }
+    }
+    if !no_local_paths {
+        manifest.upload_files.push(dest_path.to_string());
+    }
+    Ok(Some(dest_path))
+}
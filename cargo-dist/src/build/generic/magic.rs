@@ -0,0 +1,263 @@
+//! Checking that a build produced binaries in the right format
+//!
+//! A misconfigured cross build can happily produce a binary for the host
+//! instead of the target, which nothing notices until someone runs it. With
+//! `check-binary-formats` (or `binary-formats`) we sniff each binary's magic
+//! bytes and make sure they match what the target should be using.
+//!
+//! A binary in the right format can still be for the wrong CPU (an x86_64
+//! ELF from a toolchain that fell back to the host compiler, for an aarch64
+//! Linux target), so `check-architectures` also reads the machine type from
+//! each binary's header (ELF `e_machine`, Mach-O `cputype`, or the PE
+//! `Machine`) and compares it to the target triple's architecture.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use camino::Utf8Path;
+
+use crate::{
+    build::output_kind::OutputKind, config::BinaryFormat, Binary, DistError, DistGraph, DistResult,
+};
+
+/// The executable format a target uses
+pub fn expected_format(target: &str) -> BinaryFormat {
+    if target.contains("windows") {
+        BinaryFormat::Pe
+    } else if target.contains("apple") {
+        BinaryFormat::MachO
+    } else if target.contains("wasm") {
+        BinaryFormat::Wasm
+    } else {
+        BinaryFormat::Elf
+    }
+}
+
+/// Work out what format a file is in from its magic bytes, if it's one we know
+pub fn detect_format(path: &Utf8Path) -> DistResult<Option<BinaryFormat>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut magic = [0; 4];
+    if file.read_exact(&mut magic).is_err() {
+        return Ok(None);
+    }
+    let format = match magic {
+        [0x7f, b'E', b'L', b'F'] => BinaryFormat::Elf,
+        [0, b'a', b's', b'm'] => BinaryFormat::Wasm,
+        // 32 and 64-bit, in either byte order, and universal binaries
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf]
+        | [0xce | 0xcf, 0xfa, 0xed, 0xfe]
+        | [0xca, 0xfe, 0xba, 0xbe] => BinaryFormat::MachO,
+        // A DOS header, which points to the PE header proper
+        [b'M', b'Z', ..] => {
+            let mut offset = [0; 4];
+            let mut signature = [0; 4];
+            let is_pe = file.seek(SeekFrom::Start(0x3c)).is_ok()
+                && file.read_exact(&mut offset).is_ok()
+                && file
+                    .seek(SeekFrom::Start(u32::from_le_bytes(offset).into()))
+                    .is_ok()
+                && file.read_exact(&mut signature).is_ok()
+                && signature == *b"PE\0\0";
+            if !is_pe {
+                return Ok(None);
+            }
+            BinaryFormat::Pe
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(format))
+}
+
+/// Check a binary a build produced is in the format it's supposed to be, if configured to
+pub(crate) fn check_binary_format(
+    dist_graph: &DistGraph,
+    binary: &Binary,
+    path: &Utf8Path,
+) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    let declared = config
+        .binary_formats
+        .as_ref()
+        .and_then(|formats| formats.get(&binary.name))
+        .copied();
+    let expected = match declared {
+        Some(format) => format,
+        None if config.check_binary_formats.unwrap_or(false) => {
+            // Archives of objects don't have a format of their own
+            if OutputKind::detect(&binary.file_name, &binary.target) == OutputKind::StaticLibrary {
+                return Ok(());
+            }
+            expected_format(&binary.target)
+        }
+        None => return Ok(()),
+    };
+    if expected == BinaryFormat::Any {
+        return Ok(());
+    }
+    let found = detect_format(path)?;
+    if found == Some(expected) {
+        return Ok(());
+    }
+    Err(DistError::WrongBinaryFormat {
+        binary: binary.name.clone(),
+        target: binary.target.clone(),
+        expected: expected.to_string(),
+        found: found.map_or_else(|| "unrecognized".to_owned(), |format| format.to_string()),
+    })
+}
+
+/// The architecture a target's binaries are for, if it's one we can check
+pub fn expected_arch(target: &str) -> Option<&'static str> {
+    let arch = target.split('-').next().unwrap_or_default();
+    let arch = match arch {
+        "x86_64" | "amd64" => "x86_64",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "aarch64" | "arm64" | "arm64e" => "aarch64",
+        "powerpc64" | "powerpc64le" => "powerpc64",
+        "powerpc" | "powerpcle" => "powerpc",
+        "s390x" => "s390x",
+        "loongarch64" => "loongarch64",
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        _ if arch.starts_with("riscv64") => "riscv64",
+        _ if arch.starts_with("riscv32") => "riscv32",
+        _ => return None,
+    };
+    Some(arch)
+}
+
+/// Read the architectures a binary is for from its header
+///
+/// Universal Mach-O binaries can be for several. Returns an empty list for
+/// files we don't recognize, and "unknown" for machine types we don't know.
+pub fn detect_arch(path: &Utf8Path) -> DistResult<Vec<&'static str>> {
+    let bytes = std::fs::read(path)?;
+    let u16_at = |offset: usize, little: bool| {
+        let bytes: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize, little: bool| {
+        let bytes: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let archs = match bytes.get(..4) {
+        Some([0x7f, b'E', b'L', b'F']) => {
+            let is_64 = bytes.get(4) == Some(&2);
+            let little = bytes.get(5) == Some(&1);
+            u16_at(18, little)
+                .map(|machine| elf_arch(machine, is_64))
+                .into_iter()
+                .collect()
+        }
+        Some([0xfe, 0xed, 0xfa, 0xce | 0xcf]) => {
+            u32_at(4, false).map(macho_arch).into_iter().collect()
+        }
+        Some([0xce | 0xcf, 0xfa, 0xed, 0xfe]) => {
+            u32_at(4, true).map(macho_arch).into_iter().collect()
+        }
+        // A universal binary: a count, then 20 bytes per architecture, all big-endian
+        Some([0xca, 0xfe, 0xba, 0xbe]) => {
+            let count = u32_at(4, false).unwrap_or_default() as usize;
+            (0..count)
+                .map_while(|idx| u32_at(8 + idx * 20, false))
+                .map(macho_arch)
+                .collect()
+        }
+        Some([b'M', b'Z', ..]) => u32_at(0x3c, true)
+            .and_then(|offset| {
+                let offset = offset as usize;
+                if bytes.get(offset..offset + 4) != Some(b"PE\0\0") {
+                    return None;
+                }
+                u16_at(offset + 4, true)
+            })
+            .map(pe_arch)
+            .into_iter()
+            .collect(),
+        _ => vec![],
+    };
+    Ok(archs)
+}
+
+fn elf_arch(machine: u16, is_64: bool) -> &'static str {
+    match (machine, is_64) {
+        (3, _) => "x86",
+        (62, _) => "x86_64",
+        (40, _) => "arm",
+        (183, _) => "aarch64",
+        (20, _) => "powerpc",
+        (21, _) => "powerpc64",
+        (22, true) => "s390x",
+        (243, true) => "riscv64",
+        (243, false) => "riscv32",
+        (258, _) => "loongarch64",
+        _ => "unknown",
+    }
+}
+
+fn macho_arch(cputype: u32) -> &'static str {
+    match cputype {
+        7 => "x86",
+        0x0100_0007 => "x86_64",
+        12 => "arm",
+        0x0100_000c => "aarch64",
+        18 => "powerpc",
+        0x0100_0012 => "powerpc64",
+        _ => "unknown",
+    }
+}
+
+fn pe_arch(machine: u16) -> &'static str {
+    match machine {
+        0x14c => "x86",
+        0x8664 => "x86_64",
+        0x1c0 | 0x1c4 => "arm",
+        0xaa64 => "aarch64",
+        _ => "unknown",
+    }
+}
+
+/// Check a binary a build produced is for its target's architecture, if configured to
+pub(crate) fn check_architecture(
+    dist_graph: &DistGraph,
+    binary: &Binary,
+    path: &Utf8Path,
+) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    if !config.check_architectures.unwrap_or(false) {
+        return Ok(());
+    }
+    // Things that aren't executables at all don't have an architecture
+    let declared = config
+        .binary_formats
+        .as_ref()
+        .and_then(|formats| formats.get(&binary.name));
+    if declared == Some(&BinaryFormat::Any)
+        || OutputKind::detect(&binary.file_name, &binary.target) == OutputKind::StaticLibrary
+    {
+        return Ok(());
+    }
+    let Some(expected) = expected_arch(&binary.target) else {
+        return Ok(());
+    };
+    let found = detect_arch(path)?;
+    if found.contains(&expected) {
+        return Ok(());
+    }
+    Err(DistError::WrongArchitecture {
+        binary: binary.name.clone(),
+        target: binary.target.clone(),
+        expected: expected.to_owned(),
+        found: if found.is_empty() {
+            "unrecognized".to_owned()
+        } else {
+            found.join("+")
+        },
+    })
+}
@@ -0,0 +1,88 @@
+//! Exporting the generic build plan as a Makefile
+//!
+//! This is an escape hatch for running builds without cargo-dist (and a
+//! handy way to see exactly what it would run): every target gets a rule
+//! that runs the same commands, in the same directory, with the same
+//! environment variables we would set.
+
+use axoprocess::Cmd;
+
+use super::{build_command, cmake::cmake_commands, shell::posix_quote};
+use crate::{config::GenericBuilder, BuildStep, DistError, DistGraph, DistResult};
+
+/// Render a Makefile with a rule for every target the given steps build
+pub fn render_makefile(dist_graph: &DistGraph, steps: &[BuildStep]) -> DistResult<String> {
+    let mut rules = crate::SortedMap::<String, Vec<String>>::new();
+    for step in steps {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        let commands = match step.builder {
+            GenericBuilder::Cmake => Vec::from(cmake_commands(dist_graph, step)?),
+            GenericBuilder::Command | GenericBuilder::Go => {
+                let mut commands = vec![];
+                for command in step.commands() {
+                    commands.push(build_command(dist_graph, command, Some(step))?);
+                }
+                commands
+            }
+        };
+        let recipe = rules.entry(step.target_triple.clone()).or_default();
+        for command in &commands {
+            recipe.push(recipe_line(command)?);
+        }
+    }
+
+    let targets = rules.keys().cloned().collect::<Vec<_>>().join(" ");
+    let mut output = String::new();
+    output.push_str(
+        "# Generated by `cargo dist export-makefile`, rerun it instead of editing this\n",
+    );
+    output.push_str(&format!(".PHONY: all {targets}\n\nall: {targets}\n"));
+    for (target, recipe) in &rules {
+        output.push_str(&format!("\n{target}:\n"));
+        for line in recipe {
+            output.push_str(&format!("\t{line}\n"));
+        }
+    }
+    Ok(output)
+}
+
+/// A recipe line that runs the command like we would (for a POSIX shell)
+fn recipe_line(command: &Cmd) -> DistResult<String> {
+    let mut words = vec![];
+    if let Some(dir) = command.get_current_dir() {
+        words.push("cd".to_owned());
+        words.push(posix_quote(&dir.to_string_lossy()));
+        words.push("&&".to_owned());
+    }
+    words.push("env".to_owned());
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy();
+        match value {
+            Some(value) => words.push(posix_quote(&format!("{key}={}", value.to_string_lossy()))),
+            None => {
+                words.push("-u".to_owned());
+                words.push(posix_quote(&key));
+            }
+        }
+    }
+    words.push(posix_quote(&command.get_program().to_string_lossy()));
+    for arg in command.get_args() {
+        words.push(posix_quote(&arg.to_string_lossy()));
+    }
+    make_escape(&words.join(" "))
+}
+
+/// Escape a recipe line so that make passes it to the shell untouched
+fn make_escape(line: &str) -> DistResult<String> {
+    // A newline would end the recipe line, and there's no way to escape one
+    // without the shell seeing the escape too
+    if line.contains('\n') {
+        return Err(DistError::MakefileUnrepresentable {
+            line: line.to_owned(),
+        });
+    }
+    // $ is the only thing make interprets in recipes
+    Ok(line.replace('$', "$$"))
+}
@@ -0,0 +1,1717 @@
+//! Functionality required to invoke a generic build's `build-command`
+
+use std::{collections::BTreeMap, env, process::ExitStatus};
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_dist_schema::{BuildProvenance, DistManifest};
+use tracing::{info, warn};
+
+pub mod annotations;
+pub mod archive;
+pub mod artifact_list;
+pub mod binaries_file;
+pub mod build_id;
+pub mod build_user;
+pub mod cache;
+pub mod checksums;
+pub mod clean_tree;
+pub mod cmake;
+pub mod collisions;
+pub mod compile_commands;
+pub mod compilers;
+pub mod container;
+pub mod data;
+pub mod debuginfo;
+pub mod default_targets;
+pub mod disk_space;
+pub mod dist_diff;
+pub mod doctor;
+pub mod effective;
+pub mod env_policy;
+pub mod events;
+pub mod failures;
+pub mod formats;
+pub mod go;
+pub mod hooks;
+pub mod infra;
+pub mod inputs;
+pub mod interrupt;
+pub mod isolated;
+pub mod layout;
+pub mod limits;
+pub mod linker;
+pub mod log_archive;
+pub mod magic;
+pub mod makefile;
+pub mod modes;
+pub mod naming;
+pub mod network;
+pub mod notarize;
+pub mod oci;
+pub mod order;
+pub mod outputs;
+pub mod patterns;
+pub mod pipeline;
+pub mod portability;
+pub mod post_process;
+pub mod preexisting;
+pub mod priority;
+pub mod pty;
+pub mod relay;
+pub mod release_diff;
+pub mod remote;
+pub mod repro;
+pub mod reproducibility;
+pub mod reproducible;
+pub mod resume;
+pub mod retry;
+pub mod rpath;
+pub mod s3_mirror;
+pub mod sanitizers;
+pub mod sbom;
+pub mod script;
+pub mod shard;
+pub mod shell;
+pub mod signature;
+pub mod sizes;
+pub mod smoke;
+pub mod staging;
+pub mod staging_manifest;
+pub mod stale;
+pub mod summary;
+pub mod symbols;
+pub mod sysroot;
+pub mod timing;
+pub mod toolchain;
+pub mod undeclared;
+pub mod unnamed;
+pub mod validate;
+pub mod variants;
+pub mod versions;
+pub mod wasm;
+pub mod watch;
+
+use clean_tree::TreeSnapshot;
+use go::go_os_arch;
+use isolated::IsolatedBuildDir;
+use outputs::{
+    collect_and_copy_outputs, copy_found_outputs, find_outputs, find_present_outputs, glob_outputs,
+    verify_destinations, wait_for_missing_outputs, wait_for_stable_outputs, ExpectedOutput,
+    FoundOutput,
+};
+use relay::{run_relayed, BuildLog};
+use timing::StageTimer;
+use toolchain::Toolchain;
+use undeclared::OutputDirSnapshot;
+use unnamed::UnnamedOutputs;
+use wasm::{ensure_wasm_toolchain, wasm_toolchain};
+
+use crate::{
+    build::{
+        copy_into, output_kind::OutputKind, package_id_string, verify_copy, BuildExpectations,
+    },
+    config::{
+        ArtifactFormat, BuildVerbosity, ChecksumStyle, EnvFilePrecedence, Flags,
+        GenericBuildConfig, GenericBuilder,
+    },
+    env::{
+        brew_env, expand_target_placeholders, fetch_pkg_config_flags, git_commit, load_env_file,
+        merge_flags, setup_env, stripped_env_vars, target_flags,
+    },
+    generate_checksum, BinaryIdx, BuildStep, DistError, DistGraph, DistGraphBuilder, DistResult,
+    ExtraBuildStep, GenericBuildStep, SortedMap, SortedSet, TargetTriple,
+};
+
+impl<'a> DistGraphBuilder<'a> {
+    pub(crate) fn compute_generic_builds(&mut self) -> DistResult<Vec<BuildStep>> {
+        // Do a workspace build for every (target-triple, build-command) pair we have
+        // a binary-that-needs-a-real-build for. Binaries usually all share the
+        // workspace's build command, but they can override it, and we must not
+        // build them with someone else's command.
+        // Host-only binaries get builds of their own, which aren't cross-compiled.
+        self.add_listed_binaries()?;
+        let host_only = self
+            .inner
+            .generic_build
+            .host_only
+            .clone()
+            .unwrap_or_default();
+        let mut targets = SortedMap::<(TargetTriple, Vec<String>, bool), Vec<BinaryIdx>>::new();
+        for (binary_idx, binary) in self.inner.binaries.iter().enumerate() {
+            if !binary.copy_exe_to.is_empty() || !binary.copy_symbols_to.is_empty() {
+                let mut build_command = self.effective_build_command(&binary.name);
+                let is_host_only = host_only.contains(&binary.name);
+                if let (Some(args), false) = (&self.inner.generic_build.target_args, is_host_only) {
+                    build_command = with_target_args(build_command, args, &binary.target);
+                }
+                targets
+                    .entry((binary.target.clone(), build_command, is_host_only))
+                    .or_default()
+                    .push(BinaryIdx(binary_idx));
+            }
+        }
+
+        let depends_on = self
+            .inner
+            .generic_build
+            .depends_on
+            .clone()
+            .unwrap_or_default();
+        let data_builds = self
+            .inner
+            .generic_build
+            .data_builds
+            .clone()
+            .unwrap_or_default();
+        for (binary, dependencies) in &depends_on {
+            for dependency in dependencies {
+                if !self.inner.binaries.iter().any(|b| &b.name == dependency)
+                    && !data_builds.iter().any(|data| &data.name == dependency)
+                {
+                    return Err(DistError::UnknownBuildDependency {
+                        binary: binary.clone(),
+                        dependency: dependency.clone(),
+                    });
+                }
+            }
+        }
+
+        let mut builds = vec![];
+        let mut names = vec![];
+        // Each target whose builds need a data build gets one of its own
+        let targets_needing = |name: &str| {
+            targets
+                .iter()
+                .filter(|(_, binaries)| {
+                    binaries.iter().any(|binary_idx| {
+                        depends_on
+                            .get(&self.inner.binary(*binary_idx).name)
+                            .is_some_and(|dependencies| dependencies.iter().any(|d| d == name))
+                    })
+                })
+                .map(|((target, _, _), _)| target.clone())
+                .collect::<SortedSet<_>>()
+        };
+        for data in &data_builds {
+            for target in targets_needing(&data.name) {
+                names.push(vec![data.name.clone()]);
+                builds.push(GenericBuildStep {
+                    target_triple: target.clone(),
+                    expected_binaries: vec![],
+                    build_command: data.build_command.clone(),
+                    builder: GenericBuilder::Command,
+                    container_image: self.inner.generic_build.container_image.clone(),
+                    remote_host: None,
+                    output_base: None,
+                    extra_outputs: vec![],
+                    allow_missing: vec![],
+                    profile: self.inner.build_profile.clone(),
+                    depends_on: vec![],
+                    sub_builds: vec![],
+                    host_only: false,
+                    extra: false,
+                    data: Some(data.name.clone()),
+                    verbosity: step_verbosity(&self.inner.generic_build, &target),
+                    variant: self.inner.variant.clone(),
+                    artifact_metadata: SortedMap::new(),
+                });
+            }
+        }
+        for ((target, build_command, host_only), binaries) in targets {
+            let allowed = self
+                .inner
+                .generic_build
+                .allow_missing
+                .as_deref()
+                .unwrap_or_default();
+            let allow_missing = binaries
+                .iter()
+                .map(|binary_idx| self.inner.binary(*binary_idx).name.clone())
+                .filter(|name| allowed.contains(name))
+                .collect();
+            names.push(
+                binaries
+                    .iter()
+                    .map(|binary_idx| self.inner.binary(*binary_idx).name.clone())
+                    .collect::<Vec<_>>(),
+            );
+            let step_depends_on = binaries
+                .iter()
+                .flat_map(|binary_idx| {
+                    depends_on
+                        .get(&self.inner.binary(*binary_idx).name)
+                        .into_iter()
+                        .flatten()
+                })
+                .cloned()
+                .collect::<SortedSet<_>>()
+                .into_iter()
+                .collect();
+            builds.push(GenericBuildStep {
+                target_triple: target.clone(),
+                expected_binaries: binaries,
+                build_command,
+                builder: self.inner.generic_build.builder.unwrap_or_default(),
+                container_image: self.inner.generic_build.container_image.clone(),
+                remote_host: remote::remote_host(&self.inner.generic_build, &target, host_only),
+                output_base: self.inner.generic_build.output_dir.clone(),
+                extra_outputs: self
+                    .inner
+                    .generic_build
+                    .extra_outputs
+                    .iter()
+                    .flatten()
+                    .map(|output| (output.path.clone(), output.dest.clone().unwrap_or_default()))
+                    .collect(),
+                allow_missing,
+                profile: self.inner.build_profile.clone(),
+                depends_on: step_depends_on,
+                sub_builds: vec![],
+                host_only,
+                extra: false,
+                data: None,
+                verbosity: step_verbosity(&self.inner.generic_build, &target),
+                variant: self.inner.variant.clone(),
+                artifact_metadata: step_artifact_metadata(&self.inner.generic_build, &target),
+            });
+        }
+
+        let mut builds = order::order_builds(builds, &names, &depends_on)?;
+        if self
+            .inner
+            .generic_build
+            .combine_build_commands
+            .unwrap_or(false)
+        {
+            builds = order::combine_builds(builds, |binary_idx| {
+                self.inner.binary(binary_idx).name.clone()
+            });
+        }
+        if let Some(shard) = self.inner.shard {
+            builds = shard::select_shard(builds, shard);
+        }
+        Ok(builds.into_iter().map(BuildStep::Generic).collect())
+    }
+
+    /// The build command that produces the given binary
+    ///
+    /// This is the workspace's build command unless the binary has an
+    /// entry in `binary-build-commands`, the variant being built has a
+    /// command of its own, or there's a `build-script`.
+    fn effective_build_command(&self, binary_name: &str) -> Vec<String> {
+        if let Some(command) = self
+            .inner
+            .generic_build
+            .binary_build_commands
+            .as_ref()
+            .and_then(|commands| commands.get(binary_name))
+        {
+            return command.clone();
+        }
+        if let Some(command) =
+            variants::variant(&self.inner.generic_build, self.inner.variant.as_deref())
+                .and_then(|variant| variant.build_command.as_ref())
+        {
+            return command.clone();
+        }
+        if let Some(script) = &self.inner.generic_build.build_script {
+            return shell::script_command(
+                self.inner.generic_build.script_shell.as_deref(),
+                cfg!(windows),
+                script,
+            );
+        }
+        self.workspace
+            .build_command
+            .clone()
+            .expect("A build command is mandatory for generic builds")
+    }
+}
+
+/// The variables tools look for their temporary directory in, across platforms
+pub const TMP_DIR_VARS: [&str; 3] = ["TMPDIR", "TEMP", "TMP"];
+
+/// How much of a build for `target` to show
+pub(crate) fn step_verbosity(config: &GenericBuildConfig, target: &str) -> BuildVerbosity {
+    config
+        .target_verbosity
+        .as_ref()
+        .and_then(|verbosity| verbosity.get(target))
+        .copied()
+        .or(config.verbosity)
+        .unwrap_or_default()
+}
+
+/// The custom metadata for a build for `target`: `artifact-metadata`, then `target-artifact-metadata`
+pub(crate) fn step_artifact_metadata(
+    config: &GenericBuildConfig,
+    target: &str,
+) -> SortedMap<String, String> {
+    let for_target = config
+        .target_artifact_metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get(target));
+    config
+        .artifact_metadata
+        .iter()
+        .chain(for_target)
+        .flatten()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// A build command with `target-args` appended, their placeholders filled in for `target`
+pub(crate) fn with_target_args(
+    mut build_command: Vec<String>,
+    args: &[String],
+    target: &str,
+) -> Vec<String> {
+    build_command.extend(
+        args.iter()
+            .filter_map(|arg| expand_target_placeholders(arg, Some(target))),
+    );
+    build_command
+}
+
+/// The targets of the given generic build steps, with how many binaries each produces
+pub fn generic_build_targets(steps: &[BuildStep]) -> SortedMap<TargetTriple, usize> {
+    let mut targets = SortedMap::new();
+    for step in steps {
+        if let BuildStep::Generic(step) = step {
+            *targets.entry(step.target_triple.clone()).or_default() += step.expected_binaries.len();
+        }
+    }
+    targets
+}
+
+/// Keep only the generic builds, or only the extra builds, if that's all that's wanted
+///
+/// With neither, every step is kept.
+pub fn select_build_steps(steps: &mut Vec<BuildStep>, only_generic: bool, only_extra: bool) {
+    if only_generic {
+        steps.retain(|step| matches!(step, BuildStep::Generic(_)));
+    }
+    if only_extra {
+        steps.retain(|step| matches!(step, BuildStep::Extra(_)));
+    }
+}
+
+/// Check that no two binaries of the given generic build steps get copied to the same place
+///
+/// Copies happen one after another, so otherwise the last one would silently win.
+/// The binaries of one build can share a symbols file (and so its destination).
+pub(crate) fn check_copy_destinations(
+    dist_graph: &DistGraph,
+    steps: &[BuildStep],
+) -> DistResult<()> {
+    // destination => (binary, the step building it, whether it's a symbols file)
+    let mut claimed = SortedMap::<&Utf8Path, (BinaryIdx, usize, bool)>::new();
+    for (step_idx, step) in steps.iter().enumerate() {
+        let BuildStep::Generic(step) = step else {
+            continue;
+        };
+        for &binary_idx in &step.expected_binaries {
+            let binary = dist_graph.binary(binary_idx);
+            let exes = binary.copy_exe_to.iter().map(|dest| (dest, false));
+            let symbols = binary.copy_symbols_to.iter().map(|dest| (dest, true));
+            for (dest, is_symbols) in exes.chain(symbols) {
+                let Some(&(other_idx, other_step, other_is_symbols)) = claimed.get(dest.as_path())
+                else {
+                    claimed.insert(dest, (binary_idx, step_idx, is_symbols));
+                    continue;
+                };
+                let shared_symbols = is_symbols && other_is_symbols && other_step == step_idx;
+                if other_idx != binary_idx && !shared_symbols {
+                    return Err(DistError::CopyDestinationCollision {
+                        dest: dest.clone(),
+                        first: dist_graph.binary(other_idx).name.clone(),
+                        second: binary.name.clone(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn platform_appropriate_cc(target: &str) -> &str {
+    if target.contains("darwin") {
+        "clang"
+    } else if target.contains("linux") {
+        "gcc"
+    } else if target.contains("windows") {
+        "cl.exe"
+    } else {
+        "cc"
+    }
+}
+
+fn platform_appropriate_cxx(target: &str) -> &str {
+    if target.contains("darwin") {
+        "clang++"
+    } else if target.contains("linux") {
+        "g++"
+    } else if target.contains("windows") {
+        "cl.exe"
+    } else {
+        "c++"
+    }
+}
+
+fn platform_appropriate_ar(target: &str) -> &str {
+    if target.contains("windows-msvc") {
+        "lib.exe"
+    } else {
+        "ar"
+    }
+}
+
+pub(crate) fn resolved_cc(dist_graph: &DistGraph, target: &str) -> String {
+    env::var("CC").unwrap_or_else(|_| match wasm_toolchain(target) {
+        Some(toolchain) => toolchain.cc(),
+        None => match compilers::detected_family(dist_graph) {
+            Some(family) => family.cc().to_owned(),
+            None => platform_appropriate_cc(target).to_owned(),
+        },
+    })
+}
+
+pub(crate) fn resolved_cxx(dist_graph: &DistGraph, target: &str) -> String {
+    env::var("CXX").unwrap_or_else(|_| match wasm_toolchain(target) {
+        Some(toolchain) => toolchain.cxx(),
+        None => match compilers::detected_family(dist_graph) {
+            Some(family) => family.cxx().to_owned(),
+            None => platform_appropriate_cxx(target).to_owned(),
+        },
+    })
+}
+
+pub(crate) fn resolved_ar(target: &str) -> String {
+    env::var("AR").unwrap_or_else(|_| match wasm_toolchain(target) {
+        Some(toolchain) => toolchain.ar(),
+        None => platform_appropriate_ar(target).to_owned(),
+    })
+}
+
+/// The toolchain each generic build target would use
+pub fn generic_build_toolchains(
+    dist_graph: &DistGraph,
+    steps: &[BuildStep],
+) -> SortedMap<TargetTriple, Toolchain> {
+    generic_build_targets(steps)
+        .into_keys()
+        .map(|target| {
+            let toolchain = Toolchain::for_target(dist_graph, &target);
+            (target, toolchain)
+        })
+        .collect()
+}
+
+/// Whether we hand this build CC/CXX
+///
+/// Pure Go builds don't touch a C toolchain at all, so only
+/// pass them along if something might actually use them.
+/// Host-only builds use whatever the host has.
+fn uses_c_compilers(dist_graph: &DistGraph, step: &GenericBuildStep) -> bool {
+    !step.host_only
+        && (step.builder != GenericBuilder::Go || dist_graph.generic_build.cgo.unwrap_or(false))
+}
+
+/// The cross-compilation toolchain file for a build, from `toolchain-files`
+///
+/// Host-only builds don't cross-compile, so they never get one.
+pub(crate) fn toolchain_file(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+) -> Option<Utf8PathBuf> {
+    if step.host_only {
+        return None;
+    }
+    let path = dist_graph
+        .generic_build
+        .toolchain_files
+        .as_ref()?
+        .get(&step.target_triple)?;
+    Some(dist_graph.workspace_dir.join(path))
+}
+
+/// Whether this build gets Homebrew's environment, according to `brew-targets`
+///
+/// Extra builds aren't for any target, so they count as being for the host.
+pub(crate) fn uses_brew(dist_graph: &DistGraph, step: Option<&GenericBuildStep>) -> bool {
+    let Some(targets) = &dist_graph.generic_build.brew_targets else {
+        return true;
+    };
+    let target = match step {
+        Some(step) => &step.target_triple,
+        None => &dist_graph.tools.cargo.host_target,
+    };
+    targets.contains(target)
+}
+
+/// Run a build command, relaying its output to the log
+///
+/// Returns the build's signature (see [`signature`][]) along with its status.
+fn run_build(
+    dist_graph: &DistGraph,
+    command_string: &[String],
+    target: Option<&GenericBuildStep>,
+    log: &BuildLog,
+) -> DistResult<(ExitStatus, String)> {
+    run_builds(dist_graph, &[command_string], target, log)
+}
+
+/// Run a build's commands one after another, stopping at the first that fails
+///
+/// The status is that of the last command that ran, and the signature covers
+/// all of them.
+fn run_builds(
+    dist_graph: &DistGraph,
+    command_strings: &[&[String]],
+    target: Option<&GenericBuildStep>,
+    log: &BuildLog,
+) -> DistResult<(ExitStatus, String)> {
+    let commands = command_strings
+        .iter()
+        .map(|command_string| prepare_command(dist_graph, command_string, target))
+        .collect::<DistResult<Vec<_>>>()?;
+    let prepared = commands.iter().collect::<Vec<_>>();
+    let signature = signature::signature_of(&prepared);
+    if let Some(step) = target.filter(|step| !step.extra && step.data.is_none()) {
+        if let Some(path) = repro::write_repro_manifest(dist_graph, &prepared, step)? {
+            info!("wrote repro manifest to {path}");
+        }
+    }
+    let mut audit = network::NetworkAudit::start(dist_graph, target)?;
+    let mut status = None;
+    for command in commands {
+        disk_space::ensure_free_space(dist_graph, target)?;
+        let dir = command
+            .get_current_dir()
+            .and_then(Utf8Path::from_path)
+            .unwrap_or(Utf8Path::new("."));
+        script::check_build_program(&command.get_program().to_string_lossy(), dir)?;
+        let command = match &mut audit {
+            Some(audit) => audit.wrap(&command),
+            None => command,
+        };
+        let mut command = wrap_command(dist_graph, command, target)?;
+        // Only real failures stop the commands after this one (as errors), but
+        // a tolerated failure is still what the build reports in the end
+        let result = run_relayed(dist_graph, &mut command, log)?;
+        if status.map_or(true, |status: ExitStatus| status.success()) {
+            status = Some(result);
+        }
+    }
+    let status = status.expect("a build must have at least one command");
+    if let Some(audit) = audit {
+        let build = match target {
+            Some(step) => step.target_triple.clone(),
+            None => log.path.file_stem().unwrap_or("the build").to_owned(),
+        };
+        audit.finish(&build)?;
+    }
+    Ok((status, signature))
+}
+
+/// Assemble the command for a build, with the environment it should run in
+pub(crate) fn build_command(
+    dist_graph: &DistGraph,
+    command_string: &[String],
+    target: Option<&GenericBuildStep>,
+) -> DistResult<Cmd> {
+    let command = prepare_command(dist_graph, command_string, target)?;
+    wrap_command(dist_graph, command, target)
+}
+
+/// The command for a build with all the environment we set for it, as it would run on the host
+pub(crate) fn prepare_command(
+    dist_graph: &DistGraph,
+    command_string: &[String],
+    target: Option<&GenericBuildStep>,
+) -> DistResult<Cmd> {
+    let mut command_string = command_string.to_owned();
+    if dist_graph.generic_build.login_shell.unwrap_or(false) {
+        command_string = shell::login_shell_command(
+            dist_graph.generic_build.shell.as_deref(),
+            cfg!(windows),
+            &command_string,
+        );
+    }
+
+    let mut desired_extra_env = vec![];
+    let mut cflags = None;
+    let mut ldflags = None;
+    let brew_env = if uses_brew(dist_graph, target) {
+        brew_env(dist_graph)?
+    } else {
+        None
+    };
+    if let Some(brew_env) = brew_env {
+        desired_extra_env.clone_from(&brew_env.extra_env);
+        cflags = Some(brew_env.cflags.clone());
+        ldflags = Some(brew_env.ldflags.clone());
+    }
+
+    let args = command_string.split_off(1);
+    let command_name = command_string
+        .first()
+        .expect("The build command must contain at least one entry");
+    let mut command = Cmd::new(command_name, format!("exec generic build: {command_name}"));
+    for arg in args {
+        command.arg(arg);
+    }
+    // Hide inherited variables first, so the ones we set below still apply
+    env_policy::apply_env_policy(dist_graph, &mut command, target);
+    if let Some(prefixes) = &dist_graph.generic_build.strip_env_prefixes {
+        let keep = dist_graph
+            .generic_build
+            .keep_env
+            .as_deref()
+            .unwrap_or_default();
+        let inherited = env::vars_os()
+            .filter_map(|(key, _)| key.into_string().ok())
+            .collect::<Vec<_>>();
+        for key in stripped_env_vars(inherited.iter().map(String::as_str), prefixes, keep) {
+            command.env_remove(key);
+        }
+    }
+    // Likewise anything that would leak who's building and where
+    if reproducible::wants_reproducible_env(dist_graph) {
+        for var in reproducible::volatile_vars(dist_graph) {
+            command.env_remove(var);
+        }
+    }
+    // Then what setup scripts did, so everything we set ourselves still wins
+    for (key, value) in setup_env(dist_graph)? {
+        match value {
+            Some(value) => command.env(key, value),
+            None => command.env_remove(key),
+        };
+    }
+    // Host-only builds are run like extra builds, without being told about the target
+    let cross = target.filter(|step| !step.host_only);
+    let mut env_file = match &dist_graph.generic_build.env_file {
+        Some(path) => load_env_file(&dist_graph.workspace_dir.join(path))?,
+        None => vec![],
+    };
+    // Applied after the global env-file, so target-specific values win
+    if let Some(path) = cross.and_then(|step| {
+        dist_graph
+            .generic_build
+            .target_env_files
+            .as_ref()?
+            .get(&step.target_triple)
+    }) {
+        env_file.extend(load_env_file(&dist_graph.workspace_dir.join(path))?);
+    }
+    let env_file_precedence = dist_graph
+        .generic_build
+        .env_file_precedence
+        .unwrap_or_default();
+    if env_file_precedence == EnvFilePrecedence::Lowest {
+        command.envs(env_file.clone());
+    }
+    // Then the configured ones, named for the target if they like
+    let triple = cross.map(|step| step.target_triple.as_str());
+    for (key, value) in dist_graph.generic_build.extra_env.iter().flatten() {
+        let key = expand_target_placeholders(key, triple);
+        let value = expand_target_placeholders(value, triple);
+        if let (Some(key), Some(value)) = (key, value) {
+            desired_extra_env.push((key, value));
+        }
+    }
+    // If we generated any extra environment variables to
+    // inject into the environment, apply them now.
+    command.envs(desired_extra_env);
+    if env_file_precedence == EnvFilePrecedence::AboveBrew {
+        command.envs(env_file.clone());
+    }
+
+    // We pipe the output of builds, which most tools take as a hint to
+    // disable colors, so tell them to keep them if the user wants.
+    if dist_graph.generic_build.force_color.unwrap_or(false) {
+        command.env("CLICOLOR_FORCE", "1");
+        command.env("FORCE_COLOR", "1");
+    }
+
+    // Big builds can fill a small /tmp, so they can be given somewhere roomier
+    if let Some(tmp_dir) = &dist_graph.generic_build.tmp_dir {
+        let tmp_dir = dist_graph.workspace_dir.join(tmp_dir);
+        std::fs::create_dir_all(&tmp_dir)?;
+        for var in TMP_DIR_VARS {
+            command.env(var, &tmp_dir);
+        }
+    }
+
+    // Let builds stamp what they make with the release they're part of
+    if let Some(commit) = git_commit(dist_graph) {
+        command.env("CARGO_DIST_GIT_COMMIT", commit);
+    }
+    if let Some(tag) = &dist_graph.announcement_tag {
+        command.env("CARGO_DIST_GIT_TAG", tag);
+    }
+    if let Some(version) = &dist_graph.announcement_version {
+        command.env("CARGO_DIST_VERSION", version);
+    }
+
+    if let Some(step) = target {
+        if let Some(dir) = isolated::isolated_build_dir(dist_graph, step) {
+            command.current_dir(dir);
+        } else if step.data.is_some() {
+            command.current_dir(&dist_graph.workspace_dir);
+        }
+
+        let target = step.target_triple.as_str();
+        // Ensure we inform the build what architecture and platform
+        // it's building for.
+        if !step.host_only {
+            command.env("CARGO_DIST_TARGET", target);
+        }
+        if let Some(toolchain_file) = toolchain_file(dist_graph, step) {
+            if !toolchain_file.is_file() {
+                return Err(DistError::ToolchainFileMissing {
+                    target: target.to_owned(),
+                    path: toolchain_file,
+                });
+            }
+            command.env("CARGO_DIST_TOOLCHAIN_FILE", toolchain_file);
+        }
+        if let Some(output_base) = &step.output_base {
+            command.env("CARGO_DIST_OUT_DIR", output_base);
+        }
+        if let Some(profile) = &step.profile {
+            command.env("CARGO_DIST_PROFILE", profile);
+        }
+        if let Some(variant) = &step.variant {
+            command.env("CARGO_DIST_VARIANT", variant);
+        }
+        command.envs(data::data_env(dist_graph, step));
+
+        if uses_c_compilers(dist_graph, step) {
+            // Containers and remote hosts bring their own toolchain, so we can't check theirs
+            if step.container_image.is_none() && step.remote_host.is_none() {
+                ensure_wasm_toolchain(target)?;
+            }
+            if let Some(flags) = wasm_toolchain(target).and_then(|wasm| wasm.flags(target)) {
+                cflags = merge_flags([cflags.as_deref(), Some(&flags)]);
+                ldflags = merge_flags([ldflags.as_deref(), Some(&flags)]);
+            }
+            let cc = resolved_cc(dist_graph, target);
+            let cxx = resolved_cxx(dist_graph, target);
+            if let Some(linker) = &dist_graph.generic_build.linker {
+                let selection = linker::select_linker(linker, &cc);
+                // Containers and remote hosts bring their own toolchain, so we can't check theirs
+                if step.container_image.is_none() && step.remote_host.is_none() {
+                    linker::ensure_linker_installed(linker, &selection)?;
+                }
+                command.env("LD", &selection.program);
+                ldflags = merge_flags([ldflags.as_deref(), selection.ldflag.as_deref()]);
+            }
+            if let Some(flag) = build_id::build_id_ldflags(dist_graph, target) {
+                ldflags = merge_flags([ldflags.as_deref(), Some(flag)]);
+            }
+            match dist_graph.generic_build.compiler_cache {
+                Some(cache) => {
+                    command.env(cache.dir_env_var(), cache::cache_dir(dist_graph, cache));
+                    // CMake is explicitly given the real compilers, and wants
+                    // wrappers like this passed as "launchers" instead
+                    command.env("CMAKE_C_COMPILER_LAUNCHER", cache.command());
+                    command.env("CMAKE_CXX_COMPILER_LAUNCHER", cache.command());
+                    command.env("CC", cache::wrap_compiler(cache, &cc));
+                    command.env("CXX", cache::wrap_compiler(cache, &cxx));
+                }
+                None => {
+                    command.env("CC", cc);
+                    command.env("CXX", cxx);
+                }
+            }
+        }
+
+        if step.builder == GenericBuilder::Go && !step.host_only {
+            let Some((goos, goarch)) = go_os_arch(target) else {
+                return Err(DistError::UnsupportedGoTarget {
+                    target: target.to_owned(),
+                });
+            };
+            command.env("GOOS", goos);
+            command.env("GOARCH", goarch);
+            let cgo = dist_graph.generic_build.cgo.unwrap_or(false);
+            command.env("CGO_ENABLED", if cgo { "1" } else { "0" });
+        }
+    }
+
+    // Merge in flags for any pkg-config packages, after brew's
+    if let Some(packages) = &dist_graph.generic_build.pkg_config_deps {
+        if let Some((pkg_cflags, pkg_ldflags)) = fetch_pkg_config_flags(packages)? {
+            cflags = merge_flags([cflags.as_deref(), Some(&pkg_cflags)]);
+            ldflags = merge_flags([ldflags.as_deref(), Some(&pkg_ldflags)]);
+        }
+    }
+
+    // Point compilers at the target's sysroot, if it has one
+    let mut cxxflags = None;
+    if let Some(step) = cross {
+        if let Some(dir) = sysroot::ensure_sysroot(dist_graph, &step.target_triple)? {
+            let flag = sysroot::sysroot_flag(&dir);
+            cflags = merge_flags([cflags.as_deref(), Some(&flag)]);
+            cxxflags = Some(flag.clone());
+            ldflags = merge_flags([ldflags.as_deref(), Some(&flag)]);
+        }
+    }
+
+    // Then sanitizers, for verification builds
+    if let (Some(flags), Some(_)) = (sanitizers::sanitizer_flags(&dist_graph.sanitizers), cross) {
+        cflags = merge_flags([cflags.as_deref(), Some(&flags)]);
+        cxxflags = merge_flags([cxxflags.as_deref(), Some(&flags)]);
+        ldflags = merge_flags([ldflags.as_deref(), Some(&flags)]);
+    }
+
+    // Then the flags profile's and the variant's, for every generic build
+    if let Some(step) = target {
+        let profile = dist_graph.flags_profile.as_ref().and_then(|profile| {
+            let profiles = dist_graph.generic_build.flags_profiles.as_ref()?;
+            profiles.get(profile)
+        });
+        let variant = variants::variant(&dist_graph.generic_build, step.variant.as_deref())
+            .map(|variant| &variant.flags);
+        for flags in profile.into_iter().chain(variant) {
+            let joined = |flags: &Option<Flags>| flags.as_ref().map(Flags::joined);
+            cflags = merge_flags([cflags.as_deref(), joined(&flags.cflags).as_deref()]);
+            cxxflags = merge_flags([cxxflags.as_deref(), joined(&flags.cxxflags).as_deref()]);
+            ldflags = merge_flags([ldflags.as_deref(), joined(&flags.ldflags).as_deref()]);
+        }
+    }
+
+    // Then the target's own flags, last so they win over brew's, pkg-config's, the profile's and the variant's
+    if let (Some(flags), Some(step)) = (&dist_graph.generic_build.target_flags, cross) {
+        let flags = target_flags(flags, &step.target_triple);
+        cflags = merge_flags([cflags.as_deref(), flags.cflags.as_deref()]);
+        cxxflags = merge_flags([cxxflags.as_deref(), flags.cxxflags.as_deref()]);
+        ldflags = merge_flags([ldflags.as_deref(), flags.ldflags.as_deref()]);
+    }
+
+    // Then have compilers record where the build is as `.`, for reproducibility
+    if reproducible::wants_reproducible_env(dist_graph) {
+        let mut dirs = vec![dist_graph.workspace_dir.clone()];
+        let run_dir = match command.get_current_dir() {
+            Some(dir) => Utf8PathBuf::from_path_buf(dir.to_owned()).ok(),
+            None => env::current_dir()
+                .ok()
+                .and_then(|dir| Utf8PathBuf::from_path_buf(dir).ok()),
+        };
+        dirs.extend(run_dir.filter(|dir| *dir != dist_graph.workspace_dir));
+        let flags = reproducible::prefix_map_flags(&dirs);
+        cflags = merge_flags([cflags.as_deref(), Some(&flags)]);
+        cxxflags = merge_flags([cxxflags.as_deref(), Some(&flags)]);
+        if target.is_some_and(|step| step.builder == GenericBuilder::Go) {
+            let goflags = env::var("GOFLAGS").ok();
+            command.env(
+                "GOFLAGS",
+                merge_flags([goflags.as_deref(), Some("-trimpath")]).unwrap_or_default(),
+            );
+        }
+    }
+
+    // Pass CFLAGS/LDFLAGS for C builds
+    if let Some(cflags) = cflags {
+        // These typically contain the same values as each other.
+        // Properly speaking, CPPFLAGS is for C++ software and CFLAGS is for
+        // C software, but many buildsystems treat them as interchangeable.
+        command.env("CFLAGS", &cflags);
+        command.env("CPPFLAGS", &cflags);
+    }
+    if let Some(cxxflags) = cxxflags {
+        command.env("CXXFLAGS", &cxxflags);
+    }
+    if let Some(ldflags) = ldflags {
+        command.env("LDFLAGS", &ldflags);
+    }
+
+    if env_file_precedence == EnvFilePrecedence::Highest {
+        command.envs(env_file);
+    }
+
+    // These were asked for just now, so they beat anything configured
+    let prefix = dist_graph
+        .generic_build
+        .build_arg_prefix
+        .as_deref()
+        .unwrap_or_default();
+    for (key, value) in &dist_graph.build_args {
+        command.env(format!("{prefix}{key}"), value);
+    }
+
+    // The command's own exec log doesn't say much about the circumstances
+    // it's running in, which makes logs for multiple targets hard to tell apart
+    info!("{}", BuildContext::new(dist_graph, &command, target)?);
+
+    Ok(command)
+}
+
+/// Arrange for a prepared build command to run where (and as who) it should
+pub(crate) fn wrap_command(
+    dist_graph: &DistGraph,
+    mut command: Cmd,
+    target: Option<&GenericBuildStep>,
+) -> DistResult<Cmd> {
+    // This must come last, so it can forward all the env we computed above
+    if let Some(image) = target.and_then(|step| step.container_image.as_deref()) {
+        command = container::containerize(dist_graph, &command, image)?;
+    } else if let Some(remote) = target.and_then(|step| step.remote_host.as_ref()) {
+        command = remote::remotize(dist_graph, &command, remote)?;
+    } else {
+        build_user::run_as_build_user(dist_graph, &mut command.inner)?;
+        limits::apply_limits(dist_graph, &mut command.inner);
+    }
+
+    Ok(command)
+}
+
+/// The circumstances a build command runs in, for logging
+pub(crate) struct BuildContext {
+    /// The target being built for (if any)
+    pub target: Option<TargetTriple>,
+    /// The machine we're building on
+    pub host: TargetTriple,
+    /// The working directory of the build
+    pub cwd: Utf8PathBuf,
+    /// The C compiler we selected (if any)
+    pub cc: Option<String>,
+    /// The C++ compiler we selected (if any)
+    pub cxx: Option<String>,
+}
+
+impl BuildContext {
+    fn new(
+        dist_graph: &DistGraph,
+        command: &Cmd,
+        target: Option<&GenericBuildStep>,
+    ) -> DistResult<Self> {
+        let env_var = |key: &str| {
+            command
+                .get_envs()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, v)| v)
+                .map(|v| v.to_string_lossy().into_owned())
+        };
+        let cwd = match command.get_current_dir() {
+            Some(dir) => dir.to_owned(),
+            None => env::current_dir()?,
+        };
+        Ok(Self {
+            target: target.map(|step| step.target_triple.clone()),
+            host: dist_graph.tools.cargo.host_target.clone(),
+            cwd: Utf8PathBuf::from_path_buf(cwd).expect("current dir isn't utf8!?"),
+            cc: env_var("CC"),
+            cxx: env_var("CXX"),
+        })
+    }
+}
+
+impl std::fmt::Display for BuildContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unset = "(unset)";
+        write!(
+            f,
+            "build context: target={} host={} cwd={} CC={} CXX={}",
+            self.target.as_deref().unwrap_or(unset),
+            self.host,
+            self.cwd,
+            self.cc.as_deref().unwrap_or(unset),
+            self.cxx.as_deref().unwrap_or(unset),
+        )
+    }
+}
+
+/// Build a generic targets
+///
+/// Returns how many files the build copied into the dist dir.
+pub fn build_generic_target(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    target: &GenericBuildStep,
+) -> DistResult<usize> {
+    // None of what data builds make goes in the dist dir
+    if let Some(name) = &target.data {
+        data::build_data(dist_graph, target, name)?;
+        return Ok(0);
+    }
+    if resume::skip_resumed_build(dist_graph, manifest, target)? {
+        return Ok(0);
+    }
+    let copied = events::with_events(dist_graph, Some(&target.target_triple), || {
+        let built = build_target_outputs(dist_graph, target)?;
+        process_built_target(dist_graph, manifest, target, built)
+    })?;
+    resume::record_completed_build(dist_graph, manifest, target)?;
+    Ok(copied)
+}
+
+/// What a generic build left behind, for [`process_built_target`][]
+pub(crate) struct BuiltTarget {
+    /// The build's log, which post-processing keeps writing to
+    log: BuildLog,
+    /// The build's isolated copy of the workspace, which its outputs may be in
+    isolated: Option<IsolatedBuildDir>,
+    /// The signature of what was run
+    signature: String,
+    /// The binaries it actually built
+    built_binaries: Vec<BinaryIdx>,
+    /// Where it put them
+    found: Vec<FoundOutput>,
+    /// How long each stage has taken so far
+    timer: StageTimer,
+}
+
+impl BuiltTarget {
+    /// Where the build put its binaries
+    pub(crate) fn output_paths(&self) -> impl Iterator<Item = &Utf8Path> {
+        self.found.iter().map(|output| output.path.as_path())
+    }
+}
+
+/// The first half of [`build_generic_target`][]: run the build and find its binaries
+///
+/// With `--verify-reproducible`, the build is run twice, and fails unless both
+/// runs made the same binaries.
+pub(crate) fn build_target_outputs(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> DistResult<BuiltTarget> {
+    if !dist_graph.verify_reproducible {
+        return build_target_outputs_once(dist_graph, target);
+    }
+    let first = build_target_outputs_once(dist_graph, target)?;
+    let first = reproducibility::set_aside_first_build(dist_graph, target, first)?;
+    eprintln!(
+        "building generic target ({}) again to check it's reproducible",
+        target.target_triple
+    );
+    let second = build_target_outputs_once(dist_graph, target)?;
+    reproducibility::check_second_build(dist_graph, target, &first, &second)?;
+    Ok(second)
+}
+
+/// Run a build once and find its binaries, for [`build_target_outputs`][]
+fn build_target_outputs_once(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> DistResult<BuiltTarget> {
+    if target.expected_binaries.is_empty() {
+        if dist_graph.generic_build.strict.unwrap_or(false) {
+            return Err(DistError::EmptyGenericBuild {
+                target: target.target_triple.clone(),
+            });
+        }
+        warn!(
+            "the generic build for {} doesn't produce any binaries, so it won't produce any artifacts",
+            target.target_triple
+        );
+    }
+
+    eprintln!(
+        "building generic target ({} via {})",
+        target.target_triple,
+        target
+            .commands()
+            .iter()
+            .map(|command| command.join(" "))
+            .collect::<Vec<_>>()
+            .join(", then ")
+    );
+
+    let mut timer = StageTimer::start();
+    patterns::validate_output_patterns(dist_graph)?;
+    shell::validate_script_shell(dist_graph)?;
+    let log = BuildLog::create(dist_graph, &target.target_triple)?.with_verbosity(target.verbosity);
+    // Must outlive everything that looks at the build's outputs
+    let isolated = IsolatedBuildDir::create(dist_graph, target)?;
+    // Isolated builds can't touch the sources
+    let snapshot = match isolated {
+        Some(_) => None,
+        None => TreeSnapshot::take(dist_graph, &dist_graph.workspace_dir)?,
+    };
+    let root = match &isolated {
+        Some(isolated) => isolated.path.clone(),
+        None => Utf8PathBuf::from("."),
+    };
+    let pending_inputs = inputs::verify_inputs_before_build(dist_graph, &root)?;
+    let unnamed = UnnamedOutputs::snapshot(dist_graph, target, &root)?;
+    preexisting::check_preexisting_outputs(dist_graph, target, &root)?;
+    let output_dirs = OutputDirSnapshot::take(dist_graph, target, &root)?;
+    remote::sync_to_remote(dist_graph, target)?;
+    timer.finish("prepare");
+    let mut attempt_start = 0;
+    let result = retry::with_retries(dist_graph, &log, || {
+        let start = log.len()?;
+        attempt_start = start;
+        let (status, signature) = match target.builder {
+            GenericBuilder::Cmake => cmake::run_cmake_build(dist_graph, target, &log),
+            GenericBuilder::Command | GenericBuilder::Go => {
+                run_builds(dist_graph, &target.commands(), Some(target), &log)
+            }
+        }?;
+        // Some tools exit zero even when they fail
+        if patterns::has_output_patterns(dist_graph) {
+            let output = log.read_since(start)?;
+            patterns::check_output_patterns(dist_graph, &target.target_triple, &output)?;
+        }
+        Ok((status, signature))
+    });
+    // Earlier attempts' diagnostics may have been fixed by retrying
+    annotations::emit_annotations(
+        dist_graph,
+        &log,
+        attempt_start,
+        isolated.as_ref().map(|isolated| isolated.path.as_path()),
+    )?;
+    let result = result.inspect_err(|e| {
+        if matches!(e, DistError::BuildInterrupted) {
+            interrupt::remove_partial_outputs(dist_graph, target);
+            return;
+        }
+        match failures::capture_failure_artifacts(dist_graph, target, &root) {
+            Ok(captured) if captured.is_empty() => {}
+            Ok(captured) => eprintln!(
+                "captured {} failure artifacts to {}",
+                captured.len(),
+                failures::failure_artifacts_dir(dist_graph, target)
+            ),
+            Err(e) => warn!("couldn't capture failure artifacts: {e}"),
+        }
+    })?;
+    let (result, signature) = result;
+    timer.finish("build");
+    inputs::verify_inputs_after_build(&pending_inputs, &root)?;
+
+    info!("build log written to {}", log.path);
+    if let Some(snapshot) = &snapshot {
+        snapshot.check_unchanged(&target.target_triple)?;
+    }
+    if let Some(cache) = dist_graph.generic_build.compiler_cache {
+        cache::report_cache_stats(dist_graph, cache);
+    }
+    if !result.success() {
+        eprintln!("Build exited non-zero: {}", result);
+    }
+
+    // Since generic builds provide no feedback, go looking for what we expected
+    let expected_outputs = target
+        .expected_binaries
+        .iter()
+        .map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            let candidates = match unnamed.candidates(&binary.name, &root)? {
+                Some(candidates) => candidates,
+                None => output_candidates(dist_graph, target, &binary.file_name),
+            };
+            Ok(ExpectedOutput {
+                owner: package_id_string(binary.pkg_id.as_ref()),
+                name: binary.file_name.clone(),
+                candidates,
+            })
+        })
+        .map(|output: DistResult<ExpectedOutput>| {
+            let mut output = output?;
+            if let Some(isolated) = &isolated {
+                for candidate in &mut output.candidates {
+                    *candidate = isolated.path.join(&*candidate);
+                }
+            }
+            Ok(output)
+        })
+        .collect::<DistResult<Vec<_>>>()?;
+    remote::fetch_outputs(dist_graph, target, &expected_outputs)?;
+    wait_for_missing_outputs(dist_graph, &expected_outputs);
+    recover_missing_outputs(dist_graph, target, &expected_outputs, &log)?;
+    // Binaries the build may legitimately not produce are dropped, with a warning
+    let mut built_binaries = vec![];
+    let mut built_outputs = vec![];
+    for (binary_idx, output) in target.expected_binaries.iter().zip(expected_outputs) {
+        let binary = dist_graph.binary(*binary_idx);
+        if target.allow_missing.contains(&binary.name)
+            && !output.candidates.iter().any(|path| path.exists())
+        {
+            warn!(
+                "the build for {} didn't produce {}, which is allowed to be missing",
+                target.target_triple, binary.name
+            );
+            continue;
+        }
+        built_binaries.push(*binary_idx);
+        built_outputs.push(output);
+    }
+    let found = find_outputs(&built_outputs)?;
+    wait_for_stable_outputs(dist_graph, found.iter().map(|output| &output.path))?;
+    undeclared::check_undeclared_outputs(dist_graph, target, &output_dirs, &found)?;
+    timer.finish("collect");
+    Ok(BuiltTarget {
+        log,
+        isolated,
+        signature,
+        built_binaries,
+        found,
+        timer,
+    })
+}
+
+/// The second half of [`build_generic_target`][]: check, copy and publish what was built
+///
+/// Returns how many files were copied into the dist dir.
+pub(crate) fn process_built_target(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    target: &GenericBuildStep,
+    built: BuiltTarget,
+) -> DistResult<usize> {
+    let BuiltTarget {
+        log,
+        isolated,
+        signature,
+        built_binaries,
+        found,
+        mut timer,
+    } = built;
+    let mut expected =
+        BuildExpectations::new(dist_graph, &built_binaries).staged(&target.target_triple);
+    let mut outputs = vec![];
+    let mut companions = vec![];
+    for (binary_idx, output) in built_binaries.iter().zip(found) {
+        let binary = dist_graph.binary(*binary_idx);
+        magic::check_binary_format(dist_graph, binary, &output.path)?;
+        magic::check_architecture(dist_graph, binary, &output.path)?;
+        portability::check_portability(dist_graph, binary, &output.path)?;
+        sizes::check_binary_size(dist_graph, binary, &output.path)?;
+        if let Some(companion) = wasm::emscripten_companion(&binary.target, &output.path) {
+            companions.push((binary, companion));
+        }
+        // Ship a stripped copy if we're splitting out debug info
+        let (path, symbols) =
+            match debuginfo::split_debuginfo(dist_graph, target, binary, &output.path)? {
+                Some((stripped, debug)) => (stripped, vec![debug]),
+                None => (output.path, vec![]),
+            };
+        let path = rpath::make_relocatable(dist_graph, target, binary, &path)?.unwrap_or(path);
+        expected.found_named_bin(
+            package_id_string(binary.pkg_id.as_ref()),
+            &binary.name,
+            path.clone(),
+            symbols,
+        );
+        outputs.push((output.name, path));
+    }
+
+    // Check and process the binaries
+    expected.process_bins(dist_graph, manifest)?;
+    timer.finish("check");
+
+    // Copy out everything else the build was supposed to produce
+    let root = match &isolated {
+        Some(isolated) => isolated.path.clone(),
+        None => Utf8PathBuf::from("."),
+    };
+    let mut extra_copied = vec![];
+    // Emscripten's JS loaders go next to the wasm they load
+    let symlinks = dist_graph.generic_build.symlinks.unwrap_or_default();
+    let copy_mode = dist_graph.generic_build.copy_mode.unwrap_or_default();
+    for (binary, companion) in &companions {
+        for dest in &binary.copy_exe_to {
+            let dest =
+                staging::staged_path(dist_graph, &target.target_triple, dest).with_extension("js");
+            let copied = copy_into(companion, &dest, symlinks, copy_mode)?;
+            verify_copy(dist_graph, companion, &copied)?;
+            extra_copied.push(copied);
+        }
+    }
+    for (pattern, dest) in &target.extra_outputs {
+        let expected = glob_outputs(&root, pattern)?;
+        if expected.is_empty() {
+            return Err(DistError::MissingExtraOutputs {
+                target: target.target_triple.clone(),
+                pattern: pattern.clone(),
+            });
+        }
+        let dest = staging::staged_path(
+            dist_graph,
+            &target.target_triple,
+            &dist_graph.dist_dir.join(dest),
+        );
+        std::fs::create_dir_all(&dest)?;
+        extra_copied.extend(collect_and_copy_outputs(&expected, &dest, dist_graph)?);
+    }
+
+    if let Some(database) = compile_commands::collect_compile_commands(dist_graph, target, &root)? {
+        info!("copied the build's compilation database to {database}");
+    }
+
+    // Arrange everything for installers, now that it's all been copied
+    let staged = layout::assemble_layout(dist_graph, target, &outputs, &extra_copied)?;
+
+    // Publish what the build staged, now that it's all there
+    let binaries_staged = built_binaries
+        .iter()
+        .flat_map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            binary.copy_exe_to.iter().chain(&binary.copy_symbols_to)
+        })
+        .map(|path| staging::staged_path(dist_graph, &target.target_triple, path))
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    staging::promote(dist_graph, &target.target_triple, &binaries_staged)?;
+    let mut extra_copied = staging::promote(dist_graph, &target.target_triple, &extra_copied)?;
+    extra_copied.extend(sbom::write_sboms(dist_graph, target, &built_binaries)?);
+    let staged = staging::promote(dist_graph, &target.target_triple, &staged)?;
+
+    // Make sure every binary and symbols file really made it
+    let exe_dests = built_binaries
+        .iter()
+        .flat_map(|binary_idx| &dist_graph.binary(*binary_idx).copy_exe_to);
+    let symbol_copies = expected.symbol_copies(dist_graph);
+    verify_destinations(exe_dests.chain(symbol_copies.iter().map(|(_, dest)| dest)))?;
+
+    timer.finish("copy");
+
+    // Then check the binaries that have checks of their own
+    validate::validate_binaries(dist_graph, target, &built_binaries, &log)?;
+    timer.finish("validate");
+
+    // Let the user's script have its way with everything, now it's where it'll stay
+    let produced = built_binaries
+        .iter()
+        .flat_map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            binary.copy_exe_to.iter().chain(&binary.copy_symbols_to)
+        })
+        .filter(|path| path.exists())
+        .chain(&extra_copied)
+        .cloned()
+        .collect::<Vec<_>>();
+    post_process::run_post_process(dist_graph, target, &produced, &log)?;
+    // After post-processing, which could have replaced them
+    modes::apply_output_modes(
+        dist_graph,
+        produced.iter().chain(&staged).map(|path| path.as_path()),
+    )?;
+    timer.finish("post-process");
+
+    // Apple wants to have a look at macOS binaries before anyone runs them
+    let notarizable = built_binaries
+        .iter()
+        .flat_map(|binary_idx| &dist_graph.binary(*binary_idx).copy_exe_to)
+        .chain(
+            extra_copied
+                .iter()
+                .filter(|path| matches!(path.extension(), Some("dmg" | "pkg"))),
+        )
+        .cloned()
+        .collect::<Vec<_>>();
+    notarize::notarize_outputs(dist_graph, &target.target_triple, &notarizable)?;
+    timer.finish("notarize");
+
+    // These only get checksummed once we know what they are
+    dist_graph
+        .extra_outputs_copied
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .extend(extra_copied.iter().cloned());
+
+    if let Some(mtime) = reproducible::output_mtime(dist_graph)? {
+        let copied = target.expected_binaries.iter().flat_map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            binary.copy_exe_to.iter().chain(&binary.copy_symbols_to)
+        });
+        // Missing binaries (and symbols) that were allowed never got copied
+        let copied = copied.filter(|path| path.exists());
+        reproducible::normalize_mtimes(
+            copied
+                .chain(&extra_copied)
+                .chain(&staged)
+                .map(|path| path.as_path()),
+            mtime,
+        )?;
+    }
+
+    let binaries_copied = target
+        .expected_binaries
+        .iter()
+        .flat_map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            binary.copy_exe_to.iter().chain(&binary.copy_symbols_to)
+        })
+        .filter(|path| path.exists())
+        .chain(&extra_copied)
+        .chain(&staged)
+        .map(|path| path.as_path())
+        .collect::<Vec<_>>();
+    events::emit_copied(dist_graph, binaries_copied.iter().copied())?;
+    staging_manifest::append_staging_manifest(
+        dist_graph,
+        Some(&target.target_triple),
+        binaries_copied,
+    )?;
+
+    if let Some(list) = artifact_list::write_artifact_list(dist_graph, &target.target_triple)? {
+        info!("wrote artifact list to {list}");
+    }
+
+    // Record how we got them
+    let provenance = build_provenance(dist_graph, target, &outputs, signature)?;
+    manifest.provenance.push(provenance);
+    manifest.provenance.sort();
+
+    staging::remove_target_staging(dist_graph, &target.target_triple)?;
+    timer.finish("record");
+    if let Some(profile) = timing::write_timing_profile(dist_graph, target, &timer)? {
+        info!("wrote timing profile to {profile}");
+    }
+    Ok(outputs.len() + extra_copied.len())
+}
+
+/// Where a build is expected to put the binary called `file_name`, unless `unnamed-outputs` says otherwise
+pub(crate) fn output_candidates(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    file_name: &str,
+) -> Vec<Utf8PathBuf> {
+    match target.builder {
+        GenericBuilder::Cmake => cmake::output_candidates(dist_graph, target, file_name),
+        GenericBuilder::Command | GenericBuilder::Go => vec![output_path(target, file_name)],
+    }
+}
+
+/// Run `on-missing` if the build didn't produce binaries it was supposed to
+///
+/// This runs once, however many binaries are missing. Whatever it produces is
+/// found like the build's own outputs, and anything still missing afterwards
+/// fails the build as usual.
+fn recover_missing_outputs(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    expected_outputs: &[ExpectedOutput],
+    log: &BuildLog,
+) -> DistResult<()> {
+    let Some(recovery) = &dist_graph.generic_build.on_missing else {
+        return Ok(());
+    };
+    let missing = target
+        .expected_binaries
+        .iter()
+        .zip(expected_outputs)
+        .filter(|(_, output)| !output.candidates.iter().any(|path| path.exists()))
+        .map(|(binary_idx, _)| dist_graph.binary(*binary_idx).name.as_str())
+        .filter(|name| !target.allow_missing.iter().any(|allowed| allowed == name))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    warn!(
+        "the build for {} didn't produce {}, running on-missing ({})",
+        target.target_triple,
+        missing.join(", "),
+        recovery.join(" ")
+    );
+    let (result, _signature) = run_build(dist_graph, recovery, Some(target), log)?;
+    if !result.success() {
+        eprintln!("on-missing exited non-zero: {}", result);
+    }
+    Ok(())
+}
+
+/// Where a (non-CMake) build is expected to leave the given output
+pub(crate) fn output_path(target: &GenericBuildStep, file_name: &str) -> Utf8PathBuf {
+    match &target.output_base {
+        Some(output_base) => output_base.join(file_name),
+        None => Utf8PathBuf::from(file_name),
+    }
+}
+
+/// Record the inputs and outputs of a generic build, for attestation
+///
+/// `signature` is what running the build returned, which is only recorded if
+/// `build-signature` is enabled.
+pub(crate) fn build_provenance(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    outputs: &[(String, Utf8PathBuf)],
+    signature: String,
+) -> DistResult<BuildProvenance> {
+    let (cc, cxx) = if uses_c_compilers(dist_graph, target) {
+        (
+            Some(resolved_cc(dist_graph, &target.target_triple)),
+            Some(resolved_cxx(dist_graph, &target.target_triple)),
+        )
+    } else {
+        (None, None)
+    };
+    let mut sha256 = BTreeMap::new();
+    for (name, path) in outputs {
+        sha256.insert(
+            name.clone(),
+            generate_checksum(&ChecksumStyle::Sha256, path)?,
+        );
+    }
+    let mut build_ids = BTreeMap::new();
+    if build_id::wants_build_id(dist_graph) && build_id::has_build_id(&target.target_triple) {
+        for (name, path) in outputs {
+            // Static libraries aren't linked yet, so they have no build ID
+            if OutputKind::detect(name, &target.target_triple) == OutputKind::StaticLibrary {
+                continue;
+            }
+            let Some(id) = build_id::read_build_id(path)? else {
+                return Err(DistError::MissingBuildId {
+                    binary: name.clone(),
+                });
+            };
+            build_ids.insert(name.clone(), id);
+        }
+    }
+    Ok(BuildProvenance {
+        target_triple: target.target_triple.clone(),
+        command: target.build_command.clone(),
+        cc,
+        cxx,
+        sha256,
+        build_ids,
+        signature: signature::recorded_signature(dist_graph, signature),
+        metadata: target.artifact_metadata.clone(),
+    })
+}
+
+/// Similar to the above, but with slightly different signatures since
+/// it's not based around axoproject-identified binaries
+pub fn run_extra_artifacts_build(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    target: &ExtraBuildStep,
+) -> DistResult<()> {
+    events::with_events(dist_graph, None, || {
+        run_extra_artifacts_build_inner(dist_graph, manifest, target)
+    })
+}
+
+/// A generic build step for running an extra build that's for `target`, so it's told about it
+fn extra_build_step(
+    dist_graph: &DistGraph,
+    extra: &ExtraBuildStep,
+    target: &TargetTriple,
+) -> GenericBuildStep {
+    GenericBuildStep {
+        target_triple: target.clone(),
+        expected_binaries: vec![],
+        build_command: extra.build_command.clone(),
+        builder: GenericBuilder::Command,
+        container_image: None,
+        remote_host: None,
+        output_base: None,
+        extra_outputs: vec![],
+        allow_missing: vec![],
+        profile: dist_graph.build_profile.clone(),
+        depends_on: vec![],
+        sub_builds: vec![],
+        host_only: false,
+        extra: true,
+        data: None,
+        verbosity: step_verbosity(&dist_graph.generic_build, target),
+        variant: dist_graph.variant.clone(),
+        artifact_metadata: Default::default(),
+    }
+}
+
+/// The body of [`run_extra_artifacts_build`][]
+fn run_extra_artifacts_build_inner(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    target: &ExtraBuildStep,
+) -> DistResult<()> {
+    eprintln!(
+        "building extra artifacts target (via {})",
+        target.build_command.join(" ")
+    );
+
+    let log_name = target
+        .expected_artifacts
+        .first()
+        .and_then(|artifact| Utf8Path::new(artifact).file_name())
+        .unwrap_or("artifacts");
+    let log = BuildLog::create(dist_graph, &format!("extra-{log_name}"))?;
+    let step = target
+        .target
+        .as_ref()
+        .map(|triple| extra_build_step(dist_graph, target, triple));
+    let (result, _signature) = run_build(dist_graph, &target.build_command, step.as_ref(), &log)?;
+
+    info!("build log written to {}", log.path);
+    if !result.success() {
+        eprintln!("Build exited non-zero: {}", result);
+    }
+
+    // Check that we got everything we expected, and copy into the distribution path
+    let expected = target
+        .artifact_names
+        .iter()
+        .map(|(source, name)| ExpectedOutput::at("extra build", source, name))
+        .collect::<Vec<_>>();
+    // Find everything that's missing, rather than just the first
+    wait_for_missing_outputs(dist_graph, &expected);
+    let (found, missing) = find_present_outputs(&expected);
+    let missing = missing
+        .iter()
+        .flat_map(|output| &output.candidates)
+        .map(|path| path.to_string())
+        .collect::<Vec<_>>();
+    let mut image_digests = vec![];
+    for output in &found {
+        match target.formats.get(output.path.as_str()) {
+            Some(ArtifactFormat::OciImage) => {
+                let digest = oci::oci_image_digest(&output.path)?;
+                image_digests.push((output.name.clone(), digest));
+            }
+            Some(format) => formats::check_artifact_format(&output.path, *format)?,
+            None => {}
+        }
+    }
+    // Only files can be uploaded, so image layouts get packed up
+    let (layouts, found): (Vec<_>, Vec<_>) = found.into_iter().partition(|output| {
+        output.path.is_dir()
+            && target.formats.get(output.path.as_str()) == Some(&ArtifactFormat::OciImage)
+    });
+    let copy_partial = dist_graph
+        .generic_build
+        .copy_partial_extra_artifacts
+        .unwrap_or(false);
+    if missing.is_empty() || copy_partial {
+        let dest = staging::work_dir(dist_graph, staging::EXTRA_BUILDS);
+        let mut copied = copy_found_outputs(&found, &dest, dist_graph)?;
+        for layout in &layouts {
+            let packed = dest.join(&layout.name);
+            if let Some(parent) = packed.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            oci::pack_layout(&layout.path, &packed)?;
+            copied.push(packed);
+        }
+        let copied = staging::promote(dist_graph, staging::EXTRA_BUILDS, &copied)?;
+        staging::remove_target_staging(dist_graph, staging::EXTRA_BUILDS)?;
+        if let Some(mtime) = reproducible::output_mtime(dist_graph)? {
+            reproducible::normalize_mtimes(copied.iter().map(|path| path.as_path()), mtime)?;
+        }
+        events::emit_copied(dist_graph, copied.iter().map(|path| path.as_path()))?;
+        staging_manifest::append_staging_manifest(
+            dist_graph,
+            None,
+            copied.iter().map(|path| path.as_path()),
+        )?;
+    }
+    if !missing.is_empty() {
+        return Err(DistError::MissingExtraArtifacts { missing });
+    }
+    for (name, digest) in image_digests {
+        if let Some(artifact) = manifest.artifacts.get_mut(&name) {
+            artifact.image_digest = Some(digest);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Giving copied outputs exactly the permissions installers expect
+//!
+//! Copies of executables get 0755, and everything else keeps whatever the build
+//! gave it. Some installers need more control than that, like a setuid helper
+//! or a config file only its owner may read. `output-modes` maps the name an
+//! output is copied under (a binary's file name, or an extra output's) to the
+//! octal mode it should have:
+//!
+//! ```toml
+//! output-modes = { app = "0755", helper = "4755", "app.conf" = "0600" }
+//! ```
+//!
+//! Once everything's copied (and post-processed), each matching copy gets its
+//! mode, which is then read back to make sure it stuck: some filesystems, and
+//! some users, can't set every bit. Modes are ignored on platforms without unix
+//! permissions.
+
+use camino::Utf8Path;
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// Parse an octal mode like `0755`, `755` or `0o755`
+pub fn parse_mode(mode: &str) -> Option<u32> {
+    let digits = mode.strip_prefix("0o").unwrap_or(mode);
+    if digits.is_empty() || !digits.chars().all(|c| ('0'..='7').contains(&c)) {
+        return None;
+    }
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
+
+/// Give each of the copied outputs the mode `output-modes` says it should have, and check it did
+pub(crate) fn apply_output_modes<'a>(
+    dist_graph: &DistGraph,
+    copied: impl IntoIterator<Item = &'a Utf8Path>,
+) -> DistResult<()> {
+    let Some(modes) = &dist_graph.generic_build.output_modes else {
+        return Ok(());
+    };
+    // Catch typos even if nothing they're for was copied
+    let modes = modes
+        .iter()
+        .map(|(name, mode)| match parse_mode(mode) {
+            Some(parsed) => Ok((name.as_str(), parsed)),
+            None => Err(DistError::InvalidOutputMode {
+                name: name.clone(),
+                mode: mode.clone(),
+            }),
+        })
+        .collect::<DistResult<Vec<_>>>()?;
+    for path in copied {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if let Some((_, mode)) = modes.iter().find(|(output, _)| *output == name) {
+            set_mode(path, *mode)?;
+        }
+    }
+    Ok(())
+}
+
+/// Give a file a mode, and check it stuck
+#[cfg(unix)]
+fn set_mode(path: &Utf8Path, mode: u32) -> DistResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    let actual = path.metadata()?.permissions().mode() & 0o7777;
+    if actual != mode {
+        return Err(DistError::OutputModeNotApplied {
+            path: path.to_owned(),
+            expected: format!("{mode:04o}"),
+            actual: format!("{actual:04o}"),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Utf8Path, _mode: u32) -> DistResult<()> {
+    Ok(())
+}
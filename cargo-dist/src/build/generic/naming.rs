@@ -0,0 +1,168 @@
+//! Naming extra artifacts after the release they're part of
+//!
+//! Extra builds normally leave their artifacts in the dist dir under the name
+//! the build gave them. With a `name-template`, each is published as something
+//! like `myscript-v{version}-{target}.sh` instead, where:
+//!
+//! * `{name}` is the artifact's own file name, minus its extension
+//! * `{version}` is the version being released
+//! * `{target}` is the extra artifact's `target` (which it must then have)
+//!
+//! Archives of each target's binaries can be named to suit an ecosystem's
+//! conventions in the same way, with `archive-name` (or, for particular
+//! targets, `target-archive-names`). Say `archive-name =
+//! "{name}_{version}_{os}_{arch}.{ext}"`, and the archive for
+//! `x86_64-unknown-linux-gnu` is `myapp_1.0.0_linux_x86_64.tar.xz`, where:
+//!
+//! * `{name}` is the app's name, and `{version}` the version being released
+//! * `{target}` is the target triple
+//! * `{arch}` is the target's architecture (the triple's first part)
+//! * `{os}` is the target's operating system, like `linux`, `darwin` or `windows`
+//! * `{ext}` is the archive's extension, without its leading dot
+//!
+//! The archive is registered in the dist-manifest under that name, and a
+//! tarball's top-level directory is named after it, minus the extension.
+
+use crate::{config::GenericBuildConfig, DistError, DistResult};
+
+/// Target triple fragments, and the operating system `{os}` is for them
+///
+/// These are checked in order, so more specific fragments must come first
+/// (android triples also contain "linux").
+const OS_MAPPING: &[(&str, &str)] = &[
+    ("apple-darwin", "darwin"),
+    ("apple-ios", "ios"),
+    ("linux-android", "android"),
+    ("linux", "linux"),
+    ("windows", "windows"),
+    ("freebsd", "freebsd"),
+    ("netbsd", "netbsd"),
+    ("openbsd", "openbsd"),
+    ("illumos", "illumos"),
+    ("wasi", "wasi"),
+];
+
+/// The name an extra artifact (produced by its build at `source`) is published under
+pub fn render_artifact_name(
+    template: &str,
+    source: &str,
+    version: &str,
+    target: Option<&str>,
+) -> DistResult<String> {
+    let name = camino::Utf8Path::new(source).file_stem().unwrap_or(source);
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(DistError::UnknownNamePlaceholder {
+                template: template.to_owned(),
+                placeholder: rest[start..].to_owned(),
+            });
+        };
+        let placeholder = &rest[start + 1..start + len];
+        match placeholder {
+            "name" => rendered.push_str(name),
+            "version" => rendered.push_str(version),
+            "target" => {
+                let Some(target) = target else {
+                    return Err(DistError::NameTemplateNeedsTarget {
+                        template: template.to_owned(),
+                    });
+                };
+                rendered.push_str(target);
+            }
+            _ => {
+                return Err(DistError::UnknownNamePlaceholder {
+                    template: template.to_owned(),
+                    placeholder: format!("{{{placeholder}}}"),
+                })
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// The operating system a target triple is for, or its third part if we don't know it
+pub fn target_os(target: &str) -> &str {
+    if let Some((_, os)) = OS_MAPPING
+        .iter()
+        .find(|(fragment, _)| target.contains(fragment))
+    {
+        return os;
+    }
+    target.split('-').nth(2).unwrap_or("unknown")
+}
+
+/// The template for the name of `target`'s archive, if one's configured
+pub fn archive_name_template<'a>(config: &'a GenericBuildConfig, target: &str) -> Option<&'a str> {
+    config
+        .target_archive_names
+        .as_ref()
+        .and_then(|names| names.get(target))
+        .or(config.archive_name.as_ref())
+        .map(String::as_str)
+}
+
+/// Check every archive-name template only uses placeholders we can fill in
+pub fn check_archive_names(config: &GenericBuildConfig) -> DistResult<()> {
+    let templates = config.archive_name.iter().chain(
+        config
+            .target_archive_names
+            .iter()
+            .flat_map(|names| names.values()),
+    );
+    for template in templates {
+        render_archive_name(
+            template,
+            "app",
+            "1.0.0",
+            "x86_64-unknown-linux-gnu",
+            "tar.xz",
+        )?;
+    }
+    Ok(())
+}
+
+/// The name of the archive of `name`'s binaries for `target`, from an archive-name template
+///
+/// `ext` is the archive's extension, without its leading dot.
+pub fn render_archive_name(
+    template: &str,
+    name: &str,
+    version: &str,
+    target: &str,
+    ext: &str,
+) -> DistResult<String> {
+    let arch = target.split('-').next().unwrap_or(target);
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(DistError::UnknownArchivePlaceholder {
+                template: template.to_owned(),
+                placeholder: rest[start..].to_owned(),
+            });
+        };
+        match &rest[start + 1..start + len] {
+            "name" => rendered.push_str(name),
+            "version" => rendered.push_str(version),
+            "target" => rendered.push_str(target),
+            "arch" => rendered.push_str(arch),
+            "os" => rendered.push_str(target_os(target)),
+            "ext" => rendered.push_str(ext),
+            placeholder => {
+                return Err(DistError::UnknownArchivePlaceholder {
+                    template: template.to_owned(),
+                    placeholder: format!("{{{placeholder}}}"),
+                })
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
@@ -0,0 +1,226 @@
+//! Auditing, and cutting off, builds' access to the network
+//!
+//! A build that downloads things as it goes isn't hermetic: what it makes
+//! depends on what was out there when it ran. Two settings help with that, on
+//! Linux hosts:
+//!
+//! * `network-audit` runs each build command under `strace`, tracing the
+//!   addresses it connects (or sends packets) to. Once the build is done, any
+//!   that aren't loopback are reported as a warning, like
+//!   `x86_64-unknown-linux-gnu reached the network: 140.82.112.3:443`.
+//! * `network-free` declares that builds don't need the network, so each build
+//!   command is run in a network namespace of its own (with `unshare`), where
+//!   there's nothing to connect to. With `network-audit` on too, a build that
+//!   tried anyway fails, listing where it tried to go, even if it coped
+//!   without.
+//!
+//! Builds in containers or on remote hosts aren't audited or cut off, and on
+//! other platforms both settings are ignored with a warning.
+
+use axoprocess::Cmd;
+use tracing::warn;
+
+use super::env_policy::env_policy;
+use crate::{config::EnvPolicy, DistError, DistGraph, DistResult, GenericBuildStep, SortedSet};
+
+/// What the trace of each build command is called (numbered, in a temp dir)
+const TRACE_PREFIX: &str = "network-trace";
+
+/// The network auditing (and isolation) of one build's commands
+pub(crate) struct NetworkAudit {
+    /// Where the traces go, if network access is being traced
+    traces: Option<temp_dir::TempDir>,
+    /// How many commands have been traced
+    traced: usize,
+    /// Whether the build is cut off from the network (and fails if it tries to use it)
+    network_free: bool,
+    /// Whether the build's environment starts out empty (see `env-policy`)
+    clear_env: bool,
+}
+
+impl NetworkAudit {
+    /// Get ready to audit a build, if that's configured (and possible here)
+    pub(crate) fn start(
+        dist_graph: &DistGraph,
+        target: Option<&GenericBuildStep>,
+    ) -> DistResult<Option<Self>> {
+        let config = &dist_graph.generic_build;
+        let audit = config.network_audit.unwrap_or(false);
+        let network_free = config.network_free.unwrap_or(false);
+        if !audit && !network_free {
+            return Ok(None);
+        }
+        // Those run where we can't see them
+        if target.is_some_and(|step| step.container_image.is_some() || step.remote_host.is_some()) {
+            return Ok(None);
+        }
+        if !cfg!(target_os = "linux") {
+            warn!("network-audit and network-free need a Linux host, so builds can use the network unchecked");
+            return Ok(None);
+        }
+        let traces = if audit && has_strace() {
+            Some(temp_dir::TempDir::new()?)
+        } else {
+            if audit {
+                warn!("network-audit needs strace, which isn't installed, so builds' network access won't be audited");
+            }
+            None
+        };
+        if traces.is_none() && !network_free {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            traces,
+            traced: 0,
+            network_free,
+            clear_env: env_policy(dist_graph, target) != EnvPolicy::Inherit,
+        }))
+    }
+
+    /// Wrap a build command so it's traced, and cut off from the network, as configured
+    pub(crate) fn wrap(&mut self, command: &Cmd) -> Cmd {
+        let mut words = vec![];
+        if let Some(traces) = &self.traces {
+            let trace = traces
+                .path()
+                .join(format!("{TRACE_PREFIX}-{}", self.traced));
+            self.traced += 1;
+            words.extend(
+                [
+                    "strace",
+                    "-f",
+                    "-qq",
+                    "-e",
+                    "trace=connect,sendto,sendmsg",
+                    "-e",
+                    "signal=none",
+                    "-o",
+                ]
+                .map(str::to_owned),
+            );
+            words.push(trace.to_string_lossy().into_owned());
+        }
+        if self.network_free {
+            // Mapping ourselves to root is the only way to get a namespace
+            // without privileges that older versions of unshare support
+            words.extend(["unshare", "--net", "--map-root-user", "--"].map(str::to_owned));
+        }
+        clone_command(command, &words, self.clear_env)
+    }
+
+    /// Report where the build tried to reach, failing it if it's declared network-free
+    ///
+    /// Returns the destinations, sorted.
+    pub(crate) fn finish(self, build: &str) -> DistResult<SortedSet<String>> {
+        let Some(traces) = &self.traces else {
+            return Ok(SortedSet::new());
+        };
+        let mut destinations = SortedSet::new();
+        for idx in 0..self.traced {
+            let trace = traces.path().join(format!("{TRACE_PREFIX}-{idx}"));
+            // A command that never started leaves no trace
+            let Ok(trace) = std::fs::read_to_string(trace) else {
+                continue;
+            };
+            destinations.extend(network_destinations(&trace));
+        }
+        if destinations.is_empty() {
+            return Ok(destinations);
+        }
+        let list = destinations.iter().cloned().collect::<Vec<_>>();
+        if self.network_free {
+            return Err(DistError::NetworkAccessed {
+                build: build.to_owned(),
+                destinations: list.join("\n"),
+            });
+        }
+        warn!("{build} reached the network: {}", list.join(", "));
+        Ok(destinations)
+    }
+}
+
+/// Whether strace is installed
+fn has_strace() -> bool {
+    Cmd::new("strace", "check for strace")
+        .arg("-V")
+        .log(None)
+        .check(false)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// A command with the same program, args, environment and working directory, run by `wrapper`
+///
+/// Commands can't tell us if their environment was cleared, so `clear_env` says whether it was.
+fn clone_command(command: &Cmd, wrapper: &[String], clear_env: bool) -> Cmd {
+    let program = command.get_program();
+    let summary = format!("exec build: {}", program.to_string_lossy());
+    let mut wrapped = match wrapper.split_first() {
+        Some((wrapper, args)) => {
+            let mut wrapped = Cmd::new(wrapper, summary);
+            for arg in args {
+                wrapped.arg(arg);
+            }
+            wrapped.arg(program);
+            wrapped
+        }
+        None => Cmd::new(program, summary),
+    };
+    for arg in command.get_args() {
+        wrapped.arg(arg);
+    }
+    if clear_env {
+        wrapped.env_clear();
+    }
+    for (key, val) in command.get_envs() {
+        match val {
+            Some(val) => wrapped.env(key, val),
+            None => wrapped.env_remove(key),
+        };
+    }
+    if let Some(dir) = command.get_current_dir() {
+        wrapped.current_dir(dir);
+    }
+    wrapped
+}
+
+/// The (non-loopback) addresses an strace of `connect`, `sendto` and `sendmsg` shows being reached
+pub fn network_destinations(trace: &str) -> SortedSet<String> {
+    let mut destinations = SortedSet::new();
+    for line in trace.lines() {
+        let destination = if line.contains("sa_family=AF_INET6") {
+            let addr = between(line, "inet_pton(AF_INET6, \"", "\"");
+            let port = between(line, "sin6_port=htons(", ")");
+            addr.zip(port)
+                .filter(|(addr, _)| !is_loopback(addr))
+                .map(|(addr, port)| format!("[{addr}]:{port}"))
+        } else if line.contains("sa_family=AF_INET") {
+            let addr = between(line, "sin_addr=inet_addr(\"", "\"");
+            let port = between(line, "sin_port=htons(", ")");
+            addr.zip(port)
+                .filter(|(addr, _)| !is_loopback(addr))
+                .map(|(addr, port)| format!("{addr}:{port}"))
+        } else {
+            None
+        };
+        destinations.extend(destination);
+    }
+    destinations
+}
+
+/// The text in `line` between `start` and the next `end`
+fn between<'a>(line: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &line[line.find(start)? + start.len()..];
+    Some(&rest[..rest.find(end)?])
+}
+
+/// Whether an address is the machine talking to itself
+fn is_loopback(addr: &str) -> bool {
+    addr.parse::<std::net::IpAddr>()
+        .is_ok_and(|addr| match addr {
+            std::net::IpAddr::V6(v6) => {
+                v6.is_loopback() || v6.to_ipv4_mapped().is_some_and(|v4| v4.is_loopback())
+            }
+            addr => addr.is_loopback(),
+        })
+}
@@ -0,0 +1,249 @@
+//! Notarizing macOS build outputs with Apple's notary service
+//!
+//! With `[dist.generic-build.notarize]` configured, once a build for an Apple
+//! target has finished, each of its binaries (and any `.dmg` or `.pkg` among
+//! its extra outputs) is zipped up and submitted with `xcrun notarytool`. We
+//! then poll until Apple is done with it; if it wasn't accepted, the build
+//! fails with the notary's log of what was wrong. Disk images and installer
+//! packages get the ticket stapled to them so they can be checked offline
+//! (bare binaries have nowhere to put one, so Gatekeeper looks theirs up).
+//!
+//! Credentials are either a keychain profile (from `xcrun notarytool
+//! store-credentials`), or an Apple ID and team ID with an app-specific password
+//! that's only ever read from the environment. The password never makes it
+//! into our logs. notarytool only exists on macOS, so on other hosts we warn
+//! and leave the outputs alone.
+
+use std::time::{Duration, Instant};
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::{info, warn};
+
+use crate::{config::NotarizeConfig, DistError, DistGraph, DistResult};
+
+/// The variable the app-specific password is read from, unless configured otherwise
+const DEFAULT_PASSWORD_ENV: &str = "APPLE_NOTARY_PASSWORD";
+/// The variable the Apple ID is read from, if it's not configured
+const APPLE_ID_ENV: &str = "APPLE_ID";
+/// The variable the team ID is read from, if it's not configured
+const TEAM_ID_ENV: &str = "APPLE_TEAM_ID";
+/// How often to ask whether Apple is done
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How long to wait for Apple before giving up
+const MAX_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// What we show in logs instead of the password
+const REDACTED: &str = "<redacted>";
+
+/// How to prove who we are to notarytool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryCredentials {
+    /// The arguments to pass to notarytool
+    pub args: Vec<String>,
+    /// The secret among them, which mustn't be logged
+    pub secret: Option<String>,
+}
+
+/// Work out the credentials to notarize with, looking up variables with `env`
+pub fn credentials(
+    config: &NotarizeConfig,
+    env: impl Fn(&str) -> Option<String>,
+) -> DistResult<NotaryCredentials> {
+    if let Some(profile) = &config.keychain_profile {
+        return Ok(NotaryCredentials {
+            args: vec!["--keychain-profile".to_owned(), profile.clone()],
+            secret: None,
+        });
+    }
+    let password_env = config
+        .password_env
+        .as_deref()
+        .unwrap_or(DEFAULT_PASSWORD_ENV);
+    let apple_id = config.apple_id.clone().or_else(|| env(APPLE_ID_ENV));
+    let team_id = config.team_id.clone().or_else(|| env(TEAM_ID_ENV));
+    let password = env(password_env);
+    let (Some(apple_id), Some(team_id), Some(password)) = (apple_id, team_id, password) else {
+        return Err(DistError::NotarizeCredentialsMissing {
+            help: format!(
+                "set keychain-profile, or apple-id and team-id (or {APPLE_ID_ENV} and {TEAM_ID_ENV}) with an app-specific password in {password_env}"
+            ),
+        });
+    };
+    Ok(NotaryCredentials {
+        args: vec![
+            "--apple-id".to_owned(),
+            apple_id,
+            "--team-id".to_owned(),
+            team_id,
+            "--password".to_owned(),
+            password.clone(),
+        ],
+        secret: Some(password),
+    })
+}
+
+/// A command line, fit for logging
+pub fn redact(args: &[String], secret: Option<&str>) -> String {
+    args.iter()
+        .map(|arg| match secret {
+            Some(secret) if arg == secret => REDACTED,
+            _ => arg.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether outputs for this target are notarized
+pub fn is_notarized_target(target: &str) -> bool {
+    target.contains("-apple-darwin")
+}
+
+/// Whether a notarization ticket can be stapled to this file
+pub fn is_stapleable(path: &Utf8Path) -> bool {
+    matches!(path.extension(), Some("dmg" | "pkg" | "app"))
+}
+
+/// Pull a field out of notarytool's JSON output
+pub fn json_field(output: &str, field: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(output).ok()?;
+    value.get(field)?.as_str().map(str::to_owned)
+}
+
+/// Notarize the outputs of a build for an Apple target, if that's configured
+pub(crate) fn notarize_outputs(
+    dist_graph: &DistGraph,
+    target: &str,
+    paths: &[Utf8PathBuf],
+) -> DistResult<()> {
+    let Some(config) = &dist_graph.generic_build.notarize else {
+        return Ok(());
+    };
+    if !is_notarized_target(target) || paths.is_empty() {
+        return Ok(());
+    }
+    if !cfg!(target_os = "macos") {
+        warn!("notarization needs a macOS host, so the outputs for {target} won't be notarized");
+        return Ok(());
+    }
+    let credentials = credentials(config, |key| std::env::var(key).ok())?;
+    for path in paths {
+        notarize(&credentials, path)?;
+    }
+    Ok(())
+}
+
+/// Submit a file to the notary service, wait for its verdict, and staple it if we can
+fn notarize(credentials: &NotaryCredentials, path: &Utf8Path) -> DistResult<()> {
+    let name = path.file_name().unwrap_or(path.as_str());
+    // notarytool takes zips, disk images, and packages
+    let tmp = temp_dir::TempDir::new()?;
+    let submission = if matches!(path.extension(), Some("dmg" | "pkg" | "zip")) {
+        path.to_owned()
+    } else {
+        let zip = Utf8Path::from_path(tmp.path())
+            .expect("temp dir should be utf8")
+            .join(format!("{name}.zip"));
+        Cmd::new("ditto", format!("zip {name} for notarization"))
+            .arg("-c")
+            .arg("-k")
+            .arg("--keepParent")
+            .arg(path)
+            .arg(&zip)
+            .run()?;
+        zip
+    };
+
+    info!("submitting {name} for notarization");
+    let output = notarytool(
+        credentials,
+        path,
+        &["submit", submission.as_str()],
+        &format!("submit {name} for notarization"),
+    )?;
+    let Some(id) = json_field(&output, "id") else {
+        return Err(DistError::NotarizationFailed {
+            path: path.to_owned(),
+            status: "not submitted".to_owned(),
+            log: output,
+        });
+    };
+
+    let start = Instant::now();
+    let status = loop {
+        let output = notarytool(
+            credentials,
+            path,
+            &["info", &id],
+            &format!("check on the notarization of {name}"),
+        )?;
+        let status = json_field(&output, "status").unwrap_or_default();
+        if status != "In Progress" {
+            break status;
+        }
+        if start.elapsed() > MAX_WAIT {
+            break "timed out".to_owned();
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    if status != "Accepted" {
+        let log = notarytool(
+            credentials,
+            path,
+            &["log", &id],
+            &format!("get the notarization log for {name}"),
+        )
+        .unwrap_or_else(|e| format!("(couldn't get the log: {e})"));
+        return Err(DistError::NotarizationFailed {
+            path: path.to_owned(),
+            status,
+            log,
+        });
+    }
+    info!("{name} was notarized");
+
+    if is_stapleable(path) {
+        Cmd::new("xcrun", format!("staple the notarization ticket to {name}"))
+            .arg("stapler")
+            .arg("staple")
+            .arg(path)
+            .run()?;
+    }
+    Ok(())
+}
+
+/// Run a notarytool subcommand, returning its (JSON) output
+///
+/// `path` is what's being notarized, for errors.
+fn notarytool(
+    credentials: &NotaryCredentials,
+    path: &Utf8Path,
+    args: &[&str],
+    summary: &str,
+) -> DistResult<String> {
+    let mut full_args = vec!["notarytool".to_owned()];
+    full_args.extend(args.iter().map(|arg| arg.to_string()));
+    full_args.extend(credentials.args.iter().cloned());
+    full_args.push("--output-format".to_owned());
+    full_args.push("json".to_owned());
+    info!(
+        "exec xcrun {}",
+        redact(&full_args, credentials.secret.as_deref())
+    );
+
+    let mut command = Cmd::new("xcrun", summary);
+    command.log(None).check(false);
+    for arg in &full_args {
+        command.arg(arg);
+    }
+    let output = command.output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() && stdout.trim().is_empty() {
+        return Err(DistError::NotarizationFailed {
+            path: path.to_owned(),
+            status: format!("notarytool {} failed", args[0]),
+            log: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    Ok(stdout)
+}
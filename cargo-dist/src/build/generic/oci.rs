@@ -0,0 +1,215 @@
+//! Container images as extra artifacts
+//!
+//! An extra build can produce a container image (with `buildah push
+//! oci-archive:...`, `docker save`, or the like) by giving it the `oci-image`
+//! format. The image can be an [OCI image layout] directory or a tarball of
+//! one (optionally gzipped), and once it's built we check it really is one:
+//!
+//! * it has an `oci-layout` file saying which layout version it is
+//! * it has an `index.json` listing at least one manifest
+//! * each manifest it lists is a well-formed digest with a blob in `blobs/`
+//!
+//! The digest of the first manifest is recorded as the artifact's
+//! `image-digest` in the dist manifest. A layout directory is packed into a
+//! tarball under the artifact's name, since only files can be uploaded.
+//!
+//! [OCI image layout]: https://github.com/opencontainers/image-spec/blob/main/image-layout.md
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufReader, Read},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+
+use crate::{config::ArtifactFormat, DistError, DistResult};
+
+/// The file that marks a directory as an OCI layout
+const OCI_LAYOUT: &str = "oci-layout";
+
+/// The file that lists a layout's manifests
+const INDEX: &str = "index.json";
+
+/// The contents of `oci-layout`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OciLayout {
+    image_layout_version: String,
+}
+
+/// The contents of `index.json` (just what we check)
+#[derive(Deserialize)]
+struct OciIndex {
+    manifests: Vec<OciDescriptor>,
+}
+
+/// A reference to a blob
+#[derive(Deserialize)]
+struct OciDescriptor {
+    digest: String,
+}
+
+/// What we need from a layout to check it
+#[derive(Default)]
+struct LayoutContents {
+    /// The contents of `oci-layout`, if it has one
+    layout: Option<String>,
+    /// The contents of `index.json`, if it has one
+    index: Option<String>,
+    /// Every file in it, relative to its root
+    files: BTreeSet<String>,
+}
+
+/// Check `path` is an OCI image, returning the digest of its first manifest
+pub fn oci_image_digest(path: &Utf8Path) -> DistResult<String> {
+    let contents = if path.is_dir() {
+        read_layout_dir(path)?
+    } else {
+        read_layout_tarball(path)?
+    };
+    check_layout(&contents).map_err(|reason| DistError::InvalidExtraArtifact {
+        path: path.to_owned(),
+        format: ArtifactFormat::OciImage,
+        reason: reason.to_owned(),
+    })
+}
+
+/// Check a layout's contents, returning the digest of its first manifest or what's wrong
+fn check_layout(contents: &LayoutContents) -> Result<String, String> {
+    let layout = contents
+        .layout
+        .as_deref()
+        .ok_or_else(|| format!("it has no {OCI_LAYOUT} file"))?;
+    let layout: OciLayout = serde_json::from_str(layout)
+        .map_err(|e| format!("its {OCI_LAYOUT} file is invalid: {e}"))?;
+    if !layout.image_layout_version.starts_with("1.") {
+        return Err(format!(
+            "it's layout version {}, but only 1.x is supported",
+            layout.image_layout_version
+        ));
+    }
+    let index = contents
+        .index
+        .as_deref()
+        .ok_or_else(|| format!("it has no {INDEX}"))?;
+    let index: OciIndex =
+        serde_json::from_str(index).map_err(|e| format!("its {INDEX} is invalid: {e}"))?;
+    for manifest in &index.manifests {
+        let blob = blob_path(&manifest.digest)
+            .ok_or_else(|| format!("{} isn't a valid digest", manifest.digest))?;
+        if !contents.files.contains(&blob) {
+            return Err(format!("the manifest {} isn't in it", manifest.digest));
+        }
+    }
+    index
+        .manifests
+        .first()
+        .map(|manifest| manifest.digest.clone())
+        .ok_or_else(|| format!("its {INDEX} doesn't list any images"))
+}
+
+/// Where in a layout the blob with `digest` is, if it's a well-formed digest
+fn blob_path(digest: &str) -> Option<String> {
+    let (algorithm, hex) = digest.split_once(':')?;
+    let len = match algorithm {
+        "sha256" => 64,
+        "sha512" => 128,
+        _ => return None,
+    };
+    let well_formed = hex.len() == len
+        && hex
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+    well_formed.then(|| format!("blobs/{algorithm}/{hex}"))
+}
+
+/// Read what we need from a layout directory
+fn read_layout_dir(dir: &Utf8Path) -> DistResult<LayoutContents> {
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+    Ok(LayoutContents {
+        layout: read(OCI_LAYOUT),
+        index: read(INDEX),
+        files: layout_files(dir)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect(),
+    })
+}
+
+/// Read what we need from a (possibly gzipped) tarball of a layout
+fn read_layout_tarball(path: &Utf8Path) -> DistResult<LayoutContents> {
+    let mut magic = [0; 2];
+    let gzipped = File::open(path)?.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
+    let file = BufReader::new(File::open(path)?);
+    let reader: Box<dyn Read> = if gzipped {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut contents = LayoutContents::default();
+    let mut archive = tar::Archive::new(reader);
+    let invalid = |e: std::io::Error| DistError::InvalidExtraArtifact {
+        path: path.to_owned(),
+        format: ArtifactFormat::OciImage,
+        reason: format!("it isn't a readable tarball: {e}"),
+    };
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(invalid)?
+            .to_string_lossy()
+            .into_owned();
+        let name = name.trim_start_matches("./").to_owned();
+        if name == OCI_LAYOUT || name == INDEX {
+            let mut text = String::new();
+            entry.read_to_string(&mut text).map_err(invalid)?;
+            if name == OCI_LAYOUT {
+                contents.layout = Some(text);
+            } else {
+                contents.index = Some(text);
+            }
+        }
+        contents.files.insert(name);
+    }
+    Ok(contents)
+}
+
+/// Every file in a layout directory, relative to it (with `/` separators) and in order
+fn layout_files(dir: &Utf8Path) -> DistResult<Vec<(String, Utf8PathBuf)>> {
+    let mut files = vec![];
+    let mut pending = vec![dir.to_owned()];
+    while let Some(current) = pending.pop() {
+        for entry in current.read_dir_utf8()? {
+            let path = entry?.into_path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let relative = path
+                    .strip_prefix(dir)
+                    .expect("walked outside the layout")
+                    .as_str()
+                    .replace('\\', "/");
+                files.push((relative, path));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Pack a layout directory into a tarball at `dest`
+pub(crate) fn pack_layout(dir: &Utf8Path, dest: &Utf8Path) -> DistResult<()> {
+    let mut builder = tar::Builder::new(File::create(dest)?);
+    builder.mode(tar::HeaderMode::Deterministic);
+    for (name, path) in layout_files(dir)? {
+        builder.append_path_with_name(&path, &name)?;
+    }
+    builder.into_inner()?;
+    Ok(())
+}
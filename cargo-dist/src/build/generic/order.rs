@@ -0,0 +1,124 @@
+//! Ordering generic builds that depend on each other
+//!
+//! Generic builds are normally independent, and run one target at a time. With
+//! `depends-on`, a binary can say it needs other binaries built first (say,
+//! a library it links against that a different build command produces). Builds
+//! for the same target are then run in an order that respects that, while builds
+//! that don't depend on each other keep their usual order. A cycle is an error.
+//!
+//! With `combine-build-commands`, the (ordered) builds for each target are
+//! then combined into one build that runs their commands one after another.
+
+use crate::{
+    config::GenericBuilder, BinaryIdx, DistError, DistResult, GenericBuildStep, SortedMap,
+    SortedSet, SubBuild,
+};
+
+/// Sort builds so each one comes after the builds it depends on
+///
+/// `names` are the names of the binaries each build produces, and `depends_on`
+/// the names of the binaries each binary needs built first.
+pub(crate) fn order_builds(
+    steps: Vec<GenericBuildStep>,
+    names: &[Vec<String>],
+    depends_on: &SortedMap<String, Vec<String>>,
+) -> DistResult<Vec<GenericBuildStep>> {
+    // Which builds each build has to wait for
+    let waits_for = (0..steps.len())
+        .map(|idx| {
+            let needs = names[idx]
+                .iter()
+                .flat_map(|name| depends_on.get(name).into_iter().flatten())
+                .collect::<Vec<_>>();
+            (0..steps.len())
+                .filter(|&other| {
+                    other != idx
+                        && steps[other].target_triple == steps[idx].target_triple
+                        && names[other].iter().any(|name| needs.contains(&name))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Repeatedly take the first build that isn't waiting on anything left
+    let mut order = Vec::with_capacity(steps.len());
+    let mut done = vec![false; steps.len()];
+    while order.len() < steps.len() {
+        let ready = (0..steps.len())
+            .find(|&idx| !done[idx] && waits_for[idx].iter().all(|&other| done[other]));
+        let Some(idx) = ready else {
+            // Everything left is waiting on something else that's left
+            let stuck = (0..steps.len()).find(|&idx| !done[idx]).unwrap_or_default();
+            let binaries = (0..steps.len())
+                .filter(|&idx| !done[idx] && steps[idx].target_triple == steps[stuck].target_triple)
+                .flat_map(|idx| names[idx].iter().cloned())
+                .collect();
+            return Err(DistError::BuildDependencyCycle {
+                target: steps[stuck].target_triple.clone(),
+                binaries,
+            });
+        };
+        done[idx] = true;
+        order.push(idx);
+    }
+
+    let mut steps = steps.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|idx| steps[idx].take())
+        .collect())
+}
+
+/// Combine each target's builds into one, keeping them in order
+///
+/// `name` gives the name of a binary, so dependencies between the combined
+/// builds (which are now just the order of their commands) can be dropped.
+/// CMake builds aren't combined, since they don't run their commands as-is.
+pub(crate) fn combine_builds(
+    steps: Vec<GenericBuildStep>,
+    name: impl Fn(BinaryIdx) -> String,
+) -> Vec<GenericBuildStep> {
+    let mut combined = Vec::<GenericBuildStep>::with_capacity(steps.len());
+    for step in steps {
+        let into = combined.iter_mut().find(|other| {
+            other.target_triple == step.target_triple
+                && other.builder == step.builder
+                && other.host_only == step.host_only
+                && step.builder != GenericBuilder::Cmake
+                // Data builds' outputs go somewhere else
+                && other.data.is_none()
+                && step.data.is_none()
+        });
+        let Some(into) = into else {
+            combined.push(step);
+            continue;
+        };
+        if into.sub_builds.is_empty() {
+            into.sub_builds.push(SubBuild {
+                build_command: into.build_command.clone(),
+                binaries: into.expected_binaries.clone(),
+            });
+        }
+        into.sub_builds.push(SubBuild {
+            build_command: step.build_command,
+            binaries: step.expected_binaries.clone(),
+        });
+        into.expected_binaries.extend(step.expected_binaries);
+        into.allow_missing.extend(step.allow_missing);
+        into.depends_on.extend(step.depends_on);
+    }
+    for step in &mut combined {
+        let own = step
+            .expected_binaries
+            .iter()
+            .map(|binary_idx| name(*binary_idx))
+            .collect::<Vec<_>>();
+        step.depends_on = std::mem::take(&mut step.depends_on)
+            .into_iter()
+            .filter(|dependency| !own.contains(dependency))
+            .collect::<SortedSet<_>>()
+            .into_iter()
+            .collect();
+    }
+    combined
+}
@@ -0,0 +1,291 @@
+//! Finding what a generic build produced, and copying it where it goes
+//!
+//! Generic builds give us no feedback about what they built, so all we can
+//! do is look in the places we expect outputs to be.
+
+use std::time::{Duration, Instant};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{
+    build::{copy_into, verify_copy},
+    DistError, DistGraph, DistResult,
+};
+
+/// An output we expect a build to have produced
+#[derive(Debug, Clone)]
+pub(crate) struct ExpectedOutput {
+    /// Who the output belongs to (for error messages)
+    pub owner: String,
+    /// What the output is called, relative to wherever it's copied to
+    pub name: String,
+    /// Places the build might have put it, in order of preference
+    pub candidates: Vec<Utf8PathBuf>,
+}
+
+impl ExpectedOutput {
+    /// An output that we only expect to find in one place (and that gets renamed when copied)
+    pub(crate) fn at(
+        owner: impl Into<String>,
+        source: impl Into<Utf8PathBuf>,
+        name: impl Into<String>,
+    ) -> Self {
+        Self {
+            owner: owner.into(),
+            name: name.into(),
+            candidates: vec![source.into()],
+        }
+    }
+}
+
+/// Check that every place a build's outputs were copied to really has them
+///
+/// Copying reported success, but something else (a concurrent cleanup, a full
+/// disk, a flaky network filesystem) can still leave a destination missing or
+/// empty, which we'd rather find out about now than from a broken archive.
+pub(crate) fn verify_destinations<'a>(
+    dests: impl IntoIterator<Item = &'a Utf8PathBuf>,
+) -> DistResult<()> {
+    for dest in dests {
+        let problem = match dest.metadata() {
+            Err(_) => "missing",
+            Ok(metadata) if metadata.is_file() && metadata.len() == 0 => "empty",
+            Ok(_) => continue,
+        };
+        return Err(DistError::DestinationNotWritten {
+            path: dest.clone(),
+            problem: problem.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Every file under `root` matching `pattern`
+///
+/// Each is named after its path relative to the part of the pattern before the
+/// first wildcard, so `locale/*/app.mo` finds `de/app.mo` and `fr/app.mo` rather
+/// than two `app.mo`s that would overwrite each other.
+///
+/// Invalid patterns are an error, but it's up to the caller what to make of no matches.
+pub(crate) fn glob_outputs(root: &Utf8Path, pattern: &str) -> DistResult<Vec<ExpectedOutput>> {
+    let full_pattern = format!("{}/{pattern}", glob::Pattern::escape(root.as_str()));
+    let literal_prefix = root.join(literal_prefix(pattern));
+    let paths = glob::glob(&full_pattern).map_err(|e| DistError::InvalidGlob {
+        pattern: pattern.to_owned(),
+        details: e.to_string(),
+    })?;
+    let mut outputs = vec![];
+    for path in paths.flatten() {
+        let Ok(path) = Utf8PathBuf::from_path_buf(path) else {
+            continue;
+        };
+        if path.is_dir() {
+            continue;
+        }
+        let Ok(name) = path.strip_prefix(&literal_prefix) else {
+            continue;
+        };
+        outputs.push(ExpectedOutput {
+            owner: pattern.to_owned(),
+            name: name.to_string(),
+            candidates: vec![path.clone()],
+        });
+    }
+    Ok(outputs)
+}
+
+/// The directories at the start of a glob pattern that don't have any wildcards in them
+fn literal_prefix(pattern: &str) -> Utf8PathBuf {
+    let mut components = pattern.split('/').collect::<Vec<_>>();
+    // The last component is what's matched, even if it's a plain file name
+    components.pop();
+    components
+        .into_iter()
+        .take_while(|component| !component.contains(['*', '?', '[']))
+        .collect()
+}
+
+/// An output we found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FoundOutput {
+    /// The name of the output (see [`ExpectedOutput::name`][])
+    pub name: String,
+    /// Where the build actually put it
+    pub path: Utf8PathBuf,
+}
+
+/// Find where the build put each expected output
+///
+/// Errors on the first output that isn't in any of its candidate locations.
+pub(crate) fn find_outputs(expected: &[ExpectedOutput]) -> DistResult<Vec<FoundOutput>> {
+    expected
+        .iter()
+        .map(|output| {
+            let path = output
+                .candidates
+                .iter()
+                .find(|path| path.exists())
+                .ok_or_else(|| DistError::MissingBinaries {
+                    pkg_name: output.owner.clone(),
+                    bin_name: output.name.clone(),
+                })?;
+            Ok(FoundOutput {
+                name: output.name.clone(),
+                path: path.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Find whichever expected outputs the build produced, and which it didn't
+///
+/// Unlike [`find_outputs`][], this doesn't stop at the first missing output.
+pub(crate) fn find_present_outputs(
+    expected: &[ExpectedOutput],
+) -> (Vec<FoundOutput>, Vec<&ExpectedOutput>) {
+    let mut found = vec![];
+    let mut missing = vec![];
+    for output in expected {
+        match output.candidates.iter().find(|path| path.exists()) {
+            Some(path) => found.push(FoundOutput {
+                name: output.name.clone(),
+                path: path.clone(),
+            }),
+            None => missing.push(output),
+        }
+    }
+    (found, missing)
+}
+
+/// How many more times to look for missing outputs, unless configured
+const MISSING_OUTPUT_RETRIES: u32 = 2;
+/// How long to wait between looks for missing outputs, unless configured
+const MISSING_OUTPUT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Give outputs that aren't there yet a moment to show up
+///
+/// On network filesystems a build's outputs can take a moment to appear after
+/// it exits. So if any are missing we look again a few times (per
+/// `missing-output-retries` and `missing-output-retry-delay-ms`) before
+/// carrying on, and whatever's still missing then is dealt with as usual.
+/// Nothing is rebuilt.
+pub(crate) fn wait_for_missing_outputs(dist_graph: &DistGraph, expected: &[ExpectedOutput]) {
+    let retries = dist_graph
+        .generic_build
+        .missing_output_retries
+        .unwrap_or(MISSING_OUTPUT_RETRIES);
+    let delay = dist_graph
+        .generic_build
+        .missing_output_retry_delay_ms
+        .map(Duration::from_millis)
+        .unwrap_or(MISSING_OUTPUT_RETRY_DELAY);
+    retry_missing_outputs(expected, retries, delay);
+}
+
+/// Look for missing outputs up to `retries` more times, `delay` apart
+///
+/// Returns whether they all turned up.
+pub(crate) fn retry_missing_outputs(
+    expected: &[ExpectedOutput],
+    retries: u32,
+    delay: Duration,
+) -> bool {
+    let all_present = || {
+        expected
+            .iter()
+            .all(|output| output.candidates.iter().any(|path| path.exists()))
+    };
+    for _ in 0..retries {
+        if all_present() {
+            return true;
+        }
+        std::thread::sleep(delay);
+    }
+    all_present()
+}
+
+/// How long to wait between looking at the size of an output
+const STABLE_INTERVAL: Duration = Duration::from_millis(250);
+/// How long an output can keep changing before we give up on it
+const STABLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Wait for outputs to stop being written, if `wait-for-stable-outputs` is on
+///
+/// On network filesystems an output can show up before whoever's writing it
+/// has finished, and copying it then gets a truncated file. So each output's
+/// size is checked twice, a moment apart, until it stops changing.
+pub(crate) fn wait_for_stable_outputs<'a>(
+    dist_graph: &DistGraph,
+    paths: impl IntoIterator<Item = &'a Utf8PathBuf>,
+) -> DistResult<()> {
+    if !dist_graph
+        .generic_build
+        .wait_for_stable_outputs
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+    for path in paths {
+        wait_for_stable_size(path, STABLE_INTERVAL, STABLE_TIMEOUT)?;
+    }
+    Ok(())
+}
+
+/// Wait until a file is the same size two looks in a row, `interval` apart
+pub fn wait_for_stable_size(
+    path: &Utf8Path,
+    interval: Duration,
+    timeout: Duration,
+) -> DistResult<()> {
+    let start = Instant::now();
+    let mut size = path.metadata()?.len();
+    loop {
+        std::thread::sleep(interval);
+        let new_size = path.metadata()?.len();
+        if new_size == size {
+            return Ok(());
+        }
+        if start.elapsed() > timeout {
+            return Err(DistError::OutputStillChanging {
+                path: path.to_owned(),
+                seconds: timeout.as_secs(),
+            });
+        }
+        size = new_size;
+    }
+}
+
+/// Find every expected output and copy it to `dest`, keeping its name (and any directories in it)
+///
+/// Returns the paths that were written to.
+pub(crate) fn collect_and_copy_outputs(
+    expected: &[ExpectedOutput],
+    dest: &Utf8Path,
+    dist_graph: &DistGraph,
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let found = find_outputs(expected)?;
+    copy_found_outputs(&found, dest, dist_graph)
+}
+
+/// Copy outputs that were found into `dest`, returning where they went
+pub(crate) fn copy_found_outputs(
+    found: &[FoundOutput],
+    dest: &Utf8Path,
+    dist_graph: &DistGraph,
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let symlinks = dist_graph.generic_build.symlinks.unwrap_or_default();
+    let copy_mode = dist_graph.generic_build.copy_mode.unwrap_or_default();
+    wait_for_stable_outputs(dist_graph, found.iter().map(|output| &output.path))?;
+    found
+        .iter()
+        .map(|output| {
+            let dest = dest.join(&output.name);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let copied = copy_into(&output.path, &dest, symlinks, copy_mode)?;
+            verify_copy(dist_graph, &output.path, &copied)?;
+            Ok(copied)
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! Judging generic builds by their output, not just their exit code
+//!
+//! Some build tools exit zero even when they fail, and only tell you by
+//! printing `ERROR:` somewhere. With `failure-pattern` a build whose output
+//! matches is a failure regardless, and with `success-pattern` a build whose
+//! output never matches is one too. Patterns are matched against each line.
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// How many matching lines to quote when a failure-pattern matches
+const MAX_QUOTED_LINES: usize = 10;
+
+/// Whether success-pattern or failure-pattern are set
+pub(crate) fn has_output_patterns(dist_graph: &DistGraph) -> bool {
+    let config = &dist_graph.generic_build;
+    config.failure_pattern.is_some() || config.success_pattern.is_some()
+}
+
+/// Check that failure-pattern and success-pattern are valid, before building anything
+pub(crate) fn validate_output_patterns(dist_graph: &DistGraph) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    for pattern in [&config.failure_pattern, &config.success_pattern]
+        .into_iter()
+        .flatten()
+    {
+        compile(pattern)?;
+    }
+    Ok(())
+}
+
+/// Check a build's output against failure-pattern and success-pattern
+pub(crate) fn check_output_patterns(
+    dist_graph: &DistGraph,
+    target: &str,
+    output: &str,
+) -> DistResult<()> {
+    if dist_graph.interactive_builds {
+        warn!("not checking the output of the {target} build, since it's interactive");
+        return Ok(());
+    }
+    let config = &dist_graph.generic_build;
+    if let Some(pattern) = &config.failure_pattern {
+        let regex = compile(pattern)?;
+        let matched = output
+            .lines()
+            .filter(|line| regex.is_match(line))
+            .collect::<Vec<_>>();
+        if !matched.is_empty() {
+            let mut lines = matched
+                .iter()
+                .take(MAX_QUOTED_LINES)
+                .map(|line| format!("  > {line}"))
+                .collect::<Vec<_>>();
+            if matched.len() > MAX_QUOTED_LINES {
+                lines.push(format!(
+                    "  ...and {} more",
+                    matched.len() - MAX_QUOTED_LINES
+                ));
+            }
+            return Err(DistError::BuildMatchedFailurePattern {
+                target: target.to_owned(),
+                pattern: pattern.clone(),
+                lines: lines.join("\n"),
+            });
+        }
+    }
+    if let Some(pattern) = &config.success_pattern {
+        let regex = compile(pattern)?;
+        if !output.lines().any(|line| regex.is_match(line)) {
+            return Err(DistError::BuildMissingSuccessPattern {
+                target: target.to_owned(),
+                pattern: pattern.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn compile(pattern: &str) -> DistResult<Regex> {
+    Regex::new(pattern).map_err(|e| DistError::InvalidOutputPattern {
+        pattern: pattern.to_owned(),
+        details: e.to_string(),
+    })
+}
@@ -0,0 +1,164 @@
+//! Overlapping a target's post-processing with the next target's build
+//!
+//! Once a generic build finishes, what it built still has to be checked,
+//! copied, post-processed and notarized, which for big outputs can take as
+//! long as the build did. With `pipeline-builds`, that happens on another
+//! thread while the next target builds. Only one target is processed at a
+//! time, so at most one build and one target's processing ever overlap.
+//!
+//! A build doesn't start until the previous target is processed if it could
+//! clobber what that target built before it's been copied: if it's for the same
+//! target, or it's going to put its outputs where the previous build put its
+//! own. Builds that write to the same place for every target therefore still
+//! run one after the other. Steps that aren't generic builds wait for every
+//! target before them to be processed.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    thread::ScopedJoinHandle,
+    time::Instant,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_dist_schema::{BuildSummary, DistManifest};
+
+use super::{
+    build_target_outputs, events, isolated::isolated_build_dir, output_candidates,
+    process_built_target, resume, summary,
+};
+use crate::{BuildStep, DistGraph, DistResult, GenericBuildStep};
+
+/// A target whose outputs are being processed
+struct InFlight<'scope> {
+    /// The target
+    target: &'scope GenericBuildStep,
+    /// When its build started
+    started: Instant,
+    /// Where its build put its binaries
+    outputs: Vec<Utf8PathBuf>,
+    /// The thread processing them
+    handle: ScopedJoinHandle<'scope, DistResult<usize>>,
+}
+
+/// Run the local build steps, processing each generic build's outputs while the next one builds
+///
+/// `run_other` runs the steps that aren't generic builds. Each generic build is
+/// recorded in `results`, in order.
+pub(crate) fn run_pipelined(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    steps: &[BuildStep],
+    results: &mut Vec<BuildSummary>,
+    mut run_other: impl FnMut(&BuildStep, &mut DistManifest) -> DistResult<()>,
+) -> DistResult<()> {
+    let manifest = Mutex::new(manifest);
+    std::thread::scope(|scope| {
+        let mut in_flight: Option<InFlight> = None;
+        for step in steps {
+            let BuildStep::Generic(target) = step else {
+                finish(in_flight.take(), results)?;
+                let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+                run_other(step, &mut manifest)?;
+                continue;
+            };
+            if in_flight
+                .as_ref()
+                .is_some_and(|previous| clobbers(dist_graph, target, previous))
+            {
+                finish(in_flight.take(), results)?;
+            }
+
+            // The previous target holds the manifest while it's processed, so
+            // only wait for it if there's a resumed build to record
+            if resume::resumable_build(dist_graph, target)?.is_some() {
+                let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+                if resume::skip_resumed_build(dist_graph, &mut manifest, target)? {
+                    continue;
+                }
+            }
+
+            let started = events::emit_started(dist_graph, Some(&target.target_triple))?;
+            let built = build_target_outputs(dist_graph, target);
+            // Only one target is processed at a time
+            finish(in_flight.take(), results)?;
+            let built = match built {
+                Ok(built) => built,
+                Err(e) => {
+                    events::emit_finished(dist_graph, Some(&target.target_triple), started, false)?;
+                    return summary::record_result(results, target, started, Err(e));
+                }
+            };
+
+            let outputs = built.output_paths().map(Utf8Path::to_owned).collect();
+            let manifest = &manifest;
+            let handle = scope.spawn(move || {
+                let mut manifest = manifest.lock().unwrap_or_else(PoisonError::into_inner);
+                let result = process_built_target(dist_graph, &mut manifest, target, built)
+                    .and_then(|copied| {
+                        resume::record_completed_build(dist_graph, &manifest, target)?;
+                        Ok(copied)
+                    });
+                events::emit_finished(
+                    dist_graph,
+                    Some(&target.target_triple),
+                    started,
+                    result.is_ok(),
+                )?;
+                result
+            });
+            in_flight = Some(InFlight {
+                target,
+                started,
+                outputs,
+                handle,
+            });
+        }
+        finish(in_flight.take(), results)
+    })
+}
+
+/// Wait for a target to be processed, and record how it went
+fn finish(in_flight: Option<InFlight>, results: &mut Vec<BuildSummary>) -> DistResult<()> {
+    let Some(in_flight) = in_flight else {
+        return Ok(());
+    };
+    let result = in_flight
+        .handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    summary::record_result(results, in_flight.target, in_flight.started, result)
+}
+
+/// Whether building `target` could clobber what the previous build made before it's copied
+fn clobbers(dist_graph: &DistGraph, target: &GenericBuildStep, previous: &InFlight) -> bool {
+    if target.target_triple == previous.target.target_triple {
+        // Same staging dir, isolated build dir and log
+        return true;
+    }
+    let isolated = isolated_build_dir(dist_graph, target);
+    let root = isolated.clone().unwrap_or_else(|| Utf8PathBuf::from("."));
+    let unnamed = dist_graph.generic_build.unnamed_outputs.as_ref();
+    // Everywhere the build could put its outputs (or, for an isolated build, delete things)
+    let mut places = isolated.into_iter().collect::<Vec<_>>();
+    for binary_idx in &target.expected_binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        match unnamed.and_then(|dirs| dirs.get(&binary.name)) {
+            Some(dir) => places.push(root.join(dir)),
+            None => places.extend(
+                output_candidates(dist_graph, target, &binary.file_name)
+                    .into_iter()
+                    .map(|candidate| root.join(candidate)),
+            ),
+        }
+    }
+    // Anything that doesn't exist yet can't be where an existing output is
+    let places = places
+        .iter()
+        .filter_map(|place| place.canonicalize_utf8().ok())
+        .collect::<Vec<_>>();
+    previous
+        .outputs
+        .iter()
+        .filter_map(|output| output.canonicalize_utf8().ok())
+        .any(|output| places.iter().any(|place| output.starts_with(place)))
+}
@@ -0,0 +1,110 @@
+//! Checking Linux binaries only depend on what they're allowed to
+//!
+//! A binary that links against a system library most distros don't have, or
+//! uses symbols from a newer glibc than the oldest distro it's meant to run on,
+//! builds and runs fine on the build machine and only breaks for users. So once
+//! a Linux binary's built, we read the libraries it needs (`DT_NEEDED`) and the
+//! glibc symbol versions it requires from its dynamic section:
+//!
+//! * any library matching a glob in `disallowed-libraries` fails the build
+//! * any `GLIBC_x.y` version newer than `max-glibc` fails the build
+//!
+//! Statically linked binaries have neither, so they always pass.
+
+use camino::Utf8Path;
+use goblin::elf::Elf;
+
+use crate::{Binary, DistError, DistGraph, DistResult};
+
+/// The prefix glibc's symbol versions have
+const GLIBC_VERSION_PREFIX: &str = "GLIBC_";
+
+/// What an ELF binary needs from the system to run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ElfDependencies {
+    /// The libraries it links against
+    pub libraries: Vec<String>,
+    /// The glibc versions its symbols need (like "2.17")
+    pub glibc_versions: Vec<String>,
+}
+
+/// Read what the binary at `path` needs, if it's an ELF binary
+pub fn elf_dependencies(path: &Utf8Path) -> DistResult<Option<ElfDependencies>> {
+    let bytes = std::fs::read(path)?;
+    let Ok(elf) = Elf::parse(&bytes) else {
+        return Ok(None);
+    };
+    let libraries = elf
+        .libraries
+        .iter()
+        .map(|library| library.to_string())
+        .collect();
+    let mut glibc_versions = vec![];
+    for need in elf.verneed.iter().flatten() {
+        for aux in &need {
+            let version = elf
+                .dynstrtab
+                .get_at(aux.vna_name)
+                .and_then(|name| name.strip_prefix(GLIBC_VERSION_PREFIX));
+            if let Some(version) = version {
+                glibc_versions.push(version.to_owned());
+            }
+        }
+    }
+    glibc_versions.sort_by_key(|version| parse_version(version));
+    glibc_versions.dedup();
+    Ok(Some(ElfDependencies {
+        libraries,
+        glibc_versions,
+    }))
+}
+
+/// A dotted version as numbers, for comparing (anything that isn't a number is 0)
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Check a Linux binary only depends on what it's allowed to, if anything's disallowed
+pub(crate) fn check_portability(
+    dist_graph: &DistGraph,
+    binary: &Binary,
+    path: &Utf8Path,
+) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    if config.disallowed_libraries.is_none() && config.max_glibc.is_none() {
+        return Ok(());
+    }
+    if !binary.target.contains("linux") {
+        return Ok(());
+    }
+    let Some(deps) = elf_dependencies(path)? else {
+        return Ok(());
+    };
+    for pattern in config.disallowed_libraries.iter().flatten() {
+        let glob = glob::Pattern::new(pattern).map_err(|e| DistError::InvalidLibraryPattern {
+            pattern: pattern.clone(),
+            details: e.to_string(),
+        })?;
+        if let Some(library) = deps.libraries.iter().find(|library| glob.matches(library)) {
+            return Err(DistError::DisallowedLibrary {
+                binary: binary.name.clone(),
+                library: library.clone(),
+                pattern: pattern.clone(),
+            });
+        }
+    }
+    if let Some(max) = &config.max_glibc {
+        let newest = deps.glibc_versions.last();
+        if let Some(newest) = newest.filter(|newest| parse_version(newest) > parse_version(max)) {
+            return Err(DistError::GlibcTooNew {
+                binary: binary.name.clone(),
+                needed: newest.clone(),
+                max: max.clone(),
+            });
+        }
+    }
+    Ok(())
+}
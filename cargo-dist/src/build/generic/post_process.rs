@@ -0,0 +1,61 @@
+//! Running a user's script over a build's outputs
+//!
+//! `post-process` is an escape hatch for transformations we don't support
+//! ourselves. It runs once per build step, after everything the build made has
+//! been copied into the dist dir, so unlike the build itself it knows where the
+//! outputs ended up. It's launched like the build (with `CARGO_DIST_TARGET`
+//! and the rest of the build's environment), plus `CARGO_DIST_PRODUCED_ARTIFACTS`:
+//! the paths of everything copied, one per line. If it exits non-zero the
+//! build fails.
+
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+
+use super::{
+    prepare_command,
+    relay::{run_relayed, BuildLog},
+    wrap_command,
+};
+use crate::{tasks::GenericBuildStep, DistError, DistGraph, DistResult};
+
+/// The variable post-process gets the copied outputs in
+pub const PRODUCED_ARTIFACTS: &str = "CARGO_DIST_PRODUCED_ARTIFACTS";
+
+/// The post-process command for a step, if one is configured
+pub(crate) fn post_process_command(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    artifacts: &[Utf8PathBuf],
+) -> DistResult<Option<Cmd>> {
+    let Some(command_string) = &dist_graph.generic_build.post_process else {
+        return Ok(None);
+    };
+    let mut command = prepare_command(dist_graph, command_string, Some(step))?;
+    let produced = artifacts
+        .iter()
+        .map(|path| path.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    command.env(PRODUCED_ARTIFACTS, produced);
+    Ok(Some(wrap_command(dist_graph, command, Some(step))?))
+}
+
+/// Run post-process over what a step copied, if it's configured
+pub(crate) fn run_post_process(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    artifacts: &[Utf8PathBuf],
+    log: &BuildLog,
+) -> DistResult<()> {
+    let Some(mut command) = post_process_command(dist_graph, step, artifacts)? else {
+        return Ok(());
+    };
+    let status = run_relayed(dist_graph, &mut command, log)?;
+    if !status.success() {
+        return Err(DistError::PostProcessFailed {
+            target: step.target_triple.clone(),
+            status: status.to_string(),
+        });
+    }
+    Ok(())
+}
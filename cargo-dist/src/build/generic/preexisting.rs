@@ -0,0 +1,65 @@
+//! Making sure the binaries we find after a build are ones it made
+//!
+//! After a build we go looking for the binaries it was supposed to make, so one
+//! left over from an earlier build makes a build that silently didn't produce
+//! it look like it did. `preexisting-outputs` closes that hole by looking
+//! wherever each binary is expected before the build runs:
+//!
+//! * `"clean-before-build"` deletes anything that's already there
+//! * `"error-on-preexisting"` fails the build if anything's already there
+//!
+//! Binaries found with `unnamed-outputs` aren't at a path we know before the
+//! build, so they aren't checked.
+
+use camino::Utf8Path;
+use tracing::info;
+
+use super::output_candidates;
+use crate::{config::PreexistingOutputs, DistError, DistGraph, DistResult, GenericBuildStep};
+
+/// Deal with binaries that are already where `target`'s build is expected to put them
+pub(crate) fn check_preexisting_outputs(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    root: &Utf8Path,
+) -> DistResult<()> {
+    let mode = dist_graph
+        .generic_build
+        .preexisting_outputs
+        .unwrap_or_default();
+    if mode == PreexistingOutputs::Keep {
+        return Ok(());
+    }
+    let unnamed = dist_graph.generic_build.unnamed_outputs.as_ref();
+    for binary_idx in &target.expected_binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        if unnamed.is_some_and(|dirs| dirs.contains_key(&binary.name)) {
+            continue;
+        }
+        for candidate in output_candidates(dist_graph, target, &binary.file_name) {
+            let path = root.join(candidate);
+            if path.symlink_metadata().is_err() {
+                continue;
+            }
+            match mode {
+                PreexistingOutputs::Keep => {}
+                PreexistingOutputs::CleanBeforeBuild => {
+                    info!("removing {path} from an earlier build");
+                    if path.is_dir() {
+                        std::fs::remove_dir_all(&path)?;
+                    } else {
+                        std::fs::remove_file(&path)?;
+                    }
+                }
+                PreexistingOutputs::ErrorOnPreexisting => {
+                    return Err(DistError::PreexistingOutput {
+                        target: target.target_triple.clone(),
+                        binary: binary.name.clone(),
+                        path,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Running generic builds at a lower priority
+//!
+//! Builds can easily saturate a machine, which is unpleasant on a shared box
+//! or a laptop. With `nice` (and `idle-io`) we lower the priority of the build
+//! before it starts, which everything it spawns inherits.
+
+use std::process::Command;
+
+use crate::DistGraph;
+
+/// The niceness builds get unless configured otherwise (i.e. unchanged)
+const DEFAULT_NICE: i32 = 0;
+
+/// Lower the priority of a build command as configured
+pub(crate) fn apply_priority(dist_graph: &DistGraph, command: &mut Command) {
+    lower_priority(
+        command,
+        dist_graph.generic_build.nice.unwrap_or(DEFAULT_NICE),
+        dist_graph.generic_build.idle_io.unwrap_or(false),
+    );
+}
+
+/// Make the command run `nice` steps nicer, and (on Linux) with idle IO priority
+#[cfg(unix)]
+pub(crate) fn lower_priority(command: &mut Command, nice: i32, idle_io: bool) {
+    use std::os::unix::process::CommandExt;
+
+    if nice == DEFAULT_NICE && !idle_io {
+        return;
+    }
+    // SAFETY: we only make async-signal-safe syscalls in the child, and
+    // failing to lower our priority isn't worth refusing to build over
+    unsafe {
+        command.pre_exec(move || {
+            if nice != DEFAULT_NICE {
+                libc::nice(nice);
+            }
+            if idle_io {
+                set_idle_io();
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Put the current process in the idle IO scheduling class (like `ionice -c 3`)
+#[cfg(unix)]
+fn set_idle_io() {
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+        // SAFETY: ioprio_set only reads its integer arguments
+        unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            );
+        }
+    }
+}
+
+/// Make the command run below normal priority (Windows has no finer steps, or IO priority)
+#[cfg(windows)]
+pub(crate) fn lower_priority(command: &mut Command, nice: i32, _idle_io: bool) {
+    use std::os::windows::process::CommandExt;
+
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    if nice > DEFAULT_NICE {
+        command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn lower_priority(_command: &mut Command, _nice: i32, _idle_io: bool) {}
@@ -0,0 +1,144 @@
+//! Running builds attached to a pseudo-terminal
+//!
+//! We normally pipe a build's output so we can relay and log it, and plenty of
+//! tools notice the pipe and behave differently: no progress bars, no colors,
+//! output buffered in big chunks. With `pty` on, the build's stdout and stderr
+//! are the secondary side of a pseudo-terminal instead, so it sees a terminal,
+//! and we read its output from the primary side.
+//!
+//! A terminal only has one output stream, so stdout and stderr arrive mixed
+//! together (and count as stdout for `failure-output`). We turn off the
+//! terminal's `\n` to `\r\n` translation so the log looks like it would with
+//! pipes. Windows has no equivalent we can use, so there `pty` is ignored (with
+//! a warning) and builds get pipes as usual.
+
+use std::{fs::File, io::Read};
+
+use tracing::warn;
+
+use crate::{DistGraph, DistResult};
+
+/// The size the terminal claims to be, for tools that lay out progress bars
+#[cfg(unix)]
+const COLUMNS: u16 = 120;
+#[cfg(unix)]
+const ROWS: u16 = 40;
+
+/// A pseudo-terminal for the build, if `pty` is on (and we can make one here)
+pub(crate) fn build_pty(dist_graph: &DistGraph) -> DistResult<Option<Pty>> {
+    if !dist_graph.generic_build.pty.unwrap_or(false) {
+        return Ok(None);
+    }
+    if cfg!(unix) {
+        Ok(Some(Pty::open()?))
+    } else {
+        warn!("pty isn't supported on this platform, so the build's output is piped");
+        Ok(None)
+    }
+}
+
+/// A pseudo-terminal for a build to write to
+pub(crate) struct Pty {
+    /// Our side, which the build's output can be read from
+    pub primary: File,
+    /// The build's side, to hand to it as stdout and stderr
+    pub secondary: File,
+}
+
+impl Pty {
+    /// Allocate a pseudo-terminal
+    #[cfg(unix)]
+    pub(crate) fn open() -> std::io::Result<Self> {
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        let check = |result: libc::c_int| {
+            if result < 0 {
+                Err(std::io::Error::last_os_error())
+            } else {
+                Ok(result)
+            }
+        };
+        // SAFETY: these are all used as documented, and every fd we get is
+        // immediately owned by a File so it's closed if anything fails
+        unsafe {
+            let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+            let primary = File::from_raw_fd(fd);
+            check(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+            let name = secondary_name(fd)?;
+            let secondary = File::from_raw_fd(check(libc::open(
+                name.as_ptr(),
+                libc::O_RDWR | libc::O_NOCTTY,
+            ))?);
+            let secondary_fd = secondary.as_raw_fd();
+            // The build gets its own copies as stdout and stderr
+            check(libc::fcntl(secondary_fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+
+            // Keep newlines as they are
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            check(libc::tcgetattr(secondary_fd, &mut termios))?;
+            termios.c_oflag &= !libc::ONLCR;
+            check(libc::tcsetattr(secondary_fd, libc::TCSANOW, &termios))?;
+
+            let size = libc::winsize {
+                ws_row: ROWS,
+                ws_col: COLUMNS,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            check(libc::ioctl(secondary_fd, libc::TIOCSWINSZ, &size))?;
+            Ok(Self { primary, secondary })
+        }
+    }
+
+    /// Pseudo-terminals aren't supported here
+    #[cfg(not(unix))]
+    pub(crate) fn open() -> std::io::Result<Self> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    /// A reader for the build's output, which ends when the build closes its side
+    pub(crate) fn reader(&self) -> std::io::Result<PtyReader> {
+        Ok(PtyReader(self.primary.try_clone()?))
+    }
+}
+
+/// The path of the secondary side of the pseudo-terminal `fd` is the primary side of
+#[cfg(target_os = "linux")]
+unsafe fn secondary_name(fd: libc::c_int) -> std::io::Result<std::ffi::CString> {
+    let mut buf = vec![0 as libc::c_char; 128];
+    let result = libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len());
+    if result != 0 {
+        return Err(std::io::Error::from_raw_os_error(result));
+    }
+    Ok(std::ffi::CStr::from_ptr(buf.as_ptr()).to_owned())
+}
+
+/// The path of the secondary side of the pseudo-terminal `fd` is the primary side of
+///
+/// `ptsname` isn't thread-safe, but we copy its answer out straight away.
+#[cfg(all(unix, not(target_os = "linux")))]
+unsafe fn secondary_name(fd: libc::c_int) -> std::io::Result<std::ffi::CString> {
+    let name = libc::ptsname(fd);
+    if name.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(std::ffi::CStr::from_ptr(name).to_owned())
+}
+
+/// Reads a build's output from the primary side of its pseudo-terminal
+///
+/// Once every process holding the secondary side has exited, Linux reports
+/// `EIO` rather than end-of-file, so that's treated as the end.
+pub(crate) struct PtyReader(File);
+
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.read(buf) {
+            #[cfg(unix)]
+            Err(e) if e.raw_os_error() == Some(libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
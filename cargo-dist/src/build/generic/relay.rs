@@ -0,0 +1,510 @@
+//! Relaying a build's output to our stderr, while also tee-ing it into a log file
+//!
+//! Build tools tend to disable colors when they notice they're writing to a pipe,
+//! so we can force them back on (see `force-color`). Colors are then passed along
+//! if our own stderr is a terminal, but always stripped from the log file.
+//!
+//! Builds get an empty stdin (or `stdin-file`), rather than ours, so tools
+//! that read it don't hang waiting for input no one will type.
+//!
+//! stdout and stderr are read concurrently and relayed line by line as they
+//! arrive, so errors show up next to the output that led to them, rather than
+//! one stream being dumped after the other. Each line is written to our
+//! stderr and the log together, so both see the same interleaving. With
+//! `event-stream` set, each line is also sent there as a `build_output` event.
+//!
+//! How much of this is shown can be set for every build with `verbosity`, or
+//! for particular targets with `target-verbosity`: `quiet` builds only write to
+//! the log, and `verbose` ones also show each command before it's run.
+//!
+//! The last few lines of each stream are also kept apart, so that when a build
+//! fails we can repeat the end of its stdout and/or stderr (see `failure-output`
+//! and `failure-output-lines`) right above the error, instead of leaving it
+//! somewhere in the scrollback. If the streams asked for were empty, as when a
+//! tool only writes to stderr, the other one is shown instead.
+//!
+//! A build that goes haywire can print gigabytes, so only the first
+//! `max-output-mb` (1 GiB by default) of each command's output is kept. After
+//! that, a note that the output was truncated is written, and the rest is read
+//! and dropped, so the build carries on as usual.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, IsTerminal, Read, Write},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use axoasset::LocalAsset;
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+use tracing::warn;
+
+use super::{
+    events::{self, BuildEvent, EventSink},
+    interrupt::{self, RunningBuild},
+    limits, priority, pty, shell,
+};
+use crate::{
+    config::{BuildVerbosity, FailureOutput},
+    DistError, DistGraph, DistResult,
+};
+
+/// What a failed build's summary says when it didn't print anything
+const NO_OUTPUT_NOTE: &str =
+    "(the build didn't print anything; run with --verbose=info to see the commands it ran)\n";
+
+/// The directory (under dist_dir) build logs are written to
+pub(crate) const BUILD_LOG_DIR: &str = "build-logs";
+/// How many lines of each stream a failure shows, unless configured otherwise
+const DEFAULT_FAILURE_OUTPUT_LINES: usize = 20;
+/// How much of a command's output is kept (in MiB), unless configured otherwise
+const DEFAULT_MAX_OUTPUT_MB: u64 = 1024;
+
+/// The last lines written to one stream
+type Tail = Arc<Mutex<VecDeque<String>>>;
+
+/// Where a build's output is shown as it runs
+pub(crate) type Echo = Arc<Mutex<dyn Write + Send>>;
+
+/// Where a command's output is relayed to, shared by its streams
+#[derive(Clone)]
+struct Relay {
+    /// The build log
+    log: Arc<Mutex<File>>,
+    /// The event stream, if there is one
+    sink: Option<Arc<EventSink>>,
+    /// Where the output is shown, unless the build is quiet
+    echo: Option<Echo>,
+    /// Whether colors are passed along to `echo`
+    keep_color: bool,
+    /// How much of the output to keep
+    limit: Arc<OutputLimit>,
+}
+
+/// How much of a command's output has been kept, shared by its streams
+struct OutputLimit {
+    /// The most that's kept, in bytes
+    max: u64,
+    /// How much has been kept so far
+    kept: AtomicU64,
+    /// Whether the note saying the rest was dropped has been written
+    truncated: AtomicBool,
+}
+
+impl OutputLimit {
+    fn new(dist_graph: &DistGraph) -> Arc<Self> {
+        let mb = dist_graph
+            .generic_build
+            .max_output_mb
+            .unwrap_or(DEFAULT_MAX_OUTPUT_MB);
+        Arc::new(Self {
+            max: mb.saturating_mul(1024 * 1024),
+            kept: AtomicU64::new(0),
+            truncated: AtomicBool::new(false),
+        })
+    }
+
+    /// How many more bytes can be kept
+    fn remaining(&self) -> u64 {
+        self.max.saturating_sub(self.kept.load(Ordering::SeqCst))
+    }
+}
+
+/// A log file that the output of one or more commands gets written to
+pub(crate) struct BuildLog {
+    /// Where the log lives
+    pub path: Utf8PathBuf,
+    file: Arc<Mutex<File>>,
+    /// The end of the last command's stdout
+    stdout_tail: Tail,
+    /// The end of the last command's stderr
+    stderr_tail: Tail,
+    /// How much of the commands to show
+    pub verbosity: BuildVerbosity,
+    /// Where to show them (our stderr, outside of tests)
+    pub echo: Echo,
+}
+
+impl BuildLog {
+    /// Create (or truncate) the log with the given name
+    pub(crate) fn create(dist_graph: &DistGraph, name: &str) -> DistResult<Self> {
+        let dir = dist_graph.dist_dir.join(BUILD_LOG_DIR);
+        LocalAsset::create_dir_all(&dir)?;
+        let path = dir.join(format!("{name}.log"));
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+            stdout_tail: Tail::default(),
+            stderr_tail: Tail::default(),
+            verbosity: dist_graph.generic_build.verbosity.unwrap_or_default(),
+            echo: Arc::new(Mutex::new(std::io::stderr())),
+        })
+    }
+
+    /// Show more or less of the commands than `verbosity` says
+    pub(crate) fn with_verbosity(mut self, verbosity: BuildVerbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// The end of the last command's output, as configured by `failure-output`
+    ///
+    /// If the streams it asks for were empty, the other stream is shown instead
+    /// (unless `failure-output-fallback` is off), and if the build printed
+    /// nothing at all, a note saying so.
+    pub(crate) fn failure_summary(&self, dist_graph: &DistGraph) -> Option<String> {
+        let config = &dist_graph.generic_build;
+        let streams = config.failure_output.unwrap_or_default();
+        if streams == FailureOutput::None || dist_graph.interactive_builds {
+            return None;
+        }
+        let summary = self.render_tails(streams.stdout(), streams.stderr());
+        if summary.is_some() || !config.failure_output_fallback.unwrap_or(true) {
+            return summary;
+        }
+        self.render_tails(true, true)
+            .or_else(|| Some(NO_OUTPUT_NOTE.to_owned()))
+    }
+
+    /// The end of whichever streams are shown, if they had anything in them
+    fn render_tails(&self, stdout: bool, stderr: bool) -> Option<String> {
+        let mut summary = String::new();
+        for (name, shown, tail) in [
+            ("stdout", stdout, &self.stdout_tail),
+            ("stderr", stderr, &self.stderr_tail),
+        ] {
+            let tail = tail.lock().expect("build log poisoned");
+            if !shown || tail.is_empty() {
+                continue;
+            }
+            summary.push_str(&format!("end of {name}:\n"));
+            for line in tail.iter() {
+                summary.push_str(&format!("  {line}\n"));
+            }
+        }
+        (!summary.is_empty()).then_some(summary)
+    }
+
+    /// How much has been written to the log so far, in bytes
+    pub(crate) fn len(&self) -> DistResult<u64> {
+        Ok(self
+            .file
+            .lock()
+            .expect("build log poisoned")
+            .metadata()?
+            .len())
+    }
+
+    /// Everything written to the log after the first `offset` bytes
+    pub(crate) fn read_since(&self, offset: u64) -> DistResult<String> {
+        let contents = std::fs::read(&self.path)?;
+        let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+        Ok(String::from_utf8_lossy(contents.get(offset..).unwrap_or_default()).into_owned())
+    }
+}
+
+/// Run the command, relaying its stdout and stderr to our stderr and to the log
+///
+/// This has the same status-checking behaviour as [`Cmd::status`][], except that
+/// exit codes in `tolerable-exit-codes` are returned instead of being an error.
+pub(crate) fn run_relayed(
+    dist_graph: &DistGraph,
+    command: &mut Cmd,
+    log: &BuildLog,
+) -> DistResult<ExitStatus> {
+    let keep_color =
+        std::io::stderr().is_terminal() || dist_graph.generic_build.force_color.unwrap_or(false);
+
+    interrupt::install_handler();
+    let pty = if dist_graph.interactive_builds {
+        None
+    } else {
+        pty::build_pty(dist_graph)?
+    };
+    if dist_graph.interactive_builds {
+        // Leave the build attached to the terminal (and in our process group,
+        // as only the foreground group may read from it)
+        command.stdin(Stdio::inherit());
+        command.stdout(Stdio::inherit());
+        command.stderr(Stdio::inherit());
+        writeln!(
+            log.file.lock().expect("build log poisoned"),
+            "(output not captured, this was an interactive build)"
+        )?;
+    } else {
+        command.stdin(build_stdin(dist_graph)?);
+        match &pty {
+            Some(pty) => {
+                command.stdout(Stdio::from(pty.secondary.try_clone()?));
+                command.stderr(Stdio::from(pty.secondary.try_clone()?));
+            }
+            None => {
+                command.stdout(Stdio::piped());
+                command.stderr(Stdio::piped());
+            }
+        }
+        interrupt::isolate(&mut command.inner);
+    }
+    priority::apply_priority(dist_graph, &mut command.inner);
+    if let Some(hook) = &dist_graph.command_hook {
+        hook.call(&mut command.inner);
+    }
+    if dist_graph.print_build_commands || log.verbosity == BuildVerbosity::Verbose {
+        writeln!(
+            log.echo.lock().expect("build output poisoned"),
+            "build command: {}",
+            shell::copy_pasteable(command, cfg!(windows))
+        )?;
+    }
+    let echo = (log.verbosity != BuildVerbosity::Quiet).then(|| log.echo.clone());
+    let sink = events::sink(dist_graph)?;
+    let lines = dist_graph
+        .generic_build
+        .failure_output_lines
+        .unwrap_or(DEFAULT_FAILURE_OUTPUT_LINES);
+    // Only this command's output belongs in its failure summary
+    let tail = |tail: &Tail| {
+        tail.lock().expect("build log poisoned").clear();
+        tail.clone()
+    };
+    let relay = Relay {
+        log: log.file.clone(),
+        sink,
+        echo,
+        keep_color,
+        limit: OutputLimit::new(dist_graph),
+    };
+    let mut child = command.spawn()?;
+    let running = RunningBuild::new(child.id());
+    // Only matters on Windows, where it must last until the build exits
+    let _confinement = limits::confine(dist_graph, &child)?;
+
+    let mut relays = vec![];
+    if let Some(pty) = pty {
+        // The build's output only ends once nothing else has its side open
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+        let reader = pty.reader()?;
+        drop(pty);
+        relays.push(relay_stream(
+            reader,
+            relay.clone(),
+            tail(&log.stdout_tail),
+            lines,
+        ));
+    }
+    // The hook may have redirected these, in which case there's nothing to relay
+    if let Some(stdout) = child.stdout.take() {
+        relays.push(relay_stream(
+            stdout,
+            relay.clone(),
+            tail(&log.stdout_tail),
+            lines,
+        ));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        relays.push(relay_stream(
+            stderr,
+            relay.clone(),
+            tail(&log.stderr_tail),
+            lines,
+        ));
+    }
+    let status = child.wait()?;
+    for relay in relays {
+        relay.join().expect("output relay thread panicked")?;
+    }
+    drop(running);
+    if interrupt::was_interrupted() {
+        return Err(DistError::BuildInterrupted);
+    }
+    limits::check_limits(dist_graph, status)?;
+
+    if !status.success() && !exit_is_tolerable(dist_graph, status) {
+        if let Some(summary) = log.failure_summary(dist_graph) {
+            eprintln!(
+                "{} exited with {status}\n{summary}",
+                command.get_program().to_string_lossy()
+            );
+        }
+    }
+    if !status.success() && exit_is_tolerable(dist_graph, status) {
+        warn!(
+            "{} exited with {status}, but that's configured as tolerable",
+            command.get_program().to_string_lossy()
+        );
+        return Ok(status);
+    }
+    command.maybe_check_status(status)?;
+    Ok(status)
+}
+
+/// What a (non-interactive) build reads from stdin: `stdin-file`, or else nothing
+///
+/// Inheriting ours would leave anything that reads stdin waiting forever in CI.
+fn build_stdin(dist_graph: &DistGraph) -> DistResult<Stdio> {
+    let Some(path) = &dist_graph.generic_build.stdin_file else {
+        return Ok(Stdio::null());
+    };
+    let path = dist_graph.workspace_dir.join(path);
+    match File::open(&path) {
+        Ok(file) => Ok(Stdio::from(file)),
+        Err(details) => Err(DistError::BuildStdinUnreadable { path, details }),
+    }
+}
+
+/// Whether the user configured this (non-zero) exit as being fine
+pub(crate) fn exit_is_tolerable(dist_graph: &DistGraph, status: ExitStatus) -> bool {
+    let Some(code) = status.code() else {
+        // Killed by a signal, that's never fine
+        return false;
+    };
+    dist_graph
+        .generic_build
+        .tolerable_exit_codes
+        .as_ref()
+        .is_some_and(|codes| codes.contains(&code))
+}
+
+/// Spawn a thread copying lines of `stream` to our stderr and the log (and the event stream)
+fn relay_stream(
+    stream: impl Read + Send + 'static,
+    relay: Relay,
+    tail: Tail,
+    tail_lines: usize,
+) -> std::thread::JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || {
+        let Relay {
+            log,
+            sink,
+            echo,
+            keep_color,
+            limit,
+        } = relay;
+        let mut reader = BufReader::new(stream);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            // Never read (much) more than can be kept, even if it's all one line
+            let most = limit.remaining().saturating_add(1);
+            if (&mut reader).take(most).read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            // Hold the log while writing to both, so the other stream's
+            // lines can't land between them in a different order
+            let mut log = log.lock().expect("build log poisoned");
+            // The other stream may have used up the rest in the meantime
+            let remaining = limit.remaining();
+            let truncated = line.len() as u64 > remaining;
+            if truncated {
+                line.truncate(remaining as usize);
+            }
+            limit.kept.fetch_add(line.len() as u64, Ordering::SeqCst);
+            if truncated {
+                write_kept(&line, &mut log, echo.as_ref(), keep_color)?;
+                if !limit.truncated.swap(true, Ordering::SeqCst) {
+                    let note = format!(
+                        "\n... output truncated at {} bytes (see max-output-mb) ...\n",
+                        limit.max
+                    );
+                    write_kept(note.as_bytes(), &mut log, echo.as_ref(), keep_color)?;
+                }
+                drop(log);
+                // Keep draining it, so the build doesn't block on a full pipe
+                std::io::copy(&mut reader, &mut std::io::sink())?;
+                return Ok(());
+            }
+            let stripped = strip_ansi(&line);
+            if let Some(echo) = &echo {
+                echo.lock()
+                    .expect("build output poisoned")
+                    .write_all(if keep_color { &line } else { &stripped })?;
+            }
+            log.write_all(&stripped)?;
+            if tail_lines > 0 {
+                let mut tail = tail.lock().expect("build log poisoned");
+                if tail.len() == tail_lines {
+                    tail.pop_front();
+                }
+                let line = String::from_utf8_lossy(&stripped);
+                tail.push_back(line.trim_end_matches(['\r', '\n']).to_owned());
+            }
+            if let Some(sink) = &sink {
+                let line = String::from_utf8_lossy(&stripped);
+                sink.emit(&BuildEvent::BuildOutput {
+                    line: line.trim_end_matches(['\r', '\n']).to_owned(),
+                });
+            }
+        }
+    })
+}
+
+/// Write the last of a command's output that's kept to our stderr and the log
+fn write_kept(
+    output: &[u8],
+    log: &mut File,
+    echo: Option<&Echo>,
+    keep_color: bool,
+) -> std::io::Result<()> {
+    let stripped = strip_ansi(output);
+    if let Some(echo) = echo {
+        echo.lock()
+            .expect("build output poisoned")
+            .write_all(if keep_color { output } else { &stripped })?;
+    }
+    log.write_all(&stripped)
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement, titles...) from some output
+pub(crate) fn strip_ansi(input: &[u8]) -> Vec<u8> {
+    const ESC: u8 = 0x1b;
+    const BEL: u8 = 0x07;
+
+    let mut output = Vec::with_capacity(input.len());
+    let mut bytes = input.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte != ESC {
+            output.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            // CSI: parameters and intermediates, ended by a byte in 0x40..=0x7e
+            Some(b'[') => {
+                for byte in bytes.by_ref() {
+                    if (0x40..=0x7e).contains(&byte) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ended by BEL or ST (ESC \)
+            Some(b']') => {
+                while let Some(byte) = bytes.next() {
+                    if byte == BEL {
+                        break;
+                    }
+                    if byte == ESC && bytes.peek() == Some(&b'\\') {
+                        bytes.next();
+                        break;
+                    }
+                }
+            }
+            // nF sequences (e.g. charset selection): intermediates, then a final byte
+            Some(0x20..=0x2f) => {
+                for byte in bytes.by_ref() {
+                    if (0x30..=0x7e).contains(&byte) {
+                        break;
+                    }
+                }
+            }
+            // Everything else is a two-byte sequence
+            _ => {}
+        }
+    }
+    output
+}
@@ -0,0 +1,228 @@
+//! Comparing what was built with the previous release
+//!
+//! Before shipping, it's worth knowing how the release differs from the last
+//! one: a binary that doubled in size, or an archive that's gone missing, is
+//! usually a mistake. `cargo dist build --compare-release
+//! <old>/dist-manifest.json` compares the artifacts in that manifest (whose
+//! files are looked for next to it) with the ones just built, and prints what
+//! changed:
+//!
+//! ```text
+//! changes since the previous release:
+//!   + app-aarch64-apple-darwin.tar.xz (1234 bytes)
+//!   - app-i686-pc-windows-msvc.zip (1000 bytes)
+//!   ~ app-x86_64-unknown-linux-gnu.tar.xz: 1000 -> 1200 bytes (+20.0%)
+//!   ~ app-x86_64-unknown-linux-gnu.dbg: symbols +new_fn -old_fn
+//! ```
+//!
+//! Artifacts are matched up by name, with each release's version taken out, so
+//! `app-v1.0.0.tar.gz` and `app-v1.1.0.tar.gz` count as the same artifact.
+//! Sizes of files that can't be found are left out. With `--compare-symbols`,
+//! the global symbols (as `nm` lists them) of symbols files and extra
+//! artifacts are compared too.
+
+use axoprocess::Cmd;
+use camino::Utf8Path;
+use cargo_dist_schema::{ArtifactKind, DistManifest};
+
+use crate::{DistResult, SortedMap, SortedSet};
+
+/// What stands in for the version when matching artifacts up
+const VERSION_PLACEHOLDER: &str = "{version}";
+
+/// An artifact of one of the releases
+#[derive(Debug, Clone)]
+pub struct ReleaseArtifact {
+    /// Its name (and so its file name)
+    pub name: String,
+    /// What kind of artifact it is
+    pub kind: ArtifactKind,
+    /// How big its file is, if it could be found
+    pub size: Option<u64>,
+}
+
+/// How an artifact's global symbols changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolChanges {
+    /// Symbols only the new artifact has
+    pub added: SortedSet<String>,
+    /// Symbols only the old artifact had
+    pub removed: SortedSet<String>,
+}
+
+/// How the new release's artifacts differ from the old one's
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseDiff {
+    /// Artifacts only the new release has
+    pub added: Vec<ReleaseArtifact>,
+    /// Artifacts only the old release had
+    pub removed: Vec<ReleaseArtifact>,
+    /// Artifacts both have, old then new
+    pub kept: Vec<(ReleaseArtifact, ReleaseArtifact)>,
+    /// How the symbols of kept artifacts changed, by their new name
+    pub symbols: SortedMap<String, SymbolChanges>,
+}
+
+/// The artifacts of a release, by their name without the version
+fn release_artifacts(
+    manifest: &DistManifest,
+    dir: &Utf8Path,
+) -> SortedMap<String, ReleaseArtifact> {
+    let versions = manifest
+        .releases
+        .iter()
+        .map(|release| release.app_version.as_str())
+        .filter(|version| !version.is_empty())
+        .collect::<Vec<_>>();
+    manifest
+        .artifacts
+        .values()
+        .filter_map(|artifact| {
+            let name = artifact.name.clone()?;
+            let key = versions.iter().fold(name.clone(), |key, version| {
+                key.replace(version, VERSION_PLACEHOLDER)
+            });
+            let size = dir
+                .join(&name)
+                .metadata()
+                .ok()
+                .map(|metadata| metadata.len());
+            let kind = artifact.kind.clone();
+            Some((key, ReleaseArtifact { name, kind, size }))
+        })
+        .collect()
+}
+
+/// Compare the artifacts of an old release (whose files are in `old_dir`) with a new one's
+pub fn diff_releases(
+    old: &DistManifest,
+    old_dir: &Utf8Path,
+    new: &DistManifest,
+    new_dir: &Utf8Path,
+) -> ReleaseDiff {
+    let mut old = release_artifacts(old, old_dir);
+    let mut diff = ReleaseDiff::default();
+    for (key, new) in release_artifacts(new, new_dir) {
+        match old.remove(&key) {
+            Some(old) => diff.kept.push((old, new)),
+            None => diff.added.push(new),
+        }
+    }
+    diff.removed = old.into_values().collect();
+    diff
+}
+
+/// Compare the symbols of the kept artifacts that can have them, listing each file's with `list_symbols`
+pub fn diff_symbols(
+    diff: &mut ReleaseDiff,
+    old_dir: &Utf8Path,
+    new_dir: &Utf8Path,
+    list_symbols: impl Fn(&Utf8Path) -> Option<SortedSet<String>>,
+) {
+    for (old, new) in &diff.kept {
+        if !matches!(
+            new.kind,
+            ArtifactKind::Symbols | ArtifactKind::ExtraArtifact
+        ) {
+            continue;
+        }
+        // Files nm can't read (or that aren't here) have nothing to compare
+        let Some(old_symbols) = list_symbols(&old_dir.join(&old.name)) else {
+            continue;
+        };
+        let Some(new_symbols) = list_symbols(&new_dir.join(&new.name)) else {
+            continue;
+        };
+        let changes = SymbolChanges {
+            added: new_symbols.difference(&old_symbols).cloned().collect(),
+            removed: old_symbols.difference(&new_symbols).cloned().collect(),
+        };
+        if !changes.added.is_empty() || !changes.removed.is_empty() {
+            diff.symbols.insert(new.name.clone(), changes);
+        }
+    }
+}
+
+/// The global symbols a file defines, if `nm` can read it
+pub fn nm_symbols(path: &Utf8Path) -> Option<SortedSet<String>> {
+    let output = Cmd::new("nm", "list symbols")
+        .arg("-g")
+        .arg("-P")
+        .arg("--defined-only")
+        .arg(path)
+        .log(None)
+        .check(false)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let symbols = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect();
+    Some(symbols)
+}
+
+/// Describe how the new release differs from the old one
+pub fn render_release_diff(diff: &ReleaseDiff) -> String {
+    let mut lines = vec![];
+    let size = |size: Option<u64>| match size {
+        Some(size) => format!(" ({size} bytes)"),
+        None => String::new(),
+    };
+    for artifact in &diff.added {
+        lines.push(format!("  + {}{}", artifact.name, size(artifact.size)));
+    }
+    for artifact in &diff.removed {
+        lines.push(format!("  - {}{}", artifact.name, size(artifact.size)));
+    }
+    for (old, new) in &diff.kept {
+        let (Some(old_size), Some(new_size)) = (old.size, new.size) else {
+            continue;
+        };
+        if old_size == new_size {
+            continue;
+        }
+        let change = if old_size == 0 {
+            String::new()
+        } else {
+            let percent = (new_size as f64 - old_size as f64) / old_size as f64 * 100.0;
+            format!(" ({percent:+.1}%)")
+        };
+        lines.push(format!(
+            "  ~ {}: {old_size} -> {new_size} bytes{change}",
+            new.name
+        ));
+    }
+    for (name, changes) in &diff.symbols {
+        let added = changes.added.iter().map(|symbol| format!("+{symbol}"));
+        let removed = changes.removed.iter().map(|symbol| format!("-{symbol}"));
+        let symbols = added.chain(removed).collect::<Vec<_>>().join(" ");
+        lines.push(format!("  ~ {name}: symbols {symbols}"));
+    }
+    if lines.is_empty() {
+        return "changes since the previous release: none\n".to_owned();
+    }
+    format!(
+        "changes since the previous release:\n{}\n",
+        lines.join("\n")
+    )
+}
+
+/// Compare what was just built in the dist dir with the release `previous` is the manifest of
+pub(crate) fn compare_with_release(
+    dist_dir: &Utf8Path,
+    manifest: &DistManifest,
+    previous: &Utf8Path,
+    compare_symbols: bool,
+) -> DistResult<String> {
+    let old: DistManifest = axoasset::SourceFile::load_local(previous)?.deserialize_json()?;
+    let old_dir = previous.parent().unwrap_or(Utf8Path::new("."));
+    let mut diff = diff_releases(&old, old_dir, manifest, dist_dir);
+    if compare_symbols {
+        diff_symbols(&mut diff, old_dir, dist_dir, nm_symbols);
+    }
+    Ok(render_release_diff(&diff))
+}
@@ -0,0 +1,176 @@
+//! Running generic builds on another machine over SSH (experimental)
+//!
+//! Some targets can only really be built natively, on a machine we might not
+//! have a CI runner for but can ssh to. `remote-hosts` maps a target to such a
+//! machine, and that target's builds then:
+//!
+//! 1. rsync the workspace (minus the target dir) to a directory on the remote
+//! 2. run each build command there with `ssh`, with the environment we
+//!    computed for it exported on the remote side (any local workspace paths
+//!    in it, or in the command's arguments, are rewritten to the remote copy)
+//! 3. rsync the binaries we expect back to where we'd look for them locally,
+//!    so they're found and copied into the dist dir like any other build's
+//!
+//! Only the expected binaries come back, not extra-outputs. Host-only builds
+//! always run locally.
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::info;
+
+use super::{outputs::ExpectedOutput, shell::posix_quote};
+use crate::{
+    config::{GenericBuildConfig, RemoteHost},
+    DistError, DistGraph, DistResult, GenericBuildStep,
+};
+
+/// What we run commands on remote hosts with
+const SSH: &str = "ssh";
+
+/// What we copy files to and from remote hosts with
+const RSYNC: &str = "rsync";
+
+/// Where workspaces are copied to on remote hosts, unless configured
+const DEFAULT_REMOTE_DIR: &str = "cargo-dist-remote";
+
+/// The machine a build for `target` should run on, if it's configured to run remotely
+pub(crate) fn remote_host(
+    config: &GenericBuildConfig,
+    target: &str,
+    host_only: bool,
+) -> Option<RemoteHost> {
+    if host_only {
+        return None;
+    }
+    config.remote_hosts.as_ref()?.get(target).cloned()
+}
+
+/// The directory on the remote host the workspace is copied to
+pub fn remote_dir(dist_graph: &DistGraph, remote: &RemoteHost) -> Utf8PathBuf {
+    match &remote.dir {
+        Some(dir) => dir.clone(),
+        None => Utf8Path::new(DEFAULT_REMOTE_DIR)
+            .join(dist_graph.workspace_dir.file_name().unwrap_or("workspace")),
+    }
+}
+
+/// Where a local path in the workspace is in the remote copy of it
+fn remote_path(
+    dist_graph: &DistGraph,
+    remote: &RemoteHost,
+    local: &Utf8Path,
+) -> Option<Utf8PathBuf> {
+    let relative = local.strip_prefix(&dist_graph.workspace_dir).ok()?;
+    Some(remote_dir(dist_graph, remote).join(relative))
+}
+
+/// The directory a command runs in
+fn command_dir(command: &Cmd) -> DistResult<Utf8PathBuf> {
+    let dir = match command.get_current_dir() {
+        Some(dir) => dir.to_owned(),
+        None => std::env::current_dir()?,
+    };
+    Ok(Utf8PathBuf::from_path_buf(dir).expect("current dir isn't utf8!?"))
+}
+
+/// Wrap a fully-assembled build command so that it runs on `remote`
+///
+/// Like with containers, the environment we computed for the build is passed
+/// along explicitly, since the remote shell doesn't inherit ours.
+pub(crate) fn remotize(
+    dist_graph: &DistGraph,
+    command: &Cmd,
+    remote: &RemoteHost,
+) -> DistResult<Cmd> {
+    let local_dir = command_dir(command)?;
+    let Some(dir) = remote_path(dist_graph, remote, &local_dir) else {
+        return Err(DistError::RemoteBuildOutsideWorkspace { dir: local_dir });
+    };
+    let workspace = dist_graph.workspace_dir.as_str();
+    let remote_workspace = remote_dir(dist_graph, remote);
+    let mut words = vec!["cd".to_owned(), posix_quote(dir.as_str()), "&&".to_owned()];
+    words.push("env".to_owned());
+    for (key, val) in command.get_envs() {
+        // Removals only make sense for our environment, which the remote
+        // shell doesn't inherit anyway
+        let Some(val) = val else {
+            continue;
+        };
+        let val = val
+            .to_string_lossy()
+            .replace(workspace, remote_workspace.as_str());
+        words.push(posix_quote(&format!("{}={val}", key.to_string_lossy())));
+    }
+    words.push(posix_quote(&command.get_program().to_string_lossy()));
+    for arg in command.get_args() {
+        let arg = arg
+            .to_string_lossy()
+            .replace(workspace, remote_workspace.as_str());
+        words.push(posix_quote(&arg));
+    }
+
+    let mut wrapped = Cmd::new(SSH, format!("exec remote build: {}", remote.host));
+    wrapped.arg(&remote.host).arg(words.join(" "));
+    Ok(wrapped)
+}
+
+/// Copy the workspace to the step's remote host, if it has one
+pub(crate) fn sync_to_remote(dist_graph: &DistGraph, step: &GenericBuildStep) -> DistResult<()> {
+    let Some(remote) = &step.remote_host else {
+        return Ok(());
+    };
+    let dir = remote_dir(dist_graph, remote);
+    info!("copying the workspace to {}:{dir}", remote.host);
+    Cmd::new(SSH, "create remote build dir")
+        .arg(&remote.host)
+        .arg(format!("mkdir -p {}", posix_quote(dir.as_str())))
+        .stdout_to_stderr()
+        .run()?;
+    let mut rsync = Cmd::new(RSYNC, "copy workspace to remote host");
+    rsync.arg("-az").arg("--delete");
+    // The target dir is ours, and is usually huge
+    if let Ok(target_dir) = dist_graph
+        .target_dir
+        .strip_prefix(&dist_graph.workspace_dir)
+    {
+        rsync.arg("--exclude").arg(format!("/{target_dir}/"));
+    }
+    rsync
+        .arg(format!("{}/", dist_graph.workspace_dir))
+        .arg(format!("{}:{dir}/", remote.host))
+        .stdout_to_stderr()
+        .run()?;
+    Ok(())
+}
+
+/// Copy whichever of the outputs we expect the remote build produced back here
+///
+/// Outputs that aren't there are skipped: they're reported missing later, like
+/// those of a local build.
+pub(crate) fn fetch_outputs(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    expected: &[ExpectedOutput],
+) -> DistResult<()> {
+    let Some(remote) = &step.remote_host else {
+        return Ok(());
+    };
+    let cwd =
+        Utf8PathBuf::from_path_buf(std::env::current_dir()?).expect("current dir isn't utf8!?");
+    for candidate in expected.iter().flat_map(|output| &output.candidates) {
+        let local = cwd.join(candidate);
+        let Some(path) = remote_path(dist_graph, remote, &local) else {
+            continue;
+        };
+        if let Some(parent) = local.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Cmd::new(RSYNC, "copy output from remote host")
+            .arg("-az")
+            .arg(format!("{}:{path}", remote.host))
+            .arg(local.as_str())
+            .check(false)
+            .output()?;
+    }
+    Ok(())
+}
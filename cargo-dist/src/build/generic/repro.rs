@@ -0,0 +1,180 @@
+//! Recording what it takes to reproduce a generic build
+//!
+//! With `cargo dist build --repro-manifest`, every generic build writes a
+//! `repro-<target>.json` to the dist dir just before it runs, recording
+//! everything we put together to run it:
+//!
+//! ```json
+//! {
+//!   "target": "x86_64-unknown-linux-gnu",
+//!   "host": "x86_64-unknown-linux-gnu",
+//!   "commands": [["make", "release"]],
+//!   "cwd": "/home/me/project",
+//!   "env": { "CC": "gcc", "CFLAGS": "-O2", "API_TOKEN": "<redacted>" },
+//!   "removed-env": [],
+//!   "container-image": null,
+//!   "tools": { "cc": { "program": "gcc", "version": "gcc (GCC) 13.2.0" } }
+//! }
+//! ```
+//!
+//! `env` is only the variables we set for the build, not everything it
+//! inherited, and the values of ones that look like secrets are redacted.
+//! The commands (CMake builds have two, to configure and then build) are as
+//! they would run on the host, before they're wrapped to run in a container.
+
+use std::collections::BTreeMap;
+
+use axoprocess::Cmd;
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+use super::{resolved_cc, resolved_cxx, uses_c_compilers, BuildContext};
+use crate::{DistGraph, DistResult, GenericBuildStep};
+
+/// What replaces the values of secret-looking variables
+pub const REDACTED: &str = "<redacted>";
+
+/// Parts of variable names that suggest their values are secret
+const SECRET_MARKERS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "API_KEY",
+    "PRIVATE_KEY",
+    "AUTH",
+];
+
+/// Everything needed to rerun a build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReproManifest {
+    /// The target being built
+    pub target: String,
+    /// The machine it was built on
+    pub host: String,
+    /// The commands, with their arguments, in the order they ran
+    pub commands: Vec<Vec<String>>,
+    /// Where it ran
+    pub cwd: Utf8PathBuf,
+    /// The variables we set for it
+    pub env: BTreeMap<String, String>,
+    /// The variables we hid from it
+    pub removed_env: Vec<String>,
+    /// The container it ran in, if it did
+    pub container_image: Option<String>,
+    /// The toolchain it was handed
+    pub tools: BTreeMap<String, ToolVersion>,
+}
+
+/// A tool a build was given, and which version it was
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolVersion {
+    /// The tool, as we handed it to the build
+    pub program: String,
+    /// The first line of its `--version`, if it had one
+    pub version: Option<String>,
+}
+
+/// Whether a variable's value looks like it's a secret
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// Where the repro manifest for a target goes
+pub fn repro_manifest_path(dist_graph: &DistGraph, target: &str) -> Utf8PathBuf {
+    dist_graph.dist_dir.join(format!("repro-{target}.json"))
+}
+
+/// Describe a build's prepared commands
+///
+/// They're all prepared for the same step, so they have the same environment.
+pub(crate) fn repro_manifest(
+    dist_graph: &DistGraph,
+    commands: &[&Cmd],
+    step: &GenericBuildStep,
+) -> DistResult<ReproManifest> {
+    let command = commands
+        .first()
+        .expect("a build must have at least one command");
+    let context = BuildContext::new(dist_graph, command, Some(step))?;
+    let mut env = BTreeMap::new();
+    let mut removed_env = vec![];
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(_) if is_secret(&key) => {
+                env.insert(key, REDACTED.to_owned());
+            }
+            Some(value) => {
+                env.insert(key, value.to_string_lossy().into_owned());
+            }
+            None => removed_env.push(key),
+        }
+    }
+    let mut tools = BTreeMap::new();
+    if uses_c_compilers(dist_graph, step) {
+        for (name, program) in [
+            ("cc", resolved_cc(dist_graph, &step.target_triple)),
+            ("cxx", resolved_cxx(dist_graph, &step.target_triple)),
+        ] {
+            let version = tool_version(&program);
+            tools.insert(name.to_owned(), ToolVersion { program, version });
+        }
+    }
+    Ok(ReproManifest {
+        target: step.target_triple.clone(),
+        host: context.host,
+        commands: commands
+            .iter()
+            .map(|command| {
+                std::iter::once(command.get_program())
+                    .chain(command.get_args())
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect()
+            })
+            .collect(),
+        cwd: context.cwd,
+        env,
+        removed_env,
+        container_image: step.container_image.clone(),
+        tools,
+    })
+}
+
+/// Write the repro manifest for a build, if we were asked to
+pub(crate) fn write_repro_manifest(
+    dist_graph: &DistGraph,
+    commands: &[&Cmd],
+    step: &GenericBuildStep,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if !dist_graph.repro_manifests {
+        return Ok(None);
+    }
+    let manifest = repro_manifest(dist_graph, commands, step)?;
+    let path = repro_manifest_path(dist_graph, &step.target_triple);
+    let contents = serde_json::to_string_pretty(&manifest).expect("serde_json failed");
+    axoasset::LocalAsset::write_new_all(&contents, &path)?;
+    Ok(Some(path))
+}
+
+/// The first line of what a tool says its version is
+///
+/// Tools like `ccache gcc` are given with their arguments, which come first.
+pub(crate) fn tool_version(program: &str) -> Option<String> {
+    let mut words = program.split_whitespace();
+    let mut command = std::process::Command::new(words.next()?);
+    command.args(words).arg("--version");
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_owned)
+}
@@ -0,0 +1,152 @@
+//! Checking generic builds are reproducible by building them twice
+//!
+//! `reproducible`, `reproducible-env` and the like only help if nothing else
+//! about the build varies from run to run. `cargo dist build
+//! --verify-reproducible` checks that end to end: each generic build is run,
+//! its binaries are moved aside (to `cargo-dist-repro/<target>` in the target
+//! dir), and then it's run again. If any binary isn't byte-for-byte the same
+//! both times, the build fails with a summary of what differed:
+//!
+//! ```text
+//! app: 1234 bytes (sha256 2cf24dba5fb0) the first time, 1240 bytes (sha256 486ea46224d1) the second, first differing at byte 56
+//! ```
+//!
+//! The second build's binaries are the ones that get shipped.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::BuiltTarget;
+use crate::{
+    config::ChecksumStyle, generate_checksum, BinaryIdx, DistError, DistGraph, DistResult,
+    GenericBuildStep, SortedMap,
+};
+
+/// The binaries a target's first build made, moved aside until the second build is done
+pub(crate) struct FirstBuild {
+    /// Where they were moved to
+    dir: Utf8PathBuf,
+    /// Where each binary is now
+    outputs: SortedMap<BinaryIdx, Utf8PathBuf>,
+}
+
+impl Drop for FirstBuild {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// Where a target's first build's binaries are kept
+pub fn first_build_dir(dist_graph: &DistGraph, target: &GenericBuildStep) -> Utf8PathBuf {
+    dist_graph
+        .target_dir
+        .join("cargo-dist-repro")
+        .join(&target.target_triple)
+}
+
+/// Move what a target's first build made out of the way of its second
+///
+/// Moving (rather than copying) them also makes sure the second build really
+/// makes them again.
+pub(crate) fn set_aside_first_build(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    built: BuiltTarget,
+) -> DistResult<FirstBuild> {
+    let dir = first_build_dir(dist_graph, target);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::create_dir_all(&dir)?;
+    let mut first = FirstBuild {
+        dir,
+        outputs: SortedMap::new(),
+    };
+    for (binary_idx, output) in built.built_binaries.iter().zip(&built.found) {
+        // Binaries can share a file name, so each gets a directory of its own
+        let dest = first.dir.join(binary_idx.0.to_string()).join(&output.name);
+        std::fs::create_dir_all(dest.parent().expect("kept binaries have a parent"))?;
+        if std::fs::rename(&output.path, &dest).is_err() {
+            std::fs::copy(&output.path, &dest)?;
+            std::fs::remove_file(&output.path)?;
+        }
+        first.outputs.insert(*binary_idx, dest);
+    }
+    Ok(first)
+}
+
+/// Check a target's second build made exactly what its first did
+pub(crate) fn check_second_build(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    first: &FirstBuild,
+    second: &BuiltTarget,
+) -> DistResult<()> {
+    let second_outputs = second
+        .built_binaries
+        .iter()
+        .zip(&second.found)
+        .map(|(binary_idx, output)| (*binary_idx, output.path.as_path()))
+        .collect::<SortedMap<_, _>>();
+    let name = |binary_idx: &BinaryIdx| &dist_graph.binary(*binary_idx).name;
+    let mut differences = vec![];
+    for (binary_idx, first_path) in &first.outputs {
+        let name = name(binary_idx);
+        match second_outputs.get(binary_idx) {
+            Some(second_path) => {
+                if let Some(difference) = describe_difference(first_path, second_path)? {
+                    differences.push(format!("{name}: {difference}"));
+                }
+            }
+            None => differences.push(format!("{name}: only made the first time")),
+        }
+    }
+    for binary_idx in second_outputs.keys() {
+        if !first.outputs.contains_key(binary_idx) {
+            let name = name(binary_idx);
+            differences.push(format!("{name}: only made the second time"));
+        }
+    }
+    if differences.is_empty() {
+        return Ok(());
+    }
+    Err(DistError::NotReproducible {
+        target: target.target_triple.clone(),
+        differences: differences.join("\n"),
+    })
+}
+
+/// How two builds of a binary differ, if they do
+fn describe_difference(first: &Utf8Path, second: &Utf8Path) -> DistResult<Option<String>> {
+    let Some(offset) = first_difference(first, second)? else {
+        return Ok(None);
+    };
+    let describe = |path: &Utf8Path| -> DistResult<String> {
+        let size = path.metadata()?.len();
+        let sha256 = generate_checksum(&ChecksumStyle::Sha256, path)?;
+        Ok(format!("{size} bytes (sha256 {})", &sha256[..12]))
+    };
+    Ok(Some(format!(
+        "{} the first time, {} the second, first differing at byte {offset}",
+        describe(first)?,
+        describe(second)?
+    )))
+}
+
+/// The offset of the first byte at which two files differ, if they do
+fn first_difference(first: &Utf8Path, second: &Utf8Path) -> DistResult<Option<u64>> {
+    let mut first = BufReader::new(File::open(first)?).bytes();
+    let mut second = BufReader::new(File::open(second)?).bytes();
+    let mut offset = 0;
+    loop {
+        match (first.next().transpose()?, second.next().transpose()?) {
+            (None, None) => return Ok(None),
+            (a, b) if a != b => return Ok(Some(offset)),
+            _ => offset += 1,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Support for making the outputs of generic builds reproducible
+//!
+//! Even if a build is itself reproducible, the files we copy out of it carry
+//! the build machine's mtimes, which end up in the archives we make. With
+//! `reproducible` we set them all to [`SOURCE_DATE_EPOCH`][sde] instead.
+//!
+//! Builds also tend to bake in where they happened: `__FILE__` and debug info
+//! record absolute paths, and some build scripts embed `$HOME` or `$USER`.
+//! With `reproducible-env`, builds don't get the variables that say who ran
+//! them and where (see [`VOLATILE_VARS`][], unless they're in `keep-env`), C
+//! and C++ compilers are told to record the build's directory as `.`, and Go
+//! builds are run with `-trimpath`.
+//!
+//! [sde]: https://reproducible-builds.org/specs/source-date-epoch/
+
+use std::{
+    fs::File,
+    time::{Duration, SystemTime},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// The environment variable reproducible builds agree on for "when the sources are from"
+pub(crate) const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Variables that say who ran a build and where, cleared with `reproducible-env`
+pub const VOLATILE_VARS: &[&str] = &["HOME", "PWD", "OLDPWD", "USER", "LOGNAME", "HOSTNAME"];
+
+/// Whether builds should be run without anything that varies between machines
+pub(crate) fn wants_reproducible_env(dist_graph: &DistGraph) -> bool {
+    dist_graph.generic_build.reproducible_env.unwrap_or(false)
+}
+
+/// The volatile variables to clear, which is all of them that aren't in `keep-env`
+pub(crate) fn volatile_vars(dist_graph: &DistGraph) -> Vec<&'static str> {
+    let keep = dist_graph
+        .generic_build
+        .keep_env
+        .as_deref()
+        .unwrap_or_default();
+    VOLATILE_VARS
+        .iter()
+        .copied()
+        .filter(|var| !keep.iter().any(|kept| kept == var))
+        .collect()
+}
+
+/// C/C++ compiler flags that record each of `dirs` as `.` in what's built
+pub(crate) fn prefix_map_flags(dirs: &[Utf8PathBuf]) -> String {
+    dirs.iter()
+        .flat_map(|dir| {
+            [
+                format!("-ffile-prefix-map={dir}=."),
+                // Older compilers only know this one, for debug info
+                format!("-fdebug-prefix-map={dir}=."),
+            ]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a `SOURCE_DATE_EPOCH` (seconds since the unix epoch)
+pub(crate) fn parse_source_date_epoch(value: &str) -> DistResult<SystemTime> {
+    let seconds = value
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| DistError::InvalidSourceDateEpoch {
+            value: value.to_owned(),
+        })?;
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// The time outputs should be stamped with, if the build should be reproducible
+pub(crate) fn output_mtime(dist_graph: &DistGraph) -> DistResult<Option<SystemTime>> {
+    if !dist_graph.generic_build.reproducible.unwrap_or(false) {
+        return Ok(None);
+    }
+    match std::env::var(SOURCE_DATE_EPOCH) {
+        Ok(value) => parse_source_date_epoch(&value).map(Some),
+        Err(_) => {
+            warn!("reproducible builds are enabled, but {SOURCE_DATE_EPOCH} isn't set; leaving output timestamps alone");
+            Ok(None)
+        }
+    }
+}
+
+/// Set the modification time of each of `paths` to `mtime`
+///
+/// Symlinks are skipped, as this would change what they point to instead.
+pub(crate) fn normalize_mtimes<'a>(
+    paths: impl IntoIterator<Item = &'a Utf8Path>,
+    mtime: SystemTime,
+) -> DistResult<()> {
+    for path in paths {
+        if path.is_symlink() {
+            continue;
+        }
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(mtime)?;
+    }
+    Ok(())
+}
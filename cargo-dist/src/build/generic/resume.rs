@@ -0,0 +1,153 @@
+//! Picking up a multi-target build where it left off
+//!
+//! If the seventh of ten targets fails to build, running `cargo dist build`
+//! again rebuilds all ten. To avoid that, every generic build that succeeds is
+//! recorded in `build-state.json` in the dist dir, along with the sha256 of
+//! each binary (and symbols file) it copied there. With `--resume`, a target
+//! whose recorded build ran the same command, and whose copied files are all
+//! still there and unchanged, is skipped: its provenance is taken from the
+//! record and nothing is rebuilt.
+//!
+//! Without `--resume` the file is started afresh, so a record never outlives
+//! the run that could have been resumed from it. Extra outputs aren't
+//! recorded, so a build that has them is always rerun.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_dist_schema::{BuildProvenance, DistManifest};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ChecksumStyle, generate_checksum, DistGraph, DistResult, GenericBuildStep, SortedMap,
+};
+
+/// What the record of finished builds is called in the dist dir
+pub const BUILD_STATE: &str = "build-state.json";
+
+/// The generic builds that have finished
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildState {
+    /// The builds, by target triple
+    pub completed: SortedMap<String, CompletedBuild>,
+}
+
+/// A generic build that finished
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedBuild {
+    /// The command it ran
+    pub command: Vec<String>,
+    /// The sha256 of each file it copied into the dist dir, by path
+    pub copied: SortedMap<Utf8PathBuf, String>,
+    /// How it was built
+    pub provenance: BuildProvenance,
+}
+
+/// Where the record of finished builds goes
+pub fn build_state_path(dist_graph: &DistGraph) -> Utf8PathBuf {
+    dist_graph.dist_dir.join(BUILD_STATE)
+}
+
+/// Read the record of finished builds, which may not exist
+fn read_build_state(path: &Utf8Path) -> DistResult<BuildState> {
+    if !path.exists() {
+        return Ok(BuildState::default());
+    }
+    let contents = std::fs::read_to_string(path)?;
+    // A record we can't read just means there's nothing to skip
+    Ok(serde_json::from_str(&contents).unwrap_or_default())
+}
+
+/// Forget the builds of earlier runs, unless we're resuming them
+pub fn reset_build_state(dist_graph: &DistGraph) -> DistResult<()> {
+    let path = build_state_path(dist_graph);
+    if !dist_graph.resume_builds && path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// The files `target`'s build copies into the dist dir, as far as we keep track
+fn copied_files(dist_graph: &DistGraph, target: &GenericBuildStep) -> Vec<Utf8PathBuf> {
+    target
+        .expected_binaries
+        .iter()
+        .flat_map(|binary_idx| {
+            let binary = dist_graph.binary(*binary_idx);
+            binary.copy_exe_to.iter().chain(&binary.copy_symbols_to)
+        })
+        .filter(|path| path.exists())
+        .cloned()
+        .collect()
+}
+
+/// The provenance of `target`'s build from an earlier run, if we're resuming and it can be skipped
+pub(crate) fn resumable_build(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+) -> DistResult<Option<BuildProvenance>> {
+    if !dist_graph.resume_builds || !target.extra_outputs.is_empty() {
+        return Ok(None);
+    }
+    let state = read_build_state(&build_state_path(dist_graph))?;
+    let Some(completed) = state.completed.get(&target.target_triple) else {
+        return Ok(None);
+    };
+    if completed.command != target.build_command || completed.copied.is_empty() {
+        return Ok(None);
+    }
+    for (path, sha256) in &completed.copied {
+        if !path.exists() || generate_checksum(&ChecksumStyle::Sha256, path)? != *sha256 {
+            return Ok(None);
+        }
+    }
+    Ok(Some(completed.provenance.clone()))
+}
+
+/// Skip `target`'s build if we're resuming and an earlier run finished it, putting back its provenance
+pub(crate) fn skip_resumed_build(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    target: &GenericBuildStep,
+) -> DistResult<bool> {
+    let Some(provenance) = resumable_build(dist_graph, target)? else {
+        return Ok(false);
+    };
+    eprintln!(
+        "skipping generic target ({}), which an earlier run already built",
+        target.target_triple
+    );
+    manifest.provenance.push(provenance);
+    manifest.provenance.sort();
+    Ok(true)
+}
+
+/// Record that `target`'s build finished, with the provenance it added to `manifest`
+pub(crate) fn record_completed_build(
+    dist_graph: &DistGraph,
+    manifest: &DistManifest,
+    target: &GenericBuildStep,
+) -> DistResult<()> {
+    let Some(provenance) = manifest.provenance.iter().find(|provenance| {
+        provenance.target_triple == target.target_triple
+            && provenance.command == target.build_command
+    }) else {
+        return Ok(());
+    };
+    let mut copied = SortedMap::new();
+    for path in copied_files(dist_graph, target) {
+        let sha256 = generate_checksum(&ChecksumStyle::Sha256, &path)?;
+        copied.insert(path, sha256);
+    }
+    let path = build_state_path(dist_graph);
+    let mut state = read_build_state(&path)?;
+    state.completed.insert(
+        target.target_triple.clone(),
+        CompletedBuild {
+            command: target.build_command.clone(),
+            copied,
+            provenance: provenance.clone(),
+        },
+    );
+    let json = serde_json::to_string_pretty(&state).expect("serde_json::to_string_pretty failed");
+    std::fs::write(path, json)?;
+    Ok(())
+}
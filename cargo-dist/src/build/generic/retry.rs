@@ -0,0 +1,54 @@
+//! Retrying generic builds that fail for reasons outside of their control
+//!
+//! Builds that download things can fail because of the network rather than
+//! because anything is wrong with them. With `retries` we run failed builds
+//! again, and with `retry-on` only if their output suggests it's worth it,
+//! so that builds that are actually broken still fail fast.
+
+use tracing::warn;
+
+use super::relay::BuildLog;
+use crate::{DistError, DistGraph, DistResult};
+
+/// Run `attempt` until it succeeds or we run out of retries
+///
+/// With `retry-on`, failures are only retried if the output they added
+/// to the log contains one of the configured strings.
+pub(crate) fn with_retries<T>(
+    dist_graph: &DistGraph,
+    log: &BuildLog,
+    mut attempt: impl FnMut() -> DistResult<T>,
+) -> DistResult<T> {
+    let retries = dist_graph.generic_build.retries.unwrap_or(0);
+    let mut retried = 0;
+    loop {
+        let start = log.len()?;
+        let result = attempt();
+        let Err(e) = &result else {
+            return result;
+        };
+        if retried >= retries || matches!(e, DistError::BuildInterrupted) {
+            return result;
+        }
+        let reason = match &dist_graph.generic_build.retry_on {
+            Some(markers) => {
+                let output = log.read_since(start)?;
+                let Some(marker) = matching_marker(&output, markers) else {
+                    return result;
+                };
+                format!("its output contained {marker:?}")
+            }
+            None => e.to_string(),
+        };
+        retried += 1;
+        warn!("build failed ({reason}), retrying ({retried}/{retries})");
+    }
+}
+
+/// The first of `markers` that appears in `output`
+pub(crate) fn matching_marker<'a>(output: &str, markers: &'a [String]) -> Option<&'a str> {
+    markers
+        .iter()
+        .find(|marker| output.contains(marker.as_str()))
+        .map(|marker| marker.as_str())
+}
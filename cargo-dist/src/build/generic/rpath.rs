@@ -0,0 +1,137 @@
+//! Making unix binaries relocatable
+//!
+//! Builds often bake absolute library search paths (rpaths) into binaries,
+//! pointing into the build machine's filesystem, which stop working as soon
+//! as an archive is unpacked anywhere else. With `relative-rpath` we rewrite
+//! them to look in `../lib` relative to the binary itself (where `layout`
+//! puts libraries):
+//!
+//! * ELF binaries get `$ORIGIN/../lib` as their only rpath, with `patchelf`
+//! * Mach-O binaries lose their absolute rpaths and gain `@loader_path/../lib`,
+//!   with `install_name_tool`. Dynamic libraries also get `@rpath/<name>` as
+//!   their install name, so whatever links to them looks them up the same way.
+//!
+//! Other formats (PE, wasm) have no rpaths and are left alone. Like
+//! split-debuginfo, we work on a copy of each output, so the build's own
+//! outputs are untouched.
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use goblin::mach::{Mach, MachO, SingleArch};
+
+use super::{magic::expected_format, shell::find_program, staging};
+use crate::{
+    build::output_kind::OutputKind, config::BinaryFormat, Binary, DistError, DistGraph, DistResult,
+    GenericBuildStep,
+};
+
+/// The rpath that finds libraries in `../lib` for a target, if its format has rpaths
+pub fn relative_rpath(target: &str) -> Option<&'static str> {
+    match expected_format(target) {
+        BinaryFormat::Elf => Some("$ORIGIN/../lib"),
+        BinaryFormat::MachO => Some("@loader_path/../lib"),
+        _ => None,
+    }
+}
+
+/// Make a copy of a build output that finds its libraries relative to itself, if configured to
+pub(crate) fn make_relocatable(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    binary: &Binary,
+    output: &Utf8Path,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if !dist_graph.generic_build.relative_rpath.unwrap_or(false) {
+        return Ok(None);
+    }
+    let kind = OutputKind::detect(&binary.file_name, &binary.target);
+    // Static libraries aren't linked yet, so they have no rpaths
+    if kind == OutputKind::StaticLibrary {
+        return Ok(None);
+    }
+    let Some(rpath) = relative_rpath(&binary.target) else {
+        return Ok(None);
+    };
+    let tool = match expected_format(&binary.target) {
+        BinaryFormat::MachO => "install_name_tool",
+        _ => "patchelf",
+    };
+    let Some(tool_path) = find_program(tool) else {
+        return Err(DistError::RelocationToolMissing {
+            tool: tool.to_owned(),
+            binary: binary.name.clone(),
+        });
+    };
+
+    let staging = staging::work_dir(dist_graph, &target.target_triple)
+        .join("relocatable")
+        .join(&target.target_triple);
+    std::fs::create_dir_all(&staging)?;
+    let relocatable = staging.join(&binary.file_name);
+    std::fs::copy(output, &relocatable)?;
+
+    let name = &binary.file_name;
+    let mut command = Cmd::new(tool_path, format!("make {name} relocatable"));
+    if tool == "patchelf" {
+        command.arg("--set-rpath").arg(rpath);
+    } else {
+        let existing = macho_rpaths(&relocatable)?;
+        let id = (kind == OutputKind::DynamicLibrary).then(|| format!("@rpath/{name}"));
+        let args = install_name_tool_args(&existing, rpath, id.as_deref());
+        if args.is_empty() {
+            return Ok(Some(relocatable));
+        }
+        for arg in args {
+            command.arg(arg);
+        }
+    }
+    command.arg(&relocatable).run()?;
+    Ok(Some(relocatable))
+}
+
+/// The rpaths a Mach-O binary has, across all of its architectures
+pub fn macho_rpaths(path: &Utf8Path) -> DistResult<Vec<String>> {
+    let bytes = std::fs::read(path)?;
+    let mut rpaths = vec![];
+    let mut add = |macho: &MachO| {
+        for rpath in &macho.rpaths {
+            if !rpaths.iter().any(|existing| existing == rpath) {
+                rpaths.push(rpath.to_string());
+            }
+        }
+    };
+    match Mach::parse(&bytes)? {
+        Mach::Binary(macho) => add(&macho),
+        Mach::Fat(multi) => {
+            for arch in &multi {
+                if let SingleArch::MachO(macho) = arch? {
+                    add(&macho);
+                }
+            }
+        }
+    }
+    Ok(rpaths)
+}
+
+/// The `install_name_tool` arguments that swap absolute rpaths for `rpath`
+///
+/// Relative rpaths (like `@executable_path/...`) are kept, as they work
+/// anywhere already. If `id` is given, it becomes the library's install name.
+pub fn install_name_tool_args(existing: &[String], rpath: &str, id: Option<&str>) -> Vec<String> {
+    let mut args = vec![];
+    for old in existing {
+        if !old.starts_with('@') {
+            args.push("-delete_rpath".to_owned());
+            args.push(old.clone());
+        }
+    }
+    if !existing.iter().any(|old| old == rpath) {
+        args.push("-add_rpath".to_owned());
+        args.push(rpath.to_owned());
+    }
+    if let Some(id) = id {
+        args.push("-id".to_owned());
+        args.push(id.to_owned());
+    }
+    args
+}
@@ -0,0 +1,45 @@
+//! Building with sanitizers, to check a release for memory bugs
+//!
+//! `cargo dist build --sanitize=address,undefined` is for verification builds,
+//! not releases: it adds `-fsanitize=...` (and `-fno-omit-frame-pointer`, for
+//! usable stack traces) to the CFLAGS, CXXFLAGS and LDFLAGS of every build for
+//! a target, before the flags profile's, the variant's and the target's own
+//! flags. The binaries it makes link against the sanitizer runtimes, which is
+//! why it's only ever done when asked for on the command line.
+//!
+//! If `smoke-test` is on, the binaries are smoke-tested with the sanitizers'
+//! options set so that any error they find fails the test (only binaries for
+//! the host are smoke-tested, so only native builds are run this way).
+
+use crate::config::Sanitizer;
+
+/// The flags that build with `sanitizers`, if there are any
+pub fn sanitizer_flags(sanitizers: &[Sanitizer]) -> Option<String> {
+    if sanitizers.is_empty() {
+        return None;
+    }
+    let names = sanitizers
+        .iter()
+        .map(|sanitizer| sanitizer.name())
+        .collect::<Vec<_>>();
+    Some(format!(
+        "-fsanitize={} -fno-omit-frame-pointer",
+        names.join(",")
+    ))
+}
+
+/// The variables sanitized binaries are run with, so that anything the sanitizers find is fatal
+pub fn sanitizer_env(sanitizers: &[Sanitizer]) -> Vec<(String, String)> {
+    let mut env = vec![];
+    for sanitizer in sanitizers {
+        let (key, value) = match sanitizer {
+            Sanitizer::Address => ("ASAN_OPTIONS", "abort_on_error=1:detect_leaks=1"),
+            Sanitizer::Undefined => ("UBSAN_OPTIONS", "halt_on_error=1:print_stacktrace=1"),
+            Sanitizer::Thread => ("TSAN_OPTIONS", "halt_on_error=1"),
+            Sanitizer::Memory => ("MSAN_OPTIONS", "halt_on_error=1"),
+            Sanitizer::Leak => ("LSAN_OPTIONS", "exitcode=23"),
+        };
+        env.push((key.to_owned(), value.to_owned()));
+    }
+    env
+}
@@ -0,0 +1,518 @@
+//! Software bills of materials for generic builds
+//!
+//! Compliance often needs a list of what went into each binary. With `sbom` set
+//! to `"cyclonedx"` or `"spdx"`, every binary a generic build makes gets an SBOM
+//! in that format (as JSON) in the dist dir, named after the binary and target
+//! (like `app-x86_64-apple-darwin.cdx.json` or `.spdx.json`). It lists what
+//! cargo-dist knows the build used:
+//!
+//! * the packages in the Brewfile, at the versions brew has installed, if the
+//!   build got Homebrew's environment
+//! * the `pkg-config-deps`, at the versions pkg-config reports
+//! * the files in `verify-inputs`, with their sha256 and where they come from
+//!
+//! Anything the build system pulls in that cargo-dist isn't told about can't be
+//! listed. Like everything else a build copies, SBOMs are checksummed and
+//! passed to `post-process`.
+
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{reproducible, uses_brew};
+use crate::{
+    config::SbomFormat, env::brew_env, Binary, BinaryIdx, DistGraph, DistResult, GenericBuildStep,
+};
+
+/// Where a build's component came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentSource {
+    /// A package in the Brewfile
+    Homebrew,
+    /// A package in `pkg-config-deps`
+    PkgConfig,
+    /// A file in `verify-inputs`
+    Input,
+}
+
+impl ComponentSource {
+    /// How the source is described in SBOMs
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            ComponentSource::Homebrew => "homebrew",
+            ComponentSource::PkgConfig => "pkg-config",
+            ComponentSource::Input => "verify-inputs",
+        }
+    }
+}
+
+/// Something a build used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Where we know about it from
+    pub source: ComponentSource,
+    /// Its name (a file's is its path)
+    pub name: String,
+    /// Its version, if we know it
+    pub version: Option<String>,
+    /// Its sha256, if we know it
+    pub sha256: Option<String>,
+    /// Where it comes from, if we know
+    pub url: Option<String>,
+}
+
+/// The binary an SBOM is for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomSubject {
+    /// The binary's name
+    pub name: String,
+    /// The version of the app it's part of, if we know it
+    pub version: Option<String>,
+    /// The target it was built for
+    pub target: String,
+}
+
+/// The packages a Brewfile installs
+pub fn parse_brewfile(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("brew "))
+        .filter_map(|rest| {
+            let rest = rest.trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let name = rest[1..].split(quote).next()?;
+            Some(name.to_owned())
+        })
+        .collect()
+}
+
+/// The Brewfile's packages, at the versions `brew` has installed
+pub fn brew_components(brew: &str, brewfile: &Utf8Path) -> DistResult<Vec<Component>> {
+    let contents = std::fs::read_to_string(brewfile)?;
+    let mut components = vec![];
+    for name in parse_brewfile(&contents) {
+        let output = Cmd::new(brew, "get installed version of brew package")
+            .arg("list")
+            .arg("--versions")
+            .arg(&name)
+            .check(false)
+            .log(None)
+            .output()?;
+        // Prints the package's name, then each version that's installed
+        let version = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .nth(1)
+            .map(|version| version.to_owned());
+        components.push(Component {
+            source: ComponentSource::Homebrew,
+            name,
+            version,
+            sha256: None,
+            url: None,
+        });
+    }
+    Ok(components)
+}
+
+/// The `pkg-config-deps`, at the versions pkg-config reports
+pub(crate) fn pkg_config_components(packages: &[String]) -> DistResult<Vec<Component>> {
+    let pkg_config = env::var("PKG_CONFIG").unwrap_or("pkg-config".to_owned());
+    let mut components = vec![];
+    for name in packages {
+        let output = Cmd::new(&pkg_config, "get version of pkg-config package")
+            .arg("--modversion")
+            .arg(name)
+            .check(false)
+            .log(None)
+            .output()?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        components.push(Component {
+            source: ComponentSource::PkgConfig,
+            name: name.clone(),
+            version: Some(version).filter(|version| !version.is_empty()),
+            sha256: None,
+            url: None,
+        });
+    }
+    Ok(components)
+}
+
+/// Everything cargo-dist knows a step's build used
+pub(crate) fn build_components(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+) -> DistResult<Vec<Component>> {
+    let mut components = vec![];
+    if let Some(brew) = &dist_graph.tools.brew {
+        if uses_brew(dist_graph, Some(step)) && brew_env(dist_graph)?.is_some() {
+            components.extend(brew_components(&brew.cmd, Utf8Path::new("Brewfile"))?);
+        }
+    }
+    if let Some(packages) = &dist_graph.generic_build.pkg_config_deps {
+        components.extend(pkg_config_components(packages)?);
+    }
+    for input in dist_graph.generic_build.verify_inputs.iter().flatten() {
+        components.push(Component {
+            source: ComponentSource::Input,
+            name: input.path.to_string(),
+            version: None,
+            sha256: Some(input.sha256.clone()),
+            url: input.url.clone(),
+        });
+    }
+    Ok(components)
+}
+
+/// The file name of an SBOM for `subject`
+pub fn sbom_file_name(format: SbomFormat, subject: &SbomSubject) -> String {
+    let ext = match format {
+        SbomFormat::Cyclonedx => "cdx.json",
+        SbomFormat::Spdx => "spdx.json",
+    };
+    format!("{}-{}.{ext}", subject.name, subject.target)
+}
+
+/// Write an SBOM for each of the binaries a step built, returning where they were written
+pub(crate) fn write_sboms(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    binaries: &[BinaryIdx],
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let Some(format) = dist_graph.generic_build.sbom else {
+        return Ok(vec![]);
+    };
+    let components = build_components(dist_graph, step)?;
+    let created = reproducible::output_mtime(dist_graph)?.unwrap_or_else(SystemTime::now);
+    let timestamp = utc_timestamp(
+        created
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    );
+    let mut written = vec![];
+    for binary_idx in binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        let subject = SbomSubject {
+            name: binary.name.clone(),
+            version: app_version(dist_graph, binary),
+            target: binary.target.clone(),
+        };
+        let contents = match format {
+            SbomFormat::Cyclonedx => render_cyclonedx(&subject, &components, &timestamp),
+            SbomFormat::Spdx => render_spdx(&subject, &components, &timestamp),
+        };
+        let path = dist_graph.dist_dir.join(sbom_file_name(format, &subject));
+        axoasset::LocalAsset::write_new_all(&contents, &path)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// The version of the release a binary is part of, if it's part of one
+fn app_version(dist_graph: &DistGraph, binary: &Binary) -> Option<String> {
+    dist_graph
+        .releases
+        .iter()
+        .find(|release| {
+            release
+                .bins
+                .iter()
+                .any(|(pkg_idx, name)| *pkg_idx == binary.pkg_idx && *name == binary.name)
+        })
+        .map(|release| release.version.to_string())
+}
+
+/// Seconds since the unix epoch, as an RFC 3339 UTC timestamp
+pub fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// A CycloneDX 1.5 BOM
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDx {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: CdxMetadata,
+    components: Vec<CdxComponent>,
+    dependencies: Vec<CdxDependency>,
+}
+
+/// What a CycloneDX BOM is about
+#[derive(Serialize)]
+struct CdxMetadata {
+    timestamp: String,
+    component: CdxComponent,
+}
+
+/// A CycloneDX component
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CdxComponent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<CdxHash>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<CdxReference>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<CdxProperty>,
+}
+
+/// A CycloneDX hash
+#[derive(Serialize)]
+struct CdxHash {
+    alg: &'static str,
+    content: String,
+}
+
+/// A CycloneDX external reference
+#[derive(Serialize)]
+struct CdxReference {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    url: String,
+}
+
+/// A CycloneDX property
+#[derive(Serialize)]
+struct CdxProperty {
+    name: &'static str,
+    value: &'static str,
+}
+
+/// A CycloneDX dependency
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CdxDependency {
+    #[serde(rename = "ref")]
+    bom_ref: String,
+    depends_on: Vec<String>,
+}
+
+/// Render a CycloneDX SBOM for `subject`
+pub fn render_cyclonedx(
+    subject: &SbomSubject,
+    components: &[Component],
+    timestamp: &str,
+) -> String {
+    let subject_ref = format!("{}-{}", subject.name, subject.target);
+    let components = components
+        .iter()
+        .enumerate()
+        .map(|(idx, component)| CdxComponent {
+            kind: match component.source {
+                ComponentSource::Input => "file",
+                ComponentSource::Homebrew | ComponentSource::PkgConfig => "library",
+            },
+            bom_ref: format!("component-{idx}"),
+            name: component.name.clone(),
+            version: component.version.clone(),
+            hashes: component
+                .sha256
+                .iter()
+                .map(|sha256| CdxHash {
+                    alg: "SHA-256",
+                    content: sha256.clone(),
+                })
+                .collect(),
+            external_references: component
+                .url
+                .iter()
+                .map(|url| CdxReference {
+                    kind: "distribution",
+                    url: url.clone(),
+                })
+                .collect(),
+            properties: vec![CdxProperty {
+                name: "cargo-dist:source",
+                value: component.source.as_str(),
+            }],
+        })
+        .collect::<Vec<_>>();
+    let bom = CycloneDx {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        version: 1,
+        metadata: CdxMetadata {
+            timestamp: timestamp.to_owned(),
+            component: CdxComponent {
+                kind: "application",
+                bom_ref: subject_ref.clone(),
+                name: subject.name.clone(),
+                version: subject.version.clone(),
+                hashes: vec![],
+                external_references: vec![],
+                properties: vec![],
+            },
+        },
+        dependencies: vec![CdxDependency {
+            bom_ref: subject_ref,
+            depends_on: components
+                .iter()
+                .map(|component| component.bom_ref.clone())
+                .collect(),
+        }],
+        components,
+    };
+    serde_json::to_string_pretty(&bom).expect("serde_json failed")
+}
+
+/// An SPDX 2.3 document
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Spdx {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+/// Who made an SPDX document, and when
+#[derive(Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+/// An SPDX package
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version_info: Option<String>,
+    download_location: String,
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<SpdxChecksum>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+/// An SPDX checksum
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+/// An SPDX relationship
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+/// What SPDX says when it doesn't know something
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Render an SPDX SBOM for `subject`
+pub fn render_spdx(subject: &SbomSubject, components: &[Component], timestamp: &str) -> String {
+    let name = format!("{}-{}", subject.name, subject.target);
+    let subject_id = "SPDXRef-Package-subject".to_owned();
+    let mut packages = vec![SpdxPackage {
+        name: subject.name.clone(),
+        spdx_id: subject_id.clone(),
+        version_info: subject.version.clone(),
+        download_location: NOASSERTION.to_owned(),
+        files_analyzed: false,
+        checksums: vec![],
+        comment: None,
+    }];
+    let mut relationships = vec![SpdxRelationship {
+        spdx_element_id: "SPDXRef-DOCUMENT".to_owned(),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: subject_id.clone(),
+    }];
+    // The namespace has to be unique to what's in the document, but stable for reproducible builds
+    let mut hasher = Sha256::new();
+    hasher.update(&name);
+    for (idx, component) in components.iter().enumerate() {
+        let spdx_id = format!("SPDXRef-Package-{idx}");
+        hasher.update(&component.name);
+        hasher.update(component.version.as_deref().unwrap_or_default());
+        packages.push(SpdxPackage {
+            name: component.name.clone(),
+            spdx_id: spdx_id.clone(),
+            version_info: component.version.clone(),
+            download_location: component
+                .url
+                .clone()
+                .unwrap_or_else(|| NOASSERTION.to_owned()),
+            files_analyzed: false,
+            checksums: component
+                .sha256
+                .iter()
+                .map(|sha256| SpdxChecksum {
+                    algorithm: "SHA256",
+                    checksum_value: sha256.clone(),
+                })
+                .collect(),
+            comment: Some(format!("from {}", component.source.as_str())),
+        });
+        relationships.push(SpdxRelationship {
+            spdx_element_id: subject_id.clone(),
+            relationship_type: "DEPENDS_ON",
+            related_spdx_element: spdx_id,
+        });
+    }
+    let digest = hasher.finalize();
+    let mut hash = String::new();
+    for byte in &digest[..8] {
+        hash.push_str(&format!("{byte:02x}"));
+    }
+    let document = Spdx {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        document_namespace: format!("https://spdx.org/spdxdocs/{name}-{hash}"),
+        name,
+        creation_info: SpdxCreationInfo {
+            created: timestamp.to_owned(),
+            creators: vec![format!("Tool: cargo-dist-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        packages,
+        relationships,
+    };
+    serde_json::to_string_pretty(&document).expect("serde_json failed")
+}
@@ -0,0 +1,76 @@
+//! Catching build commands that are scripts which can't be run
+//!
+//! When `build-command` starts with the path of a script, a missing executable
+//! bit or shebang only gets a cryptic "permission denied" or "exec format
+//! error" from the OS. So before running one, we check for both ourselves, and
+//! say how to fix it. Only programs given as paths (with a `/` in them) are
+//! checked; bare names are looked up on PATH like any other tool.
+//!
+//! Windows decides how to run things by their extension instead, so there's
+//! nothing to check there.
+
+use camino::Utf8Path;
+
+use crate::{DistError, DistResult};
+
+/// How executables (rather than scripts) start: ELF, and the Mach-O variants
+const EXECUTABLE_MAGIC: &[&[u8]] = &[
+    b"\x7fELF",
+    &[0xfe, 0xed, 0xfa, 0xce],
+    &[0xfe, 0xed, 0xfa, 0xcf],
+    &[0xce, 0xfa, 0xed, 0xfe],
+    &[0xcf, 0xfa, 0xed, 0xfe],
+    &[0xca, 0xfe, 0xba, 0xbe],
+];
+
+/// Check that `program`, if it's a path to a file, is something we can run
+///
+/// Relative paths are relative to `dir`, where the build runs.
+pub(crate) fn check_build_program(program: &str, dir: &Utf8Path) -> DistResult<()> {
+    if cfg!(windows) || !program.contains('/') {
+        return Ok(());
+    }
+    let path = dir.join(program);
+    // If it's not there, the OS's error says as much
+    if !path.is_file() {
+        return Ok(());
+    }
+    if !is_executable(&path)? {
+        return Err(DistError::BuildScriptNotExecutable {
+            path: program.to_owned(),
+        });
+    }
+    let header = read_header(&path)?;
+    let is_executable_format = EXECUTABLE_MAGIC
+        .iter()
+        .any(|magic| header.starts_with(magic));
+    if !is_executable_format && !header.starts_with(b"#!") {
+        return Err(DistError::BuildScriptMissingShebang {
+            path: program.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// The first few bytes of a file, enough to tell what it is
+fn read_header(path: &Utf8Path) -> DistResult<Vec<u8>> {
+    use std::io::Read;
+
+    let mut header = vec![];
+    std::fs::File::open(path)?
+        .take(4)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Utf8Path) -> DistResult<bool> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(path.metadata()?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Utf8Path) -> DistResult<bool> {
+    Ok(true)
+}
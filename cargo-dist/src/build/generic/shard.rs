@@ -0,0 +1,42 @@
+//! Splitting the generic builds across several CI jobs
+//!
+//! A release with many targets can take a long time to build on one machine.
+//! `cargo dist build --shard 2/5` plans every build as usual, then only runs
+//! the second of five shards of them, so five jobs can share the work. The
+//! targets being built are sorted and dealt out to the shards in turn (the
+//! first to shard 1, the second to shard 2, and so on, wrapping around), and
+//! each build goes to its target's shard. Every job plans the same builds, so
+//! the shards never overlap and no target is left out; and all of a target's
+//! builds (and so anything one depends on) end up in the same shard.
+//!
+//! Shards can be empty, if there are more shards than targets.
+
+use crate::{config::Shard, GenericBuildStep, SortedSet, TargetTriple};
+
+/// The shard (counting from 1) each target's builds go to, out of `count`
+pub fn shard_targets<'a>(
+    targets: impl IntoIterator<Item = &'a TargetTriple>,
+    count: usize,
+) -> Vec<(TargetTriple, usize)> {
+    targets
+        .into_iter()
+        .cloned()
+        .collect::<SortedSet<_>>()
+        .into_iter()
+        .enumerate()
+        .map(|(idx, target)| (target, idx % count + 1))
+        .collect()
+}
+
+/// Only keep the builds in the given shard, in the order they were planned
+pub(crate) fn select_shard(builds: Vec<GenericBuildStep>, shard: Shard) -> Vec<GenericBuildStep> {
+    let in_shard = shard_targets(builds.iter().map(|step| &step.target_triple), shard.count)
+        .into_iter()
+        .filter(|(_, idx)| *idx == shard.index)
+        .map(|(target, _)| target)
+        .collect::<SortedSet<_>>();
+    builds
+        .into_iter()
+        .filter(|step| in_shard.contains(&step.target_triple))
+        .collect()
+}
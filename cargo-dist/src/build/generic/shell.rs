@@ -0,0 +1,210 @@
+//! Running generic builds through a (login) shell
+//!
+//! Builds that rely on things like version managers need the environment a
+//! login shell sets up, so we can reassemble the build command into a single
+//! shell command string. This requires quoting every argument properly, so
+//! that the shell sees exactly the argv we would have exec'd directly.
+
+use std::ffi::OsStr;
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{DistError, DistGraph, DistResult};
+
+/// The shell we use on unix if none is configured
+const DEFAULT_UNIX_SHELL: &str = "bash";
+/// The shell we use on windows if none is configured
+const DEFAULT_WINDOWS_SHELL: &str = "powershell";
+/// What we run build-script with on unix if no script-shell is configured
+const DEFAULT_UNIX_SCRIPT_SHELL: &[&str] = &["sh", "-c"];
+/// What we run build-script with on windows if no script-shell is configured
+const DEFAULT_WINDOWS_SCRIPT_SHELL: &[&str] = &["cmd", "/C"];
+
+/// Quote a string for a POSIX shell
+pub(crate) fn posix_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_owned();
+    }
+    // Nothing is special inside single quotes, except single quotes themselves,
+    // which we have to end the quoting for and escape
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Quote a string for PowerShell
+pub(crate) fn powershell_quote(arg: &str) -> String {
+    // In single-quoted strings the only special character is the single quote,
+    // which is escaped by doubling it
+    format!("'{}'", arg.replace('\'', "''"))
+}
+
+/// Render a command as a line that can be pasted into a shell to run it again
+///
+/// This includes its working directory and the environment we set for it,
+/// for PowerShell on windows, and a POSIX shell elsewhere.
+pub(crate) fn copy_pasteable(command: &Cmd, windows: bool) -> String {
+    let program = command.get_program().to_string_lossy();
+    let args = command.get_args().map(|arg| arg.to_string_lossy());
+    let dir = command
+        .get_current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned());
+    let envs = command
+        .get_envs()
+        .map(|(key, value)| {
+            (
+                key.to_string_lossy().into_owned(),
+                value.map(|value| value.to_string_lossy().into_owned()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut words = vec![];
+    if windows {
+        if let Some(dir) = dir {
+            words.push(format!("Set-Location {};", powershell_quote(&dir)));
+        }
+        for (key, value) in envs {
+            match value {
+                Some(value) => words.push(format!("$env:{key} = {};", powershell_quote(&value))),
+                None => words.push(format!("Remove-Item Env:{key};")),
+            }
+        }
+        words.push(format!("& {}", powershell_quote(&program)));
+        words.extend(args.map(|arg| powershell_quote(&arg)));
+    } else {
+        if let Some(dir) = dir {
+            words.push(format!("cd {} &&", posix_quote(&dir)));
+        }
+        // Unsetting variables needs env, which takes assignments too
+        if envs.iter().any(|(_, value)| value.is_none()) {
+            words.push("env".to_owned());
+        }
+        for (key, value) in &envs {
+            if value.is_none() {
+                words.push(format!("-u {}", posix_quote(key)));
+            }
+        }
+        for (key, value) in &envs {
+            if let Some(value) = value {
+                words.push(format!("{key}={}", posix_quote(value)));
+            }
+        }
+        words.push(posix_quote(&program));
+        words.extend(args.map(|arg| posix_quote(&arg)));
+    }
+    words.join(" ")
+}
+
+/// Rewrite a build command to run in a login shell
+///
+/// On unix this is `bash -l -c '<cmd>'` (with `shell` replacing bash),
+/// and on windows `powershell -NoLogo -NonInteractive -Command "& <cmd>"`,
+/// which loads the user's PowerShell profile.
+pub(crate) fn login_shell_command(
+    shell: Option<&str>,
+    windows: bool,
+    command_string: &[String],
+) -> Vec<String> {
+    if windows {
+        let shell = shell.unwrap_or(DEFAULT_WINDOWS_SHELL);
+        let invocation = command_string
+            .iter()
+            .map(|arg| powershell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        // `&` is needed to invoke a quoted command, and without the explicit exit
+        // PowerShell only reports whether the command succeeded, not its exit code
+        vec![
+            shell.to_owned(),
+            "-NoLogo".to_owned(),
+            "-NonInteractive".to_owned(),
+            "-Command".to_owned(),
+            format!("& {invocation}; exit $LASTEXITCODE"),
+        ]
+    } else {
+        let shell = shell.unwrap_or(DEFAULT_UNIX_SHELL);
+        let invocation = command_string
+            .iter()
+            .map(|arg| posix_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        vec![
+            shell.to_owned(),
+            "-l".to_owned(),
+            "-c".to_owned(),
+            invocation,
+        ]
+    }
+}
+
+/// The command that runs a build-script with the script-shell
+///
+/// Unlike [`login_shell_command`][], the script is passed along as-is, since
+/// it's already a command line for the shell.
+pub(crate) fn script_command(
+    script_shell: Option<&[String]>,
+    windows: bool,
+    script: &str,
+) -> Vec<String> {
+    let mut command = match script_shell {
+        Some(script_shell) => script_shell.to_owned(),
+        None if windows => DEFAULT_WINDOWS_SCRIPT_SHELL
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        None => DEFAULT_UNIX_SCRIPT_SHELL
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    };
+    command.push(script.to_owned());
+    command
+}
+
+/// Check the script-shell exists before we try to build with it
+pub(crate) fn validate_script_shell(dist_graph: &DistGraph) -> DistResult<()> {
+    let config = &dist_graph.generic_build;
+    if config.build_script.is_none() {
+        return Ok(());
+    }
+    let command = script_command(config.script_shell.as_deref(), cfg!(windows), "");
+    let program = &command[0];
+    // With no script-shell at all, the "program" is the (empty) script
+    if command.len() < 2 || find_program(program).is_none() {
+        return Err(DistError::ScriptShellMissing {
+            shell: program.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Where a program would be run from, looking on the PATH if it's a bare name
+pub(crate) fn find_program(program: &str) -> Option<Utf8PathBuf> {
+    find_program_in(program, &std::env::var_os("PATH")?)
+}
+
+/// Where a program would be run from, looking in `search_path` (like PATH) if it's a bare name
+pub(crate) fn find_program_in(program: &str, search_path: &OsStr) -> Option<Utf8PathBuf> {
+    let path = Utf8Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_owned());
+    }
+    if program.is_empty() {
+        return None;
+    }
+    // Windows finds programs without their extension
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat", ".com"]
+    } else {
+        &[""]
+    };
+    std::env::split_paths(search_path)
+        .filter_map(|dir| Utf8PathBuf::from_path_buf(dir).ok())
+        .flat_map(|dir| {
+            extensions
+                .iter()
+                .map(move |ext| dir.join(format!("{program}{ext}")))
+        })
+        .find(|candidate| candidate.is_file())
+}
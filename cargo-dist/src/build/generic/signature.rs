@@ -0,0 +1,79 @@
+//! Fingerprinting the inputs of a generic build
+//!
+//! With `build-signature` each build's provenance gets a short hash of the
+//! commands we ran and the environment variables we set for them, so tooling
+//! downstream can tell when an artifact was built with different inputs than
+//! it expected. Only variables we set are included (not the whole inherited
+//! environment), minus ones that change from run to run without changing the
+//! build, and they're sorted so the order we happened to set them in doesn't
+//! matter either.
+//!
+//! Commands are signed before they're wrapped to run in a container, so the
+//! container engine's host paths (which vary by checkout) don't count either.
+
+use std::ffi::OsStr;
+
+use axoprocess::Cmd;
+use sha2::{Digest, Sha256};
+
+use crate::DistGraph;
+
+/// How many hex digits of the hash to keep
+const SIGNATURE_LEN: usize = 16;
+
+/// Variables that differ between runs (or terminals) without affecting what gets built
+const VOLATILE_VARS: &[&str] = &[
+    "PWD",
+    "OLDPWD",
+    "SHLVL",
+    "_",
+    "CLICOLOR_FORCE",
+    "FORCE_COLOR",
+];
+
+/// The signature to record for a build, if configured to
+pub(crate) fn recorded_signature(dist_graph: &DistGraph, signature: String) -> Option<String> {
+    dist_graph
+        .generic_build
+        .build_signature
+        .unwrap_or(false)
+        .then_some(signature)
+}
+
+/// Hash the given commands (in order) and the environment variables they set
+pub fn signature_of(commands: &[&Cmd]) -> String {
+    let mut hasher = Sha256::new();
+    for command in commands {
+        // Everything is NUL-terminated, so ["a b"] and ["a", "b"] differ
+        hasher.update(b"command\0");
+        hash_str(&mut hasher, command.get_program());
+        for arg in command.get_args() {
+            hash_str(&mut hasher, arg);
+        }
+        let mut envs = command
+            .get_envs()
+            .filter(|(key, _)| !VOLATILE_VARS.iter().any(|volatile| key == volatile))
+            .collect::<Vec<_>>();
+        envs.sort();
+        hasher.update(b"env\0");
+        for (key, value) in envs {
+            hash_str(&mut hasher, key);
+            match value {
+                Some(value) => {
+                    hasher.update(b"=");
+                    hash_str(&mut hasher, value);
+                }
+                // Removing a variable is an input too
+                None => hasher.update(b"-\0"),
+            }
+        }
+    }
+    let mut signature = format!("{:x}", hasher.finalize());
+    signature.truncate(SIGNATURE_LEN);
+    signature
+}
+
+fn hash_str(hasher: &mut Sha256, value: &OsStr) {
+    hasher.update(value.to_string_lossy().as_bytes());
+    hasher.update(b"\0");
+}
@@ -0,0 +1,79 @@
+//! Sanity-checking the sizes of the binaries builds produce
+//!
+//! A "binary" of a few hundred bytes almost always means something went wrong
+//! (a link that silently did nothing, a script left where the binary should be),
+//! and one of gigabytes usually means the wrong thing got shipped. With
+//! `binary-sizes`, each binary can be given a `min-size` and/or `max-size`,
+//! like `"100KB"` or `"1.5GiB"`, which it's checked against once it's built.
+
+use camino::Utf8Path;
+
+use crate::{Binary, DistError, DistGraph, DistResult};
+
+/// Parse a size like `"512"`, `"100KB"` or `"1.5 MiB"` into bytes
+///
+/// `K`/`KB`, `M`/`MB` and `G`/`GB` are powers of 1000, and `KiB`, `MiB` and
+/// `GiB` powers of 1024. Units are case-insensitive.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1u64,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1000 * 1000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "gib" => 1 << 30,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Check a binary a build produced is within its configured size range, if it has one
+pub(crate) fn check_binary_size(
+    dist_graph: &DistGraph,
+    binary: &Binary,
+    path: &Utf8Path,
+) -> DistResult<()> {
+    let Some(range) = dist_graph
+        .generic_build
+        .binary_sizes
+        .as_ref()
+        .and_then(|sizes| sizes.get(&binary.name))
+    else {
+        return Ok(());
+    };
+    let bound = |size: &Option<String>| {
+        size.as_deref()
+            .map(|size| {
+                parse_size(size).ok_or_else(|| DistError::InvalidBinarySize {
+                    binary: binary.name.clone(),
+                    size: size.to_owned(),
+                })
+            })
+            .transpose()
+    };
+    let min = bound(&range.min_size)?;
+    let max = bound(&range.max_size)?;
+
+    let size = path.metadata()?.len();
+    let out_of_range = |problem: &str, limit: u64| DistError::BinarySizeOutOfRange {
+        binary: binary.name.clone(),
+        target: binary.target.clone(),
+        size,
+        problem: problem.to_owned(),
+        limit,
+    };
+    if let Some(min) = min.filter(|min| size < *min) {
+        return Err(out_of_range("smaller than the minimum", min));
+    }
+    if let Some(max) = max.filter(|max| size > *max) {
+        return Err(out_of_range("larger than the maximum", max));
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Smoke-testing archives the way users will get them
+//!
+//! With `smoke-test` on, once an archive of binaries has been made, it's
+//! extracted to a temporary directory, and each binary in it is run from there
+//! with `--version` (or `smoke-test-args`), which must exit zero. Anything that
+//! only worked because of where the build left it, like an rpath pointing back
+//! into the build tree or a library that never made it into the archive, fails
+//! the build here instead of on users' machines.
+//!
+//! Binaries for a target other than the host can't be run, so their archives
+//! are skipped.
+
+use std::fs::File;
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::info;
+
+use super::sanitizers::sanitizer_env;
+use crate::{
+    config::{CompressionImpl, ZipStyle},
+    tasks::ArtifactKind,
+    DistError, DistGraph, DistResult,
+};
+
+/// What binaries are run with, unless `smoke-test-args` says otherwise
+const DEFAULT_ARGS: &[&str] = &["--version"];
+
+/// Smoke-test the archive at `archive_path`, if it's one of binaries and `smoke-test` is on
+pub(crate) fn smoke_test_artifact(
+    dist_graph: &DistGraph,
+    archive_path: &Utf8Path,
+) -> DistResult<()> {
+    if !dist_graph.generic_build.smoke_test.unwrap_or(false) {
+        return Ok(());
+    }
+    let Some(artifact) = dist_graph.artifacts.iter().find(|artifact| {
+        artifact.file_path == archive_path
+            && matches!(artifact.kind, ArtifactKind::ExecutableZip(_))
+    }) else {
+        return Ok(());
+    };
+    let Some(archive) = &artifact.archive else {
+        return Ok(());
+    };
+    let host = &dist_graph.tools.cargo.host_target;
+    if artifact.target_triples.iter().any(|target| target != host) {
+        info!("not smoke-testing {archive_path}, its binaries can't run on {host}");
+        return Ok(());
+    }
+    let binaries = artifact
+        .required_binaries
+        .values()
+        .filter_map(|dest| dest.strip_prefix(&archive.dir_path).ok())
+        .map(|path| match &archive.with_root {
+            Some(root) => root.join(path),
+            None => path.to_owned(),
+        })
+        .collect::<Vec<_>>();
+    let args = match &dist_graph.generic_build.smoke_test_args {
+        Some(args) => args.clone(),
+        None => DEFAULT_ARGS.iter().map(|arg| arg.to_string()).collect(),
+    };
+    let env = sanitizer_env(&dist_graph.sanitizers);
+    smoke_test_archive(archive_path, &archive.zip_style, &binaries, &args, &env)
+}
+
+/// Extract an archive, and run each of `binaries` (paths within it) with `args` and `env`
+pub fn smoke_test_archive(
+    archive_path: &Utf8Path,
+    zip_style: &ZipStyle,
+    binaries: &[Utf8PathBuf],
+    args: &[String],
+    env: &[(String, String)],
+) -> DistResult<()> {
+    let dir = temp_dir::TempDir::new()?;
+    let dest = Utf8PathBuf::from_path_buf(dir.path().to_owned()).expect("temp dir isn't utf8!?");
+    extract_archive(archive_path, zip_style, &dest)?;
+    for binary in binaries {
+        let path = dest.join(binary);
+        let mut command = Cmd::new(&path, format!("smoke test {binary}"));
+        for arg in args {
+            command.arg(arg);
+        }
+        for (key, value) in env {
+            command.env(key, value);
+        }
+        let output = command
+            .current_dir(&dest)
+            .check(false)
+            .output()
+            .map_err(|e| DistError::SmokeTestFailed {
+                binary: binary.clone(),
+                archive: archive_path.to_owned(),
+                status: "couldn't be run".to_owned(),
+                output: e.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(DistError::SmokeTestFailed {
+                binary: binary.clone(),
+                archive: archive_path.to_owned(),
+                status: output.status.to_string(),
+                output: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+        info!("smoke test of {binary} from {archive_path} passed");
+    }
+    Ok(())
+}
+
+/// Unpack an archive into `dest`
+pub fn extract_archive(
+    archive_path: &Utf8Path,
+    zip_style: &ZipStyle,
+    dest: &Utf8Path,
+) -> DistResult<()> {
+    let file = File::open(archive_path)?;
+    match zip_style {
+        ZipStyle::Zip => zip::ZipArchive::new(file)?.extract(dest)?,
+        ZipStyle::Tar(CompressionImpl::Gzip) => {
+            tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(dest)?
+        }
+        ZipStyle::Tar(CompressionImpl::Xzip) => {
+            tar::Archive::new(xz2::read::XzDecoder::new(file)).unpack(dest)?
+        }
+        ZipStyle::Tar(CompressionImpl::Zstd) => {
+            tar::Archive::new(zstd::Decoder::new(file)?).unpack(dest)?
+        }
+        ZipStyle::TempDir => {}
+    }
+    Ok(())
+}
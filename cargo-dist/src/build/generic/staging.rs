@@ -0,0 +1,131 @@
+//! Staging generic build outputs before they're published to the dist dir
+//!
+//! Normally builds copy their outputs straight into the dist dir. With
+//! `cargo dist build --staging-dir <dir>`, each generic build copies them into
+//! the staging dir instead (at the same paths relative to it), does all its
+//! post-processing there (debuginfo splitting, rpath rewriting, layouts), and
+//! only once the build has succeeded are they copied to the dist dir. So the
+//! dist dir only ever has the outputs of builds that finished, and whatever
+//! is in the staging dir is left there to be inspected.
+//!
+//! `--target-staging` does the same, but each target gets its own staging dir
+//! at `<dist-dir>/.staging/<target>/`, so it's easy to find what a particular
+//! build got up to. These are removed once their build succeeds, so the only
+//! ones left are those of builds that failed. (Extra builds have no target, and
+//! share `.staging/extra/`.)
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{copy_file, DistError, DistGraph, DistResult};
+
+/// The directory (under dist_dir) that `--target-staging` stages builds in
+pub const STAGING_DIR: &str = ".staging";
+
+/// What extra builds are staged as, since they have no target
+pub const EXTRA_BUILDS: &str = "extra";
+
+/// The staging dir for builds of `target`, if builds are staged
+pub fn staging_dir(dist_graph: &DistGraph, target: &str) -> Option<Utf8PathBuf> {
+    if let Some(staging_dir) = &dist_graph.staging_dir {
+        return Some(staging_dir.clone());
+    }
+    dist_graph
+        .target_staging
+        .then(|| dist_graph.dist_dir.join(STAGING_DIR).join(target))
+}
+
+/// Where builds for `target` should do their intermediate work (the staging dir, or else the dist dir)
+pub fn work_dir(dist_graph: &DistGraph, target: &str) -> Utf8PathBuf {
+    staging_dir(dist_graph, target).unwrap_or_else(|| dist_graph.dist_dir.clone())
+}
+
+/// Where a build for `target` should put something that's meant to end up at `path` in the dist dir
+pub fn staged_path(dist_graph: &DistGraph, target: &str, path: &Utf8Path) -> Utf8PathBuf {
+    let Some(staging_dir) = staging_dir(dist_graph, target) else {
+        return path.to_owned();
+    };
+    match path.strip_prefix(&dist_graph.dist_dir) {
+        Ok(relative) => staging_dir.join(relative),
+        Err(_) => path.to_owned(),
+    }
+}
+
+/// Where something is relative to the dist dir, whether it's been promoted yet or not
+pub fn dist_relative<'a>(dist_graph: &DistGraph, target: &str, path: &'a Utf8Path) -> &'a Utf8Path {
+    let staged = staging_dir(dist_graph, target)
+        .and_then(|staging_dir| path.strip_prefix(&staging_dir).ok());
+    staged
+        .or_else(|| path.strip_prefix(&dist_graph.dist_dir).ok())
+        .unwrap_or(path)
+}
+
+/// Copy staged files to their places in the dist dir, returning where they went
+///
+/// Without a staging dir, everything is already in place.
+pub(crate) fn promote(
+    dist_graph: &DistGraph,
+    target: &str,
+    staged: &[Utf8PathBuf],
+) -> DistResult<Vec<Utf8PathBuf>> {
+    let Some(staging_dir) = staging_dir(dist_graph, target) else {
+        return Ok(staged.to_vec());
+    };
+    let mut promoted = Vec::with_capacity(staged.len());
+    for path in staged {
+        let Ok(relative) = path.strip_prefix(&staging_dir) else {
+            promoted.push(path.clone());
+            continue;
+        };
+        let dest = dist_graph.dist_dir.join(relative);
+        promote_file(&staging_dir, &dist_graph.dist_dir, path)?;
+        promoted.push(dest);
+    }
+    Ok(promoted)
+}
+
+/// Remove the staging dir of a build of `target` that succeeded, if it has one of its own
+///
+/// A `--staging-dir` is shared by every build, so it's left alone.
+pub(crate) fn remove_target_staging(dist_graph: &DistGraph, target: &str) -> DistResult<()> {
+    if dist_graph.staging_dir.is_some() || !dist_graph.target_staging {
+        return Ok(());
+    }
+    let staging_dir = dist_graph.dist_dir.join(STAGING_DIR).join(target);
+    if staging_dir.exists() {
+        std::fs::remove_dir_all(&staging_dir)?;
+    }
+    Ok(())
+}
+
+/// Copy a staged file into place
+///
+/// Symlinks stay symlinks, and whatever they point to (in the staging dir)
+/// gets promoted along with them.
+fn promote_file(staging_dir: &Utf8Path, dist_dir: &Utf8Path, path: &Utf8Path) -> DistResult<()> {
+    // How long of a chain of staged symlinks we'll follow
+    const MAX_LINKS: usize = 40;
+
+    let mut path = path.to_owned();
+    for _ in 0..MAX_LINKS {
+        let Ok(relative) = path.strip_prefix(staging_dir) else {
+            return Ok(());
+        };
+        let dest = dist_dir.join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if dest.symlink_metadata().is_ok() {
+            std::fs::remove_file(&dest)?;
+        }
+        #[cfg(unix)]
+        if path.is_symlink() {
+            // Staged symlinks are relative to where they are, so they work here too
+            let target = path.read_link_utf8()?;
+            std::os::unix::fs::symlink(&target, &dest)?;
+            path = path.parent().unwrap_or(Utf8Path::new("")).join(target);
+            continue;
+        }
+        return copy_file(&path, &dest);
+    }
+    Err(DistError::SymlinkChainTooLong { path })
+}
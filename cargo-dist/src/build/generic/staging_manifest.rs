@@ -0,0 +1,89 @@
+//! A running list of artifacts, for uploading them as soon as they're built
+//!
+//! CI that uploads each target's artifacts once they're done can't wait for
+//! the dist-manifest, which is only written at the very end. With
+//! `staging-manifest`, every generic build appends a line to
+//! `staging-manifest.jsonl` in the dist dir for each artifact it copied, right
+//! after copying it:
+//!
+//! ```json
+//! {"path":"app-x86_64-unknown-linux-gnu/app","target":"x86_64-unknown-linux-gnu","sha256":"...","size":1234}
+//! ```
+//!
+//! Paths are relative to the dist dir. Extra builds have a `null` target. The
+//! file is emptied at the start of every `cargo dist build`, so a watcher
+//! tailing it only sees this run's artifacts, and a line is only written once
+//! the artifact is complete.
+
+use std::{fs::OpenOptions, io::Write};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ChecksumStyle, generate_checksum, DistGraph, DistResult};
+
+/// What the staging manifest is called in the dist dir
+pub const STAGING_MANIFEST: &str = "staging-manifest.jsonl";
+
+/// A line of the staging manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedArtifact {
+    /// Where it is, relative to the dist dir
+    pub path: Utf8PathBuf,
+    /// The target it was built for, if any
+    pub target: Option<String>,
+    /// Its sha256 digest
+    pub sha256: String,
+    /// Its size in bytes
+    pub size: u64,
+}
+
+/// Where the staging manifest goes
+pub fn staging_manifest_path(dist_graph: &DistGraph) -> Utf8PathBuf {
+    dist_graph.dist_dir.join(STAGING_MANIFEST)
+}
+
+fn wants_staging_manifest(dist_graph: &DistGraph) -> bool {
+    dist_graph.generic_build.staging_manifest.unwrap_or(false)
+}
+
+/// Empty the staging manifest, for a new run
+pub fn reset_staging_manifest(dist_graph: &DistGraph) -> DistResult<()> {
+    if !wants_staging_manifest(dist_graph) {
+        return Ok(());
+    }
+    std::fs::write(staging_manifest_path(dist_graph), "")?;
+    Ok(())
+}
+
+/// Add what a build for `target` just copied to the staging manifest, if it's enabled
+pub(crate) fn append_staging_manifest<'a>(
+    dist_graph: &DistGraph,
+    target: Option<&str>,
+    paths: impl IntoIterator<Item = &'a Utf8Path>,
+) -> DistResult<()> {
+    if !wants_staging_manifest(dist_graph) {
+        return Ok(());
+    }
+    let mut lines = String::new();
+    for path in paths {
+        let artifact = StagedArtifact {
+            path: path
+                .strip_prefix(&dist_graph.dist_dir)
+                .unwrap_or(path)
+                .to_owned(),
+            target: target.map(|target| target.to_owned()),
+            sha256: generate_checksum(&ChecksumStyle::Sha256, path)?,
+            size: path.metadata()?.len(),
+        };
+        lines.push_str(&serde_json::to_string(&artifact).expect("serde_json failed"));
+        lines.push('\n');
+    }
+    // One write, so a watcher never sees half of a build's lines
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(staging_manifest_path(dist_graph))?;
+    file.write_all(lines.as_bytes())?;
+    Ok(())
+}
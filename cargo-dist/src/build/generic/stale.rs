@@ -0,0 +1,86 @@
+//! Removing stale files from the dist dir
+//!
+//! The dist dir isn't emptied between runs, so artifacts for targets that are
+//! no longer built (or were renamed) stick around, and can end up uploaded
+//! with a release. With `cargo dist build --clean`, before building we remove
+//! everything in the dist dir that isn't one of this run's planned artifacts,
+//! or inside one of their directories. `--clean-dry-run` lists what would go
+//! without removing anything.
+//!
+//! Nothing outside the dist dir is ever removed.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{DistGraph, DistResult};
+
+/// Everything this run plans to put in the dist dir
+pub fn planned_outputs(dist_graph: &DistGraph) -> Vec<Utf8PathBuf> {
+    let artifacts = dist_graph.artifacts.iter().flat_map(|artifact| {
+        let dir = artifact.archive.as_ref().map(|archive| &archive.dir_path);
+        std::iter::once(&artifact.file_path).chain(dir)
+    });
+    let binaries = dist_graph
+        .binaries
+        .iter()
+        .flat_map(|binary| binary.copy_exe_to.iter().chain(&binary.copy_symbols_to));
+    artifacts.chain(binaries).cloned().collect()
+}
+
+/// The files and directories in `dist_dir` that aren't planned, and aren't inside something planned
+///
+/// Directories that only have stale things in them are listed, rather than their contents.
+pub fn stale_files(dist_dir: &Utf8Path, planned: &[Utf8PathBuf]) -> DistResult<Vec<Utf8PathBuf>> {
+    let mut stale = vec![];
+    if dist_dir.is_dir() {
+        find_stale(dist_dir, planned, &mut stale)?;
+    }
+    Ok(stale)
+}
+
+fn find_stale(
+    dir: &Utf8Path,
+    planned: &[Utf8PathBuf],
+    stale: &mut Vec<Utf8PathBuf>,
+) -> DistResult<()> {
+    let mut children = dir
+        .read_dir_utf8()?
+        .map(|entry| entry.map(|entry| entry.path().to_owned()))
+        .collect::<Result<Vec<_>, _>>()?;
+    children.sort();
+    for path in children {
+        if planned.contains(&path) {
+            continue;
+        }
+        let holds_planned = planned.iter().any(|planned| planned.starts_with(&path));
+        if holds_planned && path.is_dir() {
+            find_stale(&path, planned, stale)?;
+        } else {
+            stale.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Remove whatever in the dist dir this run won't produce, or just list it if `dry_run`
+///
+/// Returns what was (or would have been) removed.
+pub fn clean_dist_dir(dist_graph: &DistGraph, dry_run: bool) -> DistResult<Vec<Utf8PathBuf>> {
+    let dist_dir = &dist_graph.dist_dir;
+    let stale = stale_files(dist_dir, &planned_outputs(dist_graph))?;
+    for path in &stale {
+        let relative = path.strip_prefix(dist_dir).unwrap_or(path);
+        if dry_run {
+            eprintln!("would remove {relative}");
+            continue;
+        }
+        eprintln!("removing {relative}");
+        // Only ever delete inside the dist dir
+        assert!(path.starts_with(dist_dir));
+        if path.is_dir() && !path.is_symlink() {
+            std::fs::remove_dir_all(path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(stale)
+}
@@ -0,0 +1,79 @@
+//! Rolling up the results of generic builds
+//!
+//! Each generic build reports on itself as it goes, which is a lot to scroll
+//! back through when there are several targets. We also keep a record of how
+//! each one went, and print it as a table once they're done (or one fails).
+
+use std::time::Instant;
+
+use cargo_dist_schema::BuildSummary;
+
+use crate::{DistResult, GenericBuildStep};
+
+/// Run a generic build, recording how it went in `results`
+///
+/// `build` should return how many files the build copied into the dist dir.
+/// If the build fails, the results so far are printed before the error is returned.
+pub(crate) fn record_build(
+    results: &mut Vec<BuildSummary>,
+    target: &GenericBuildStep,
+    build: impl FnOnce() -> DistResult<usize>,
+) -> DistResult<()> {
+    let start = Instant::now();
+    let result = build();
+    record_result(results, target, start, result)
+}
+
+/// Record how a build that started at `start` went, like [`record_build`][]
+pub(crate) fn record_result(
+    results: &mut Vec<BuildSummary>,
+    target: &GenericBuildStep,
+    start: Instant,
+    result: DistResult<usize>,
+) -> DistResult<()> {
+    results.push(BuildSummary {
+        target_triple: target.target_triple.clone(),
+        succeeded: result.is_ok(),
+        duration_ms: start.elapsed().as_millis() as u64,
+        artifacts: *result.as_ref().unwrap_or(&0),
+    });
+    if result.is_err() {
+        eprint!("{}", render_summary(results));
+    }
+    result.map(|_| ())
+}
+
+/// Render build results as a table
+pub fn render_summary(results: &[BuildSummary]) -> String {
+    const HEADINGS: [&str; 4] = ["target", "result", "time", "artifacts"];
+    let rows = results
+        .iter()
+        .map(|result| {
+            [
+                result.target_triple.clone(),
+                if result.succeeded { "ok" } else { "FAILED" }.to_owned(),
+                format!("{:.1}s", result.duration_ms as f64 / 1000.0),
+                result.artifacts.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut widths = HEADINGS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut output = String::from("generic build summary:\n");
+    let headings = HEADINGS.map(str::to_owned);
+    for row in std::iter::once(&headings).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        output.push_str(&format!("  {}\n", line.trim_end()));
+    }
+    output
+}
@@ -0,0 +1,41 @@
+//! Compressing symbols files
+//!
+//! Symbols are often many times the size of the binaries they describe. With
+//! `compress-symbols` on, each symbols artifact is zstd-compressed as it's
+//! copied to the dist dir, and named `<name>.<ext>.zst`; the binaries are left
+//! alone. Anything that records the symbols' name for debuggers to find, like a
+//! debuglink, still uses the uncompressed name, since that's what the file will
+//! be called once it's unpacked on a symbol server.
+
+use std::fs::File;
+
+use camino::Utf8Path;
+
+use crate::{DistGraph, DistResult};
+
+/// The extension compressed symbols get on top of their usual one
+pub const COMPRESSED_EXT: &str = "zst";
+
+/// Whether symbols should be compressed
+pub(crate) fn wants_compressed_symbols(dist_graph: &DistGraph) -> bool {
+    dist_graph.generic_build.compress_symbols.unwrap_or(false)
+}
+
+/// The name symbols called `name` have once they're compressed
+pub fn compressed_name(name: &str) -> String {
+    format!("{name}.{COMPRESSED_EXT}")
+}
+
+/// The name symbols called `name` had before they were compressed
+pub fn uncompressed_name(name: &str) -> &str {
+    name.strip_suffix(&format!(".{COMPRESSED_EXT}"))
+        .unwrap_or(name)
+}
+
+/// Compress the symbols at `src_path` into `dest_path`, without loading them all into memory
+pub fn compress_symbols(src_path: &Utf8Path, dest_path: &Utf8Path) -> DistResult<()> {
+    let src = File::open(src_path)?;
+    let dest = File::create(dest_path)?;
+    zstd::stream::copy_encode(src, dest, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    Ok(())
+}
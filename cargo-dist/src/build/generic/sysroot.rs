@@ -0,0 +1,166 @@
+//! Building against a sysroot taken from a container image
+//!
+//! To control which glibc a Linux build links against, it can be compiled
+//! against the headers and libraries of an older distro instead of the host's.
+//! `sysroots` maps a target to a container image (and optionally a path within
+//! it): the first build for that target copies that path out of the image into
+//! `cargo-dist-cache/sysroots/` in the target dir, and every build for it gets
+//! `--sysroot=<dir>` in CFLAGS, CXXFLAGS and LDFLAGS. The build itself still
+//! runs on the host.
+//!
+//! Extracted sysroots are kept by image digest, so they're only extracted again
+//! when the image changes. The image is pulled if it isn't already present.
+//!
+//! A sysroot that's already on disk (like one a cross toolchain ships with) can
+//! be given with `sysroot-dirs` instead, which wins over `sysroots`. It's
+//! checked before the target's build runs, since a compiler pointed at a directory
+//! that isn't a sysroot fails in far more confusing ways: it has to exist and
+//! have `usr/include` and `usr/lib` in it.
+
+use axoprocess::Cmd;
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::info;
+
+use super::container;
+use crate::{DistError, DistGraph, DistResult};
+
+/// Where in the image the sysroot is, unless configured
+const DEFAULT_SYSROOT_PATH: &str = "/";
+
+/// What a directory needs to have in it to be a sysroot
+const SYSROOT_SUBDIRS: &[&str] = &["usr/include", "usr/lib"];
+
+/// The directory extracted sysroots are kept in
+pub(crate) fn sysroots_dir(dist_graph: &DistGraph) -> Utf8PathBuf {
+    dist_graph
+        .target_dir
+        .join("cargo-dist-cache")
+        .join("sysroots")
+}
+
+/// The flag that points a compiler (or the linker driver) at a sysroot
+pub fn sysroot_flag(dir: &Utf8Path) -> String {
+    format!("--sysroot={dir}")
+}
+
+/// Check `target`'s directory from `sysroot-dirs` is a sysroot, returning it made absolute
+pub fn validate_sysroot_dir(
+    dist_graph: &DistGraph,
+    target: &str,
+    dir: &Utf8Path,
+) -> DistResult<Utf8PathBuf> {
+    let invalid = |reason: &str| DistError::InvalidSysroot {
+        target: target.to_owned(),
+        dir: dir.to_owned(),
+        reason: reason.to_owned(),
+    };
+    let path = dist_graph.workspace_dir.join(dir);
+    if !path.exists() {
+        return Err(invalid("doesn't exist"));
+    }
+    if !path.is_dir() {
+        return Err(invalid("isn't a directory"));
+    }
+    for subdir in SYSROOT_SUBDIRS {
+        if !path.join(subdir).is_dir() {
+            return Err(invalid(&format!("has no {subdir}")));
+        }
+    }
+    Ok(path)
+}
+
+/// The sysroot builds for `target` should use, extracting it if it isn't already
+pub(crate) fn ensure_sysroot(
+    dist_graph: &DistGraph,
+    target: &str,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if let Some(dir) = dist_graph
+        .generic_build
+        .sysroot_dirs
+        .as_ref()
+        .and_then(|dirs| dirs.get(target))
+    {
+        return validate_sysroot_dir(dist_graph, target, dir).map(Some);
+    }
+    let Some(sysroot) = dist_graph
+        .generic_build
+        .sysroots
+        .as_ref()
+        .and_then(|sysroots| sysroots.get(target))
+    else {
+        return Ok(None);
+    };
+    let engine = container::engine(dist_graph);
+    let digest = image_digest(engine, &sysroot.image)?;
+    let dir = sysroots_dir(dist_graph).join(digest.replace(':', "-"));
+    if !dir.exists() {
+        let path = sysroot
+            .path
+            .as_deref()
+            .unwrap_or(Utf8Path::new(DEFAULT_SYSROOT_PATH));
+        info!("extracting a sysroot for {target} from {}", sysroot.image);
+        extract(engine, &sysroot.image, path, &dir)?;
+    }
+    Ok(Some(dir))
+}
+
+/// The digest of `image`, pulling it if it isn't present
+fn image_digest(engine: &str, image: &str) -> DistResult<String> {
+    let inspect = || {
+        Cmd::new(engine, "inspect sysroot image")
+            .arg("image")
+            .arg("inspect")
+            .arg("--format")
+            .arg("{{.Id}}")
+            .arg(image)
+            .log(None)
+            .output()
+    };
+    let output = match inspect() {
+        Ok(output) => output,
+        Err(_) => {
+            Cmd::new(engine, "pull sysroot image")
+                .arg("pull")
+                .arg(image)
+                .stdout_to_stderr()
+                .run()?;
+            inspect()?
+        }
+    };
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Copy `path` out of `image` into `dest`
+///
+/// It's copied somewhere else first and then moved into place, so an
+/// interrupted extraction isn't mistaken for a finished one.
+fn extract(engine: &str, image: &str, path: &Utf8Path, dest: &Utf8Path) -> DistResult<()> {
+    let partial = dest.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    std::fs::create_dir_all(&partial)?;
+    let output = Cmd::new(engine, "create sysroot container")
+        .arg("create")
+        .arg(image)
+        .output()?;
+    let container = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    let copied = Cmd::new(engine, "copy sysroot out of container")
+        .arg("cp")
+        .arg(format!(
+            "{container}:{}/.",
+            path.as_str().trim_end_matches('/')
+        ))
+        .arg(partial.as_str())
+        .stdout_to_stderr()
+        .run();
+    // Clean up the container whether or not the copy worked
+    Cmd::new(engine, "remove sysroot container")
+        .arg("rm")
+        .arg(&container)
+        .stdout_to_stderr()
+        .run()?;
+    copied?;
+    std::fs::rename(&partial, dest)?;
+    Ok(())
+}
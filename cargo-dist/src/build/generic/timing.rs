@@ -0,0 +1,135 @@
+//! Per-target timing profiles of generic builds
+//!
+//! The build summary says how long each target took, but not where the time
+//! went. With `timing-profiles`, each stage of a target's build is timed, and
+//! once it's done a `timing-<target>.json` is written to the dist dir (and the
+//! same breakdown printed as a table):
+//!
+//! ```json
+//! {
+//!   "target": "x86_64-unknown-linux-gnu",
+//!   "stages": [
+//!     { "stage": "prepare", "duration-ms": 12 },
+//!     { "stage": "build", "duration-ms": 48210 },
+//!     { "stage": "copy", "duration-ms": 310 }
+//!   ],
+//!   "total-ms": 48532
+//! }
+//! ```
+//!
+//! The stages, in order, are `prepare` (snapshots, checks and syncing before
+//! the build), `build` (the build commands, with any retries), `collect`
+//! (finding the outputs), `check` (checking, splitting and relocating the
+//! binaries), `copy` (copying everything into the dist dir), `validate`,
+//! `post-process`, `notarize` and `record` (manifests, lists and provenance).
+//! Builds for a variant get `timing-<target>-<variant>.json`.
+
+use std::time::Instant;
+
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::{DistGraph, DistResult, GenericBuildStep};
+
+/// How long each stage of a target's build took
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TimingProfile {
+    /// The target triple
+    pub target: String,
+    /// The stages, in the order they ran
+    pub stages: Vec<StageTiming>,
+    /// How long they took altogether, in milliseconds
+    pub total_ms: u64,
+}
+
+/// How long a stage of a build took
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StageTiming {
+    /// The stage
+    pub stage: String,
+    /// How long it took, in milliseconds
+    pub duration_ms: u64,
+}
+
+/// Times the stages of a build, each starting where the last one finished
+#[derive(Debug)]
+pub(crate) struct StageTimer {
+    /// When the current stage started
+    started: Instant,
+    /// The stages finished so far
+    stages: Vec<StageTiming>,
+}
+
+impl StageTimer {
+    /// Start timing the first stage
+    pub(crate) fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            stages: vec![],
+        }
+    }
+
+    /// Finish the current stage, calling it `stage`, and start the next
+    pub(crate) fn finish(&mut self, stage: &str) {
+        let now = Instant::now();
+        self.stages.push(StageTiming {
+            stage: stage.to_owned(),
+            duration_ms: (now - self.started).as_millis() as u64,
+        });
+        self.started = now;
+    }
+
+    /// The profile of the stages finished so far
+    pub(crate) fn profile(&self, target: &str) -> TimingProfile {
+        TimingProfile {
+            target: target.to_owned(),
+            stages: self.stages.clone(),
+            total_ms: self.stages.iter().map(|stage| stage.duration_ms).sum(),
+        }
+    }
+}
+
+/// Where the timing profile for a build goes
+pub fn timing_profile_path(dist_graph: &DistGraph, target: &GenericBuildStep) -> Utf8PathBuf {
+    let name = match &target.variant {
+        Some(variant) => format!("timing-{}-{variant}.json", target.target_triple),
+        None => format!("timing-{}.json", target.target_triple),
+    };
+    dist_graph.dist_dir.join(name)
+}
+
+/// Write (and print) the timing profile for a build, if configured to
+pub(crate) fn write_timing_profile(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    timer: &StageTimer,
+) -> DistResult<Option<Utf8PathBuf>> {
+    if !dist_graph.generic_build.timing_profiles.unwrap_or(false) {
+        return Ok(None);
+    }
+    let profile = timer.profile(&target.target_triple);
+    eprint!("{}", render_timing_profile(&profile));
+    let path = timing_profile_path(dist_graph, target);
+    let contents = serde_json::to_string_pretty(&profile).expect("serde_json failed");
+    axoasset::LocalAsset::write_new_all(&contents, &path)?;
+    Ok(Some(path))
+}
+
+/// Render a timing profile as a table
+pub fn render_timing_profile(profile: &TimingProfile) -> String {
+    let seconds = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+    let rows = profile
+        .stages
+        .iter()
+        .map(|stage| (stage.stage.as_str(), seconds(stage.duration_ms)))
+        .chain([("total", seconds(profile.total_ms))])
+        .collect::<Vec<_>>();
+    let width = rows.iter().map(|(stage, _)| stage.len()).max().unwrap_or(0);
+    let mut out = format!("build stages for {}:\n", profile.target);
+    for (stage, time) in rows {
+        out.push_str(&format!("  {stage:width$}  {time:>7}\n"));
+    }
+    out
+}
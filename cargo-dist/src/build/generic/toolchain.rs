@@ -0,0 +1,103 @@
+//! Reporting the C/C++ toolchain generic builds use for each target
+//!
+//! Projects that mix cargo and generic builds want their cargo builds to use
+//! the same compilers, so we can render our choices in forms other tools read:
+//! target-suffixed environment variables (understood by the `cc` crate and
+//! cross-rs) as a shell `export` block, or a `.cargo/config.toml` snippet.
+
+use serde::Serialize;
+
+use super::{resolved_ar, resolved_cc, resolved_cxx, shell::posix_quote};
+use crate::{config::ToolchainFormat, DistGraph, SortedMap, TargetTriple};
+
+/// The tools a generic build for some target is given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Toolchain {
+    /// The C compiler (CC)
+    pub cc: String,
+    /// The C++ compiler (CXX)
+    pub cxx: String,
+    /// The archiver (AR)
+    pub ar: String,
+    /// What cargo should link with
+    pub linker: String,
+}
+
+impl Toolchain {
+    /// The toolchain we'd use for the given target, including CC/CXX/AR overrides
+    pub fn for_target(dist_graph: &DistGraph, target: &str) -> Self {
+        let cc = resolved_cc(dist_graph, target);
+        // MSVC's compiler can't drive the linker the way gcc/clang can
+        let linker = if target.contains("windows-msvc") {
+            "link.exe".to_owned()
+        } else {
+            cc.clone()
+        };
+        Self {
+            cxx: resolved_cxx(dist_graph, target),
+            ar: resolved_ar(target),
+            cc,
+            linker,
+        }
+    }
+}
+
+/// Render the toolchains for some targets in the given format
+pub fn render_toolchains(
+    toolchains: &SortedMap<TargetTriple, Toolchain>,
+    format: ToolchainFormat,
+) -> String {
+    let mut output = String::new();
+    match format {
+        ToolchainFormat::Shell => {
+            for (target, toolchain) in toolchains {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(&format!("# {target}\n"));
+                for (key, value) in toolchain_vars(target, toolchain) {
+                    output.push_str(&format!("export {key}={}\n", posix_quote(value)));
+                }
+            }
+        }
+        ToolchainFormat::Cargo => {
+            // Cargo only reads CARGO_TARGET_<triple>_LINKER from the real environment,
+            // so the linker goes in the target's table and everything else in [env]
+            let mut env = String::new();
+            for (target, toolchain) in toolchains {
+                output.push_str(&format!("[target.{target}]\n"));
+                output.push_str(&format!("linker = {}\n\n", toml_string(&toolchain.linker)));
+                for (key, value) in toolchain_vars(target, toolchain).into_iter().take(3) {
+                    env.push_str(&format!("{key} = {}\n", toml_string(value)));
+                }
+            }
+            if !env.is_empty() {
+                output.push_str("[env]\n");
+                output.push_str(&env);
+            }
+        }
+    }
+    output
+}
+
+/// The target-specific environment variables that select a toolchain
+///
+/// The linker always comes last.
+fn toolchain_vars<'a>(target: &str, toolchain: &'a Toolchain) -> [(String, &'a String); 4] {
+    let suffix = target.replace('-', "_");
+    [
+        (format!("CC_{suffix}"), &toolchain.cc),
+        (format!("CXX_{suffix}"), &toolchain.cxx),
+        (format!("AR_{suffix}"), &toolchain.ar),
+        (
+            format!("CARGO_TARGET_{}_LINKER", suffix.to_uppercase()),
+            &toolchain.linker,
+        ),
+    ]
+}
+
+/// Quote a string for TOML
+fn toml_string(value: &str) -> String {
+    // JSON strings are valid TOML basic strings
+    serde_json::Value::from(value).to_string()
+}
@@ -0,0 +1,146 @@
+//! Noticing binaries a build made that nobody asked for
+//!
+//! We only copy the binaries a build is expected to produce, so a build that
+//! starts making an extra helper goes unnoticed, and it isn't shipped (or is,
+//! if something else globs it up). With `undeclared-outputs`, we note what's in
+//! each directory a build's binaries land in before it runs, and afterwards
+//! look at the new files there that aren't one of its binaries:
+//!
+//! * `"warn"` prints a warning listing them
+//! * `"error"` fails the build
+//!
+//! Only files that start like an executable or library (ELF, Mach-O, PE or
+//! WebAssembly) count, so object files, logs and the like are left alone.
+//! Binaries found with `unnamed-outputs` aren't at a path we know before the
+//! build, so their directories aren't checked.
+
+use std::collections::btree_map::Entry;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+
+use super::{magic::detect_format, output_candidates, outputs::FoundOutput};
+use crate::{
+    config::UndeclaredOutputs, DistError, DistGraph, DistResult, GenericBuildStep, SortedMap,
+    SortedSet,
+};
+
+/// Extensions of object files, which are in executable formats but aren't binaries
+const OBJECT_EXTENSIONS: &[&str] = &["o", "obj"];
+
+/// What was in the directories a build's binaries land in, before it ran
+pub(crate) struct OutputDirSnapshot {
+    /// Each directory, and the names of the files in it
+    before: SortedMap<Utf8PathBuf, SortedSet<String>>,
+}
+
+impl OutputDirSnapshot {
+    /// Look at the directories `target`'s binaries are expected in, if we're checking them
+    pub(crate) fn take(
+        dist_graph: &DistGraph,
+        target: &GenericBuildStep,
+        root: &Utf8Path,
+    ) -> DistResult<Self> {
+        let mut before = SortedMap::new();
+        let mode = dist_graph
+            .generic_build
+            .undeclared_outputs
+            .unwrap_or_default();
+        if mode == UndeclaredOutputs::Ignore {
+            return Ok(Self { before });
+        }
+        let unnamed = dist_graph.generic_build.unnamed_outputs.as_ref();
+        for binary_idx in &target.expected_binaries {
+            let binary = dist_graph.binary(*binary_idx);
+            if unnamed.is_some_and(|dirs| dirs.contains_key(&binary.name)) {
+                continue;
+            }
+            for candidate in output_candidates(dist_graph, target, &binary.file_name) {
+                let dir = root.join(candidate.parent().unwrap_or(Utf8Path::new("")));
+                if let Entry::Vacant(entry) = before.entry(dir) {
+                    let files = list_files(entry.key())?;
+                    entry.insert(files);
+                }
+            }
+        }
+        Ok(Self { before })
+    }
+
+    /// The binaries in the snapshotted directories that are new, and aren't in `found`
+    pub(crate) fn undeclared(&self, found: &[FoundOutput]) -> DistResult<Vec<Utf8PathBuf>> {
+        let declared = found
+            .iter()
+            .filter_map(|output| output.path.canonicalize_utf8().ok())
+            .collect::<SortedSet<_>>();
+        let mut undeclared = vec![];
+        for (dir, before) in &self.before {
+            for name in list_files(dir)? {
+                if before.contains(&name) {
+                    continue;
+                }
+                let path = dir.join(&name);
+                if path
+                    .canonicalize_utf8()
+                    .is_ok_and(|path| declared.contains(&path))
+                {
+                    continue;
+                }
+                let is_object = path
+                    .extension()
+                    .is_some_and(|ext| OBJECT_EXTENSIONS.contains(&ext));
+                if !is_object && detect_format(&path)?.is_some() {
+                    undeclared.push(path);
+                }
+            }
+        }
+        Ok(undeclared)
+    }
+}
+
+/// Deal with binaries `target`'s build made alongside its own that it wasn't expected to
+pub(crate) fn check_undeclared_outputs(
+    dist_graph: &DistGraph,
+    target: &GenericBuildStep,
+    snapshot: &OutputDirSnapshot,
+    found: &[FoundOutput],
+) -> DistResult<()> {
+    let undeclared = snapshot.undeclared(found)?;
+    if undeclared.is_empty() {
+        return Ok(());
+    }
+    match dist_graph
+        .generic_build
+        .undeclared_outputs
+        .unwrap_or_default()
+    {
+        UndeclaredOutputs::Ignore => Ok(()),
+        UndeclaredOutputs::Warn => {
+            for path in &undeclared {
+                warn!(
+                    "the build for {} made {path}, which isn't one of its binaries, so it won't be shipped",
+                    target.target_triple
+                );
+            }
+            Ok(())
+        }
+        UndeclaredOutputs::Error => Err(DistError::UndeclaredOutputs {
+            target: target.target_triple.clone(),
+            outputs: undeclared,
+        }),
+    }
+}
+
+/// The names of the files in `dir`, which may not exist yet
+fn list_files(dir: &Utf8Path) -> DistResult<SortedSet<String>> {
+    let mut files = SortedSet::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.insert(entry.file_name().to_owned());
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,85 @@
+//! Finding binaries whose names aren't known until they're built
+//!
+//! Some builds name their outputs after a hash of the contents, so there's no
+//! filename we can look for. `unnamed-outputs` maps a binary's name to the
+//! directory its output lands in instead: we note what's in each of those
+//! directories before the build, and afterwards the one file that wasn't there
+//! before is the binary (it's still copied to the dist dir under the binary's
+//! usual name). Anything other than exactly one new file is an error, since we
+//! can't tell which one was meant.
+//!
+//! Each binary needs a directory of its own, which only it writes new files to.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{tasks::GenericBuildStep, DistError, DistGraph, DistResult, SortedMap, SortedSet};
+
+/// What was in each unnamed binary's directory before the build
+pub(crate) struct UnnamedOutputs {
+    /// The directory (relative to where the build runs) and its contents, by binary name
+    before: SortedMap<String, (Utf8PathBuf, SortedSet<String>)>,
+}
+
+impl UnnamedOutputs {
+    /// Look at the directories of the step's binaries that are in `unnamed-outputs`
+    pub(crate) fn snapshot(
+        dist_graph: &DistGraph,
+        step: &GenericBuildStep,
+        root: &Utf8Path,
+    ) -> DistResult<Self> {
+        let mut before = SortedMap::new();
+        let Some(dirs) = &dist_graph.generic_build.unnamed_outputs else {
+            return Ok(Self { before });
+        };
+        for binary_idx in &step.expected_binaries {
+            let binary = dist_graph.binary(*binary_idx);
+            if let Some(dir) = dirs.get(&binary.name) {
+                let files = list_files(&root.join(dir))?;
+                before.insert(binary.name.clone(), (dir.clone(), files));
+            }
+        }
+        Ok(Self { before })
+    }
+
+    /// Where to look for `binary`, if it's unnamed
+    ///
+    /// That's the one file in its directory that's new since the snapshot, or
+    /// nowhere if there are none (so it's reported missing like any other binary).
+    pub(crate) fn candidates(
+        &self,
+        binary: &str,
+        root: &Utf8Path,
+    ) -> DistResult<Option<Vec<Utf8PathBuf>>> {
+        let Some((dir, before)) = self.before.get(binary) else {
+            return Ok(None);
+        };
+        let new = list_files(&root.join(dir))?
+            .into_iter()
+            .filter(|name| !before.contains(name))
+            .collect::<Vec<_>>();
+        match new.as_slice() {
+            [] => Ok(Some(vec![])),
+            [name] => Ok(Some(vec![dir.join(name)])),
+            _ => Err(DistError::AmbiguousUnnamedOutput {
+                binary: binary.to_owned(),
+                dir: dir.clone(),
+                found: new,
+            }),
+        }
+    }
+}
+
+/// The names of the files in `dir`, which may not exist yet
+fn list_files(dir: &Utf8Path) -> DistResult<SortedSet<String>> {
+    let mut files = SortedSet::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.insert(entry.file_name().to_owned());
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,65 @@
+//! Running a user's checks over individual binaries
+//!
+//! `binary-validators` maps a binary's name to a command that checks it, like
+//! `["mylinter", "{path}"]`. Once the binary's been copied into the dist dir,
+//! the command is run for each place it was copied to, with `{path}` in its
+//! arguments replaced by that path. It's launched like the build (with
+//! `CARGO_DIST_TARGET` and the rest of the build's environment), and if it
+//! exits non-zero the build fails, naming the binary that didn't pass.
+//!
+//! Unlike `post-process`, which sees everything a build made at once, this is
+//! for checks that only make sense for one binary, like verifying a signature
+//! only it has.
+
+use camino::Utf8Path;
+
+use super::{
+    prepare_command,
+    relay::{run_relayed, BuildLog},
+    wrap_command,
+};
+use crate::{tasks::GenericBuildStep, BinaryIdx, DistError, DistGraph, DistResult};
+
+/// What's replaced with the path of the binary being validated
+pub const PATH_PLACEHOLDER: &str = "{path}";
+
+/// The validator for a binary copied to `path`, with its placeholders filled in
+pub(crate) fn validator_command(command_string: &[String], path: &Utf8Path) -> Vec<String> {
+    command_string
+        .iter()
+        .map(|arg| arg.replace(PATH_PLACEHOLDER, path.as_str()))
+        .collect()
+}
+
+/// Run the validators of each of the step's binaries that has one
+pub(crate) fn validate_binaries(
+    dist_graph: &DistGraph,
+    step: &GenericBuildStep,
+    binaries: &[BinaryIdx],
+    log: &BuildLog,
+) -> DistResult<()> {
+    let Some(validators) = &dist_graph.generic_build.binary_validators else {
+        return Ok(());
+    };
+    for binary_idx in binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        let Some(validator) = validators.get(&binary.name) else {
+            continue;
+        };
+        for path in &binary.copy_exe_to {
+            let command_string = validator_command(validator, path);
+            let command = prepare_command(dist_graph, &command_string, Some(step))?;
+            let mut command = wrap_command(dist_graph, command, Some(step))?;
+            command.check(false);
+            let status = run_relayed(dist_graph, &mut command, log)?;
+            if !status.success() {
+                return Err(DistError::BinaryValidationFailed {
+                    binary: binary.name.clone(),
+                    path: path.clone(),
+                    status: status.to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
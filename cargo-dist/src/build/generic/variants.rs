@@ -0,0 +1,69 @@
+//! Picking which of several mutually exclusive builds to make
+//!
+//! Some projects ship one of a few flavors of the same binaries, like a
+//! `with-gui` and a `headless` build, and pick one per release. `variants`
+//! names each of them, with the build command and flags it needs:
+//!
+//! ```toml
+//! [dist.generic-build]
+//! default-variant = "with-gui"
+//!
+//! [dist.generic-build.variants.headless]
+//! build-command = ["make", "HEADLESS=1"]
+//! cflags = "-DNO_GUI"
+//! ```
+//!
+//! `--variant` picks one, and otherwise `default-variant` is built. There's
+//! still only one build per target: the chosen variant's command replaces the
+//! usual one (though `binary-build-commands` still win), its flags are added
+//! after the flags profile's, and the build gets its name as
+//! `CARGO_DIST_VARIANT`.
+
+use crate::{
+    config::{BuildVariant, GenericBuildConfig},
+    DistError, DistResult,
+};
+
+/// The variant to build, given the one asked for (if any)
+///
+/// Without any variants nothing needs picking. With some, we build the one
+/// asked for or else `default-variant`, and it has to be one of them.
+pub fn select_variant(
+    config: &GenericBuildConfig,
+    requested: Option<&str>,
+) -> DistResult<Option<String>> {
+    let known = config.variants.as_ref();
+    let Some(selected) = requested.or(config.default_variant.as_deref()) else {
+        if known.is_some_and(|variants| !variants.is_empty()) {
+            return Err(DistError::NoVariantSelected {
+                known: known_variants(config),
+            });
+        }
+        return Ok(None);
+    };
+    if !known.is_some_and(|variants| variants.contains_key(selected)) {
+        return Err(DistError::UnknownVariant {
+            variant: selected.to_owned(),
+            known: known_variants(config),
+        });
+    }
+    Ok(Some(selected.to_owned()))
+}
+
+/// The definition of the variant with the given name
+pub(crate) fn variant<'a>(
+    config: &'a GenericBuildConfig,
+    name: Option<&str>,
+) -> Option<&'a BuildVariant> {
+    config.variants.as_ref()?.get(name?)
+}
+
+fn known_variants(config: &GenericBuildConfig) -> String {
+    config
+        .variants
+        .iter()
+        .flatten()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
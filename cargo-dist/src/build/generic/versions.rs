@@ -0,0 +1,144 @@
+//! Reporting which versions of tools and packages builds would get
+//!
+//! Before a release it's worth checking the runner has the toolchain you
+//! expect. `cargo dist doctor --report-versions` looks at the builds we'd run
+//! without running them, and for each program [`doctor`][] says they need
+//! (the build command, `CC`/`CXX`, the linker or container engine) prints the
+//! first line of its `--version`, along with the versions of the Brewfile's
+//! packages and the `pkg-config-deps`:
+//!
+//! ```text
+//! tools:
+//!   make       GNU Make 4.4.1                   (x86_64-unknown-linux-gnu: build command)
+//!   gcc        gcc (GCC) 13.2.0                 (x86_64-unknown-linux-gnu: CC)
+//! packages:
+//!   homebrew   openssl@3 3.3.1
+//! ```
+//!
+//! Each program is listed once, however many builds need it.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+
+use super::{
+    doctor::{doctor, DoctorCheck},
+    repro::tool_version,
+    sbom::{brew_components, pkg_config_components, Component, ComponentSource},
+};
+use crate::{BuildStep, DistGraph, DistResult};
+
+/// The versions of everything the planned builds would use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionReport {
+    /// The programs builds run
+    pub tools: Vec<ToolReport>,
+    /// The packages builds use
+    pub packages: Vec<PackageReport>,
+}
+
+/// A program builds run, and its version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolReport {
+    /// The program
+    pub program: String,
+    /// Where it was found, if it was
+    pub found: Option<Utf8PathBuf>,
+    /// The first line of its `--version`, if it has one
+    pub version: Option<String>,
+    /// What needs it, like "x86_64-unknown-linux-gnu: CC"
+    pub used_by: Vec<String>,
+}
+
+/// A package builds use, and its installed version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageReport {
+    /// Where it's from, like "homebrew"
+    pub source: String,
+    /// Its name
+    pub name: String,
+    /// Its installed version, if it's installed
+    pub version: Option<String>,
+}
+
+/// Find the versions of everything the given builds would use
+pub fn report_versions(dist_graph: &DistGraph, steps: &[BuildStep]) -> DistResult<VersionReport> {
+    let checks = doctor(dist_graph, steps)?;
+    let tools = consolidate(&checks);
+
+    let mut components = vec![];
+    let brewfile = Utf8Path::new("Brewfile");
+    if let Some(brew) = &dist_graph.tools.brew {
+        if brewfile.exists() {
+            components.extend(brew_components(&brew.cmd, brewfile)?);
+        }
+    }
+    if let Some(packages) = &dist_graph.generic_build.pkg_config_deps {
+        components.extend(pkg_config_components(packages)?);
+    }
+    let packages = components
+        .into_iter()
+        .filter(|component| component.source != ComponentSource::Input)
+        .map(
+            |Component {
+                 source,
+                 name,
+                 version,
+                 ..
+             }| PackageReport {
+                source: source.as_str().to_owned(),
+                name,
+                version,
+            },
+        )
+        .collect();
+    Ok(VersionReport { tools, packages })
+}
+
+/// One report per program, in the order they're first needed
+fn consolidate(checks: &[DoctorCheck]) -> Vec<ToolReport> {
+    let mut tools = Vec::<ToolReport>::new();
+    for check in checks {
+        let used_by = format!("{}: {}", check.target, check.role);
+        if let Some(tool) = tools.iter_mut().find(|tool| tool.program == check.program) {
+            tool.used_by.push(used_by);
+            continue;
+        }
+        let path = check.found.as_ref().map(|path| path.as_str());
+        tools.push(ToolReport {
+            program: check.program.clone(),
+            found: check.found.clone(),
+            // Only ask programs that are there
+            version: path.and_then(tool_version),
+            used_by: vec![used_by],
+        });
+    }
+    tools
+}
+
+/// Render the report as a table
+pub fn render_version_report(report: &VersionReport) -> String {
+    let mut out = String::from("tools:\n");
+    for tool in &report.tools {
+        let version = match (&tool.found, &tool.version) {
+            (None, _) => "MISSING",
+            (Some(_), None) => "(unknown version)",
+            (Some(_), Some(version)) => version,
+        };
+        out.push_str(&format!(
+            "  {:<10} {version:<32} ({})\n",
+            tool.program,
+            tool.used_by.join(", ")
+        ));
+    }
+    if !report.packages.is_empty() {
+        out.push_str("packages:\n");
+        for package in &report.packages {
+            let version = package.version.as_deref().unwrap_or("not installed");
+            out.push_str(&format!(
+                "  {:<10} {} {version}\n",
+                package.source, package.name
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,146 @@
+//! Compilers for WebAssembly targets
+//!
+//! The host's `cc` can't build for wasm, so wasm targets get a wasm toolchain
+//! instead, picked by the triple:
+//!
+//! * WASI targets (`wasm32-wasi`, `wasm32-wasip1`...) get the clang from
+//!   [wasi-sdk](https://github.com/WebAssembly/wasi-sdk), found through
+//!   `WASI_SDK_PATH`, along with `--target` and its sysroot.
+//! * Every other wasm target (`wasm32-unknown-unknown`, `wasm32-unknown-emscripten`)
+//!   gets [Emscripten](https://emscripten.org)'s `emcc` and `em++`.
+//!
+//! Wasm binaries are `<name>.wasm`. Emscripten usually also writes a JS file
+//! that loads the wasm (`<name>.js`), which ships alongside it if it's there.
+//!
+//! As everywhere else, `CC`/`CXX`/`AR` override the compilers we'd pick.
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::shell::find_program;
+use crate::{DistError, DistResult};
+
+/// Where wasi-sdk is installed
+const WASI_SDK_PATH: &str = "WASI_SDK_PATH";
+
+/// A toolchain that can build for wasm
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmToolchain {
+    /// emcc and friends
+    Emscripten,
+    /// wasi-sdk's clang
+    WasiSdk,
+}
+
+/// The wasm toolchain for a target, if it's a wasm target
+pub fn wasm_toolchain(target: &str) -> Option<WasmToolchain> {
+    if !target.starts_with("wasm") {
+        None
+    } else if target.contains("wasi") {
+        Some(WasmToolchain::WasiSdk)
+    } else {
+        Some(WasmToolchain::Emscripten)
+    }
+}
+
+impl WasmToolchain {
+    /// What to tell people when the toolchain can't be found
+    fn name(self) -> &'static str {
+        match self {
+            WasmToolchain::Emscripten => "Emscripten",
+            WasmToolchain::WasiSdk => "wasi-sdk",
+        }
+    }
+
+    /// The path to one of wasi-sdk's tools, if we know where it's installed
+    fn wasi_sdk_tool(tool: &str) -> Option<String> {
+        let sdk = std::env::var(WASI_SDK_PATH).ok()?;
+        Some(Utf8Path::new(&sdk).join("bin").join(tool).into_string())
+    }
+
+    /// The C compiler
+    pub fn cc(self) -> String {
+        match self {
+            WasmToolchain::Emscripten => "emcc".to_owned(),
+            WasmToolchain::WasiSdk => {
+                Self::wasi_sdk_tool("clang").unwrap_or_else(|| "clang".to_owned())
+            }
+        }
+    }
+
+    /// The C++ compiler
+    pub fn cxx(self) -> String {
+        match self {
+            WasmToolchain::Emscripten => "em++".to_owned(),
+            WasmToolchain::WasiSdk => {
+                Self::wasi_sdk_tool("clang++").unwrap_or_else(|| "clang++".to_owned())
+            }
+        }
+    }
+
+    /// The archiver
+    pub fn ar(self) -> String {
+        match self {
+            WasmToolchain::Emscripten => "emar".to_owned(),
+            WasmToolchain::WasiSdk => {
+                Self::wasi_sdk_tool("llvm-ar").unwrap_or_else(|| "llvm-ar".to_owned())
+            }
+        }
+    }
+
+    /// Flags the compiler needs to build for the target (for both CFLAGS and LDFLAGS)
+    pub fn flags(self, target: &str) -> Option<String> {
+        match self {
+            // emcc only builds for wasm, and brings its own sysroot
+            WasmToolchain::Emscripten => None,
+            WasmToolchain::WasiSdk => {
+                let mut flags = format!("--target={target}");
+                if let Ok(sdk) = std::env::var(WASI_SDK_PATH) {
+                    let sysroot = Utf8Path::new(&sdk).join("share").join("wasi-sysroot");
+                    flags.push_str(&format!(" --sysroot={sysroot}"));
+                }
+                Some(flags)
+            }
+        }
+    }
+}
+
+/// Make sure the toolchain for a wasm target is installed
+///
+/// A `CC` set by the user is trusted to know what it's doing.
+pub(crate) fn ensure_wasm_toolchain(target: &str) -> DistResult<()> {
+    let Some(toolchain) = wasm_toolchain(target) else {
+        return Ok(());
+    };
+    if std::env::var("CC").is_ok() {
+        return Ok(());
+    }
+    let found = match toolchain {
+        WasmToolchain::Emscripten => find_program("emcc").is_some(),
+        WasmToolchain::WasiSdk => WasmToolchain::wasi_sdk_tool("clang")
+            .is_some_and(|clang| find_program(&clang).is_some()),
+    };
+    if found {
+        return Ok(());
+    }
+    Err(DistError::WasmToolchainMissing {
+        target: target.to_owned(),
+        toolchain: toolchain.name().to_owned(),
+        help: match toolchain {
+            WasmToolchain::Emscripten => {
+                "install Emscripten and activate it (emsdk_env.sh, or setup-scripts) so emcc is on PATH, or set CC".to_owned()
+            }
+            WasmToolchain::WasiSdk => {
+                format!("install wasi-sdk and set {WASI_SDK_PATH} to where it is, or set CC")
+            }
+        },
+    })
+}
+
+/// The JS file Emscripten wrote to load a wasm output, if there is one
+pub fn emscripten_companion(target: &str, output: &Utf8Path) -> Option<Utf8PathBuf> {
+    if wasm_toolchain(target) != Some(WasmToolchain::Emscripten) {
+        return None;
+    }
+    let companion = output.with_extension("js");
+    companion.is_file().then_some(companion)
+}
@@ -0,0 +1,239 @@
+//! Rebuilding generic targets when their sources change
+//!
+//! `cargo dist build --watch` does the usual build, then keeps polling the
+//! sources of each build step and reruns only the steps whose sources
+//! changed. By default a step's sources are the whole workspace, but
+//! `watch-paths` (and `binary-watch-paths`, for the binaries a step builds)
+//! narrow that down, so editing one target's sources doesn't rebuild them all.
+//!
+//! We poll rather than subscribing to filesystem events, which keeps this
+//! working the same everywhere (including network filesystems and
+//! containers' bind mounts) at the cost of a little latency.
+
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_dist_schema::DistManifest;
+use tracing::warn;
+
+use super::{build_generic_target, checksums::write_combined_checksums};
+use crate::{BuildStep, DistError, DistGraph, DistResult, GenericBuildStep, SortedMap};
+
+/// How often to check the sources for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the sources must stay unchanged before we rebuild
+///
+/// Editors and VCS checkouts often write several files (or one file several
+/// times) in quick succession, and we only want to rebuild once for them.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Directory entries that are never sources
+const IGNORED_ENTRIES: &[&str] = &[".git"];
+
+/// The files and directories whose changes should rebuild a step
+pub fn watch_roots(dist_graph: &DistGraph, step: &GenericBuildStep) -> Vec<Utf8PathBuf> {
+    let config = &dist_graph.generic_build;
+    let mut roots = vec![];
+    for binary_idx in &step.expected_binaries {
+        let binary = dist_graph.binary(*binary_idx);
+        let paths = config
+            .binary_watch_paths
+            .as_ref()
+            .and_then(|paths| paths.get(&binary.name))
+            .or(config.watch_paths.as_ref());
+        match paths {
+            Some(paths) => {
+                roots.extend(paths.iter().map(|path| dist_graph.workspace_dir.join(path)))
+            }
+            None => roots.push(dist_graph.workspace_dir.clone()),
+        }
+    }
+    if step.expected_binaries.is_empty() {
+        roots.push(dist_graph.workspace_dir.clone());
+    }
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// The generic build steps that have a changed file in their sources
+pub fn affected_steps<'a>(
+    dist_graph: &DistGraph,
+    steps: &'a [BuildStep],
+    changed: &[Utf8PathBuf],
+) -> Vec<&'a GenericBuildStep> {
+    steps
+        .iter()
+        .filter_map(|step| match step {
+            BuildStep::Generic(step) => Some(step),
+            _ => None,
+        })
+        .filter(|step| {
+            let roots = watch_roots(dist_graph, step);
+            changed
+                .iter()
+                .any(|path| roots.iter().any(|root| path.starts_with(root)))
+        })
+        .collect()
+}
+
+/// The modification time and size of every file under some roots
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SourceSnapshot {
+    files: SortedMap<Utf8PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl SourceSnapshot {
+    /// Snapshot everything under `roots`, except anything under `skip`
+    ///
+    /// Roots that don't exist (yet) are fine, and count as empty.
+    pub fn take(roots: &[Utf8PathBuf], skip: &[&Utf8Path]) -> DistResult<Self> {
+        let mut snapshot = Self::default();
+        for root in roots {
+            snapshot.add(root, skip)?;
+        }
+        Ok(snapshot)
+    }
+
+    fn add(&mut self, path: &Utf8Path, skip: &[&Utf8Path]) -> DistResult<()> {
+        if skip.iter().any(|skip| path.starts_with(skip)) {
+            return Ok(());
+        }
+        // Don't follow symlinks, a link to somewhere above us would never end
+        let Ok(metadata) = path.symlink_metadata() else {
+            return Ok(());
+        };
+        if metadata.is_dir() {
+            for entry in path.read_dir_utf8()? {
+                let entry = entry?;
+                if !IGNORED_ENTRIES.contains(&entry.file_name()) {
+                    self.add(entry.path(), skip)?;
+                }
+            }
+        } else {
+            self.files
+                .insert(path.to_owned(), (metadata.modified().ok(), metadata.len()));
+        }
+        Ok(())
+    }
+
+    /// The size of a file in the snapshot, if it's there
+    pub fn size(&self, path: &Utf8Path) -> Option<u64> {
+        self.files.get(path).map(|(_, size)| *size)
+    }
+
+    /// The files that were added, removed, or modified since `earlier`
+    pub fn changed_since(&self, earlier: &Self) -> Vec<Utf8PathBuf> {
+        let mut changed = earlier
+            .files
+            .keys()
+            .chain(self.files.keys())
+            .filter(|path| earlier.files.get(*path) != self.files.get(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+/// Rebuild generic build steps whenever their sources change, until interrupted
+///
+/// Failed rebuilds are reported, but don't stop us watching. After each batch
+/// of rebuilds the combined checksums are rewritten, and the updated manifest
+/// is passed to `report`.
+pub(crate) fn watch(
+    dist_graph: &DistGraph,
+    manifest: &mut DistManifest,
+    steps: &[BuildStep],
+    mut report: impl FnMut(&DistManifest),
+) -> DistResult<()> {
+    let mut roots = steps
+        .iter()
+        .filter_map(|step| match step {
+            BuildStep::Generic(step) => Some(watch_roots(dist_graph, step)),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if roots.is_empty() {
+        warn!("there are no generic builds to watch");
+        return Ok(());
+    }
+    roots.sort();
+    roots.dedup();
+    // Our own outputs aren't sources, and would have us rebuilding forever
+    let skip = [
+        dist_graph.target_dir.as_path(),
+        dist_graph.dist_dir.as_path(),
+    ];
+    let snapshot_sources = || SourceSnapshot::take(&roots, &skip);
+
+    eprintln!("watching for changes (press Ctrl-C to stop):");
+    for root in &roots {
+        eprintln!("  {root}");
+    }
+    let mut snapshot = snapshot_sources()?;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut current = snapshot_sources()?;
+        let mut changed = current.changed_since(&snapshot);
+        if changed.is_empty() {
+            continue;
+        }
+        loop {
+            thread::sleep(DEBOUNCE);
+            let next = snapshot_sources()?;
+            if next == current {
+                break;
+            }
+            changed.extend(next.changed_since(&current));
+            current = next;
+        }
+        changed.sort();
+        changed.dedup();
+
+        let affected = affected_steps(dist_graph, steps, &changed);
+        for step in &affected {
+            eprintln!(
+                "sources changed ({}), rebuilding {}",
+                describe_changes(dist_graph, &changed),
+                step.target_triple
+            );
+            // Keep one provenance entry per build, not one per rebuild
+            manifest.provenance.retain(|provenance| {
+                provenance.target_triple != step.target_triple
+                    || provenance.command != step.build_command
+            });
+            match build_generic_target(dist_graph, manifest, step) {
+                Ok(_) => eprintln!("rebuilt {}", step.target_triple),
+                Err(DistError::BuildInterrupted) => return Err(DistError::BuildInterrupted),
+                Err(e) => warn!("{:?}", miette::Report::new(e)),
+            }
+        }
+        if !affected.is_empty() {
+            // The rebuilt outputs have new checksums
+            write_combined_checksums(dist_graph)?;
+            report(manifest);
+        }
+        // Builds that write into the source tree shouldn't set themselves off again
+        snapshot = snapshot_sources()?;
+    }
+}
+
+/// A short description of some changed files, for logging
+fn describe_changes(dist_graph: &DistGraph, changed: &[Utf8PathBuf]) -> String {
+    let first = changed
+        .first()
+        .map(|path| path.strip_prefix(&dist_graph.workspace_dir).unwrap_or(path))
+        .map(|path| path.to_string())
+        .unwrap_or_default();
+    match changed.len() {
+        0 | 1 => first,
+        count => format!("{first} and {} more", count - 1),
+    }
+}
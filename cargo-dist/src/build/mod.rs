@@ -1,18 +1,23 @@
 //! Compiling Things
 
 use axoproject::PackageId;
-use camino::Utf8PathBuf;
-use cargo_dist_schema::{AssetInfo, DistManifest};
-use tracing::info;
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_dist_schema::{AssetInfo, AssetKind, DistManifest};
+use tracing::{info, warn};
 
 use crate::{
-    copy_file, linkage::determine_linkage, Binary, BinaryIdx, DistError, DistGraph, DistResult,
-    SortedMap, TargetTriple,
+    config::{ChecksumStyle, CopyMode, SymlinkMode},
+    copy_error, generate_checksum,
+    linkage::determine_linkage,
+    Binary, BinaryIdx, DistError, DistGraph, DistResult, SortedMap, SortedSet, TargetTriple,
 };
 
 pub mod cargo;
 pub mod fake;
 pub mod generic;
+pub mod output_kind;
+
+use output_kind::{apply_output_mode, OutputKind};
 
 /// Output expectations for builds, and computed facts (all packages)
 pub struct BuildExpectations {
@@ -20,6 +25,8 @@ pub struct BuildExpectations {
     pub packages: SortedMap<String, BinaryExpectations>,
     /// Whether this is fake (--artifacts=lies)
     fake: bool,
+    /// The target whose staging dir binaries get copied to rather than the dist dir, if any
+    staged: Option<TargetTriple>,
 }
 
 /// Output expectations for builds, and computed facts (one package)
@@ -67,7 +74,30 @@ impl BuildExpectations {
         Self {
             packages,
             fake: false,
+            staged: None,
+        }
+    }
+
+    /// Copy binaries into the staging dir (if there is one), for promoting later
+    pub fn staged(mut self, target: &str) -> Self {
+        self.staged = Some(target.to_owned());
+        self
+    }
+
+    /// Where a copy destination actually gets copied to
+    fn copy_dest(&self, dist: &DistGraph, dest_path: &Utf8Path) -> DistResult<Utf8PathBuf> {
+        let Some(target) = &self.staged else {
+            return Ok(dest_path.to_owned());
+        };
+        if generic::staging::staging_dir(dist, target).is_none() {
+            return Ok(dest_path.to_owned());
+        }
+        // The staging dir doesn't have the artifact dirs the dist dir was set up with
+        let staged = generic::staging::staged_path(dist, target, dest_path);
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        Ok(staged)
     }
 
     /// Create a new BuildExpectations, but don't sweat things being faked
@@ -99,6 +129,24 @@ impl BuildExpectations {
         // of feature resolution), this can produce a bunch of binaries for examples or
         // packages you don't care about, which cargo/rustc will happily report back to us,
         // and we need to be aware enough to throw those irrelevant results out.
+        // lookup the binary by the name it was built with
+        let Some(bin_name) = src_path.file_stem() else {
+            return;
+        };
+        let bin_name = bin_name.to_owned();
+        self.found_named_bin(pkg_id, &bin_name, src_path, maybe_symbols);
+    }
+
+    /// Like [`BuildExpectations::found_bin`][], but for a binary we already know the name of
+    ///
+    /// This is for builds whose outputs aren't named after the binary.
+    pub fn found_named_bin(
+        &mut self,
+        pkg_id: String,
+        bin_name: &str,
+        src_path: Utf8PathBuf,
+        maybe_symbols: Vec<Utf8PathBuf>,
+    ) {
         info!("got a new binary: {}", src_path);
 
         // lookup the package
@@ -107,9 +155,6 @@ impl BuildExpectations {
         };
 
         // lookup the binary in the package
-        let Some(bin_name) = src_path.file_stem() else {
-            return;
-        };
         let Some(bin_result) = pkg.binaries.get_mut(bin_name) else {
             return;
         };
@@ -120,7 +165,10 @@ impl BuildExpectations {
         // Also register symbols
         for sym_path in maybe_symbols {
             // FIXME: unhardcode this when we add support for other symbol kinds!
-            let is_symbols = sym_path.extension().map(|e| e == "pdb").unwrap_or(false);
+            let is_symbols = sym_path
+                .extension()
+                .map(|e| e == "pdb" || e == "debug")
+                .unwrap_or(false);
             if !is_symbols {
                 continue;
             }
@@ -136,7 +184,9 @@ impl BuildExpectations {
     ///
     /// * checking src_path was set by found_bin
     /// * computing linkage for the binary
-    /// * copying the binary and symbols to their final homes
+    /// * copying the binary to its final homes
+    /// * copying symbols to their final homes (once, even if binaries share them)
+    /// * giving the copies the permissions their kind of output should have
     ///
     /// In the future this may also include:
     ///
@@ -156,12 +206,13 @@ impl BuildExpectations {
                     continue;
                 }
                 let bin = dist.binary(result_bin.idx);
+                let kind = OutputKind::detect(&bin.file_name, &bin.target);
 
                 // compute linkage for the binary
-                self.compute_linkage(dist, manifest, result_bin, &bin.target)?;
+                self.compute_linkage(dist, manifest, result_bin, &bin.target, kind)?;
 
                 // copy files to their final homes
-                self.copy_assets(result_bin, bin)?;
+                self.copy_assets(dist, result_bin, bin, kind)?;
             }
         }
 
@@ -171,9 +222,92 @@ impl BuildExpectations {
             return Err(DistError::MissingBinaries { pkg_name, bin_name });
         }
 
+        // Several binaries can share one symbols file, which only needs copying once
+        let symlinks = dist.generic_build.symlinks.unwrap_or_default();
+        let copy_mode = dist.generic_build.copy_mode.unwrap_or_default();
+        let symbols_optional = dist.generic_build.symbols_optional.unwrap_or(false);
+        for (sym_path, dest_path) in self.symbol_copies(dist) {
+            if !sym_path.exists() {
+                if !symbols_optional {
+                    return Err(DistError::MissingSymbols { path: sym_path });
+                }
+                warn!("failed to find symbols {sym_path}, so they won't be shipped");
+                self.drop_symbols_artifacts(dist, manifest, &dest_path);
+                continue;
+            }
+            let dest_path = self.copy_dest(dist, &dest_path)?;
+            if generic::symbols::wants_compressed_symbols(dist) {
+                generic::symbols::compress_symbols(&sym_path, &dest_path)?;
+            } else {
+                let copied = copy_into(&sym_path, &dest_path, symlinks, copy_mode)?;
+                verify_copy(dist, &sym_path, &copied)?;
+            }
+        }
+
         Ok(())
     }
 
+    /// Forget the symbols artifacts that would have been copied to the given path
+    ///
+    /// This is for symbols-optional, so the manifest doesn't list symbols that never existed.
+    fn drop_symbols_artifacts(
+        &self,
+        dist: &DistGraph,
+        manifest: &mut DistManifest,
+        dest: &Utf8Path,
+    ) {
+        for pkg in self.packages.values() {
+            for result_bin in pkg.binaries.values() {
+                let bin = dist.binary(result_bin.idx);
+                if !bin.copy_symbols_to.iter().any(|path| path == dest) {
+                    continue;
+                }
+                let Some(artifact_idx) = bin.symbols_artifact else {
+                    continue;
+                };
+                let id = &dist.artifact(artifact_idx).id;
+                manifest.artifacts.remove(id);
+                for release in &mut manifest.releases {
+                    release.artifacts.retain(|artifact| artifact != id);
+                }
+                for artifact in manifest.artifacts.values_mut() {
+                    for asset in &mut artifact.assets {
+                        if let AssetKind::Executable(exe) = &mut asset.kind {
+                            if exe.symbols_artifact.as_ref() == Some(id) {
+                                exe.symbols_artifact = None;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every copy of symbols the found binaries need, as (symbols, destination)
+    ///
+    /// Binaries that share a symbols file and destination only produce one copy.
+    pub(crate) fn symbol_copies(&self, dist: &DistGraph) -> SortedSet<(Utf8PathBuf, Utf8PathBuf)> {
+        let mut copies = SortedSet::new();
+        for pkg in self.packages.values() {
+            for result_bin in pkg.binaries.values() {
+                if result_bin.src_path.is_none() {
+                    continue;
+                }
+                let bin = dist.binary(result_bin.idx);
+                // Static libraries have their symbols built in
+                if OutputKind::detect(&bin.file_name, &bin.target) == OutputKind::StaticLibrary {
+                    continue;
+                }
+                for sym_path in &result_bin.sym_paths {
+                    for dest_path in &bin.copy_symbols_to {
+                        copies.insert((sym_path.clone(), dest_path.clone()));
+                    }
+                }
+            }
+        }
+        copies
+    }
+
     // Compute the linkage info for this binary
     fn compute_linkage(
         &self,
@@ -181,6 +315,7 @@ impl BuildExpectations {
         manifest: &mut DistManifest,
         src: &ExpectedBinary,
         target: &TargetTriple,
+        kind: OutputKind,
     ) -> DistResult<()> {
         let src_path = src
             .src_path
@@ -196,6 +331,9 @@ impl BuildExpectations {
                 source: None,
             });
             linkage
+        } else if kind == OutputKind::StaticLibrary {
+            // Static archives aren't linked yet, so have no linkage of their own
+            cargo_dist_schema::Linkage::default()
         } else {
             determine_linkage(src_path, target)?
         };
@@ -214,25 +352,217 @@ impl BuildExpectations {
     }
 
     // Copy the assets for this binary
-    fn copy_assets(&self, src: &ExpectedBinary, dests: &Binary) -> DistResult<()> {
-        // Copy the main binary
+    fn copy_assets(
+        &self,
+        dist: &DistGraph,
+        src: &ExpectedBinary,
+        dests: &Binary,
+        kind: OutputKind,
+    ) -> DistResult<()> {
+        let symlinks = dist.generic_build.symlinks.unwrap_or_default();
+        let copy_mode = dist.generic_build.copy_mode.unwrap_or_default();
         let src_path = src
             .src_path
             .as_deref()
             .expect("bin src_path should have been checked by caller");
         for dest_path in &dests.copy_exe_to {
-            copy_file(src_path, dest_path)?;
+            let dest_path = self.copy_dest(dist, dest_path)?;
+            let copied = copy_into(src_path, &dest_path, symlinks, copy_mode)?;
+            verify_copy(dist, src_path, &copied)?;
+            apply_output_mode(&copied, kind)?;
         }
 
-        // Copy the symbols
-        for sym_path in &src.sym_paths {
-            for dest_path in &dests.copy_symbols_to {
-                copy_file(sym_path, dest_path)?;
+        Ok(())
+    }
+}
+
+/// Copy a build output to a destination, which may be an existing directory
+///
+/// If it's a directory we copy into it, keeping the output's file name
+/// (unless that would clobber another directory). Returns the path written to.
+///
+/// If the output is a symlink, `symlinks` decides what we do about that, and
+/// `copy_mode` decides whether real files are copied or hard-linked.
+pub(crate) fn copy_into(
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    symlinks: SymlinkMode,
+    copy_mode: CopyMode,
+) -> DistResult<Utf8PathBuf> {
+    let dest_path = if dest_path.is_dir() {
+        let file_name = src_path
+            .file_name()
+            .expect("build outputs should have file names");
+        let nested = dest_path.join(file_name);
+        if nested.is_dir() {
+            return Err(DistError::CopyDestinationIsDir {
+                src_path: src_path.to_owned(),
+                dest_path: dest_path.to_owned(),
+            });
+        }
+        nested
+    } else {
+        dest_path.to_owned()
+    };
+    if symlinks != SymlinkMode::Follow && src_path.is_symlink() {
+        #[cfg(unix)]
+        {
+            copy_symlink(src_path, &dest_path, symlinks, copy_mode)?;
+            return Ok(dest_path);
+        }
+        #[cfg(not(unix))]
+        tracing::warn!("{src_path} is a symlink, but symlinks can only be copied on unix; copying what it points to");
+    }
+    place_file(src_path, &dest_path, copy_mode)?;
+    Ok(dest_path)
+}
+
+/// Check a copied output matches its source, if it's big enough to be worth it
+///
+/// Only outputs of at least `verify-copies-over-mb` are checked. If the copy
+/// doesn't match, it's removed, so the destination is either missing or right.
+pub(crate) fn verify_copy(
+    dist: &DistGraph,
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+) -> DistResult<()> {
+    let Some(threshold_mb) = dist.generic_build.verify_copies_over_mb else {
+        return Ok(());
+    };
+    let size = std::fs::metadata(src_path)?.len();
+    if size < threshold_mb.saturating_mul(1024 * 1024) {
+        return Ok(());
+    }
+    let expected = generate_checksum(&ChecksumStyle::Sha256, src_path)?;
+    let actual = generate_checksum(&ChecksumStyle::Sha256, dest_path)?;
+    if expected != actual {
+        std::fs::remove_file(dest_path).map_err(|e| copy_error(src_path, dest_path, e))?;
+        return Err(DistError::CopyVerificationFailed {
+            src: src_path.to_owned(),
+            dest: dest_path.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Copy or hard-link a file, falling back to copying if it can't be linked
+///
+/// Links only work within a filesystem (and not on every filesystem), so
+/// failing to make one just means we copy instead.
+fn place_file(src_path: &Utf8Path, dest_path: &Utf8Path, copy_mode: CopyMode) -> DistResult<()> {
+    if let (Ok(src), Ok(dest)) = (src_path.canonicalize(), dest_path.canonicalize()) {
+        if src == dest {
+            // Already there, and replacing it would lose it
+            return Ok(());
+        }
+    }
+    // Links can't replace an existing file, and copying over one that's a
+    // link (from an earlier hard-linked build) would write through it
+    if dest_path.symlink_metadata().is_ok() {
+        std::fs::remove_file(dest_path).map_err(|e| copy_error(src_path, dest_path, e))?;
+    }
+    if copy_mode == CopyMode::Hardlink {
+        match std::fs::hard_link(src_path, dest_path) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                tracing::debug!("couldn't hard-link {src_path} to {dest_path} ({e}), copying it")
             }
         }
+    }
+    copy_file_atomically(src_path, dest_path, |src, dest| {
+        std::fs::copy(src, dest).map(|_| ())
+    })
+}
 
-        Ok(())
+/// Copy a file, so that the destination is either missing or the whole file
+///
+/// It's written under a temporary name next to the destination and then
+/// renamed into place, so an interrupted copy of a huge output never leaves a
+/// partial file that a later run could take for the real one. The actual
+/// copying is done by `copy`.
+pub(crate) fn copy_file_atomically(
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    copy: impl FnOnce(&Utf8Path, &Utf8Path) -> std::io::Result<()>,
+) -> DistResult<()> {
+    let partial = partial_copy_path(dest_path);
+    let result = copy(src_path, &partial).and_then(|()| std::fs::rename(&partial, dest_path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(copy_error(src_path, dest_path, e));
+    }
+    Ok(())
+}
+
+/// Where a copy to `dest_path` is written before it's complete
+pub(crate) fn partial_copy_path(dest_path: &Utf8Path) -> Utf8PathBuf {
+    let file_name = dest_path.file_name().unwrap_or("copy");
+    dest_path.with_file_name(format!(".{file_name}.partial"))
+}
+
+/// Copy a symlink and whatever it points to next to each other
+#[cfg(unix)]
+fn copy_symlink(
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    mode: SymlinkMode,
+    copy_mode: CopyMode,
+) -> DistResult<()> {
+    // How long of a libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3 chain we'll put up with
+    const MAX_LINKS: usize = 40;
+
+    let dest_dir = dest_path
+        .parent()
+        .expect("copy destinations should have parents");
+    match mode {
+        SymlinkMode::Follow => place_file(src_path, dest_path, copy_mode)?,
+        SymlinkMode::Preserve => {
+            // Walk the chain, reproducing every link in it
+            let mut link = src_path.to_owned();
+            let mut link_dest = dest_path.to_owned();
+            for _ in 0..MAX_LINKS {
+                let target = link
+                    .parent()
+                    .unwrap_or(Utf8Path::new(""))
+                    .join(link.read_link_utf8()?);
+                let target_name = target.file_name().expect("symlink target has no name!?");
+                let target_dest = dest_dir.join(target_name);
+                replace_symlink(target_name, &link_dest)?;
+                if !target.is_symlink() {
+                    place_file(&target, &target_dest, copy_mode)?;
+                    return Ok(());
+                }
+                link = target;
+                link_dest = target_dest;
+            }
+            return Err(DistError::SymlinkChainTooLong {
+                path: src_path.to_owned(),
+            });
+        }
+        SymlinkMode::Recreate => {
+            // Copy the real file under its real name, and link straight to it
+            let real = src_path.canonicalize_utf8()?;
+            let real_name = real.file_name().expect("symlink target has no name!?");
+            let real_dest = dest_dir.join(real_name);
+            if real_dest == dest_path {
+                place_file(&real, dest_path, copy_mode)?;
+            } else {
+                place_file(&real, &real_dest, copy_mode)?;
+                replace_symlink(real_name, dest_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create a symlink, replacing whatever was there before
+#[cfg(unix)]
+fn replace_symlink(target: &str, link: &Utf8Path) -> DistResult<()> {
+    if link.symlink_metadata().is_ok() {
+        std::fs::remove_file(link)?;
     }
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
 }
 
 fn package_id_string(id: Option<&PackageId>) -> String {
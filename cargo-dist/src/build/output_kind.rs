@@ -0,0 +1,67 @@
+//! Telling executables and libraries apart
+//!
+//! Binaries don't have to be executables: a generic build can just as well
+//! produce `libfoo.a` or `libfoo.so`. Those need different handling once
+//! copied (libraries aren't executable, and static ones have no linkage or
+//! symbols of their own), so we classify outputs by their name and target.
+
+use camino::Utf8Path;
+
+use crate::DistResult;
+
+/// What kind of thing a build output is
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputKind {
+    /// Something you run
+    Executable,
+    /// An archive of objects to link into something else (`.a`, `.lib`)
+    StaticLibrary,
+    /// A library loaded at runtime (`.so`, `.dylib`, `.dll`)
+    DynamicLibrary,
+}
+
+impl OutputKind {
+    /// Classify an output from its file name and the target it was built for
+    pub fn detect(file_name: &str, target: &str) -> Self {
+        let windows = target.contains("windows");
+        let apple = target.contains("apple");
+        let has_ext = |ext: &str| {
+            Utf8Path::new(file_name)
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+        };
+        if has_ext("a") || (windows && has_ext("lib")) {
+            OutputKind::StaticLibrary
+        } else if (windows && has_ext("dll"))
+            || (apple && has_ext("dylib"))
+            // Versioned shared objects look like libfoo.so.1.2.3
+            || (!windows && !apple && (has_ext("so") || file_name.contains(".so.")))
+        {
+            OutputKind::DynamicLibrary
+        } else {
+            OutputKind::Executable
+        }
+    }
+
+    /// The unix permissions a copy of this output should have
+    pub fn mode(self) -> u32 {
+        match self {
+            OutputKind::Executable => 0o755,
+            OutputKind::StaticLibrary | OutputKind::DynamicLibrary => 0o644,
+        }
+    }
+}
+
+/// Give a copied output the permissions appropriate for its kind
+///
+/// Permissions are left alone on platforms without unix modes.
+pub(crate) fn apply_output_mode(path: &Utf8Path, kind: OutputKind) -> DistResult<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(kind.mode()))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, kind);
+    Ok(())
+}
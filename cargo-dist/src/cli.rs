@@ -146,6 +146,48 @@ pub enum Commands {
     /// by forcing a couple flags to have specific values. You probably want that.
     #[clap(disable_version_flag = true)]
     Manifest(ManifestArgs),
+    /// List the targets generic builds will be run for
+    ///
+    /// This computes the same build plan as 'cargo dist plan' without running
+    /// anything, and prints each target a generic build would be run for along
+    /// with how many binaries it produces. Useful for making sure your CI
+    /// matrix matches what will actually be built.
+    #[clap(disable_version_flag = true)]
+    ListTargets(ListTargetsArgs),
+    /// Print the C/C++ toolchain generic builds use for each target
+    ///
+    /// This prints the compilers, archiver, and linker cargo-dist picks for each
+    /// target a generic build would be run for (including any CC/CXX/AR you set),
+    /// as target-specific environment variables that cargo, the cc crate, and
+    /// cross-rs understand. Useful for making cargo builds use the same toolchain.
+    #[clap(disable_version_flag = true)]
+    ToolchainEnv(ToolchainEnvArgs),
+    /// Check that the programs generic builds need are installed
+    ///
+    /// This computes the same build plan as 'cargo dist plan' without running
+    /// anything, and checks that each build's command, the C/C++ compilers and
+    /// linker it would be given (or the container engine, for containerized
+    /// builds) can be found. Useful for catching a missing toolchain before
+    /// spending minutes of CI on a build that can't succeed.
+    #[clap(disable_version_flag = true)]
+    Doctor(DoctorArgs),
+    /// Write a Makefile that runs the same generic builds we would
+    ///
+    /// Every target gets a make rule that runs the same commands, in the same
+    /// directory, with the same environment variables cargo-dist would. This is
+    /// useful for debugging builds, or running them without cargo-dist.
+    #[clap(disable_version_flag = true)]
+    ExportMakefile(ExportMakefileArgs),
+    /// Print the config generic builds use, as TOML
+    ///
+    /// By default this prints the generic build config as cargo-dist read it.
+    /// With --effective, it instead resolves everything the way a build would
+    /// (defaults, per-target settings, profiles, flags profiles and variants)
+    /// and prints what each planned build is given: its commands, environment,
+    /// compilers, flags, and where its binaries are copied to. Useful for
+    /// working out which setting won.
+    #[clap(disable_version_flag = true)]
+    Config(ConfigArgs),
     /// Print --help as markdown (for generating docs)
     ///
     /// The output of this is not stable or guaranteed.
@@ -209,6 +251,216 @@ pub struct BuildArgs {
     /// * linkage: prints information on dynamic libraries used by build artifacts
     #[clap(long, short, value_delimiter(','))]
     pub print: Vec<String>,
+
+    /// Let generic builds use the terminal, so they can prompt for input
+    ///
+    /// Normally we capture the output of generic builds (to relay and log it),
+    /// which deadlocks builds that ask questions. With this their stdin, stdout,
+    /// and stderr are left attached to the terminal instead, so nothing is
+    /// captured: build logs will be empty, and --output-format=json can't be used.
+    /// Intended for debugging stubborn builds locally.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub interactive: bool,
+
+    /// Print each generic build's command as a line you can paste into a shell
+    ///
+    /// This includes the environment variables we set for the build (like CC and
+    /// CFLAGS), quoted for the shell you're likely to be using (PowerShell on
+    /// windows, a POSIX shell elsewhere), so you can reproduce a build by hand.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub print_command: bool,
+
+    /// Write a repro-<target>.json to the dist dir describing each generic build
+    ///
+    /// This records everything needed to rerun the build on another machine:
+    /// the command, the working directory, the environment variables we set
+    /// for it (with secret-looking values redacted), the host and target, and
+    /// the versions of the C compilers it was given.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub repro_manifest: bool,
+
+    /// Print what the builds added to, changed in, and removed from the dist dir
+    ///
+    /// The dist dir is snapshotted before the local builds run and compared
+    /// afterwards, listing each file that changed with its size. Unlike the
+    /// manifest, this includes stray files nothing expected the builds to make.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub diff_dist_dir: bool,
+
+    /// Remove files from the dist dir that this run won't produce, before building
+    ///
+    /// Artifacts for targets you no longer build otherwise linger in the dist
+    /// dir, where they can end up in a release. Everything that isn't one of
+    /// the planned artifacts (or inside one of their directories) is removed.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub clean: bool,
+
+    /// List what --clean would remove from the dist dir, without removing anything
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub clean_dry_run: bool,
+
+    /// Skip generic builds that already finished in an earlier, failed run
+    ///
+    /// Each finished build is recorded in build-state.json in the dist dir. A
+    /// target whose build ran the same command, and whose binaries are still
+    /// there unchanged, isn't rebuilt.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub resume: bool,
+
+    /// Ask brew for its environment, even if an earlier run cached it
+    ///
+    /// The environment `brew bundle exec` gives builds is cached under the
+    /// target dir, keyed by a hash of the Brewfile, so later runs (like the
+    /// other steps of a CI job) can skip asking brew. This ignores that cache.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub no_brew_cache: bool,
+
+    /// After building, keep watching for source changes and rebuild what they affect
+    ///
+    /// Only generic builds are rebuilt, and only the ones whose sources changed
+    /// (see `watch-paths`). This runs until you press Ctrl-C.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub watch: bool,
+
+    /// Set an environment variable for generic builds, as KEY=VALUE (repeatable)
+    ///
+    /// This is for toggling things at release time without editing your config,
+    /// like `--build-arg ENABLE_X=1`. These override any other variable with the
+    /// same name, and get `build-arg-prefix` added to their names if it's set.
+    #[clap(long, value_name = "KEY=VALUE")]
+    pub build_arg: Vec<String>,
+
+    /// Stage the outputs of generic builds here before copying them to the dist dir
+    ///
+    /// Each generic build copies its outputs into this directory (laid out like
+    /// the dist dir), post-processes them there, and only copies them into the
+    /// dist dir once it's succeeded. Failed builds never touch the dist dir.
+    #[clap(long, value_name = "DIR")]
+    pub staging_dir: Option<Utf8PathBuf>,
+
+    /// Stage each target's generic build in target/distrib/.staging/<TARGET>/
+    ///
+    /// Like --staging-dir, but every target gets its own staging dir, which is
+    /// removed once its build succeeds. The staging dirs of failed builds are
+    /// kept, to see what they got up to.
+    #[clap(long, conflicts_with = "staging_dir")]
+    #[clap(default_value_t = false)]
+    pub target_staging: bool,
+
+    /// Only run generic builds, skipping extra artifacts' builds
+    ///
+    /// Nothing that needs every build's outputs, like archives and installers,
+    /// is made either.
+    #[clap(long, conflicts_with = "only_extra")]
+    #[clap(default_value_t = false)]
+    pub only_generic: bool,
+
+    /// Only run extra artifacts' builds, skipping generic builds
+    ///
+    /// Nothing that needs every build's outputs, like archives and installers,
+    /// is made either.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub only_extra: bool,
+
+    /// Build a named profile (like "debug"), into a dist dir of its own
+    ///
+    /// Everything this build produces goes under target/distrib/<PROFILE>/, so
+    /// builds of different profiles don't overwrite each other. Generic builds
+    /// get the profile as CARGO_DIST_PROFILE, so they can build differently.
+    #[clap(long, value_name = "PROFILE")]
+    pub profile: Option<String>,
+
+    /// Give generic builds the flags of one of the flags-profiles in the config
+    ///
+    /// Its CFLAGS, CXXFLAGS and LDFLAGS are added to every generic build's,
+    /// before any target-flags.
+    #[clap(long, value_name = "PROFILE")]
+    pub flags_profile: Option<String>,
+
+    /// Build one of the variants in the config, instead of the default one
+    ///
+    /// Generic builds use the variant's build command and flags, and get its
+    /// name as CARGO_DIST_VARIANT.
+    #[clap(long, value_name = "VARIANT")]
+    pub variant: Option<String>,
+
+    /// Build generic builds with sanitizers, for checking for memory bugs before a release
+    ///
+    /// Adds -fsanitize= to their CFLAGS, CXXFLAGS and LDFLAGS, and smoke tests
+    /// run with the sanitizers' errors made fatal. Don't ship what this builds.
+    #[clap(long, value_delimiter(','), value_name = "SANITIZER")]
+    pub sanitize: Vec<Sanitizer>,
+
+    /// Build each generic build twice, and fail unless both builds made identical binaries
+    ///
+    /// The first build's binaries are set aside before the second build runs,
+    /// and any that differ are listed with where they first differ. Only the
+    /// second build's binaries are shipped.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub verify_reproducible: bool,
+
+    /// Only run one shard of the generic builds, like 2/5, to split them across CI jobs
+    ///
+    /// Targets are sorted, then dealt out to the shards in turn, so every
+    /// target's builds run in exactly one shard. Only generic builds are run:
+    /// nothing that needs every build's outputs, like archives, installers and
+    /// extra artifacts, is made.
+    #[clap(long, value_name = "I/N")]
+    pub shard: Option<String>,
+
+    /// Compare what was built with a previous release, given its dist-manifest.json
+    ///
+    /// The previous release's artifacts are looked for next to its manifest.
+    /// Artifacts that were added or removed are listed, along with how the size
+    /// of each one both releases have changed.
+    #[clap(long, value_name = "MANIFEST")]
+    pub compare_release: Option<Utf8PathBuf>,
+
+    /// With --compare-release, also compare the global symbols of symbols files and extra artifacts
+    ///
+    /// This needs `nm`.
+    #[clap(long, requires = "compare_release")]
+    #[clap(default_value_t = false)]
+    pub compare_symbols: bool,
+}
+
+/// A sanitizer to build with
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum Sanitizer {
+    /// AddressSanitizer
+    Address,
+    /// UndefinedBehaviorSanitizer
+    Undefined,
+    /// ThreadSanitizer
+    Thread,
+    /// MemorySanitizer
+    Memory,
+    /// LeakSanitizer
+    Leak,
+}
+
+impl Sanitizer {
+    /// Convert the application version of this enum to the library version
+    pub fn to_lib(self) -> cargo_dist::config::Sanitizer {
+        match self {
+            Sanitizer::Address => cargo_dist::config::Sanitizer::Address,
+            Sanitizer::Undefined => cargo_dist::config::Sanitizer::Undefined,
+            Sanitizer::Thread => cargo_dist::config::Sanitizer::Thread,
+            Sanitizer::Memory => cargo_dist::config::Sanitizer::Memory,
+            Sanitizer::Leak => cargo_dist::config::Sanitizer::Leak,
+        }
+    }
 }
 
 /// How we should select the artifacts to build
@@ -428,6 +680,74 @@ pub struct ManifestArgs {
 #[derive(Args, Clone, Debug)]
 pub struct PlanArgs {}
 
+#[derive(Args, Clone, Debug)]
+pub struct ListTargetsArgs {}
+
+#[derive(Args, Clone, Debug)]
+pub struct ToolchainEnvArgs {
+    /// How to print the toolchains
+    #[clap(long, value_enum)]
+    #[clap(default_value_t = ToolchainFormat::Shell)]
+    pub format: ToolchainFormat,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct DoctorArgs {
+    /// Print the version of each tool and package the builds would use
+    ///
+    /// Runs `--version` on every program the planned builds need, and asks
+    /// brew and pkg-config for their packages' versions, without building.
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub report_versions: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ConfigArgs {
+    /// Print what each planned build is given, after resolving everything
+    #[clap(long)]
+    #[clap(default_value_t = false)]
+    pub effective: bool,
+
+    /// Resolve the config for a build profile, like `build --profile`
+    #[clap(long, value_name = "PROFILE")]
+    pub profile: Option<String>,
+
+    /// Resolve the config with a flags profile, like `build --flags-profile`
+    #[clap(long, value_name = "PROFILE")]
+    pub flags_profile: Option<String>,
+
+    /// Resolve the config for a variant, like `build --variant`
+    #[clap(long, value_name = "VARIANT")]
+    pub variant: Option<String>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ExportMakefileArgs {
+    /// Write the Makefile to the named file instead of stdout
+    #[clap(long)]
+    pub output: Option<Utf8PathBuf>,
+}
+
+/// How to print toolchains
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum ToolchainFormat {
+    /// A block of shell `export`s
+    Shell,
+    /// A snippet of .cargo/config.toml
+    Cargo,
+}
+
+impl ToolchainFormat {
+    /// Convert the application version of this enum to the library version
+    pub fn to_lib(self) -> cargo_dist::config::ToolchainFormat {
+        match self {
+            ToolchainFormat::Shell => cargo_dist::config::ToolchainFormat::Shell,
+            ToolchainFormat::Cargo => cargo_dist::config::ToolchainFormat::Cargo,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputFormat {
     Human,
@@ -360,6 +360,10 @@ pub struct DistMetadata {
     /// Whether to install an updater program alongside the software
     #[serde(skip_serializing_if = "Option::is_none")]
     pub install_updater: Option<bool>,
+
+    /// Settings for generic (non-Cargo) builds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generic_build: Option<GenericBuildConfig>,
 }
 
 impl DistMetadata {
@@ -412,6 +416,7 @@ impl DistMetadata {
             tag_namespace: _,
             install_updater: _,
             github_releases_repo: _,
+            generic_build: _,
         } = self;
         if let Some(include) = include {
             for include in include {
@@ -484,6 +489,7 @@ impl DistMetadata {
             tag_namespace,
             install_updater,
             github_releases_repo,
+            generic_build,
         } = self;
 
         // Check for global settings on local packages
@@ -623,6 +629,9 @@ impl DistMetadata {
         if install_updater.is_none() {
             *install_updater = workspace_config.install_updater;
         }
+        if generic_build.is_none() {
+            generic_build.clone_from(&workspace_config.generic_build);
+        }
 
         // This was historically implemented as extend, but I'm not convinced the
         // inconsistency is worth the inconvenience...
@@ -663,6 +672,50 @@ pub struct Config {
     pub announcement_tag: Option<String>,
     /// What command was being invoked here, used for SystemIds
     pub root_cmd: String,
+    /// Whether generic builds get our stdin/stdout/stderr, so they can prompt for input
+    ///
+    /// Their output isn't captured (or logged) in this mode.
+    pub interactive_builds: bool,
+    /// Whether to print generic build commands in a form that can be pasted into a shell
+    pub print_build_commands: bool,
+    /// Whether each generic build writes a `repro-<target>.json` describing how to rerun it
+    pub repro_manifests: bool,
+    /// Whether to print what the local builds added to, changed in, and removed from the dist dir
+    pub diff_dist_dir: bool,
+    /// Whether to remove files from the dist dir that this run won't produce, before building
+    pub clean: bool,
+    /// Whether to just list what `clean` would remove, without removing it
+    pub clean_dry_run: bool,
+    /// Whether to skip generic builds that finished in an earlier run
+    pub resume: bool,
+    /// The sanitizers to build generic builds with, for verification builds
+    pub sanitizers: Vec<Sanitizer>,
+    /// Whether to build each generic build twice, and check both made the same binaries
+    pub verify_reproducible: bool,
+    /// Which shard of the generic builds to run, for splitting them across CI jobs
+    pub shard: Option<Shard>,
+    /// The manifest of a previous release to compare what was built with
+    pub compare_release: Option<Utf8PathBuf>,
+    /// Whether comparing with a previous release compares symbols too
+    pub compare_symbols: bool,
+    /// Whether to ask brew for its environment even if it's cached from an earlier run
+    pub no_brew_cache: bool,
+    /// Variables to set for generic builds from `--build-arg KEY=VALUE`, in order
+    pub build_args: Vec<(String, String)>,
+    /// Where generic builds stage their outputs before they're copied to the dist dir
+    pub staging_dir: Option<Utf8PathBuf>,
+    /// Whether each target's generic build is staged in a dist dir subdirectory of its own
+    pub target_staging: bool,
+    /// Whether to only run generic builds
+    pub only_generic: bool,
+    /// Whether to only run extra artifacts' builds
+    pub only_extra: bool,
+    /// The build profile (like "debug"), whose outputs go in a dist dir of their own
+    pub build_profile: Option<String>,
+    /// The flags profile (from `flags-profiles`) generic builds get the flags of
+    pub flags_profile: Option<String>,
+    /// The variant (from `variants`) to build, if not the default one
+    pub variant: Option<String>,
 }
 
 /// How we should select the artifacts to build
@@ -1393,11 +1446,948 @@ pub enum ProductionMode {
 /// An extra artifact to upload alongside the release tarballs,
 /// and the build command which produces it.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct ExtraArtifact {
     /// The build command to invoke
     pub build: Vec<String>,
     /// The artifact(s) produced via this build script
     pub artifacts: Vec<String>,
+    /// What to publish the artifacts as, with `{name}`, `{version}` and `{target}` placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_template: Option<String>,
+    /// The target the artifacts are for, if they're for one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// What kind of file specific artifacts should be, mapped by artifact, to check them once built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formats: Option<BTreeMap<String, ArtifactFormat>>,
+}
+
+/// Settings for generic builds, read from `[dist.generic-build]` in dist.toml
+/// (or `[workspace.metadata.dist.generic-build]` in Cargo.toml)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GenericBuildConfig {
+    /// What kind of build system the build-command drives
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builder: Option<GenericBuilder>,
+    /// For Go builds, whether cgo should be enabled
+    ///
+    /// Defaults to false, producing pure-Go and easily cross-compiled binaries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgo: Option<bool>,
+    /// For CMake builds, the directory to configure and build in
+    ///
+    /// Each target gets its own subdirectory of this. Defaults to "build".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmake_build_dir: Option<Utf8PathBuf>,
+    /// For CMake builds, toolchain files to pass as CMAKE_TOOLCHAIN_FILE, mapped by target triple
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmake_toolchain_files: Option<BTreeMap<String, Utf8PathBuf>>,
+    /// Cross-compilation toolchain files (relative to the workspace) for any builder, mapped by target triple
+    ///
+    /// Builds get it as CARGO_DIST_TOOLCHAIN_FILE, and CMake builds as CMAKE_TOOLCHAIN_FILE.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain_files: Option<BTreeMap<String, Utf8PathBuf>>,
+    /// Whether to run builds attached to a pseudo-terminal instead of pipes (unix only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pty: Option<bool>,
+    /// Whether to set CLICOLOR_FORCE/FORCE_COLOR so builds keep their colors,
+    /// even though we're piping their output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_color: Option<bool>,
+    /// A directory (relative to the workspace) for builds to use as TMPDIR/TEMP/TMP, created if missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmp_dir: Option<Utf8PathBuf>,
+    /// A container image to run builds inside of (e.g. "ghcr.io/cross-rs/aarch64-unknown-linux-gnu")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_image: Option<String>,
+    /// The container engine to run container-image with (defaults to "docker")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_engine: Option<String>,
+    /// Container images to extract sysroots for C/C++ builds from, mapped by target triple
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sysroots: Option<BTreeMap<String, SysrootImage>>,
+    /// Sysroot directories for C/C++ builds, mapped by target triple (these win over `sysroots`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sysroot_dirs: Option<BTreeMap<String, Utf8PathBuf>>,
+    /// Machines to run builds on over SSH, mapped by target triple (experimental)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_hosts: Option<BTreeMap<String, RemoteHost>>,
+    /// A compiler cache to run C/C++ compilers through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler_cache: Option<CompilerCache>,
+    /// Write a single checksum file covering every generic and extra build output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combined_checksum: Option<ChecksumStyle>,
+    /// The name of the combined-checksum file (defaults to e.g. "SHA256SUMS")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combined_checksum_file: Option<String>,
+    /// Whether to bundle the build logs into a build-logs.tar.gz artifact (they may reveal the build environment)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_build_logs: Option<bool>,
+    /// What to do with build outputs that are symlinks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlinks: Option<SymlinkMode>,
+    /// Whether build outputs are copied into the dist dir, or hard-linked there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_mode: Option<CopyMode>,
+    /// The octal modes to give outputs once they're copied, by the name they're copied under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_modes: Option<BTreeMap<String, String>>,
+    /// Check outputs at least this many megabytes have the same sha256 as their source after copying them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_copies_over_mb: Option<u64>,
+    /// Whether to check and copy each target's outputs while the next target builds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline_builds: Option<bool>,
+    /// Write an SBOM in this format for each binary, listing the dependencies we know the build used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sbom: Option<SbomFormat>,
+    /// What to do about binaries that are already where a build will put them, before it runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preexisting_outputs: Option<PreexistingOutputs>,
+    /// Whether to show compiler errors and warnings from builds as GitHub Actions annotations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_annotations: Option<bool>,
+    /// The targets to build for when none are given with `--target` (`"host"` is the host's)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_targets: Option<Vec<String>>,
+    /// Custom key/values to record with every build's binaries in the dist manifest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_metadata: Option<BTreeMap<String, String>>,
+    /// Custom key/values for particular targets' binaries, added to `artifact-metadata`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_artifact_metadata: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    /// What to do about binaries builds make alongside the ones they're expected to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undeclared_outputs: Option<UndeclaredOutputs>,
+    /// Whether to write a timing profile of each stage of each target's build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing_profiles: Option<bool>,
+    /// How to rename binaries different targets would copy to the same place, with `{name}`, `{target}` and `{arch}` placeholders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collision_rename: Option<String>,
+    /// What to name each target's archive, with `{name}`, `{version}`, `{target}`, `{arch}`, `{os}` and `{ext}` placeholders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_name: Option<String>,
+    /// What to name particular targets' archives, overriding `archive-name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_archive_names: Option<BTreeMap<String, String>>,
+    /// Whether to wait for each output's size to settle before copying it (for network filesystems)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for_stable_outputs: Option<bool>,
+    /// How many more times to look for outputs that aren't there when a build finishes (for network filesystems)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_output_retries: Option<u32>,
+    /// How long to wait between looks for missing outputs, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_output_retry_delay_ms: Option<u64>,
+    /// Non-zero exit codes that should be treated as success (with a warning)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerable_exit_codes: Option<Vec<i32>>,
+    /// How much of builds' output and commands to show
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<BuildVerbosity>,
+    /// How much to show for particular targets' builds, overriding `verbosity`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_verbosity: Option<BTreeMap<String, BuildVerbosity>>,
+    /// Which of a failed build's output streams to show the end of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_output: Option<FailureOutput>,
+    /// How many lines of each stream to show when a build fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_output_lines: Option<usize>,
+    /// Whether a failed build whose chosen streams were empty shows the other one instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_output_fallback: Option<bool>,
+    /// The most output (in MiB) to keep from each build command, after which the rest is dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_mb: Option<u64>,
+    /// Whether to run build commands in a login shell, so that e.g. ~/.profile is loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_shell: Option<bool>,
+    /// The shell to use for login-shell (defaults to bash, or powershell on windows)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// A shell command line to build with, instead of the package's build-command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_script: Option<String>,
+    /// The interpreter (and its flags) to run build-script with
+    ///
+    /// Defaults to `["sh", "-c"]`, or `["cmd", "/C"]` on windows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_shell: Option<Vec<String>>,
+    /// Per-binary build commands, for binaries that aren't produced by the workspace's build command
+    ///
+    /// Binaries for the same target with different commands get separate builds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_build_commands: Option<BTreeMap<String, Vec<String>>>,
+    /// Arguments appended to the build command for each target, which may use `{target}` placeholders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_args: Option<Vec<String>>,
+    /// Whether a target's binaries with different build commands share one build instead
+    ///
+    /// Its commands run one after another, in the same environment.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combine_build_commands: Option<bool>,
+    /// Binaries (by name) whose builds only make sense on the host, so they're never cross-compiled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_only: Option<Vec<String>>,
+    /// Binaries (by name) whose builds have to finish before a binary's build, mapped by binary name
+    ///
+    /// Only matters when they're produced by different builds for the same target.
+    /// Data builds can be listed too.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<BTreeMap<String, Vec<String>>>,
+    /// Builds that make files for other builds (that list them in `depends-on`) rather than for shipping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_builds: Option<Vec<DataBuild>>,
+    /// Whether to turn likely misconfigurations (like a build with no binaries) into errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+    /// Whether to build in a scratch copy of the source tree, instead of in-tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isolated_build: Option<bool>,
+    /// pkg-config packages whose flags should be passed to the build via CFLAGS/LDFLAGS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pkg_config_deps: Option<Vec<String>>,
+    /// Extra CFLAGS/LDFLAGS for specific targets, mapped by target triple or architecture
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_flags: Option<BTreeMap<String, TargetFlags>>,
+    /// Whether to stamp build outputs with SOURCE_DATE_EPOCH, for reproducible archives
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reproducible: Option<bool>,
+    /// Whether to run builds without variables that leak who ran them and where, and with prefix-mapped paths
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reproducible_env: Option<bool>,
+    /// Glob patterns for files to save to dist_dir/build-failures/<target>/ when a build fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_artifacts: Option<Vec<String>>,
+    /// The directory builds write their binaries to, relative to where they run
+    ///
+    /// Passed to the build as CARGO_DIST_OUT_DIR, and used to find its binaries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<Utf8PathBuf>,
+    /// Non-binary files (man pages, completions...) the build produces that should be shipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_outputs: Option<Vec<ExtraOutput>>,
+    /// How much nicer than us builds should be (like `nice -n`), to leave CPU for other work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// Whether builds should only get IO time nothing else wants (like `ionice -c 3`, Linux only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_io: Option<bool>,
+    /// The most memory (in MiB) a build may use, per process on unix and in total on Windows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// The most CPU time (in seconds) a build may use, per process on unix and in total on Windows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cpu_seconds: Option<u64>,
+    /// How much space must stay free on the dist dir's filesystem for builds to go ahead, like "5GiB"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_free_space: Option<String>,
+    /// A file (relative to the workspace) to give builds as their stdin, instead of nothing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin_file: Option<Utf8PathBuf>,
+    /// Scripts (relative to the workspace) to source for the environment builds run in, in order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup_scripts: Option<Vec<Utf8PathBuf>>,
+    /// Variables to set for builds, whose names and values may use `{target}` placeholders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_env: Option<BTreeMap<String, String>>,
+    /// Files the build uses (like vendored tarballs) and the sha256 they must have
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_inputs: Option<Vec<InputChecksum>>,
+    /// Whether builds use Homebrew's environment, and whether a Brewfile is required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brew_mode: Option<BrewMode>,
+    /// Whether to gather each build's compile_commands.json into the dist dir, and merge them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compile_commands: Option<CompileCommands>,
+    /// The targets whose builds get Homebrew's environment (all of them if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brew_targets: Option<Vec<String>>,
+    /// A `.env` file (relative to the workspace) of variables to set for builds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Utf8PathBuf>,
+    /// Which other variables the env-file's variables win out over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file_precedence: Option<EnvFilePrecedence>,
+    /// `.env` files for specific targets, mapped by target triple, layered over env-file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_env_files: Option<BTreeMap<String, Utf8PathBuf>>,
+    /// How much of our environment builds inherit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_policy: Option<EnvPolicy>,
+    /// How much of our environment particular targets' builds inherit, overriding `env-policy`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_env_policy: Option<BTreeMap<String, EnvPolicy>>,
+    /// Variables (or prefixes, ending in `*`) builds inherit under the `filtered` env-policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_allowlist: Option<Vec<String>>,
+    /// Prefixes of inherited environment variables (like "GITHUB_") to hide from builds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_env_prefixes: Option<Vec<String>>,
+    /// Variables to keep even if they match strip-env-prefixes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_env: Option<Vec<String>>,
+    /// A prefix to add to the names of variables passed with `--build-arg`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_arg_prefix: Option<String>,
+    /// How many more times to run a build that fails (defaults to 0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Only retry builds whose output contains one of these strings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_on: Option<Vec<String>>,
+    /// How many more times to run all the builds when one fails because of the machine rather than the build (defaults to 0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infra_retries: Option<u32>,
+    /// More exit codes that mean a build failed because of the machine, for `infra-retries`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub infra_exit_codes: Option<Vec<i32>>,
+    /// Whether to fail builds that modify files tracked by git
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forbid_source_changes: Option<bool>,
+    /// Whether to trace which network addresses builds connect to, and report them (Linux, needs strace)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_audit: Option<bool>,
+    /// Whether builds are declared not to need the network, so they're run without one (Linux)
+    ///
+    /// With `network-audit`, a build that tries to use it anyway fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_free: Option<bool>,
+    /// The linker builds should use (like "mold" or "lld"), independently of CC/CXX
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linker: Option<String>,
+    /// Which compilers to look for, most preferred first, when CC/CXX aren't set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler_preference: Option<Vec<CompilerFamily>>,
+    /// Whether to link binaries with a build ID and record it in their provenance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_id: Option<bool>,
+    /// Named sets of compiler and linker flags, one of which `--flags-profile` picks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags_profiles: Option<BTreeMap<String, TargetFlags>>,
+    /// Mutually exclusive ways to build, one of which is picked for each release with `--variant`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variants: Option<BTreeMap<String, BuildVariant>>,
+    /// The variant to build when `--variant` isn't passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_variant: Option<String>,
+    /// Whether to ship stripped binaries, with their debug info in separate `.debug` files (ELF only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split_debuginfo: Option<bool>,
+    /// Whether to zstd-compress symbols files as they're copied to the dist dir
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress_symbols: Option<bool>,
+    /// Whether a binary's missing symbols file only warns, instead of failing the build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbols_optional: Option<bool>,
+    /// A regex that fails the build if any line of its output matches, even if it exits zero
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_pattern: Option<String>,
+    /// A regex that some line of a successful build's output must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success_pattern: Option<String>,
+    /// The uid to run builds as, so their outputs aren't owned by root (unix or containers only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_uid: Option<u32>,
+    /// The gid to run builds as (unix or containers only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_gid: Option<u32>,
+    /// Whether to check binaries are in the executable format their target uses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_binary_formats: Option<bool>,
+    /// The format specific binaries should be in, mapped by binary name (implies checking them)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_formats: Option<BTreeMap<String, BinaryFormat>>,
+    /// The range of sizes specific binaries should be within, mapped by binary name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_sizes: Option<BTreeMap<String, SizeRange>>,
+    /// Whether to check binaries are for the CPU architecture of their target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_architectures: Option<bool>,
+    /// Globs of libraries Linux binaries mustn't link against, like "libssl.so*"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disallowed_libraries: Option<Vec<String>>,
+    /// The newest glibc Linux binaries may need symbols from, like "2.17"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_glibc: Option<String>,
+    /// Whether to rewrite the rpaths of unix binaries to `../lib` relative to themselves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_rpath: Option<bool>,
+    /// Binaries (by name) that builds may not produce, which only warns instead of failing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_missing: Option<Vec<String>>,
+    /// Directories (by binary name) that binaries with unpredictable filenames are built into
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unnamed_outputs: Option<BTreeMap<String, Utf8PathBuf>>,
+    /// A JSON or TOML file (relative to the workspace) listing more binaries to build, and where to copy them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binaries_file: Option<Utf8PathBuf>,
+    /// A command to run when a build doesn't produce a binary, before we give up on it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_missing: Option<Vec<String>>,
+    /// Whether an extra build that's missing some artifacts still copies the ones it made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_partial_extra_artifacts: Option<bool>,
+    /// A command to run over everything a build copied into the dist dir
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_process: Option<Vec<String>>,
+    /// Commands to check binaries with once they're copied, with `{path}` placeholders, mapped by binary name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_validators: Option<BTreeMap<String, Vec<String>>>,
+    /// A command to run once before any generic or extra build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_all: Option<Vec<String>>,
+    /// A command to run once after every generic and extra build, even if some failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_all: Option<Vec<String>>,
+    /// Whether to extract each archive of binaries and check they run from there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoke_test: Option<bool>,
+    /// What the smoke test runs binaries with, instead of `--version`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smoke_test_args: Option<Vec<String>>,
+    /// Where to write build events as JSON Lines: a path (relative to the workspace) or `fd:N`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_stream: Option<String>,
+    /// An install layout (bin/lib/share) to arrange each target's outputs into
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<InstallLayout>,
+    /// How to notarize outputs for Apple targets with Apple's notary service (off if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notarize: Option<NotarizeConfig>,
+    /// An S3-compatible bucket to mirror everything that was built to (off if unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_mirror: Option<S3MirrorConfig>,
+    /// Whether to record a hash of each build's commands and environment in its provenance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_signature: Option<bool>,
+    /// The paths (relative to the workspace) whose changes `--watch` rebuilds for
+    ///
+    /// Defaults to the whole workspace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_paths: Option<Vec<Utf8PathBuf>>,
+    /// Paths to watch for specific binaries, mapped by binary name
+    ///
+    /// Overrides `watch-paths` for the builds of those binaries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_watch_paths: Option<BTreeMap<String, Vec<Utf8PathBuf>>>,
+    /// Whether to write an `artifacts-<target>.json` listing what was built for each target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_lists: Option<bool>,
+    /// Whether to append each artifact to `staging-manifest.jsonl` as soon as it's copied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staging_manifest: Option<bool>,
+}
+
+/// Extra compiler and linker flags, for a target from `target-flags` or from `flags-profiles`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TargetFlags {
+    /// Added to CFLAGS (and CPPFLAGS)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cflags: Option<Flags>,
+    /// Added to CXXFLAGS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cxxflags: Option<Flags>,
+    /// Added to LDFLAGS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ldflags: Option<Flags>,
+}
+
+/// Compiler or linker flags, as one string or a list of them
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Flags {
+    /// `"-O2 -g"`
+    Line(String),
+    /// `["-O2", "-g"]`
+    List(Vec<String>),
+}
+
+impl Flags {
+    /// The flags as they'd go in an environment variable
+    pub fn joined(&self) -> String {
+        match self {
+            Flags::Line(line) => line.clone(),
+            Flags::List(list) => list.join(" "),
+        }
+    }
+}
+
+/// Where to arrange generic build outputs, read from `[dist.generic-build.layout]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InstallLayout {
+    /// The directory (relative to the dist dir) to put layouts in, one per target
+    ///
+    /// Defaults to "layout".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<Utf8PathBuf>,
+    /// Where executables (and Windows DLLs) go, defaults to "bin"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin_dir: Option<Utf8PathBuf>,
+    /// Where libraries go, defaults to "lib"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lib_dir: Option<Utf8PathBuf>,
+    /// Where extra-outputs go, defaults to "share"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_dir: Option<Utf8PathBuf>,
+}
+
+/// Credentials for notarizing macOS outputs, read from `[dist.generic-build.notarize]`
+///
+/// Either `keychain-profile`, or an Apple ID and team ID (which can also come
+/// from `APPLE_ID` and `APPLE_TEAM_ID`) with a password from the environment.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NotarizeConfig {
+    /// A profile saved with `xcrun notarytool store-credentials`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keychain_profile: Option<String>,
+    /// The Apple ID to submit as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apple_id: Option<String>,
+    /// The developer team to submit for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<String>,
+    /// The environment variable with the app-specific password, defaults to APPLE_NOTARY_PASSWORD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+}
+
+/// Where to mirror build outputs to, read from `[dist.generic-build.s3-mirror]`
+///
+/// `endpoint`, `bucket` and `prefix` can also come from `CARGO_DIST_S3_ENDPOINT`,
+/// `CARGO_DIST_S3_BUCKET` and `CARGO_DIST_S3_PREFIX`. Credentials only ever come
+/// from the environment.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct S3MirrorConfig {
+    /// The URL of the S3-compatible service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// The bucket to upload to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// What to put in front of every object's key, like `releases/v1.0.0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// The region to sign requests for, defaults to us-east-1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// The environment variable with the access key ID, defaults to AWS_ACCESS_KEY_ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_id_env: Option<String>,
+    /// The environment variable with the secret access key, defaults to AWS_SECRET_ACCESS_KEY
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_access_key_env: Option<String>,
+}
+
+/// How much of a build to show as it runs
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildVerbosity {
+    /// Only log the build's output (a failure still shows the end of it)
+    Quiet,
+    /// Show the build's output
+    #[default]
+    Normal,
+    /// Show the build's output, and the commands that were run
+    Verbose,
+}
+
+/// Which output streams to show the end of when a build fails
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureOutput {
+    /// Both stdout and stderr
+    #[default]
+    Both,
+    /// Just stdout
+    Stdout,
+    /// Just stderr
+    Stderr,
+    /// Neither (everything is still in the build log)
+    None,
+}
+
+impl FailureOutput {
+    /// Whether stdout is shown
+    pub fn stdout(self) -> bool {
+        matches!(self, FailureOutput::Both | FailureOutput::Stdout)
+    }
+
+    /// Whether stderr is shown
+    pub fn stderr(self) -> bool {
+        matches!(self, FailureOutput::Both | FailureOutput::Stderr)
+    }
+}
+
+/// How builds use Homebrew's environment
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrewMode {
+    /// Use it if brew is installed and there's a Brewfile
+    #[default]
+    Auto,
+    /// Use it, and fail if brew is installed but there's no Brewfile
+    Require,
+    /// Never use it, like setting `DO_NOT_USE_BREWFILE`
+    Off,
+}
+
+/// What to do with the compile_commands.json builds produce
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompileCommands {
+    /// Leave them where the builds put them
+    #[default]
+    Off,
+    /// Copy each target's into the dist dir
+    PerTarget,
+    /// Copy each target's into the dist dir, and merge them all into one
+    Merged,
+}
+
+/// How variables from an env-file are layered with the ones we set for builds
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvFilePrecedence {
+    /// Everything else we set (including Homebrew's environment) overrides the env-file
+    Lowest,
+    /// The env-file overrides Homebrew's environment, but not the variables we set ourselves
+    #[default]
+    AboveBrew,
+    /// The env-file overrides everything
+    Highest,
+}
+
+/// How much of our environment builds inherit
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvPolicy {
+    /// All of it
+    #[default]
+    Inherit,
+    /// Only what `env-allowlist` names (and what's needed to run anything)
+    Filtered,
+    /// Only what's needed to run anything
+    Clean,
+}
+
+/// A build that makes files for other builds to use, rather than anything to ship
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DataBuild {
+    /// Its name, which the builds that need its files list in `depends-on`
+    pub name: String,
+    /// The command that makes the files
+    pub build_command: Vec<String>,
+    /// Glob patterns for the files, relative to the workspace (where the build runs)
+    pub outputs: Vec<String>,
+}
+
+/// Some non-binary files a generic build produces, and where to put them
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExtraOutput {
+    /// A glob pattern for the files, relative to where the build runs
+    pub path: String,
+    /// The directory (relative to the dist dir) to copy them to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest: Option<Utf8PathBuf>,
+}
+
+/// What to do when a build output is a symlink (e.g. `libfoo.so -> libfoo.so.1.2.3`)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkMode {
+    /// Copy whatever the symlink points to, under the symlink's name
+    #[default]
+    Follow,
+    /// Reproduce the whole chain of symlinks, and the real file at the end of it
+    Preserve,
+    /// Copy the real file under its real name, and link to it directly
+    Recreate,
+}
+
+/// How build outputs get into the dist dir
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CopyMode {
+    /// Copy them, so the dist dir has files of its own
+    #[default]
+    Copy,
+    /// Hard-link them where possible (on the same filesystem), copying otherwise
+    Hardlink,
+}
+
+/// An executable format, for checking what a build produced
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinaryFormat {
+    /// Linux and most other unixes
+    Elf,
+    /// Apple platforms
+    MachO,
+    /// Windows
+    Pe,
+    /// WebAssembly
+    Wasm,
+    /// Anything goes (e.g. for scripts)
+    Any,
+}
+
+impl std::fmt::Display for BinaryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryFormat::Elf => "ELF".fmt(f),
+            BinaryFormat::MachO => "Mach-O".fmt(f),
+            BinaryFormat::Pe => "PE".fmt(f),
+            BinaryFormat::Wasm => "WebAssembly".fmt(f),
+            BinaryFormat::Any => "any".fmt(f),
+        }
+    }
+}
+
+/// A family of C/C++ compilers, named by how they're run
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompilerFamily {
+    /// clang and clang++
+    Clang,
+    /// gcc and g++
+    Gcc,
+}
+
+impl std::fmt::Display for CompilerFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompilerFamily::Clang => "clang".fmt(f),
+            CompilerFamily::Gcc => "gcc".fmt(f),
+        }
+    }
+}
+
+/// What kind of file an extra artifact is, for checking what a build produced
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactFormat {
+    /// A non-empty script, starting with a shebang (unless it's for Windows)
+    Script,
+    /// A zip, tarball, or compressed file
+    Archive,
+    /// A JSON document
+    Json,
+    /// A container image, as an OCI layout directory or a tarball of one
+    OciImage,
+}
+
+impl std::fmt::Display for ArtifactFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArtifactFormat::Script => "script".fmt(f),
+            ArtifactFormat::Archive => "archive".fmt(f),
+            ArtifactFormat::Json => "JSON".fmt(f),
+            ArtifactFormat::OciImage => "OCI image".fmt(f),
+        }
+    }
+}
+
+/// The sizes a binary should be between, like "100KB" or "1.5GiB"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SizeRange {
+    /// Anything smaller is probably a broken build
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+    /// Anything larger is probably the wrong file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+}
+
+/// A file a build uses, and the hash it should have
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InputChecksum {
+    /// Where the file is (or where the build downloads it to), relative to where the build runs
+    pub path: Utf8PathBuf,
+    /// Where the file comes from, for error messages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The file's sha256, in hex
+    pub sha256: String,
+}
+
+/// Where to get a target's sysroot from, from `sysroots`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SysrootImage {
+    /// The container image to extract it from
+    pub image: String,
+    /// The path within the image to extract, if not `/`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<Utf8PathBuf>,
+}
+
+/// One way to build, from `variants`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildVariant {
+    /// The command to build this variant with, if not the usual one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_command: Option<Vec<String>>,
+    /// Flags to build this variant with, on top of the usual ones
+    #[serde(flatten)]
+    pub flags: TargetFlags,
+}
+
+/// A machine to run a target's builds on, from `remote-hosts`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemoteHost {
+    /// Where to ssh to (like "me@mac-mini")
+    pub host: String,
+    /// The directory on it to copy the workspace to, if not `cargo-dist-remote/<workspace>`
+    ///
+    /// Relative paths are relative to the remote user's home directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<Utf8PathBuf>,
+}
+
+/// What to do about binaries that are already where a build will put them
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PreexistingOutputs {
+    /// Leave them, and let the build overwrite them
+    #[default]
+    Keep,
+    /// Delete them before the build
+    CleanBeforeBuild,
+    /// Fail the build
+    ErrorOnPreexisting,
+}
+
+/// One of several CI jobs the generic builds are split across, from `--shard 2/5`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Shard {
+    /// Which shard this is, counting from 1
+    pub index: usize,
+    /// How many shards there are
+    pub count: usize,
+}
+
+/// Parse a `--shard` like `2/5`
+pub fn parse_shard(shard: &str) -> DistResult<Shard> {
+    let parsed = shard
+        .split_once('/')
+        .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)));
+    match parsed {
+        Some((index, count)) if index >= 1 && index <= count => Ok(Shard { index, count }),
+        _ => Err(DistError::InvalidShard {
+            shard: shard.to_owned(),
+        }),
+    }
+}
+
+/// A sanitizer to build with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sanitizer {
+    /// AddressSanitizer
+    Address,
+    /// UndefinedBehaviorSanitizer
+    Undefined,
+    /// ThreadSanitizer
+    Thread,
+    /// MemorySanitizer
+    Memory,
+    /// LeakSanitizer
+    Leak,
+}
+
+impl Sanitizer {
+    /// Its name in `-fsanitize=`
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+            Sanitizer::Leak => "leak",
+        }
+    }
+}
+
+/// What to do about binaries a build makes that it isn't expected to
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UndeclaredOutputs {
+    /// Don't look for them
+    #[default]
+    Ignore,
+    /// Warn about them
+    Warn,
+    /// Fail the build
+    Error,
+}
+
+/// The format of the SBOMs generic builds write
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SbomFormat {
+    /// [CycloneDX](https://cyclonedx.org) 1.5 JSON
+    Cyclonedx,
+    /// [SPDX](https://spdx.dev) 2.3 JSON
+    Spdx,
+}
+
+/// A compiler cache usable by generic builds
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompilerCache {
+    /// [sccache](https://github.com/mozilla/sccache)
+    Sccache,
+    /// [ccache](https://ccache.dev)
+    Ccache,
+}
+
+/// How to print the toolchains generic builds use
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToolchainFormat {
+    /// A shell script exporting target-specific environment variables
+    Shell,
+    /// A snippet of .cargo/config.toml
+    Cargo,
+}
+
+/// The kind of build system a generic build drives
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GenericBuilder {
+    /// Run the build-command with C/C++ compiler environment variables
+    #[default]
+    Command,
+    /// Run the build-command as a Go build, additionally setting GOOS/GOARCH/CGO_ENABLED
+    Go,
+    /// Configure and build a CMake project out-of-source, using the build-command's
+    /// first entry as the cmake executable and the rest as extra configure arguments
+    Cmake,
+}
+
+impl std::fmt::Display for GenericBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenericBuilder::Command => "command".fmt(f),
+            GenericBuilder::Go => "go".fmt(f),
+            GenericBuilder::Cmake => "cmake".fmt(f),
+        }
+    }
 }
 
 impl std::fmt::Display for ProductionMode {
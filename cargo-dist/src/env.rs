@@ -1,13 +1,17 @@
 //! Functions to parse and manipulate the environment
 
-use std::env;
+use std::{collections::BTreeMap, env};
 
 use crate::{
+    config::{BrewMode, Flags, TargetFlags},
     errors::{DistError, DistResult},
     DistGraph, SortedMap,
 };
 use axoprocess::Cmd;
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
 
 /// Fetches the Homebrew environment from `brew bundle exec`
 pub fn fetch_brew_env(dist_graph: &DistGraph) -> DistResult<Option<String>> {
@@ -30,6 +34,196 @@ pub fn fetch_brew_env(dist_graph: &DistGraph) -> DistResult<Option<String>> {
     Ok(None)
 }
 
+/// The Homebrew environment builds get, from [`fetch_brew_env`][]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrewEnv {
+    /// The raw output of `brew bundle exec -- env`
+    pub output: String,
+    /// The variables builds should get (see [`select_brew_env`][])
+    pub extra_env: Vec<(String, String)>,
+    /// CFLAGS for brew's packages
+    pub cflags: String,
+    /// LDFLAGS for brew's packages
+    pub ldflags: String,
+}
+
+/// The Homebrew environment builds get, if any
+///
+/// The Brewfile doesn't change mid-run, so this only asks brew the first time,
+/// and reuses the answer for every later build. It's also cached on disk (see
+/// [`brew_env_cache_path`][]), so later runs with the same Brewfile don't ask
+/// brew at all, unless `--no-brew-cache` is passed. `DO_NOT_USE_BREWFILE` or
+/// `brew-mode = "off"` disables it, and `brew-mode = "require"` makes a missing
+/// Brewfile an error when brew is installed.
+pub fn brew_env(dist_graph: &DistGraph) -> DistResult<Option<&BrewEnv>> {
+    let mode = dist_graph.generic_build.brew_mode.unwrap_or_default();
+    if mode == BrewMode::Off || env::var("DO_NOT_USE_BREWFILE").is_ok() {
+        return Ok(None);
+    }
+    if let Some(cached) = dist_graph.brew_env.get() {
+        return Ok(cached.as_ref());
+    }
+    let brewfile = Utf8Path::new("Brewfile");
+    if !use_brew(mode, dist_graph.tools.brew.is_some(), brewfile)? {
+        return Ok(dist_graph.brew_env.get_or_init(|| None).as_ref());
+    }
+    let cache_path = if dist_graph.no_brew_cache || dist_graph.tools.brew.is_none() {
+        None
+    } else {
+        brew_env_cache_path(dist_graph, brewfile)
+    };
+    if let Some(cached) = cache_path.as_deref().and_then(read_brew_env_cache) {
+        return Ok(dist_graph.brew_env.get_or_init(|| Some(cached)).as_ref());
+    }
+    let computed = match fetch_brew_env(dist_graph)? {
+        Some(output) => {
+            let parsed = parse_env(&output)?;
+            let extra_env = select_brew_env(&parsed);
+            let cflags = calculate_cflags(&parsed);
+            let ldflags = calculate_ldflags(&parsed);
+            Some(BrewEnv {
+                output: output.clone(),
+                extra_env,
+                cflags,
+                ldflags,
+            })
+        }
+        None => None,
+    };
+    if let (Some(path), Some(computed)) = (&cache_path, &computed) {
+        let contents = serde_json::to_string(computed).expect("serde_json failed");
+        // The cache only saves time, so not being able to write it isn't worth failing over
+        if let Err(e) = axoasset::LocalAsset::write_new_all(&contents, path) {
+            warn!("couldn't cache the Homebrew environment: {e}");
+        }
+    }
+    Ok(dist_graph.brew_env.get_or_init(|| computed).as_ref())
+}
+
+/// Whether to ask brew for its environment, given the brew-mode
+///
+/// Errors if the mode requires a Brewfile, brew is installed, and there isn't one.
+pub fn use_brew(mode: BrewMode, brew_installed: bool, brewfile: &Utf8Path) -> DistResult<bool> {
+    let has_brewfile = brewfile.exists();
+    match mode {
+        BrewMode::Off => Ok(false),
+        BrewMode::Auto => Ok(brew_installed && has_brewfile),
+        BrewMode::Require if brew_installed && !has_brewfile => Err(DistError::BrewfileMissing {
+            path: brewfile.to_owned(),
+        }),
+        BrewMode::Require => Ok(brew_installed),
+    }
+}
+
+/// Where the Homebrew environment for a Brewfile is cached, if there's a Brewfile
+///
+/// The cache is keyed by a hash of the Brewfile, so editing it means asking brew again.
+pub fn brew_env_cache_path(dist_graph: &DistGraph, brewfile: &Utf8Path) -> Option<Utf8PathBuf> {
+    let contents = std::fs::read(brewfile).ok()?;
+    let mut hash = format!("{:x}", Sha256::digest(contents));
+    hash.truncate(16);
+    Some(
+        dist_graph
+            .target_dir
+            .join("cargo-dist-cache")
+            .join("brew-env")
+            .join(format!("{hash}.json")),
+    )
+}
+
+/// A cached Homebrew environment, if there is one we can read
+pub fn read_brew_env_cache(path: &Utf8Path) -> Option<BrewEnv> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// What separates the environment before and after sourcing setup scripts
+const SETUP_SCRIPTS_SOURCED: &str = "--- cargo-dist: setup-scripts sourced ---";
+
+/// The changes `setup-scripts` make to the environment, as (variable, new value or None if unset)
+///
+/// Like [`brew_env`][], this only sources them the first time, and reuses the
+/// answer for every later build.
+pub fn setup_env(dist_graph: &DistGraph) -> DistResult<&[(String, Option<String>)]> {
+    if let Some(cached) = dist_graph.setup_env.get() {
+        return Ok(cached);
+    }
+    let scripts = dist_graph
+        .generic_build
+        .setup_scripts
+        .as_deref()
+        .unwrap_or_default();
+    let computed = if scripts.is_empty() {
+        vec![]
+    } else {
+        let mut command = Cmd::new("sh", "source setup-scripts");
+        command
+            .arg("-c")
+            .arg(format!(
+                "env && for script do . \"$script\" || exit 1; done && echo '{SETUP_SCRIPTS_SOURCED}' && env"
+            ))
+            .arg("sh")
+            .current_dir(&dist_graph.workspace_dir);
+        for script in scripts {
+            let path = dist_graph.workspace_dir.join(script);
+            if !path.is_file() {
+                return Err(DistError::SetupScriptMissing { path });
+            }
+            command.arg(path);
+        }
+        let output = String::from_utf8_lossy(&command.output()?.stdout).into_owned();
+        let (before, after) = output
+            .split_once(&format!("\n{SETUP_SCRIPTS_SOURCED}\n"))
+            .unwrap_or((&output, ""));
+        env_delta(&parse_env(before)?, &parse_env(after)?)
+    };
+    Ok(dist_graph.setup_env.get_or_init(|| computed))
+}
+
+/// The commit the workspace is at, if git is installed and it's a git repo
+///
+/// Like [`setup_env`][], this only asks git the first time.
+pub fn git_commit(dist_graph: &DistGraph) -> Option<&str> {
+    dist_graph
+        .git_commit
+        .get_or_init(|| {
+            let git = dist_graph.tools.git.as_ref()?;
+            let output = Cmd::new(&git.cmd, "get the HEAD commit")
+                .arg("rev-parse")
+                .arg("HEAD")
+                .current_dir(&dist_graph.workspace_dir)
+                .stderr(std::process::Stdio::null())
+                .check(false)
+                .output()
+                .ok()?;
+            let commit = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+            (output.status.success() && !commit.is_empty()).then_some(commit)
+        })
+        .as_deref()
+}
+
+/// The variables that were set, changed, or unset between two environments
+pub fn env_delta(
+    before: &SortedMap<&str, &str>,
+    after: &SortedMap<&str, &str>,
+) -> Vec<(String, Option<String>)> {
+    // The shell keeps these up to date itself, so they change without meaning anything
+    const SHELL_VARS: &[&str] = &["_", "SHLVL", "PWD", "OLDPWD"];
+
+    let mut delta = vec![];
+    for (key, value) in after {
+        if !SHELL_VARS.contains(key) && before.get(key) != Some(value) {
+            delta.push((key.to_string(), Some(value.to_string())));
+        }
+    }
+    for key in before.keys() {
+        if !SHELL_VARS.contains(key) && !after.contains_key(key) {
+            delta.push((key.to_string(), None));
+        }
+    }
+    delta
+}
+
 /// Takes a string in KEY=value environment variable format and
 /// parses it into a BTreeMap. The string syntax is sh-compatible, and also the
 /// format returned by `env`.
@@ -143,3 +337,241 @@ pub fn calculate_cflags(environment: &SortedMap<&str, &str>) -> String {
         .collect::<Vec<String>>()
         .join(" ")
 }
+
+/// Asks pkg-config for the compiler and linker flags needed to build
+/// against the given packages, returning `(cflags, ldflags)`.
+/// Respects `PKG_CONFIG` for choosing which pkg-config to run.
+pub fn fetch_pkg_config_flags(packages: &[String]) -> DistResult<Option<(String, String)>> {
+    if packages.is_empty() {
+        return Ok(None);
+    }
+    let pkg_config = env::var("PKG_CONFIG").unwrap_or("pkg-config".to_owned());
+
+    // pkg-config's own errors for missing packages are a bit cryptic,
+    // so check for each of them up front to say exactly what's missing.
+    for package in packages {
+        let found = Cmd::new(&pkg_config, "check for pkg-config package")
+            .arg("--exists")
+            .arg(package)
+            .check(false)
+            .status()?;
+        if !found.success() {
+            return Err(DistError::PkgConfigPackageMissing {
+                package: package.clone(),
+            });
+        }
+    }
+
+    let mut flags = vec![];
+    for query in ["--cflags", "--libs"] {
+        let mut command = Cmd::new(&pkg_config, format!("pkg-config {query}"));
+        command.arg(query);
+        for package in packages {
+            command.arg(package);
+        }
+        let result = command.output()?;
+        flags.push(String::from_utf8_lossy(&result.stdout).trim().to_owned());
+    }
+    let ldflags = flags.pop().unwrap_or_default();
+    let cflags = flags.pop().unwrap_or_default();
+
+    Ok(Some((cflags, ldflags)))
+}
+
+/// Combines flags from multiple sources into one set of flags.
+/// Earlier sources take precedence, as compilers search include and
+/// library directories in the order they're given.
+pub fn merge_flags<'a>(sources: impl IntoIterator<Item = Option<&'a str>>) -> Option<String> {
+    let flags = sources
+        .into_iter()
+        .flatten()
+        .filter(|flags| !flags.is_empty())
+        .collect::<Vec<_>>();
+    if flags.is_empty() {
+        None
+    } else {
+        Some(flags.join(" "))
+    }
+}
+
+/// Flags for compilers and linkers, as they'd go in CFLAGS, CXXFLAGS and LDFLAGS
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompilerFlags {
+    /// For CFLAGS (and CPPFLAGS)
+    pub cflags: Option<String>,
+    /// For CXXFLAGS
+    pub cxxflags: Option<String>,
+    /// For LDFLAGS
+    pub ldflags: Option<String>,
+}
+
+/// The `target-flags` for a target
+///
+/// Flags for the target's architecture (like `aarch64`) come first, then
+/// flags for its full triple, so the more specific ones win when compilers
+/// take the last of conflicting flags (as they do with `-march`).
+pub fn target_flags(flags: &BTreeMap<String, TargetFlags>, target: &str) -> CompilerFlags {
+    let arch = target.split('-').next().unwrap_or(target);
+    let matching = [arch, target]
+        .into_iter()
+        .filter_map(|key| flags.get(key))
+        .collect::<Vec<_>>();
+    let merged = |pick: fn(&TargetFlags) -> Option<&Flags>| {
+        let joined = matching
+            .iter()
+            .filter_map(|flags| pick(flags).map(Flags::joined))
+            .collect::<Vec<_>>();
+        merge_flags(joined.iter().map(|flags| Some(flags.as_str())))
+    };
+    CompilerFlags {
+        cflags: merged(|flags| flags.cflags.as_ref()),
+        cxxflags: merged(|flags| flags.cxxflags.as_ref()),
+        ldflags: merged(|flags| flags.ldflags.as_ref()),
+    }
+}
+
+/// A target triple as it appears in variable names, like `aarch64_unknown_linux_gnu`
+pub fn target_underscore(target: &str) -> String {
+    target.replace(['-', '.'], "_")
+}
+
+/// Fills in the target placeholders in a variable's name or value
+///
+/// * `{target}`: the triple itself (`aarch64-unknown-linux-gnu`)
+/// * `{target_underscore}`: the triple with underscores (`aarch64_unknown_linux_gnu`),
+///   as the `cc` crate reads `CC_<target>`
+/// * `{target_upper}`: the same, in capitals (`AARCH64_UNKNOWN_LINUX_GNU`),
+///   as cargo reads `CARGO_TARGET_<TARGET>_LINKER`
+/// * `{arch}`: the triple's architecture (`aarch64`)
+///
+/// Without a target (for extra builds), there's nothing to fill them in with,
+/// so this returns `None` if there are any.
+pub fn expand_target_placeholders(template: &str, target: Option<&str>) -> Option<String> {
+    const PLACEHOLDERS: [&str; 4] = [
+        "{target}",
+        "{target_underscore}",
+        "{target_upper}",
+        "{arch}",
+    ];
+    let Some(target) = target else {
+        let templated = PLACEHOLDERS
+            .iter()
+            .any(|placeholder| template.contains(placeholder));
+        return (!templated).then(|| template.to_owned());
+    };
+    let underscore = target_underscore(target);
+    Some(
+        template
+            .replace("{target}", target)
+            .replace("{target_underscore}", &underscore)
+            .replace("{target_upper}", &underscore.to_ascii_uppercase())
+            .replace("{arch}", target.split('-').next().unwrap_or(target)),
+    )
+}
+
+/// Picks out the variables matching any of `prefixes`, except those in `keep`
+pub fn stripped_env_vars<'a>(
+    vars: impl IntoIterator<Item = &'a str>,
+    prefixes: &[String],
+    keep: &[String],
+) -> Vec<&'a str> {
+    vars.into_iter()
+        .filter(|var| {
+            prefixes
+                .iter()
+                .any(|prefix| var.starts_with(prefix.as_str()))
+        })
+        .filter(|var| !keep.iter().any(|kept| kept == var))
+        .collect()
+}
+
+/// Parses a `--build-arg` (`KEY=VALUE`, where the value may be empty)
+pub fn parse_build_arg(arg: &str) -> DistResult<(String, String)> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() && !key.contains(char::is_whitespace) => {
+            Ok((key.to_owned(), value.to_owned()))
+        }
+        _ => Err(DistError::InvalidBuildArg {
+            arg: arg.to_owned(),
+        }),
+    }
+}
+
+/// Reads a `.env` file, returning its variables in the order they're set
+pub fn load_env_file(path: &Utf8Path) -> DistResult<Vec<(String, String)>> {
+    let contents = axoasset::LocalAsset::load_string(path)?;
+    parse_dotenv(&contents).map_err(|(line_number, line)| DistError::DotenvParseError {
+        path: path.to_owned(),
+        line_number,
+        line,
+    })
+}
+
+/// Parses the contents of a `.env` file
+///
+/// This supports the common subset of the format: blank lines, `#` comments,
+/// an optional leading `export`, and values that are unquoted, 'single-quoted'
+/// (taken literally), or "double-quoted" (with `\n`, `\"`, and `\\` escapes).
+/// Variables aren't expanded. On failure, returns the (1-based) number and
+/// contents of the first malformed line.
+pub fn parse_dotenv(contents: &str) -> Result<Vec<(String, String)>, (usize, String)> {
+    let mut vars = vec![];
+    for (idx, raw_line) in contents.lines().enumerate() {
+        let malformed = || (idx + 1, raw_line.to_owned());
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or_else(malformed)?;
+        let key = key.trim();
+        let valid_key = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(malformed());
+        }
+        let value = value.trim();
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            quoted.strip_suffix('\'').ok_or_else(malformed)?.to_owned()
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let quoted = quoted.strip_suffix('"').ok_or_else(malformed)?;
+            unescape_double_quoted(quoted).ok_or_else(malformed)?
+        } else {
+            // Unquoted values end at a comment
+            match value.split_once(" #") {
+                Some((value, _comment)) => value.trim_end().to_owned(),
+                None => value.to_owned(),
+            }
+        };
+        vars.push((key.to_owned(), value));
+    }
+    Ok(vars)
+}
+
+/// Processes the escapes in a double-quoted `.env` value
+fn unescape_double_quoted(value: &str) -> Option<String> {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            // Unescaped quotes can only end the value
+            return None;
+        }
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => output.push('\n'),
+            'r' => output.push('\r'),
+            't' => output.push('\t'),
+            escaped @ ('"' | '\\' | '$') => output.push(escaped),
+            other => {
+                output.push('\\');
+                output.push(other);
+            }
+        }
+    }
+    Some(output)
+}
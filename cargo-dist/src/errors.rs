@@ -61,6 +61,10 @@ pub enum DistError {
     #[error(transparent)]
     AxotagError(#[from] axotag::errors::TagError),
 
+    /// random zip error
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+
     /// A problem with a jinja template, which is always a cargo-dist bug
     #[error("Failed to render template")]
     #[diagnostic(help("this is a bug in cargo-dist, let us know and we'll fix it: https://github.com/axodotdev/cargo-dist/issues/new"))]
@@ -275,6 +279,53 @@ pub enum DistError {
         line: String,
     },
 
+    /// A build modified files that are checked in
+    #[error("the generic build for {target} modified files tracked by git:\n  {files}")]
+    #[diagnostic(help("builds shouldn't regenerate checked-in files; commit the results, or have the build write them elsewhere"))]
+    BuildModifiedSources {
+        /// The target being built
+        target: String,
+        /// The files, one per line
+        files: String,
+    },
+
+    /// A build command can't be written as a line of a Makefile
+    #[error("this build command can't be put in a Makefile, as it contains a newline: {line}")]
+    MakefileUnrepresentable {
+        /// The command, as a shell command line
+        line: String,
+    },
+
+    /// A line of an env-file couldn't be parsed
+    #[error("couldn't parse line {line_number} of {path}: {line}")]
+    #[diagnostic(help("lines should look like KEY=value, and quotes must be closed"))]
+    DotenvParseError {
+        /// The env file
+        path: Utf8PathBuf,
+        /// Which line it was (1-based)
+        line_number: usize,
+        /// The line of text that couldn't be parsed
+        line: String,
+    },
+
+    /// A `--build-arg` wasn't KEY=VALUE
+    #[error("couldn't parse build arg {arg}")]
+    #[diagnostic(help("build args should look like KEY=VALUE"))]
+    InvalidBuildArg {
+        /// The build arg we were given
+        arg: String,
+    },
+
+    /// A --shard that isn't one of some number of shards
+    #[error("couldn't parse shard {shard}")]
+    #[diagnostic(help(
+        "shards look like I/N, for the Ith of N shards (counting from 1), like 2/5"
+    ))]
+    InvalidShard {
+        /// The shard we were given
+        shard: String,
+    },
+
     /// An error running `git archive`
     #[error("We failed to generate a source tarball for your project")]
     #[diagnostic(help("This is probably not your fault, please file an issue!"))]
@@ -313,6 +364,14 @@ pub enum DistError {
         bin_name: String,
     },
 
+    /// Symbols a binary was built with have vanished
+    #[error("failed to find symbols {path}")]
+    #[diagnostic(help("did the above build fail?"))]
+    MissingSymbols {
+        /// Where the symbols were supposed to be
+        path: Utf8PathBuf,
+    },
+
     /// Error during `cargo dist selfupdate`
     #[error("`cargo dist selfupdate` failed; the new version isn't in the place we expected")]
     #[diagnostic(help("This is probably not your fault, please file an issue!"))]
@@ -367,6 +426,838 @@ pub enum DistError {
         /// The input
         pair: String,
     },
+
+    /// A Go generic build was requested for a target Go doesn't know about
+    #[error("Don't know what GOOS/GOARCH to use for {target}")]
+    #[diagnostic(help(
+        "Go builds currently support the common linux, macOS, and windows targets"
+    ))]
+    UnsupportedGoTarget {
+        /// The target triple
+        target: String,
+    },
+
+    /// Tried to copy a build output to a directory, but couldn't pick a path in it
+    #[error("Couldn't copy {src_path} into {dest_path}, as it already contains a directory with that name")]
+    #[diagnostic(help("Either remove that directory, or specify the full path to copy to"))]
+    CopyDestinationIsDir {
+        /// The file we were copying
+        src_path: Utf8PathBuf,
+        /// The directory we were copying to
+        dest_path: Utf8PathBuf,
+    },
+
+    /// A build output was a symlink we couldn't get to the bottom of
+    #[error("{path} is a symlink to a symlink to a symlink... (or a symlink loop)")]
+    SymlinkChainTooLong {
+        /// The build output
+        path: Utf8PathBuf,
+    },
+
+    /// A generic build has no binaries to look for
+    #[error("the generic build for {target} doesn't produce any binaries")]
+    #[diagnostic(help("does your package list its binaries?"))]
+    EmptyGenericBuild {
+        /// The target being built
+        target: String,
+    },
+
+    /// pkg-config doesn't know about a package we were told to build against
+    #[error("pkg-config couldn't find the package {package}")]
+    #[diagnostic(help("is it installed, and is it on your PKG_CONFIG_PATH?"))]
+    PkgConfigPackageMissing {
+        /// The package
+        package: String,
+    },
+
+    /// The user hit Ctrl-C during a build
+    #[error("the build was interrupted")]
+    BuildInterrupted,
+
+    /// A build ran into one of the resource limits we set for it
+    #[error("the build was stopped ({status}) after exceeding its {limit} limit")]
+    #[diagnostic(help(
+        "raise {setting} if the build really needs more, or look for a runaway process"
+    ))]
+    BuildResourceLimit {
+        /// Which limit it was
+        limit: String,
+        /// The setting for that limit, and its value
+        setting: String,
+        /// How the build exited
+        status: String,
+    },
+
+    /// A glob pattern in the config couldn't be parsed
+    #[error("invalid glob pattern {pattern}: {details}")]
+    InvalidGlob {
+        /// The pattern
+        pattern: String,
+        /// What's wrong with it
+        details: String,
+    },
+
+    /// An extra-outputs pattern didn't match anything the build produced
+    #[error("the generic build for {target} didn't produce anything matching {pattern}")]
+    #[diagnostic(help(
+        "did the above build fail, or is the pattern relative to the wrong directory?"
+    ))]
+    MissingExtraOutputs {
+        /// The target being built
+        target: String,
+        /// The pattern that matched nothing
+        pattern: String,
+    },
+
+    /// An unnamed output's directory got more than one new file
+    #[error(
+        "the build made several new files in {dir}, so which one is {binary} is unclear: {found:?}"
+    )]
+    #[diagnostic(help(
+        "unnamed-outputs needs a directory per binary that nothing else adds files to"
+    ))]
+    AmbiguousUnnamedOutput {
+        /// The binary we were looking for
+        binary: String,
+        /// The directory it should be in
+        dir: Utf8PathBuf,
+        /// The new files
+        found: Vec<String>,
+    },
+
+    /// A build's compile_commands.json couldn't be merged
+    #[error("couldn't merge {path}, it isn't a list of compile commands: {details}")]
+    InvalidCompileCommands {
+        /// The database
+        path: Utf8PathBuf,
+        /// What's wrong with it
+        details: String,
+    },
+
+    /// The build command is a script without permission to run
+    #[error("the build command {path} isn't executable")]
+    #[diagnostic(help(
+        "run `chmod +x {path}`, or name its interpreter in build-command, like [\"sh\", \"{path}\"]"
+    ))]
+    BuildScriptNotExecutable {
+        /// The script, as build-command has it
+        path: String,
+    },
+
+    /// The build command is a script that doesn't say what runs it
+    #[error("the build command {path} doesn't start with a shebang, so it can't be run")]
+    #[diagnostic(help(
+        "start it with a line like `#!/bin/sh`, or name its interpreter in build-command, like [\"sh\", \"{path}\"]"
+    ))]
+    BuildScriptMissingShebang {
+        /// The script, as build-command has it
+        path: String,
+    },
+
+    /// An extra build produced an artifact that isn't the kind of file it should be
+    #[error("the extra artifact {path} isn't a valid {format}: {reason}")]
+    #[diagnostic(help(
+        "did the build write something else to that path, or is its format in extra-artifacts wrong?"
+    ))]
+    InvalidExtraArtifact {
+        /// Where the build put the artifact
+        path: Utf8PathBuf,
+        /// What it was supposed to be
+        format: crate::config::ArtifactFormat,
+        /// What's wrong with it
+        reason: String,
+    },
+
+    /// An extra build didn't produce some of the artifacts it was supposed to
+    #[error("the extra build didn't produce {missing:?}")]
+    #[diagnostic(help(
+        "did the above build fail, or are the artifacts' paths relative to the wrong directory?"
+    ))]
+    MissingExtraArtifacts {
+        /// Every artifact that wasn't there
+        missing: Vec<String>,
+    },
+
+    /// A failure-pattern or success-pattern couldn't be parsed
+    #[error("invalid build output pattern {pattern}: {details}")]
+    InvalidOutputPattern {
+        /// The pattern
+        pattern: String,
+        /// What's wrong with it
+        details: String,
+    },
+
+    /// A generic build printed something matching failure-pattern
+    #[error("the generic build for {target} printed lines matching the failure-pattern {pattern}:\n{lines}")]
+    BuildMatchedFailurePattern {
+        /// The target being built
+        target: String,
+        /// The pattern
+        pattern: String,
+        /// The matching lines, quoted
+        lines: String,
+    },
+
+    /// A generic build never printed anything matching success-pattern
+    #[error("the generic build for {target} never printed anything matching the success-pattern {pattern}")]
+    #[diagnostic(help("did the build silently fail, or is the pattern wrong?"))]
+    BuildMissingSuccessPattern {
+        /// The target being built
+        target: String,
+        /// The pattern
+        pattern: String,
+    },
+
+    /// We can't switch to the configured build-uid/build-gid
+    #[error("can't run builds as {user} without running cargo-dist as root")]
+    #[diagnostic(help(
+        "run cargo-dist as root, use a container-image, or unset build-uid and build-gid"
+    ))]
+    BuildUserNotPermitted {
+        /// The uid:gid we were asked to run as
+        user: String,
+    },
+
+    /// A build produced a binary in the wrong format for its target
+    #[error("{binary} was supposed to be a {expected} binary for {target}, but it's {found}")]
+    #[diagnostic(help(
+        "did a cross build produce a binary for the host instead? (see binary-formats to change what's expected)"
+    ))]
+    WrongBinaryFormat {
+        /// The binary
+        binary: String,
+        /// The target it was built for
+        target: String,
+        /// The format we expected
+        expected: String,
+        /// The format we found
+        found: String,
+    },
+
+    /// A binary's size is outside the range it's supposed to be in
+    #[error("{binary} for {target} is {size} bytes, {problem} of {limit} bytes")]
+    #[diagnostic(help(
+        "a tiny binary usually means the build silently failed to link it, and a huge one that the wrong file was picked up (see binary-sizes to change what's expected)"
+    ))]
+    BinarySizeOutOfRange {
+        /// The binary
+        binary: String,
+        /// The target it was built for
+        target: String,
+        /// How big it is
+        size: u64,
+        /// "smaller than the minimum" or "larger than the maximum"
+        problem: String,
+        /// The size it's past
+        limit: u64,
+    },
+
+    /// binary-sizes has a size we can't make sense of
+    #[error("the size {size:?} configured for {binary} isn't a size")]
+    #[diagnostic(help(
+        "sizes are a number of bytes with an optional unit, like \"512\", \"100KB\" or \"1.5GiB\""
+    ))]
+    InvalidBinarySize {
+        /// The binary
+        binary: String,
+        /// The size
+        size: String,
+    },
+
+    /// min-free-space isn't a size
+    #[error("min-free-space {size:?} isn't a size")]
+    #[diagnostic(help(
+        "sizes are a number of bytes with an optional unit, like \"512\", \"100KB\" or \"1.5GiB\" (\"0\" turns the check off)"
+    ))]
+    InvalidMinFreeSpace {
+        /// The size
+        size: String,
+    },
+
+    /// There isn't enough disk space left to start a build
+    #[error(
+        "only {free} bytes are free for {dir}, but the build for {build} needs about {needed}"
+    )]
+    #[diagnostic(help(
+        "free up some space, or lower min-free-space if you're sure the build will fit"
+    ))]
+    LowDiskSpace {
+        /// The build
+        build: String,
+        /// The dist dir
+        dir: Utf8PathBuf,
+        /// How many bytes are free
+        free: u64,
+        /// How many bytes need to be (min-free-space, plus what the build should write)
+        needed: u64,
+    },
+
+    /// Notarization is configured, but without enough to log in with
+    #[error("notarization is configured, but there are no credentials to notarize with")]
+    NotarizeCredentialsMissing {
+        /// What's needed
+        #[help]
+        help: String,
+    },
+
+    /// Apple's notary service didn't accept something
+    #[error("notarizing {path} failed ({status}):\n{log}")]
+    #[diagnostic(help("the notary log above says what Apple didn't like; usually the binary needs signing with a Developer ID and the hardened runtime"))]
+    NotarizationFailed {
+        /// What was being notarized
+        path: Utf8PathBuf,
+        /// What the notary service said
+        status: String,
+        /// The notary's log of the problems
+        log: String,
+    },
+
+    /// Mirroring to S3 is configured, but not where to, or without credentials
+    #[error("mirroring to S3 is configured, but {missing} isn't set")]
+    S3MirrorIncomplete {
+        /// What's missing
+        missing: String,
+        /// How to set it
+        #[help]
+        help: String,
+    },
+
+    /// Uploading something to the S3 mirror failed
+    #[error("couldn't mirror {path} to {url}:\n{log}")]
+    #[diagnostic(help(
+        "check the endpoint and bucket exist, and that the credentials can write to them"
+    ))]
+    S3MirrorFailed {
+        /// What was being uploaded
+        path: Utf8PathBuf,
+        /// Where it was going
+        url: String,
+        /// What the upload said, with any credentials redacted
+        log: String,
+    },
+
+    /// A build that's declared network-free tried to use the network
+    #[error("{build} is declared network-free, but tried to reach:\n{destinations}")]
+    #[diagnostic(help(
+        "fetch what the build needs before it runs (e.g. in a setup script), or unset network-free"
+    ))]
+    NetworkAccessed {
+        /// The build (its target, or its command)
+        build: String,
+        /// Where it tried to connect, one per line
+        destinations: String,
+    },
+
+    /// A build produced a binary for the wrong architecture
+    #[error("{binary} was supposed to be built for {expected} ({target}), but it's for {found}")]
+    #[diagnostic(help(
+        "did the toolchain fall back to the host compiler? check CC/CXX for this target"
+    ))]
+    WrongArchitecture {
+        /// The binary
+        binary: String,
+        /// The target it was built for
+        target: String,
+        /// The architecture we expected
+        expected: String,
+        /// The architecture(s) we found
+        found: String,
+    },
+
+    /// A disallowed-libraries pattern isn't a valid glob
+    #[error("disallowed-libraries has an invalid pattern {pattern}: {details}")]
+    InvalidLibraryPattern {
+        /// The pattern
+        pattern: String,
+        /// What's wrong with it
+        details: String,
+    },
+
+    /// A binary links against a library it's not allowed to
+    #[error(
+        "{binary} links against {library}, which matches disallowed-libraries pattern {pattern}"
+    )]
+    #[diagnostic(help(
+        "link it statically, or check the build didn't pick up the library from the build machine"
+    ))]
+    DisallowedLibrary {
+        /// The binary
+        binary: String,
+        /// The library it links against
+        library: String,
+        /// The pattern it matched
+        pattern: String,
+    },
+
+    /// A binary needs a newer glibc than it's allowed to
+    #[error("{binary} needs glibc {needed}, but max-glibc is {max}")]
+    #[diagnostic(help(
+        "build it against an older glibc, like with a sysroot from an older distro (see sysroots)"
+    ))]
+    GlibcTooNew {
+        /// The binary
+        binary: String,
+        /// The newest glibc version it needs
+        needed: String,
+        /// The newest it's allowed
+        max: String,
+    },
+
+    /// A binary that should have a build ID doesn't
+    #[error("{binary} doesn't have a build ID, so it can't be matched with its symbols")]
+    #[diagnostic(help(
+        "make sure the build passes LDFLAGS to the linker, and doesn't link with --build-id=none or -no_uuid"
+    ))]
+    MissingBuildId {
+        /// The binary
+        binary: String,
+    },
+
+    /// The tool we need to make a binary relocatable isn't installed
+    #[error("couldn't find {tool}, which is needed to rewrite the rpath of {binary}")]
+    #[diagnostic(help("install {tool}, or turn off relative-rpath"))]
+    RelocationToolMissing {
+        /// The tool (patchelf or install_name_tool)
+        tool: String,
+        /// The binary
+        binary: String,
+    },
+
+    /// The file builds are supposed to read stdin from can't be opened
+    #[error("couldn't open {path} to use as the stdin of builds")]
+    #[diagnostic(help("check the stdin-file setting in your generic-build config"))]
+    BuildStdinUnreadable {
+        /// The file
+        path: Utf8PathBuf,
+        /// Why
+        #[source]
+        details: std::io::Error,
+    },
+
+    /// The compiler for a wasm target isn't installed
+    #[error("building for {target} needs {toolchain}, which couldn't be found")]
+    WasmToolchainMissing {
+        /// The target
+        target: String,
+        /// Emscripten or wasi-sdk
+        toolchain: String,
+        /// How to get it
+        #[help]
+        help: String,
+    },
+
+    /// A build profile name that can't be a directory name
+    #[error("{profile:?} isn't a valid build profile name")]
+    #[diagnostic(help("profile names can only have letters, numbers, '-', '_', and '.'"))]
+    InvalidBuildProfile {
+        /// The profile
+        profile: String,
+    },
+
+    /// A remote build would run somewhere that isn't copied to the remote host
+    #[error(
+        "a remote build can't run in {dir}, since only the workspace is copied to the remote host"
+    )]
+    #[diagnostic(help("run cargo-dist from within your workspace"))]
+    RemoteBuildOutsideWorkspace {
+        /// Where the build would run
+        dir: Utf8PathBuf,
+    },
+
+    /// There are variants, but none was picked
+    #[error("there are build variants ({known}), but none was picked")]
+    #[diagnostic(help("pick one with --variant, or set default-variant"))]
+    NoVariantSelected {
+        /// The variants there are
+        known: String,
+    },
+
+    /// A variant that isn't in variants
+    #[error("there's no build variant called {variant:?}")]
+    #[diagnostic(help("the variants are: {known}"))]
+    UnknownVariant {
+        /// The variant
+        variant: String,
+        /// The variants there are
+        known: String,
+    },
+
+    /// A flags profile that isn't in flags-profiles
+    #[error("there's no flags profile called {profile:?}")]
+    #[diagnostic(help("the profiles in flags-profiles are: {known}"))]
+    UnknownFlagsProfile {
+        /// The profile
+        profile: String,
+        /// The profiles there are
+        known: String,
+    },
+
+    /// One of the setup-scripts doesn't exist
+    #[error("couldn't find the setup script {path}")]
+    #[diagnostic(help("setup-scripts are relative to your workspace"))]
+    SetupScriptMissing {
+        /// Where it should be
+        path: Utf8PathBuf,
+    },
+
+    /// A build output kept changing size while we waited for it to be finished
+    #[error("{path} was still being written after {seconds} seconds")]
+    #[diagnostic(help("is something still running in the background of your build?"))]
+    OutputStillChanging {
+        /// The output
+        path: Utf8PathBuf,
+        /// How long we waited
+        seconds: u64,
+    },
+
+    /// A build output was copied somewhere, but isn't there now
+    #[error("{path} should have been written by the build, but it's {problem}")]
+    #[diagnostic(help(
+        "make sure nothing else is writing to or cleaning the dist dir while building"
+    ))]
+    DestinationNotWritten {
+        /// Where the output should be
+        path: Utf8PathBuf,
+        /// "missing" or "empty"
+        problem: String,
+    },
+
+    /// A file couldn't be copied because we weren't allowed to write it
+    #[error("couldn't copy {src} to {dest}: permission denied")]
+    #[diagnostic(help(
+        "check who owns {dir} and that you can write to it (and that {dest} isn't read-only)"
+    ))]
+    CopyPermissionDenied {
+        /// What was being copied
+        src: Utf8PathBuf,
+        /// Where it was going (made absolute)
+        dest: Utf8PathBuf,
+        /// The directory it was going into
+        dir: Utf8PathBuf,
+    },
+
+    /// A file couldn't be copied
+    #[error("couldn't copy {src} to {dest}")]
+    CopyFailed {
+        /// What was being copied
+        src: Utf8PathBuf,
+        /// Where it was going
+        dest: Utf8PathBuf,
+        /// What went wrong
+        #[source]
+        details: std::io::Error,
+    },
+
+    /// A copied output doesn't match what it was copied from
+    #[error("{dest} doesn't match {src} after copying it, so it was removed")]
+    #[diagnostic(help(
+        "check the disk isn't full or failing, and that nothing else writes to the dist dir"
+    ))]
+    CopyVerificationFailed {
+        /// What was being copied
+        src: Utf8PathBuf,
+        /// Where it was copied to
+        dest: Utf8PathBuf,
+    },
+
+    /// binaries-file lists a binary the workspace doesn't define
+    #[error("{path} lists a binary called {binary}, but there's no binary by that name")]
+    #[diagnostic(help(
+        "binaries-file can only say where to build and copy your package's binaries"
+    ))]
+    UnknownListedBinary {
+        /// The binaries file
+        path: Utf8PathBuf,
+        /// The binary it listed
+        binary: String,
+    },
+
+    /// depends-on names a binary that nothing builds
+    #[error("{binary}'s build is configured to depend on {dependency}, but there's no binary or data build by that name")]
+    #[diagnostic(help("depends-on should name binaries from your package, or data-builds"))]
+    UnknownBuildDependency {
+        /// The binary with the dependency
+        binary: String,
+        /// The binary it depends on
+        dependency: String,
+    },
+
+    /// Builds depend on each other in a circle
+    #[error("the builds of {binaries:?} for {target} depend on each other, so none of them can go first")]
+    #[diagnostic(help("check depends-on for a cycle"))]
+    BuildDependencyCycle {
+        /// The target
+        target: String,
+        /// The binaries whose builds are stuck
+        binaries: Vec<String>,
+    },
+
+    /// An extra artifact's name-template uses a placeholder we don't know
+    #[error(
+        "the name-template {template} uses {placeholder}, which isn't something we can fill in"
+    )]
+    #[diagnostic(help("the placeholders are {{name}}, {{version}} and {{target}}"))]
+    UnknownNamePlaceholder {
+        /// The template
+        template: String,
+        /// The placeholder
+        placeholder: String,
+    },
+
+    /// An archive-name template uses a placeholder we don't know
+    #[error(
+        "the archive name {template} uses {placeholder}, which isn't something we can fill in"
+    )]
+    #[diagnostic(help(
+        "the placeholders are {{name}}, {{version}}, {{target}}, {{arch}}, {{os}} and {{ext}}"
+    ))]
+    UnknownArchivePlaceholder {
+        /// The template
+        template: String,
+        /// The placeholder
+        placeholder: String,
+    },
+
+    /// An extra artifact's name-template uses {target}, but the artifact isn't for a target
+    #[error("the name-template {template} uses {{target}}, but its artifacts don't have a target")]
+    #[diagnostic(help("set target on the extra-artifacts entry"))]
+    NameTemplateNeedsTarget {
+        /// The template
+        template: String,
+    },
+
+    /// A build input doesn't have the hash verify-inputs says it should
+    #[error("{path}{origin} has sha256 {actual}, but verify-inputs expects {expected}")]
+    #[diagnostic(help(
+        "the file may have been tampered with; if it was deliberately updated, update its sha256 too"
+    ))]
+    BuildInputChecksumMismatch {
+        /// The input
+        path: Utf8PathBuf,
+        /// Where it came from, if we know
+        origin: String,
+        /// The hash it should have
+        expected: String,
+        /// The hash it has
+        actual: String,
+    },
+
+    /// A binary was already where the build was supposed to put it, before it ran
+    #[error("{path} already exists before the build for {target}, so we couldn't tell if the build made {binary}")]
+    #[diagnostic(help(
+        "delete it (or set preexisting-outputs to \"clean-before-build\" to have it deleted for you)"
+    ))]
+    PreexistingOutput {
+        /// The target being built
+        target: String,
+        /// The binary expected there
+        binary: String,
+        /// Where it was
+        path: Utf8PathBuf,
+    },
+
+    /// A target's sysroot-dirs entry isn't a usable sysroot
+    #[error("the sysroot for {target}, {dir}, {reason}")]
+    #[diagnostic(help(
+        "a sysroot needs the target's headers in usr/include and its libraries in usr/lib"
+    ))]
+    InvalidSysroot {
+        /// The target it's for
+        target: String,
+        /// The configured directory
+        dir: Utf8PathBuf,
+        /// What's wrong with it
+        reason: String,
+    },
+
+    /// An output-modes entry isn't an octal mode
+    #[error("output-modes gives {name} the mode {mode:?}, which isn't an octal mode")]
+    #[diagnostic(help("modes are written in octal, like \"0755\" or \"0600\""))]
+    InvalidOutputMode {
+        /// The output it's for
+        name: String,
+        /// The mode
+        mode: String,
+    },
+
+    /// An output didn't end up with the mode output-modes gave it
+    #[error("{path} should have mode {expected}, but it has {actual}")]
+    #[diagnostic(help(
+        "the filesystem or user may not support some of its bits, such as setuid when not running as root"
+    ))]
+    OutputModeNotApplied {
+        /// The output
+        path: Utf8PathBuf,
+        /// The mode it should have
+        expected: String,
+        /// The mode it has
+        actual: String,
+    },
+
+    /// A build made different binaries when it was run again
+    #[error("the build for {target} isn't reproducible:\n{differences}")]
+    #[diagnostic(help(
+        "something about the build varies between runs; reproducible and reproducible-env may help"
+    ))]
+    NotReproducible {
+        /// The target being built
+        target: String,
+        /// What differed between the builds, one binary per line
+        differences: String,
+    },
+
+    /// A build made binaries it wasn't expected to
+    #[error("the build for {target} made binaries that aren't declared: {outputs:?}")]
+    #[diagnostic(help(
+        "add them to your binaries if they should be shipped, or set undeclared-outputs to \"warn\""
+    ))]
+    UndeclaredOutputs {
+        /// The target being built
+        target: String,
+        /// The binaries nobody expected
+        outputs: Vec<Utf8PathBuf>,
+    },
+
+    /// A build input wasn't there before the build, and the build didn't fetch it either
+    #[error("verify-inputs lists {path}, but it doesn't exist, even after the build")]
+    #[diagnostic(help("check the path is relative to where the build runs"))]
+    BuildInputMissing {
+        /// The input
+        path: Utf8PathBuf,
+    },
+
+    /// toolchain-files points at a file that isn't there
+    #[error("the toolchain file for {target} doesn't exist: {path}")]
+    #[diagnostic(help("toolchain-files paths are relative to the workspace"))]
+    ToolchainFileMissing {
+        /// The target it's for
+        target: String,
+        /// Where it should be
+        path: Utf8PathBuf,
+    },
+
+    /// A binary didn't run from its archive
+    #[error("smoke test of {binary} from {archive} failed ({status}):\n{output}")]
+    #[diagnostic(help(
+        "it ran fine where it was built, so check it doesn't need anything from there, like libraries found through an rpath"
+    ))]
+    SmokeTestFailed {
+        /// The binary, relative to the archive
+        binary: Utf8PathBuf,
+        /// The archive it was run from
+        archive: Utf8PathBuf,
+        /// How it exited
+        status: String,
+        /// What it printed to stderr
+        output: String,
+    },
+
+    /// post-process exited non-zero
+    #[error("post-process for {target} failed ({status})")]
+    #[diagnostic(help(
+        "post-process's output above (and in the build log) should say what went wrong"
+    ))]
+    PostProcessFailed {
+        /// The target whose outputs were being processed
+        target: String,
+        /// How it exited
+        status: String,
+    },
+
+    /// A binary's validator exited non-zero
+    #[error("{binary} at {path} failed validation ({status})")]
+    #[diagnostic(help(
+        "the validator's output above (and in the build log) should say what's wrong with it"
+    ))]
+    BinaryValidationFailed {
+        /// The binary that was checked
+        binary: String,
+        /// Where it was checked
+        path: Utf8PathBuf,
+        /// How the validator exited
+        status: String,
+    },
+
+    /// before-all or after-all exited non-zero
+    #[error("{hook} failed ({status})")]
+    #[diagnostic(help("{hook}'s output above (and in the build log) should say what went wrong"))]
+    BuildHookFailed {
+        /// Which hook it was
+        hook: String,
+        /// How it exited
+        status: String,
+    },
+
+    /// brew-mode = "require", but there's no Brewfile
+    #[error("brew-mode is \"require\", but there's no Brewfile at {path}")]
+    #[diagnostic(help(
+        "add a Brewfile listing your build's dependencies, or set brew-mode to \"auto\" or \"off\""
+    ))]
+    BrewfileMissing {
+        /// Where the Brewfile should be
+        path: Utf8PathBuf,
+    },
+
+    /// `cargo dist doctor` found programs that builds need but aren't installed
+    #[error("{count} of the programs your builds need couldn't be found")]
+    #[diagnostic(help("install them (or put them on your PATH) before building"))]
+    DoctorFoundProblems {
+        /// How many are missing
+        count: usize,
+    },
+
+    /// Two binaries would be copied to the same place
+    #[error("{first} and {second} would both be copied to {dest}")]
+    #[diagnostic(help(
+        "the later copy would overwrite the earlier one; do these binaries have the same name?"
+    ))]
+    CopyDestinationCollision {
+        /// Where they'd be copied to
+        dest: Utf8PathBuf,
+        /// The first binary
+        first: String,
+        /// The second binary
+        second: String,
+    },
+
+    /// The collision-rename template uses a placeholder we don't know
+    #[error(
+        "the collision-rename template {template} uses {placeholder}, which isn't something we can fill in"
+    )]
+    #[diagnostic(help("the placeholders are {{name}}, {{target}} and {{arch}}"))]
+    UnknownCollisionPlaceholder {
+        /// The template
+        template: String,
+        /// The placeholder
+        placeholder: String,
+    },
+
+    /// The configured linker isn't installed
+    #[error("generic builds are configured to link with {linker}, but {program} couldn't be run")]
+    #[diagnostic(help("is {linker} installed, and on your PATH?"))]
+    LinkerMissing {
+        /// The configured linker
+        linker: String,
+        /// The executable we looked for
+        program: String,
+    },
+
+    /// The shell build-script should be run with doesn't exist
+    #[error(
+        "generic builds are configured to run build-script with {shell}, which couldn't be found"
+    )]
+    #[diagnostic(help("is it installed and on your PATH? script-shell should be the interpreter followed by its flags, like [\"bash\", \"-c\"]"))]
+    ScriptShellMissing {
+        /// The shell we looked for
+        shell: String,
+    },
+
+    /// SOURCE_DATE_EPOCH wasn't a timestamp
+    #[error("SOURCE_DATE_EPOCH was set to {value}, which isn't a number of seconds since the unix epoch")]
+    InvalidSourceDateEpoch {
+        /// The value it was set to
+        value: String,
+    },
 }
 
 /// Errors related to finding the project
@@ -265,6 +265,7 @@ fn get_new_dist_metadata(
             bin_aliases: None,
             tag_namespace: None,
             install_updater: None,
+            generic_build: None,
         }
     };
 
@@ -840,6 +841,7 @@ fn apply_dist_to_metadata(metadata: &mut toml_edit::Item, meta: &DistMetadata) {
         github_custom_runners: _,
         bin_aliases: _,
         install_updater,
+        generic_build: _,
     } = &meta;
 
     apply_optional_value(
@@ -18,7 +18,10 @@ use backend::{
     ci::CiInfo,
     installer::{self, msi::MsiInstallerInfo, InstallerImpl},
 };
-use build::generic::{build_generic_target, run_extra_artifacts_build};
+use build::generic::{
+    build_generic_target, check_copy_destinations, checksums::write_combined_checksums,
+    run_extra_artifacts_build, summary, toolchain::Toolchain,
+};
 use build::{
     cargo::{build_cargo_target, rustup_toolchain},
     fake::{build_fake_cargo_target, build_fake_generic_target},
@@ -54,9 +57,69 @@ mod tests;
 
 /// cargo dist build -- actually build binaries and installers!
 pub fn do_build(cfg: &Config) -> DistResult<DistManifest> {
+    do_build_with_hook(cfg, None)
+}
+
+/// Like [`do_build`][], but with a hook to customize how generic builds are launched
+///
+/// See [`CommandHook`][] for when it's invoked.
+pub fn do_build_with_hook(
+    cfg: &Config,
+    command_hook: Option<CommandHook>,
+) -> DistResult<DistManifest> {
+    let (_dist, manifest) = build_everything(cfg, command_hook)?;
+    Ok(manifest)
+}
+
+/// Like [`do_build_with_hook`][], but then keep rebuilding generic builds whose sources change
+///
+/// `report` gets the manifest once everything is built, and again after each
+/// batch of rebuilds. This only returns if watching fails (or is interrupted).
+pub fn do_build_and_watch(
+    cfg: &Config,
+    command_hook: Option<CommandHook>,
+    mut report: impl FnMut(&DistManifest),
+) -> DistResult<()> {
+    let (dist, mut manifest) = build_everything(cfg, command_hook)?;
+    report(&manifest);
+    build::generic::watch::watch(&dist, &mut manifest, &dist.local_build_steps, report)
+}
+
+/// Build everything, returning the graph that was built along with the manifest
+fn build_everything(
+    cfg: &Config,
+    command_hook: Option<CommandHook>,
+) -> DistResult<(DistGraph, DistManifest)> {
     check_integrity(cfg)?;
 
-    let (dist, mut manifest) = tasks::gather_work(cfg)?;
+    let (mut dist, mut manifest) = tasks::gather_work(cfg)?;
+    dist.command_hook = command_hook;
+    dist.interactive_builds = cfg.interactive_builds;
+    dist.print_build_commands = cfg.print_build_commands;
+    dist.repro_manifests = cfg.repro_manifests;
+    dist.no_brew_cache = cfg.no_brew_cache;
+    dist.resume_builds = cfg.resume;
+    dist.sanitizers.clone_from(&cfg.sanitizers);
+    dist.verify_reproducible = cfg.verify_reproducible;
+    dist.build_args.clone_from(&cfg.build_args);
+    dist.staging_dir.clone_from(&cfg.staging_dir);
+    dist.target_staging = cfg.target_staging;
+    if cfg.only_generic || cfg.only_extra {
+        build::generic::select_build_steps(
+            &mut dist.local_build_steps,
+            cfg.only_generic,
+            cfg.only_extra,
+        );
+        // These need everything to have been built
+        dist.global_build_steps.clear();
+    }
+    if cfg.shard.is_some() {
+        // Extra builds, and everything global, need every shard's builds
+        build::generic::select_build_steps(&mut dist.local_build_steps, true, false);
+        dist.global_build_steps.clear();
+    }
+    build::generic::collisions::rename_colliding_destinations(&mut dist)?;
+    check_copy_destinations(&dist, &dist.local_build_steps)?;
 
     // FIXME: parallelize this by working this like a dependency graph, so we can start
     // bundling up an executable the moment it's built! Note however that you shouldn't
@@ -76,13 +139,69 @@ pub fn do_build(cfg: &Config) -> DistResult<DistManifest> {
     }
     eprintln!();
 
-    // Run all the local build steps first
-    for step in &dist.local_build_steps {
-        if dist.local_builds_are_lies {
-            build_fake(&dist, step, &mut manifest)?;
-        } else {
-            run_build_step(&dist, step, &mut manifest)?;
-        }
+    if cfg.clean || cfg.clean_dry_run {
+        build::generic::stale::clean_dist_dir(&dist, cfg.clean_dry_run)?;
+    }
+
+    build::generic::staging_manifest::reset_staging_manifest(&dist)?;
+    build::generic::resume::reset_build_state(&dist)?;
+
+    let dist_dir_before = if cfg.diff_dist_dir {
+        Some(build::generic::dist_diff::snapshot_dist_dir(
+            &dist.dist_dir,
+        )?)
+    } else {
+        None
+    };
+
+    // Run all the local build steps first, between the global hooks (again, if the machine failed them)
+    let build_summary =
+        build::generic::infra::with_infra_retries(&dist, &mut manifest, |manifest| {
+            build::generic::hooks::run_with_hooks(&dist, |build_summary| {
+                if dist.generic_build.pipeline_builds.unwrap_or(false)
+                    && !dist.local_builds_are_lies
+                {
+                    return build::generic::pipeline::run_pipelined(
+                        &dist,
+                        manifest,
+                        &dist.local_build_steps,
+                        build_summary,
+                        |step, manifest| run_build_step(&dist, step, manifest),
+                    );
+                }
+                for step in &dist.local_build_steps {
+                    if dist.local_builds_are_lies {
+                        build_fake(&dist, step, manifest)?;
+                    } else if let BuildStep::Generic(target) = step {
+                        // Generic builds get rolled up into a summary at the end
+                        summary::record_build(build_summary, target, || {
+                            build_generic_target(&dist, manifest, target)
+                        })?;
+                    } else {
+                        run_build_step(&dist, step, manifest)?;
+                    }
+                }
+                Ok(())
+            })
+        })?;
+    if !build_summary.is_empty() {
+        eprint!("{}", summary::render_summary(&build_summary));
+        manifest.build_summary = build_summary;
+    }
+    if let Some(merged) = build::generic::compile_commands::merge_compile_commands(&dist)? {
+        eprintln!("merged compilation databases into {merged}");
+    }
+    if let Some(before) = &dist_dir_before {
+        let after = build::generic::dist_diff::snapshot_dist_dir(&dist.dist_dir)?;
+        eprint!(
+            "{}",
+            build::generic::dist_diff::render_dist_diff(&dist.dist_dir, before, &after)
+        );
+    }
+    if let Some(archive) =
+        build::generic::log_archive::archive_build_logs(&dist, &mut manifest, cfg.no_local_paths)?
+    {
+        eprintln!("archived build logs to {archive}");
     }
 
     // Next the global steps
@@ -94,7 +213,31 @@ pub fn do_build(cfg: &Config) -> DistResult<DistManifest> {
         }
     }
 
-    Ok(manifest)
+    // Finally, anything that needs to look at all the outputs at once
+    let checksums = write_combined_checksums(&dist)?;
+    if let Some(checksums) = &checksums {
+        eprintln!("wrote combined checksums to {checksums}");
+    }
+    if let Some(previous) = &cfg.compare_release {
+        eprint!(
+            "{}",
+            build::generic::release_diff::compare_with_release(
+                &dist.dist_dir,
+                &manifest,
+                previous,
+                cfg.compare_symbols,
+            )?
+        );
+    }
+    if !dist.local_builds_are_lies {
+        if let Some(count) =
+            build::generic::s3_mirror::mirror_to_s3(&dist, &manifest, checksums.as_deref())?
+        {
+            eprintln!("mirrored {count} files to S3");
+        }
+    }
+
+    Ok((dist, manifest))
 }
 
 /// Just generate the manifest produced by `cargo dist build` without building
@@ -105,6 +248,60 @@ pub fn do_manifest(cfg: &Config) -> DistResult<DistManifest> {
     Ok(manifest)
 }
 
+/// List the targets generic builds would be run for, and how many binaries each produces
+pub fn do_list_targets(cfg: &Config) -> DistResult<SortedMap<TargetTriple, usize>> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    Ok(build::generic::generic_build_targets(
+        &dist.local_build_steps,
+    ))
+}
+
+/// A Makefile that reproduces the generic builds for each target
+pub fn do_export_makefile(cfg: &Config) -> DistResult<String> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    build::generic::makefile::render_makefile(&dist, &dist.local_build_steps)
+}
+
+/// The generic build config, as it was read
+pub fn do_generic_build_config(cfg: &Config) -> DistResult<config::GenericBuildConfig> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    Ok(dist.generic_build)
+}
+
+/// What each generic build would be given, after resolving everything
+pub fn do_effective_config(cfg: &Config) -> DistResult<build::generic::effective::EffectiveConfig> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    build::generic::effective::effective_config(&dist, &dist.local_build_steps)
+}
+
+/// The C/C++ toolchain generic builds would use for each of their targets
+pub fn do_toolchain_env(cfg: &Config) -> DistResult<SortedMap<TargetTriple, Toolchain>> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    Ok(build::generic::generic_build_toolchains(
+        &dist,
+        &dist.local_build_steps,
+    ))
+}
+
+/// Whether the programs each generic build needs are installed
+pub fn do_doctor(cfg: &Config) -> DistResult<Vec<build::generic::doctor::DoctorCheck>> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    build::generic::doctor::doctor(&dist, &dist.local_build_steps)
+}
+
+/// The versions of the tools and packages each generic build would use
+pub fn do_report_versions(cfg: &Config) -> DistResult<build::generic::versions::VersionReport> {
+    let (dist, _manifest) = gather_work(cfg)?;
+
+    build::generic::versions::report_versions(&dist, &dist.local_build_steps)
+}
+
 /// Run some build step
 fn run_build_step(
     dist_graph: &DistGraph,
@@ -112,7 +309,9 @@ fn run_build_step(
     manifest: &mut DistManifest,
 ) -> DistResult<()> {
     match target {
-        BuildStep::Generic(target) => build_generic_target(dist_graph, manifest, target)?,
+        BuildStep::Generic(target) => {
+            build_generic_target(dist_graph, manifest, target)?;
+        }
         BuildStep::Cargo(target) => build_cargo_target(dist_graph, manifest, target)?,
         BuildStep::Rustup(cmd) => rustup_toolchain(dist_graph, cmd)?,
         BuildStep::CopyFile(CopyStep {
@@ -132,7 +331,13 @@ fn run_build_step(
             dest_path,
             zip_style,
             with_root,
-        }) => zip_dir(src_path, dest_path, zip_style, with_root.as_deref())?,
+        }) => zip_dir(
+            dist_graph,
+            src_path,
+            dest_path,
+            zip_style,
+            with_root.as_deref(),
+        )?,
         BuildStep::GenerateInstaller(installer) => {
             generate_installer(dist_graph, installer, manifest)?
         }
@@ -153,7 +358,7 @@ fn run_build_step(
             prefix,
             target,
         }) => generate_source_tarball(dist_graph, committish, prefix, target)?,
-        BuildStep::Extra(target) => run_extra_artifacts_build(dist_graph, target)?,
+        BuildStep::Extra(target) => run_extra_artifacts_build(dist_graph, manifest, target)?,
         BuildStep::Updater(updater) => fetch_updater(dist_graph, updater)?,
     };
     Ok(())
@@ -291,7 +496,13 @@ fn build_fake(
             dest_path,
             zip_style,
             with_root,
-        }) => zip_dir(src_path, dest_path, zip_style, with_root.as_deref())?,
+        }) => zip_dir(
+            dist_graph,
+            src_path,
+            dest_path,
+            zip_style,
+            with_root.as_deref(),
+        )?,
         BuildStep::GenerateInstaller(installer) => match installer {
             // MSI, unlike other installers, isn't safe to generate on any platform
             InstallerImpl::Msi(msi) => generate_fake_msi(dist_graph, msi, manifest)?,
@@ -329,8 +540,8 @@ fn build_fake(
 }
 
 fn run_fake_extra_artifacts_build(dist: &DistGraph, target: &ExtraBuildStep) -> DistResult<()> {
-    for artifact in &target.expected_artifacts {
-        let path = dist.dist_dir.join(artifact);
+    for (_, name) in &target.artifact_names {
+        let path = dist.dist_dir.join(name);
         LocalAsset::write_new_all("", &path)?;
     }
 
@@ -368,7 +579,10 @@ fn generate_and_write_checksum(
 }
 
 /// Generate a checksum for the src_path and return it as a string
-fn generate_checksum(checksum: &ChecksumStyle, src_path: &Utf8Path) -> DistResult<String> {
+pub(crate) fn generate_checksum(
+    checksum: &ChecksumStyle,
+    src_path: &Utf8Path,
+) -> DistResult<String> {
     info!("generating {checksum:?} for {src_path}");
     use sha2::Digest;
     use std::fmt::Write;
@@ -483,6 +697,37 @@ pub(crate) fn copy_file(src_path: &Utf8Path, dest_path: &Utf8Path) -> DistResult
     Ok(())
 }
 
+/// Say what went wrong copying (or replacing) a file, and where
+///
+/// Permission problems are usually about the destination, so they get the
+/// full path to it and a hint about where to look.
+pub(crate) fn copy_error(
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    e: std::io::Error,
+) -> DistError {
+    if e.kind() != std::io::ErrorKind::PermissionDenied {
+        return DistError::CopyFailed {
+            src: src_path.to_owned(),
+            dest: dest_path.to_owned(),
+            details: e,
+        };
+    }
+    let dest = match Utf8PathBuf::try_from(std::env::current_dir().unwrap_or_default()) {
+        Ok(cwd) if dest_path.is_relative() => cwd.join(dest_path),
+        _ => dest_path.to_owned(),
+    };
+    let dir = dest
+        .parent()
+        .map(Utf8Path::to_owned)
+        .unwrap_or_else(|| dest.clone());
+    DistError::CopyPermissionDenied {
+        src: src_path.to_owned(),
+        dest,
+        dir,
+    }
+}
+
 pub(crate) fn copy_dir(src_path: &Utf8Path, dest_path: &Utf8Path) -> DistResult<()> {
     LocalAsset::copy_dir_named(src_path, dest_path)?;
     Ok(())
@@ -497,6 +742,24 @@ pub(crate) fn copy_file_or_dir(src_path: &Utf8Path, dest_path: &Utf8Path) -> Dis
 }
 
 fn zip_dir(
+    dist_graph: &DistGraph,
+    src_path: &Utf8Path,
+    dest_path: &Utf8Path,
+    zip_style: &ZipStyle,
+    with_root: Option<&Utf8Path>,
+) -> DistResult<()> {
+    if dist_graph.generic_build.reproducible.unwrap_or(false) {
+        let mtime = build::generic::archive::archive_mtime()?;
+        build::generic::archive::write_reproducible_archive(
+            src_path, dest_path, zip_style, with_root, mtime,
+        )?;
+    } else {
+        write_archive(src_path, dest_path, zip_style, with_root)?;
+    }
+    build::generic::smoke::smoke_test_artifact(dist_graph, dest_path)
+}
+
+fn write_archive(
     src_path: &Utf8Path,
     dest_path: &Utf8Path,
     zip_style: &ZipStyle,
@@ -641,6 +904,27 @@ pub fn check_integrity(cfg: &Config) -> DistResult<()> {
         installers: vec![],
         announcement_tag: None,
         root_cmd: "check".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
     let (dist, _manifest) = tasks::gather_work(&check_config)?;
 
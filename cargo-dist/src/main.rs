@@ -9,12 +9,13 @@ use axoprocess::Cmd;
 use axoupdater::AxoUpdater;
 use camino::Utf8PathBuf;
 // Import everything from the lib version of ourselves
-use cargo_dist::{linkage::LinkageDisplay, *};
+use cargo_dist::{config::parse_shard, env::parse_build_arg, linkage::LinkageDisplay, *};
 use cargo_dist_schema::{AssetKind, DistManifest};
 use clap::Parser;
 use cli::{
-    Cli, Commands, FakeCli, GenerateMode, HelpMarkdownArgs, HostArgs, ManifestArgs, OutputFormat,
-    PlanArgs,
+    Cli, Commands, ConfigArgs, DoctorArgs, ExportMakefileArgs, FakeCli, GenerateMode,
+    HelpMarkdownArgs, HostArgs, ListTargetsArgs, ManifestArgs, OutputFormat, PlanArgs,
+    ToolchainEnvArgs,
 };
 use console::Term;
 use miette::{miette, IntoDiagnostic};
@@ -48,6 +49,11 @@ fn real_main(cli: &axocli::CliApp<Cli>) -> Result<(), miette::Report> {
         Commands::Linkage(args) => cmd_linkage(config, args),
         Commands::Manifest(args) => cmd_manifest(config, args),
         Commands::Plan(args) => cmd_plan(config, args),
+        Commands::ListTargets(args) => cmd_list_targets(config, args),
+        Commands::ToolchainEnv(args) => cmd_toolchain_env(config, args),
+        Commands::Doctor(args) => cmd_doctor(config, args),
+        Commands::ExportMakefile(args) => cmd_export_makefile(config, args),
+        Commands::Config(args) => cmd_config(config, args),
         Commands::HelpMarkdown(args) => cmd_help_md(config, args),
         Commands::ManifestSchema(args) => cmd_manifest_schema(config, args),
         Commands::Build(args) => cmd_build(config, args),
@@ -190,6 +196,11 @@ fn print_human_linkage(out: &mut Term, report: &DistManifest) -> Result<(), std:
 }
 
 fn cmd_build(cli: &Cli, args: &BuildArgs) -> Result<(), miette::Report> {
+    if args.interactive && cli.output_format == OutputFormat::Json {
+        return Err(miette!(
+            "--interactive can't be used with --output-format=json, as the output of builds would end up mixed into the json"
+        ));
+    }
     let config = cargo_dist::config::Config {
         needs_coherent_announcement_tag: true,
         create_hosting: false,
@@ -201,14 +212,47 @@ fn cmd_build(cli: &Cli, args: &BuildArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: "build".to_owned(),
+        interactive_builds: args.interactive,
+        print_build_commands: args.print_command,
+        diff_dist_dir: args.diff_dist_dir,
+        clean: args.clean,
+        clean_dry_run: args.clean_dry_run,
+        resume: args.resume,
+        sanitizers: args
+            .sanitize
+            .iter()
+            .map(|sanitizer| sanitizer.to_lib())
+            .collect(),
+        verify_reproducible: args.verify_reproducible,
+        shard: args.shard.as_deref().map(parse_shard).transpose()?,
+        compare_release: args.compare_release.clone(),
+        compare_symbols: args.compare_symbols,
+        no_brew_cache: args.no_brew_cache,
+        repro_manifests: args.repro_manifest,
+        build_args: args
+            .build_arg
+            .iter()
+            .map(|arg| parse_build_arg(arg))
+            .collect::<Result<_, _>>()?,
+        staging_dir: args.staging_dir.clone(),
+        target_staging: args.target_staging,
+        only_generic: args.only_generic,
+        only_extra: args.only_extra,
+        build_profile: args.profile.clone(),
+        flags_profile: args.flags_profile.clone(),
+        variant: args.variant.clone(),
     };
+    let print_linkage = args.print.contains(&"linkage".to_owned());
+    if args.watch {
+        do_build_and_watch(&config, None, |report| {
+            if let Err(e) = print(cli, report, print_linkage, None) {
+                eprintln!("{e:?}");
+            }
+        })?;
+        return Ok(());
+    }
     let report = do_build(&config)?;
-    print(
-        cli,
-        &report,
-        args.print.contains(&"linkage".to_owned()),
-        None,
-    )
+    print(cli, &report, print_linkage, None)
 }
 
 fn cmd_host(cli: &Cli, args: &HostArgs) -> Result<(), miette::Report> {
@@ -233,6 +277,27 @@ fn cmd_host(cli: &Cli, args: &HostArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: format!("host:{arg_key}"),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
 
     let report = cargo_dist::host::do_host(&config, args)?;
@@ -251,6 +316,27 @@ fn cmd_manifest(cli: &Cli, args: &ManifestArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: "plan".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
     let report = do_manifest(&config)?;
     print(cli, &report, false, Some("manifest"))
@@ -265,12 +351,319 @@ fn cmd_plan(cli: &Cli, _args: &PlanArgs) -> Result<(), miette::Report> {
         build_args: BuildArgs {
             artifacts: cli::ArtifactMode::All,
             print: vec![],
+            interactive: false,
+            print_command: false,
+            repro_manifest: false,
+            diff_dist_dir: false,
+            clean: false,
+            clean_dry_run: false,
+            resume: false,
+            no_brew_cache: false,
+            watch: false,
+            build_arg: vec![],
+            staging_dir: None,
+            target_staging: false,
+            only_generic: false,
+            only_extra: false,
+            profile: None,
+            flags_profile: None,
+            variant: None,
+            sanitize: vec![],
+            verify_reproducible: false,
+            shard: None,
+            compare_release: None,
+            compare_symbols: false,
         },
     };
 
     cmd_manifest(&new_cli, args)
 }
 
+fn cmd_list_targets(cli: &Cli, _args: &ListTargetsArgs) -> Result<(), miette::Report> {
+    let config = cargo_dist::config::Config {
+        needs_coherent_announcement_tag: false,
+        create_hosting: false,
+        artifact_mode: cargo_dist::config::ArtifactMode::All,
+        no_local_paths: cli.no_local_paths,
+        allow_all_dirty: cli.allow_dirty,
+        targets: cli.target.clone(),
+        ci: cli.ci.iter().map(|ci| ci.to_lib()).collect(),
+        installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
+        announcement_tag: cli.tag.clone(),
+        root_cmd: "list-targets".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
+    };
+    let targets = do_list_targets(&config)?;
+
+    let mut out = Term::stdout();
+    match cli.output_format {
+        OutputFormat::Human => {
+            for (target, binaries) in &targets {
+                let noun = if *binaries == 1 { "binary" } else { "binaries" };
+                writeln!(out, "{target} ({binaries} {noun})").into_diagnostic()?;
+            }
+        }
+        OutputFormat::Json => {
+            let string = serde_json::to_string_pretty(&targets).into_diagnostic()?;
+            writeln!(out, "{string}").into_diagnostic()?;
+        }
+    }
+    Ok(())
+}
+
+fn cmd_toolchain_env(cli: &Cli, args: &ToolchainEnvArgs) -> Result<(), miette::Report> {
+    let config = cargo_dist::config::Config {
+        needs_coherent_announcement_tag: false,
+        create_hosting: false,
+        artifact_mode: cargo_dist::config::ArtifactMode::All,
+        no_local_paths: cli.no_local_paths,
+        allow_all_dirty: cli.allow_dirty,
+        targets: cli.target.clone(),
+        ci: cli.ci.iter().map(|ci| ci.to_lib()).collect(),
+        installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
+        announcement_tag: cli.tag.clone(),
+        root_cmd: "toolchain-env".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
+    };
+    let toolchains = do_toolchain_env(&config)?;
+
+    let mut out = Term::stdout();
+    match cli.output_format {
+        OutputFormat::Human => {
+            let rendered = cargo_dist::build::generic::toolchain::render_toolchains(
+                &toolchains,
+                args.format.to_lib(),
+            );
+            write!(out, "{rendered}").into_diagnostic()?;
+        }
+        OutputFormat::Json => {
+            let string = serde_json::to_string_pretty(&toolchains).into_diagnostic()?;
+            writeln!(out, "{string}").into_diagnostic()?;
+        }
+    }
+    Ok(())
+}
+
+fn cmd_doctor(cli: &Cli, args: &DoctorArgs) -> Result<(), miette::Report> {
+    let config = cargo_dist::config::Config {
+        needs_coherent_announcement_tag: false,
+        create_hosting: false,
+        artifact_mode: cargo_dist::config::ArtifactMode::All,
+        no_local_paths: cli.no_local_paths,
+        allow_all_dirty: cli.allow_dirty,
+        targets: cli.target.clone(),
+        ci: cli.ci.iter().map(|ci| ci.to_lib()).collect(),
+        installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
+        announcement_tag: cli.tag.clone(),
+        root_cmd: "doctor".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
+    };
+    if args.report_versions {
+        let report = do_report_versions(&config)?;
+        let mut out = Term::stdout();
+        match cli.output_format {
+            OutputFormat::Human => {
+                let rendered = cargo_dist::build::generic::versions::render_version_report(&report);
+                write!(out, "{rendered}").into_diagnostic()?;
+            }
+            OutputFormat::Json => {
+                let string = serde_json::to_string_pretty(&report).into_diagnostic()?;
+                writeln!(out, "{string}").into_diagnostic()?;
+            }
+        }
+        return Ok(());
+    }
+    let checks = do_doctor(&config)?;
+
+    let mut out = Term::stdout();
+    match cli.output_format {
+        OutputFormat::Human => {
+            let rendered = cargo_dist::build::generic::doctor::render_checks(&checks);
+            write!(out, "{rendered}").into_diagnostic()?;
+        }
+        OutputFormat::Json => {
+            let string = serde_json::to_string_pretty(&checks).into_diagnostic()?;
+            writeln!(out, "{string}").into_diagnostic()?;
+        }
+    }
+    let count = checks.iter().filter(|check| check.found.is_none()).count();
+    if count > 0 {
+        return Err(cargo_dist::errors::DistError::DoctorFoundProblems { count }.into());
+    }
+    Ok(())
+}
+
+fn cmd_export_makefile(cli: &Cli, args: &ExportMakefileArgs) -> Result<(), miette::Report> {
+    let config = cargo_dist::config::Config {
+        needs_coherent_announcement_tag: false,
+        create_hosting: false,
+        artifact_mode: cargo_dist::config::ArtifactMode::All,
+        no_local_paths: cli.no_local_paths,
+        allow_all_dirty: cli.allow_dirty,
+        targets: cli.target.clone(),
+        ci: cli.ci.iter().map(|ci| ci.to_lib()).collect(),
+        installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
+        announcement_tag: cli.tag.clone(),
+        root_cmd: "export-makefile".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
+    };
+    let makefile = do_export_makefile(&config)?;
+
+    if let Some(path) = &args.output {
+        LocalAsset::write_new(&makefile, path)?;
+    } else {
+        let mut out = Term::stdout();
+        write!(out, "{makefile}").into_diagnostic()?;
+    }
+    Ok(())
+}
+
+fn cmd_config(cli: &Cli, args: &ConfigArgs) -> Result<(), miette::Report> {
+    let config = cargo_dist::config::Config {
+        needs_coherent_announcement_tag: false,
+        create_hosting: false,
+        artifact_mode: cargo_dist::config::ArtifactMode::All,
+        no_local_paths: cli.no_local_paths,
+        allow_all_dirty: cli.allow_dirty,
+        targets: cli.target.clone(),
+        ci: cli.ci.iter().map(|ci| ci.to_lib()).collect(),
+        installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
+        announcement_tag: cli.tag.clone(),
+        root_cmd: "config".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: args.profile.clone(),
+        flags_profile: args.flags_profile.clone(),
+        variant: args.variant.clone(),
+    };
+
+    let mut out = Term::stdout();
+    if args.effective {
+        let effective = do_effective_config(&config)?;
+        match cli.output_format {
+            OutputFormat::Human => {
+                let rendered =
+                    cargo_dist::build::generic::effective::render_effective_config(&effective);
+                write!(out, "{rendered}").into_diagnostic()?;
+            }
+            OutputFormat::Json => {
+                let string = serde_json::to_string_pretty(&effective).into_diagnostic()?;
+                writeln!(out, "{string}").into_diagnostic()?;
+            }
+        }
+    } else {
+        let generic_build = do_generic_build_config(&config)?;
+        match cli.output_format {
+            OutputFormat::Human => {
+                let rendered =
+                    axoasset::toml::to_string_pretty(&generic_build).into_diagnostic()?;
+                write!(out, "{rendered}").into_diagnostic()?;
+            }
+            OutputFormat::Json => {
+                let string = serde_json::to_string_pretty(&generic_build).into_diagnostic()?;
+                writeln!(out, "{string}").into_diagnostic()?;
+            }
+        }
+    }
+    Ok(())
+}
+
 fn cmd_init(cli: &Cli, args: &InitArgs) -> Result<(), miette::Report> {
     let config = cargo_dist::config::Config {
         needs_coherent_announcement_tag: false,
@@ -283,6 +676,27 @@ fn cmd_init(cli: &Cli, args: &InitArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: "init".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
     let args = cargo_dist::InitArgs {
         yes: args.yes,
@@ -306,6 +720,27 @@ fn cmd_generate(cli: &Cli, args: &GenerateArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: "generate".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
     let args = cargo_dist::GenerateArgs {
         check: args.check,
@@ -327,6 +762,27 @@ fn cmd_linkage(cli: &Cli, args: &LinkageArgs) -> Result<(), miette::Report> {
         installers: cli.installer.iter().map(|ins| ins.to_lib()).collect(),
         announcement_tag: cli.tag.clone(),
         root_cmd: "linkage".to_owned(),
+        interactive_builds: false,
+        print_build_commands: false,
+        diff_dist_dir: false,
+        clean: false,
+        clean_dry_run: false,
+        resume: false,
+        sanitizers: vec![],
+        verify_reproducible: false,
+        shard: None,
+        compare_release: None,
+        compare_symbols: false,
+        no_brew_cache: false,
+        repro_manifests: false,
+        build_args: vec![],
+        staging_dir: None,
+        target_staging: false,
+        only_generic: false,
+        only_extra: false,
+        build_profile: None,
+        flags_profile: None,
+        variant: None,
     };
     let mut options = cargo_dist::linkage::LinkageArgs {
         print_output: args.print_output,
@@ -83,6 +83,8 @@ pub(crate) fn load_and_merge_manifests(
             assets,
             ci,
             linkage,
+            provenance,
+            build_summary,
         } = manifest;
 
         // Discard clearly unrelated manifests
@@ -133,6 +135,9 @@ pub(crate) fn load_and_merge_manifests(
         output.systems.extend(systems);
         output.assets.extend(assets);
         output.linkage.extend(linkage);
+        output.provenance.extend(provenance);
+        output.provenance.sort();
+        output.build_summary.extend(build_summary);
     }
 
     Ok(())
@@ -154,6 +159,9 @@ fn merge_artifact(output: &mut DistManifest, artifact_id: ArtifactId, artifact:
 
             // Merge checksums
             out_artifact.checksums.extend(artifact.checksums);
+            if artifact.image_digest.is_some() {
+                out_artifact.image_digest = artifact.image_digest;
+            }
 
             // Merge assets
             for asset in artifact.assets {
@@ -381,6 +389,7 @@ fn add_manifest_artifact(
         kind,
         checksum,
         checksums: Default::default(),
+        image_digest: None,
     };
 
     if !cfg.no_local_paths {
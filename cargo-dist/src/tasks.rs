@@ -49,6 +49,7 @@
 //! steps to give them the freedom to do whatever they need to do.
 
 use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use axoprocess::Cmd;
 use axoproject::platforms::{
@@ -64,7 +65,17 @@ use tracing::{info, warn};
 use crate::announce::{self, AnnouncementTag};
 use crate::backend::ci::github::GithubCiInfo;
 use crate::backend::ci::CiInfo;
-use crate::config::{DependencyKind, DirtyMode, ExtraArtifact, ProductionMode, SystemDependencies};
+use crate::build::generic::debuginfo::wants_split_debuginfo;
+use crate::build::generic::events::EventSink;
+use crate::build::generic::naming::{
+    archive_name_template, check_archive_names, render_archive_name, render_artifact_name,
+};
+use crate::build::generic::symbols::{compressed_name, wants_compressed_symbols};
+use crate::config::{
+    ArtifactFormat, BuildVerbosity, CompilerFamily, DependencyKind, DirtyMode, ExtraArtifact,
+    GenericBuildConfig, GenericBuilder, ProductionMode, RemoteHost, SystemDependencies,
+};
+use crate::env::BrewEnv;
 use crate::platform::PlatformSupport;
 use crate::{
     backend::{
@@ -269,6 +280,85 @@ pub struct DistGraph {
     pub install_updater: bool,
     /// Publish GitHub Releases to this other repo
     pub github_releases_repo: Option<config::GithubRepoPair>,
+    /// Settings for generic builds
+    pub generic_build: GenericBuildConfig,
+    /// A hook to customize generic build commands right before they're run
+    pub command_hook: Option<CommandHook>,
+    /// Whether generic builds get our stdio (see [`Config::interactive_builds`][])
+    pub interactive_builds: bool,
+    /// Whether to print build commands for pasting into a shell (see [`Config::print_build_commands`][])
+    pub print_build_commands: bool,
+    /// Whether to write what's needed to reproduce each build (see [`Config::repro_manifests`][])
+    pub repro_manifests: bool,
+    /// Variables to set for generic builds from the cli (see [`Config::build_args`][])
+    pub build_args: Vec<(String, String)>,
+    /// Where generic builds stage their outputs (see [`Config::staging_dir`][])
+    pub staging_dir: Option<Utf8PathBuf>,
+    /// Whether each target stages its outputs separately (see [`Config::target_staging`][])
+    pub target_staging: bool,
+    /// The build profile, which the dist dir is namespaced by (see [`Config::build_profile`][])
+    pub build_profile: Option<String>,
+    /// The flags profile generic builds get the flags of (see [`Config::flags_profile`][])
+    pub flags_profile: Option<String>,
+    /// The variant generic builds build (see [`Config::variant`][] and `default-variant`)
+    pub variant: Option<String>,
+    /// Whether to ignore the on-disk cache of the Homebrew environment (see [`Config::no_brew_cache`][])
+    pub no_brew_cache: bool,
+    /// Whether to skip generic builds an earlier run finished (see [`Config::resume`][])
+    pub resume_builds: bool,
+    /// The sanitizers generic builds are built with (see [`Config::sanitizers`][])
+    pub sanitizers: Vec<crate::config::Sanitizer>,
+    /// Whether generic builds are built twice to check they're reproducible (see [`Config::verify_reproducible`][])
+    pub verify_reproducible: bool,
+    /// The shard of the generic builds to plan (see [`Config::shard`][])
+    pub shard: Option<crate::config::Shard>,
+    /// The tag being announced, which builds get as `CARGO_DIST_GIT_TAG`
+    pub announcement_tag: Option<String>,
+    /// The version being announced, which builds get as `CARGO_DIST_VERSION`
+    pub announcement_version: Option<String>,
+    /// The commit being built, looked up the first time a build needs it (see [`crate::env::git_commit`][])
+    pub git_commit: OnceLock<Option<String>>,
+    /// The Homebrew environment, fetched the first time a build needs it (see [`brew_env`][])
+    pub brew_env: OnceLock<Option<BrewEnv>>,
+    /// The environment setup-scripts make, sourced the first time a build needs it (see [`crate::env::setup_env`][])
+    pub setup_env: OnceLock<Vec<(String, Option<String>)>>,
+    /// Where build events go, opened the first time there's one (see [`crate::build::generic::events::sink`][])
+    pub event_sink: OnceLock<Option<Arc<EventSink>>>,
+    /// The compiler family picked from `compiler-preference`, probed the first time a build needs it (see [`crate::build::generic::compilers::detected_family`][])
+    pub compiler_family: OnceLock<Option<CompilerFamily>>,
+    /// The extra-outputs builds have copied into the dist dir so far
+    pub extra_outputs_copied: Mutex<SortedSet<Utf8PathBuf>>,
+}
+
+/// A hook for library users to customize how generic builds are launched
+///
+/// It's invoked with the fully assembled `Command`, right before it's spawned.
+/// This is after all of cargo-dist's own arguments and environment variables
+/// have been applied (and it's been wrapped for `container-image`), and after
+/// stdout/stderr have been piped for our output relay, so anything the
+/// hook sets takes precedence.
+pub struct CommandHook(Mutex<Box<CommandHookFn>>);
+
+/// The signature of a [`CommandHook`][]
+pub type CommandHookFn = dyn FnMut(&mut std::process::Command) + Send;
+
+impl CommandHook {
+    /// Create a new hook
+    pub fn new(hook: impl FnMut(&mut std::process::Command) + Send + 'static) -> Self {
+        Self(Mutex::new(Box::new(hook)))
+    }
+
+    /// Invoke the hook on a command
+    pub(crate) fn call(&self, command: &mut std::process::Command) {
+        let mut hook = self.0.lock().expect("command hook poisoned");
+        hook(command)
+    }
+}
+
+impl std::fmt::Debug for CommandHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CommandHook(..)")
+    }
 }
 
 /// Info about artifacts should be hosted
@@ -350,7 +440,7 @@ pub struct Binary {
     pub copy_symbols_to: Vec<Utf8PathBuf>,
     /// feature flags!
     pub features: CargoTargetFeatures,
-    pkg_idx: PackageIdx,
+    pub(crate) pkg_idx: PackageIdx,
 }
 
 /// A build step we would like to perform
@@ -411,6 +501,76 @@ pub struct GenericBuildStep {
     pub expected_binaries: Vec<BinaryIdx>,
     /// The command to run to produce the expected binaries
     pub build_command: Vec<String>,
+    /// The kind of build system the command drives
+    pub builder: GenericBuilder,
+    /// A container image to run the build inside of
+    pub container_image: Option<String>,
+    /// A machine to run the build on over SSH
+    pub remote_host: Option<RemoteHost>,
+    /// Where the build puts its outputs, if not the directory it runs in
+    ///
+    /// Passed to the build as CARGO_DIST_OUT_DIR.
+    pub output_base: Option<Utf8PathBuf>,
+    /// Other files the build produces, as (glob pattern, directory under dist_dir to copy them to)
+    pub extra_outputs: Vec<(String, Utf8PathBuf)>,
+    /// The names of expected binaries the build may not produce
+    pub allow_missing: Vec<String>,
+    /// The build profile (like "debug"), if one was picked
+    ///
+    /// Passed to the build as CARGO_DIST_PROFILE.
+    pub profile: Option<String>,
+    /// Binaries (and data builds) by name that have to be built before this build runs
+    pub depends_on: Vec<String>,
+    /// The commands to run one after another, and what each of them builds
+    ///
+    /// Empty when `build_command` builds everything, which is the first of
+    /// them otherwise (see `combine-build-commands`).
+    pub sub_builds: Vec<SubBuild>,
+    /// Whether the build only makes sense on the host, and mustn't be cross-compiled
+    ///
+    /// Like extra builds, these aren't given CARGO_DIST_TARGET, compilers, or flags for the target.
+    pub host_only: bool,
+    /// Whether this is really an extra build, given a target (see [`ExtraBuildStep::target`][])
+    ///
+    /// These always run in the workspace, rather than an isolated build dir, and
+    /// don't get repro manifests.
+    pub extra: bool,
+    /// The data build this is, if it is one (see [`GenericBuildConfig::data_builds`][])
+    ///
+    /// Its outputs go to the target dir for the builds that depend on it, rather
+    /// than the dist dir, and it has no binaries.
+    pub data: Option<String>,
+    /// How much of this build to show, from `target-verbosity` or else `verbosity`
+    pub verbosity: BuildVerbosity,
+    /// The variant being built, if there are any
+    ///
+    /// Passed to the build as CARGO_DIST_VARIANT.
+    pub variant: Option<String>,
+    /// Custom key/values to record with the build's binaries in the manifest
+    pub artifact_metadata: SortedMap<String, String>,
+}
+
+impl GenericBuildStep {
+    /// Every command the build runs, in order
+    pub fn commands(&self) -> Vec<&[String]> {
+        if self.sub_builds.is_empty() {
+            vec![&self.build_command]
+        } else {
+            self.sub_builds
+                .iter()
+                .map(|sub_build| sub_build.build_command.as_slice())
+                .collect()
+        }
+    }
+}
+
+/// One of the commands of a [`GenericBuildStep`][] that runs several
+#[derive(Debug)]
+pub struct SubBuild {
+    /// The command
+    pub build_command: Vec<String>,
+    /// The binaries it builds
+    pub binaries: Vec<BinaryIdx>,
 }
 
 /// An "extra" build step, producing new sidecar artifacts
@@ -418,8 +578,18 @@ pub struct GenericBuildStep {
 pub struct ExtraBuildStep {
     /// Binaries we expect from this build
     pub expected_artifacts: Vec<String>,
+    /// Each artifact's path from the build, and its name in the dist dir
+    ///
+    /// The names are the same unless the artifacts have a `name-template`.
+    pub artifact_names: Vec<(String, String)>,
     /// The command to run to produce the expected binaries
     pub build_command: Vec<String>,
+    /// What kind of file specific artifacts (by their path from the build) should be
+    pub formats: BTreeMap<String, ArtifactFormat>,
+    /// The target the artifacts are for, if they're for one
+    ///
+    /// The build is then given CARGO_DIST_TARGET, compilers and flags for it, like a generic build.
+    pub target: Option<TargetTriple>,
 }
 
 /// A cargo build (and copy the outputs to various locations)
@@ -496,6 +666,8 @@ pub enum SymbolKind {
     Dsym,
     /// DWARF DWPs
     Dwp,
+    /// ELF debug info split out with objcopy
+    Debug,
 }
 
 impl SymbolKind {
@@ -505,6 +677,7 @@ impl SymbolKind {
             SymbolKind::Pdb => "pdb",
             SymbolKind::Dsym => "dSYM",
             SymbolKind::Dwp => "dwp",
+            SymbolKind::Debug => "debug",
         }
     }
 }
@@ -589,7 +762,7 @@ pub struct ExecutableZip {
 #[derive(Clone, Debug)]
 pub struct Symbols {
     /// The kind of symbols this is
-    kind: SymbolKind,
+    pub kind: SymbolKind,
 }
 
 /// A source tarball artifact
@@ -609,6 +782,8 @@ pub struct SourceTarball {
 pub struct ExtraArtifactImpl {
     /// The build command to run to produce this artifact
     pub build: Vec<String>,
+    /// Where the build leaves the artifact (before it's renamed)
+    pub source: String,
     /// The artifact this build should produce
     pub artifact: Utf8PathBuf,
 }
@@ -858,6 +1033,7 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
             github_custom_runners: _,
             bin_aliases: _,
             install_updater,
+            generic_build,
         } = &workspace_metadata;
 
         let desired_cargo_dist_version = cargo_dist_version.clone();
@@ -1076,6 +1252,30 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                     workspace_metadata.bin_aliases.clone().unwrap_or_default(),
                 ),
                 install_updater: install_updater.unwrap_or_default(),
+                generic_build: generic_build.clone().unwrap_or_default(),
+                command_hook: None,
+                interactive_builds: false,
+                print_build_commands: false,
+                repro_manifests: false,
+                build_args: vec![],
+                staging_dir: None,
+                target_staging: false,
+                build_profile: None,
+                flags_profile: None,
+                variant: None,
+                no_brew_cache: false,
+                resume_builds: false,
+                sanitizers: vec![],
+                verify_reproducible: false,
+                shard: None,
+                announcement_tag: None,
+                announcement_version: None,
+                git_commit: OnceLock::new(),
+                brew_env: OnceLock::new(),
+                setup_env: OnceLock::new(),
+                event_sink: OnceLock::new(),
+                compiler_family: OnceLock::new(),
+                extra_outputs_copied: Mutex::new(SortedSet::new()),
             },
             manifest: DistManifest {
                 dist_version: Some(env!("CARGO_PKG_VERSION").to_owned()),
@@ -1094,6 +1294,8 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                 ci: None,
                 linkage: vec![],
                 upload_files: vec![],
+                provenance: vec![],
+                build_summary: vec![],
             },
             package_metadata,
             workspace_metadata,
@@ -1246,7 +1448,13 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                 };
 
                 let target_is_windows = target.contains("windows");
-                let platform_exe_ext = if target_is_windows { ".exe" } else { "" };
+                let platform_exe_ext = if target_is_windows {
+                    ".exe"
+                } else if target.starts_with("wasm") {
+                    ".wasm"
+                } else {
+                    ""
+                };
 
                 let file_name = format!("{binary_name}{platform_exe_ext}");
 
@@ -1316,25 +1524,37 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
         }
     }
 
-    fn add_extra_artifacts(&mut self, dist_metadata: &DistMetadata, to_release: ReleaseIdx) {
+    fn add_extra_artifacts(
+        &mut self,
+        dist_metadata: &DistMetadata,
+        to_release: ReleaseIdx,
+    ) -> DistResult<()> {
         if !self.global_artifacts_enabled() {
-            return;
+            return Ok(());
         }
         let dist_dir = &self.inner.dist_dir.to_owned();
         let artifacts = dist_metadata.extra_artifacts.to_owned().unwrap_or_default();
+        let version = self.inner.release(to_release).version.to_string();
 
         for extra in artifacts {
-            for filename in extra.artifacts.clone() {
+            for source in extra.artifacts.clone() {
+                let filename = match &extra.name_template {
+                    Some(template) => {
+                        render_artifact_name(template, &source, &version, extra.target.as_deref())?
+                    }
+                    None => source.clone(),
+                };
                 let target_path = dist_dir.join(&filename);
 
                 let artifact = Artifact {
                     id: filename.to_owned(),
-                    target_triples: vec![],
+                    target_triples: extra.target.iter().cloned().collect(),
                     file_path: target_path.to_owned(),
                     required_binaries: FastMap::new(),
                     archive: None,
                     kind: ArtifactKind::ExtraArtifact(ExtraArtifactImpl {
                         build: extra.build.to_owned(),
+                        source: source.clone(),
                         artifact: target_path.to_owned(),
                     }),
                     checksum: None,
@@ -1344,6 +1564,7 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                 self.add_global_artifact(to_release, artifact);
             }
         }
+        Ok(())
     }
 
     fn add_source_tarball(&mut self, _tag: &str, to_release: ReleaseIdx) {
@@ -1555,10 +1776,27 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
             release.unix_archive
         };
 
-        let artifact_dir_name = variant.id.clone();
-        let artifact_dir_path = dist_dir.join(&artifact_dir_name);
         let artifact_ext = zip_style.ext();
-        let artifact_name = format!("{artifact_dir_name}{artifact_ext}");
+        let (artifact_dir_name, artifact_name) =
+            match archive_name_template(&self.inner.generic_build, &variant.target) {
+                Some(template) => {
+                    let artifact_name = render_archive_name(
+                        template,
+                        &release.app_name,
+                        &release.version.to_string(),
+                        &variant.target,
+                        artifact_ext.trim_start_matches('.'),
+                    )
+                    .expect("archive names are checked when the graph is made");
+                    let artifact_dir_name = artifact_name
+                        .strip_suffix(artifact_ext)
+                        .unwrap_or(&artifact_name)
+                        .to_owned();
+                    (artifact_dir_name, artifact_name)
+                }
+                None => (variant.id.clone(), format!("{}{artifact_ext}", variant.id)),
+            };
+        let artifact_dir_path = dist_dir.join(&artifact_dir_name);
         let artifact_path = dist_dir.join(&artifact_name);
 
         let static_assets = variant.static_assets.clone();
@@ -1617,6 +1855,9 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
         dest_path: Utf8PathBuf,
     ) {
         let dist_dir = self.inner.dist_dir.clone();
+        let split_debuginfo = self.workspace.kind == axoproject::WorkspaceKind::Generic
+            && wants_split_debuginfo(&self.inner, self.binary(binary_idx));
+        let compress_symbols = wants_compressed_symbols(&self.inner);
         let binary = self.binary_mut(binary_idx);
 
         // Tell the binary that it should copy the exe to the given path
@@ -1624,7 +1865,9 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
 
         // Try to make a symbols artifact for this binary now that we're building it
         if binary.symbols_artifact.is_none() {
-            if let Some(symbol_kind) = target_symbol_kind(&binary.target) {
+            let symbol_kind =
+                target_symbol_kind(&binary.target).or(split_debuginfo.then_some(SymbolKind::Debug));
+            if let Some(symbol_kind) = symbol_kind {
                 // FIXME: For some formats these won't be the same but for now stubbed out
 
                 // FIXME: rustc/cargo has so more complex logic to do platform-specifc name remapping
@@ -1638,7 +1881,10 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                 // let base_name = &binary.name;
                 let binary_id = &binary.id;
                 // let src_symbol_name = format!("{base_name}.{src_symbol_ext}");
-                let dest_symbol_name = format!("{binary_id}.{dest_symbol_ext}");
+                let mut dest_symbol_name = format!("{binary_id}.{dest_symbol_ext}");
+                if compress_symbols {
+                    dest_symbol_name = compressed_name(&dest_symbol_name);
+                }
                 let artifact_path = dist_dir.join(&dest_symbol_name);
 
                 let artifact = Artifact {
@@ -2203,35 +2449,63 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
     }
 
     fn compute_extra_builds(&mut self) -> Vec<BuildStep> {
-        let artifacts = self
-            .inner
-            .artifacts
-            .iter()
-            .map(|artifact| artifact.id.clone())
-            .collect::<Vec<String>>();
+        // Where each extra build's artifacts ended up being named
+        let named = |extra: &ExtraArtifact, source: &str| {
+            self.inner
+                .artifacts
+                .iter()
+                .filter_map(move |artifact| match &artifact.kind {
+                    ArtifactKind::ExtraArtifact(impl_)
+                        if impl_.build == extra.build && impl_.source == source =>
+                    {
+                        Some(artifact.id.clone())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
 
         self.inner
             .extra_artifacts
             .iter()
             // We want to avoid adding build jobs for any artifacts
             // that were already filtered out in a previous step
-            .filter(|extra| extra.artifacts.iter().any(|a| artifacts.contains(a)))
+            .filter(|extra| {
+                extra
+                    .artifacts
+                    .iter()
+                    .any(|source| !named(extra, source).is_empty())
+            })
             .map(|extra| {
+                let artifact_names = extra
+                    .artifacts
+                    .iter()
+                    .flat_map(|source| {
+                        let mut names = named(extra, source);
+                        if names.is_empty() {
+                            names.push(source.clone());
+                        }
+                        names.into_iter().map(|name| (source.clone(), name))
+                    })
+                    .collect();
                 BuildStep::Extra(ExtraBuildStep {
                     expected_artifacts: extra.artifacts.clone(),
+                    artifact_names,
                     build_command: extra.build.clone(),
+                    formats: extra.formats.clone().unwrap_or_default(),
+                    target: extra.target.clone(),
                 })
             })
             .collect()
     }
 
-    fn compute_build_steps(&mut self) {
+    fn compute_build_steps(&mut self) -> DistResult<()> {
         // FIXME: more intelligently schedule these in a proper graph?
 
         let mut local_build_steps = vec![];
         let mut global_build_steps = vec![];
         let builds = match self.workspace.kind {
-            axoproject::WorkspaceKind::Generic => self.compute_generic_builds(),
+            axoproject::WorkspaceKind::Generic => self.compute_generic_builds()?,
             axoproject::WorkspaceKind::Rust => self.compute_cargo_builds(),
         };
         local_build_steps.extend(builds);
@@ -2258,6 +2532,7 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
 
         self.inner.local_build_steps = local_build_steps;
         self.inner.global_build_steps = global_build_steps;
+        Ok(())
     }
 
     fn add_build_steps_for_artifacts(artifacts: &Vec<&Artifact>, build_steps: &mut Vec<BuildStep>) {
@@ -2277,6 +2552,9 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
                         SymbolKind::Dwp => {
                             // No additional steps needed?
                         }
+                        SymbolKind::Debug => {
+                            // The generic build splits these out as it goes
+                        }
                     }
                 }
                 ArtifactKind::Installer(installer) => {
@@ -2396,7 +2674,7 @@ impl<'pkg_graph> DistGraphBuilder<'pkg_graph> {
             self.add_source_tarball(&announcing.tag, release);
 
             // Add any extra artifacts defined in the config
-            self.add_extra_artifacts(&package_config, release);
+            self.add_extra_artifacts(&package_config, release)?;
 
             // Add installers to the Release
             // Prefer the CLI's choices (`cfg`) if they're non-empty
@@ -2522,6 +2800,16 @@ impl DistGraph {
     }
 }
 
+/// Whether a build profile name can be used as a directory name
+pub fn is_valid_profile_name(profile: &str) -> bool {
+    !profile.is_empty()
+        && profile != "."
+        && profile != ".."
+        && profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 /// Precompute all the work this invocation will need to do
 pub fn gather_work(cfg: &Config) -> DistResult<(DistGraph, DistManifest)> {
     info!("analyzing workspace:");
@@ -2541,6 +2829,34 @@ pub fn gather_work(cfg: &Config) -> DistResult<(DistGraph, DistManifest)> {
         cfg.allow_all_dirty,
         cfg.announcement_tag.is_none(),
     )?;
+    // Each profile gets a dist dir of its own, so they can coexist
+    if let Some(profile) = &cfg.build_profile {
+        if !is_valid_profile_name(profile) {
+            return Err(DistError::InvalidBuildProfile {
+                profile: profile.clone(),
+            });
+        }
+        graph.inner.dist_dir = graph.inner.dist_dir.join(profile);
+        graph.inner.build_profile = Some(profile.clone());
+    }
+    if let Some(profile) = &cfg.flags_profile {
+        let profiles = graph.inner.generic_build.flags_profiles.as_ref();
+        if !profiles.is_some_and(|profiles| profiles.contains_key(profile)) {
+            return Err(DistError::UnknownFlagsProfile {
+                profile: profile.clone(),
+                known: profiles
+                    .map(|profiles| profiles.keys().cloned().collect::<Vec<_>>().join(", "))
+                    .unwrap_or_default(),
+            });
+        }
+        graph.inner.flags_profile = Some(profile.clone());
+    }
+    graph.inner.variant = crate::build::generic::variants::select_variant(
+        &graph.inner.generic_build,
+        cfg.variant.as_deref(),
+    )?;
+    graph.inner.shard = cfg.shard;
+    check_archive_names(&graph.inner.generic_build)?;
 
     // Prefer the CLI (cfg) if it's non-empty, but only select a subset
     // of what the workspace supports if it's non-empty
@@ -2569,8 +2885,18 @@ pub fn gather_work(cfg: &Config) -> DistResult<(DistGraph, DistManifest)> {
 
     // Choose which set of target triples we're building for
     let mut bypass_package_target_prefs = false;
+    let default_targets = crate::build::generic::default_targets::default_targets(
+        &graph.inner.generic_build,
+        cfg.artifact_mode,
+        &graph.inner.tools.cargo.host_target,
+    );
     let triples = if cfg.targets.is_empty() {
-        if matches!(cfg.artifact_mode, ArtifactMode::Host) {
+        if let Some(default_targets) = &default_targets {
+            info!("using default target-triples");
+            // Like host mode, these are what the user asked for, not what packages claim
+            bypass_package_target_prefs = true;
+            &default_targets[..]
+        } else if matches!(cfg.artifact_mode, ArtifactMode::Host) {
             info!("using host target-triple");
             // In "host" mode we want to build for the host arch regardless of what the
             // packages claim they support.
@@ -2621,7 +2947,7 @@ pub fn gather_work(cfg: &Config) -> DistResult<(DistGraph, DistManifest)> {
     graph.compute_announcement_info(&announcing);
 
     // Finally compute all the build steps!
-    graph.compute_build_steps();
+    graph.compute_build_steps()?;
 
     // And now figure out how to orchestrate the result in CI
     graph.compute_ci();
@@ -1,3 +1,4 @@
 mod config;
+mod generic;
 mod mock;
 mod tag;